@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, SamplingMode};
+
+use compiler::cli::Options;
+use compiler::codegen::{CodeGenerator, CodegenMode};
+use compiler::irgen::{self, Context, Function, PassManager, Signature};
+use compiler::parser::{parse, NodeArena, ParseTreeNode};
+use compiler::tokenizer::tokenize;
+
+/// A single function returning an expression nested as deeply as the parser comfortably handles
+fn deep_expression(depth: usize) -> String
+{
+    let mut expression = String::from("x");
+
+    for i in 0..depth
+    {
+        let operator = ["+", "*", "-", "^"][i % 4];
+        expression = format!("({} {} {})", expression, operator, i + 1);
+    }
+
+    format!("i32 deep(i32 x)\n{{\n    return {};\n}}\n", expression)
+}
+
+/// Many small functions, each with a loop and a branch, calling the one before it
+fn many_functions(count: usize) -> String
+{
+    let mut result = String::new();
+
+    for i in 0..count
+    {
+        let call = if i == 0 {String::from("0")} else {format!("f{}(total, y)", i - 1)};
+
+        result += &format!("i32 f{}(i32 x, i32 y)\n{{\n    i32 total = x;\n    i32 i = 0;\n    while (i < y)\n    {{\n        if (i % 3 == 0)\n        {{\n            total += x * i;\n        }}\n        i++;\n    }}\n    return total + {};\n}}\n\n", i, call);
+    }
+
+    result
+}
+
+/// One function made of a long run of assignments with no control flow
+fn straight_line(statements: usize) -> String
+{
+    let mut result = String::from("i32 straight(i32 x)\n{\n    i32 v0 = x;\n");
+
+    for i in 1..statements
+    {
+        result += &format!("    i32 v{} = v{} * 3 + {} - x;\n", i, i - 1, i);
+    }
+
+    result += &format!("    return v{};\n}}\n", statements - 1);
+
+    result
+}
+
+/// Lower every function of a library to IR with its types corrected, as the compiler does before
+/// optimizing
+fn lower(library: &ParseTreeNode) -> Vec<Function>
+{
+    // The generated libraries declare no structs or global variables
+    let context = Rc::new(Context::default());
+
+    let signatures: Rc<HashMap<String, Signature>> = Rc::new(library.children().iter()
+        .map(|child| Function::prototype_from_parse_tree_node(child, &context).unwrap())
+        .map(|function| (function.name.clone(), function.signature()))
+        .collect());
+
+    library.children().iter().map(|child|
+    {
+        let mut function = Function::from_parse_tree_node(child, &signatures, &context).unwrap();
+        irgen::correct_types(&mut function).unwrap();
+        function
+    }).collect()
+}
+
+/// Benchmark each phase of the compiler on the given source, each phase starting from the output
+/// of the one before it
+fn bench_phases(c: &mut Criterion, name: &str, source: String)
+{
+    let mut group = c.benchmark_group(name);
+    group.sample_size(10);
+
+    // Each sample runs a phase once, as a phase of the larger inputs takes seconds
+    group.sampling_mode(SamplingMode::Flat);
+
+    let tokens = tokenize(source.clone(), format!("{}.pc", name));
+
+    let arena = NodeArena::new();
+    let library = parse(tokens.clone(), &arena).unwrap();
+
+    let functions = lower(&library);
+
+    let pass_manager = PassManager::new(2, true);
+    let mut optimized = functions.clone();
+
+    for function in &mut optimized
+    {
+        pass_manager.run(function);
+    }
+
+    let options = Options::new(vec![String::from("compiler")]);
+
+    group.bench_function("tokenize", |b| b.iter_batched(|| source.clone(), |source| tokenize(source, format!("{}.pc", name)), BatchSize::SmallInput));
+
+    group.bench_function("parse", |b| b.iter_batched(|| tokens.clone(), |tokens|
+    {
+        let arena = NodeArena::new();
+        parse(tokens, &arena).unwrap().children().len()
+    }, BatchSize::SmallInput));
+
+    group.bench_function("irgen", |b| b.iter(|| lower(&library)));
+
+    group.bench_function("optimize", |b| b.iter_batched(|| functions.clone(), |mut functions|
+    {
+        for function in &mut functions
+        {
+            pass_manager.run(function);
+        }
+
+        functions
+    }, BatchSize::SmallInput));
+
+    group.bench_function("codegen", |b| b.iter(|| CodeGenerator::new(CodegenMode::LLVM, &optimized, &options).render().unwrap()));
+
+    group.finish();
+}
+
+fn phases(c: &mut Criterion)
+{
+    bench_phases(c, "deep_expression", deep_expression(40));
+    bench_phases(c, "many_functions", many_functions(10_000));
+    bench_phases(c, "straight_line", straight_line(250));
+}
+
+criterion_group!(benches, phases);
+criterion_main!(benches);
@@ -0,0 +1,58 @@
+use std::fmt;
+
+use compiler::cli::{self, Error, Options};
+use compiler::irgen::{Function, Pass};
+use compiler::plugin::{self, Backend, PassStage};
+
+/// Code generator listing each function along with the number of IR instructions left in it,
+/// selected with `-g summary`
+struct Summary;
+
+impl Backend for Summary
+{
+    fn extension(&self) -> &'static str
+    {
+        "txt"
+    }
+
+    fn render(&self, functions: &[&Function], external: &[&Function], _options: &Options, out: &mut dyn fmt::Write) -> Result<(), Error>
+    {
+        let write_error = |_| Error::fatal_error("Could not write the summary").with_code("E0101");
+
+        for function in functions
+        {
+            writeln!(out, "{} {}", function.name, function.instructions.len()).map_err(write_error)?;
+        }
+
+        for function in external
+        {
+            writeln!(out, "{} external", function.name).map_err(write_error)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Pass reporting the size of each function once it has been optimized
+fn report_size(function: &mut Function)
+{
+    eprintln!("{}: {} instructions", function.name, function.instructions.len());
+}
+
+/// A compiler with a pass and a code generator of its own, used exactly as the compiler is, as in
+/// `cargo run --example plugin -- file.pc -g summary --stdout -O 2`
+fn main()
+{
+    plugin::register_pass(Pass { name: "report-size", run: report_size }, PassStage::End, 0).unwrap();
+    plugin::register_backend("summary", Box::new(Summary)).unwrap();
+
+    compiler::ice::install_panic_hook();
+
+    let options = Options::new(std::env::args().collect());
+
+    if let Err(error) = cli::run(&options)
+    {
+        eprintln!("{}\nCompilation Terminated", error);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,32 @@
+use super::{Options, Error, ErrorRecorder};
+
+/// Check each input file for errors without compiling it, reporting every error found rather
+/// than stopping at the first
+pub fn check(opts: &Options) -> Result<(), Error>
+{
+    let mut recorder: ErrorRecorder = ErrorRecorder::new();
+    let mut errors = 0;
+
+    let filenames: Vec<String> = opts.get_raw_values().into_iter().skip(1).collect();
+
+    if filenames.is_empty()
+    {
+        recorder.report_error(Error::fatal_error("No input files").with_code("E0100"))?;
+    }
+
+    for filename in filenames
+    {
+        match recorder.wrap_return(crate::io::InputFile::new(filename))?
+        {
+            Some(input) => {errors += crate::compile::check(&input, opts)?;},
+            None => {errors += 1;}
+        }
+    }
+
+    if errors > 0
+    {
+        Err(Error::fatal_error(&format!("Found {} error{}", errors, if errors == 1 {""} else {"s"})).with_code("E0109"))?
+    }
+
+    Ok(())
+}
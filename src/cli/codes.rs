@@ -0,0 +1,449 @@
+use super::Error;
+
+/// Stable codes given to diagnostics, along with a summary and the longer description `--explain`
+/// prints, codes are never reused once given out
+pub static ERROR_CODES: &[(&str, &str, &str)] = &[
+    ("E0001", "Unknown identifier",
+"A variable was used which has not been declared in the function, either as an argument or with a
+declaration before the use.
+
+    i32 main()
+    {
+        return count; // 'count' was never declared
+    }
+
+Check the spelling of the name, and that the declaration comes before the first use."),
+
+    ("E0002", "Unknown builtin",
+"A function starting with '__builtin_' was called which is not one of the builtins the compiler
+provides. See the Builtins section of the README for the list."),
+
+    ("E0003", "Wrong number of arguments",
+"A function, builtin or macro was called with more or fewer arguments than it takes.
+
+    u32 n = __builtin_popcount(a, b); // '__builtin_popcount' takes 1 argument"),
+
+    ("E0004", "Loop control outside of a loop",
+"'break' and 'continue' can only be used inside the body of a 'while', 'do', 'for' or 'loop' loop, as
+there is no loop for them to leave or continue."),
+
+    ("E0005", "Syntax error",
+"The parser found a token it was not expecting, the message gives what it expected instead. The
+location given is that of the unexpected token, so the mistake is often just before it, such as a
+missing ';' or ')'."),
+
+    ("E0006", "Unexpected end of file",
+"The file ended part of the way through a construct, such as a function missing its closing '}'.
+The message gives what the parser was expecting when the input ran out."),
+
+    ("E0007", "Integer literal too large",
+"An integer literal does not fit in the 128 bits the compiler uses to hold literals."),
+
+    ("E0008", "Bad integer type",
+"An integer type such as 'i24' or 'u7' gives a width which is not allowed, widths must be between
+1 and 64 bits."),
+
+    ("E0009", "Bad vector type",
+"A vector type such as 'u32x4' must have lanes 8, 16, 32 or 64 bits wide, and a power of two
+lanes up to the maximum."),
+
+    ("E0010", "Bad alignment",
+"The alignment given with 'align(N)' must be an integer power of two, up to 4096.
+
+    align(24) u8 buffer; // 24 is not a power of two"),
+
+    ("E0011", "Alignment not allowed here",
+"An alignment can only be given for a variable declaration, not for a cast or the return type of
+a function."),
+
+    ("E0012", "Restrict on a non-pointer",
+"Only pointer arguments can be marked 'restrict', as the promise it makes is about the memory the
+argument points to."),
+
+    ("E0013", "Unknown attribute",
+"An attribute was given which the compiler does not know for the item it is attached to. Functions
+accept 'noreturn', 'const', 'interrupt', 'used', 'constructor', 'optimize(N)' and
+'section(\"name\")', and loops accept 'vectorize' and 'unroll(N)'."),
+
+    ("E0014", "Bad attribute arguments",
+"An attribute was given the wrong number of arguments, or an argument it cannot use, such as an
+'unroll' count of zero, an 'optimize' level which does not exist or a 'section' name which is not a
+non-empty string."),
+
+    ("E0015", "Noreturn function can return",
+"A function marked '#[noreturn]' has a path through it which returns. Every path through such a
+function must end in a loop which is never left, such as a 'loop' without a 'break'."),
+
+    ("E0016", "Not a constant expression",
+"An expression which must be worked out while compiling, such as the condition of a
+'static_assert', uses something only known when the program runs, such as a variable or a call."),
+
+    ("E0017", "Division by zero in a constant expression",
+"A constant expression divides, or takes the remainder, by zero."),
+
+    ("E0018", "Overflow in a constant expression",
+"A constant expression produces a value too large to be held while it is worked out."),
+
+    ("E0019", "Static assertion failed",
+"The condition of a 'static_assert' is false. The message given with the assertion is included in
+the diagnostic."),
+
+    ("E0020", "Invalid cast",
+"A value was cast with 'as' to a type it cannot be converted to, such as a vector to a pointer."),
+
+    ("E0021", "Invalid condition",
+"The condition of an 'if', a loop or a ternary must be a single value, vectors and void
+expressions cannot be used. Compare the lanes of a vector one at a time instead."),
+
+    ("E0022", "Invalid index",
+"A value was indexed which cannot be, or a lane was given which is out of range for a vector.
+
+    u32x4 v = 1;
+    u32 x = v[4]; // lanes of a 'u32x4' are 0 to 3"),
+
+    ("E0023", "Type mismatch",
+"An operation was given values of types which do not go together, for example adding two vectors
+of different types, subtracting pointers to different types, or using 'null' as something other
+than a pointer.
+
+    u32* p = &a;
+    u8* q = &b;
+    i64 n = p - q; // pointers to different types
+
+Cast one of the values to the type of the other with 'as'."),
+
+    ("E0024", "Unknown type",
+"A type was named which is not one of the types the compiler knows."),
+
+    ("E0025", "Missing semicolon",
+"A statement was not ended with a ';'. The location given is the end of the statement, where the
+';' should go, rather than the start of whatever follows it.
+
+    i32 total = 0
+    total = total + 1; // ';' missing after '0' on the line before"),
+
+    ("E0026", "Conflicting function declarations",
+"A function was defined more than once, or given by prototypes or a definition which do not agree
+on its return type or the types of its arguments.
+
+    i32 square(i32 x);
+    u32 square(u32 x) // the prototype gives 'i32'
+    {
+        return x * x;
+    }"),
+
+    ("E0027", "Missing default argument value",
+"Once an argument is given a default value, every argument after it must be given one too, as a
+call can only leave out arguments from the end.
+
+    i32 clamp(i32 value, i32 low = 0, i32 high) // 'high' needs a default value"),
+
+    ("E0028", "Condition needs parentheses",
+"The condition of an 'if' or 'while' can be written without parentheses when the body after it is
+a block in braces, but must be in parentheses when the body is a single statement, as otherwise
+the condition would run on into the statement.
+
+    if x -y;     // is this 'if (x) -y;' or 'if (x - y);'?
+    if (x) -y;
+    if x { -y; }"),
+
+    ("E0029", "Bad use of a macro",
+"A macro was defined more than once, used within an expression, or used within its own expansion.
+A macro expands to a block, so it can only be used on its own as a statement, and a macro which
+uses itself would expand without end.
+
+    macro double(x) { x = x + x; }
+    i32 y = double(a); // 'double' is not an expression, write 'double(a);' on its own"),
+
+    ("E0030", "Cannot import file",
+"A file named in an 'import' does not exist or cannot be read. The path is relative to the
+directory of the file containing the import, not the current directory."),
+
+    ("E0031", "Circular import",
+"A file imports itself, either directly or through the files it imports. The message gives the
+chain of imports which leads back to the first file. Move whatever the files need from each other
+into a file of its own which both of them import."),
+
+    ("E0032", "Unknown module or function",
+"A function was named with a module, as in 'math::square', or brought in with 'use', where the
+module is not imported by the file or does not define a function of that name. A module is named
+after its file, so 'import \"lib/math.pc\";' imports the module 'math'."),
+
+    ("E0033", "Ambiguous name",
+"A name could refer to more than one function, or two imported files would give modules of the
+same name. Either a function called by its name alone is defined by more than one of the imported
+modules, or 'use' brings in a name which is already defined or used from another module.
+
+    import \"a.pc\";
+    import \"b.pc\";   // both define 'twice'
+    twice(1);        // write 'a::twice(1)' or 'b::twice(1)'"),
+
+    ("E0034", "Private function",
+"A function of an imported module was called from another file without being marked 'pub', or a
+global variable of one was used from another file. The functions and global variables of an
+imported module are private to it unless marked 'pub', which keeps its helpers and state out of
+the way of the files importing it.
+
+    pub i32 square(i32 x) // can be called as 'math::square' from files importing 'math.pc'
+    {
+        return multiply(x, x); // 'multiply' can only be called from within 'math.pc'
+    }
+
+    pub u32 calls = 0; // can be used from files importing 'math.pc'"),
+
+    ("E0035", "Builtin needs a hosted environment",
+"One of the builtins which call into the C library ('__builtin_print', '__builtin_alloc',
+'__builtin_assert' and '__builtin_abort') was used when compiling for a freestanding environment,
+either with --freestanding or for one of the bare metal --target presets. There is no C library to
+link against there, so the call would only fail when linking. Pass --hosted if the target does
+have a C library."),
+
+    ("E0036", "Rename would clash",
+"'compiler rename' was asked to give a function, argument or variable a name which is already
+taken by another function named in one of the same files, or another argument or variable of the
+same function, or which would change what some other name refers to, such as a call which would
+become ambiguous between two imported modules. Choose another name, or rename the other first."),
+
+    ("E0037", "Nested too deeply",
+"Expressions and statements were nested within each other more than 100 levels deep. Each set of
+parentheses, block, statement and operator applied to the result of another counts as a level,
+including each operator of a run such as 'x + 1 + 1 + 1', which is applied to the result of the
+one before it. Nesting is limited so the compiler cannot run out of stack. Split the expression
+up with variables, or move the inner statements into a function of their own."),
+
+    ("E0038", "Bad interrupt handler",
+"A function marked '#[interrupt]' cannot be entered by the hardware as written, or is called. An
+interrupt handler returns void, and takes no arguments, or a pointer to the interrupt frame the
+processor pushes, optionally followed by the error code as an integer the size of a pointer. Only
+x86 handlers are passed the frame and error code, those for other architectures take no arguments.
+The processor enters the handler itself, so it cannot be called from other functions.
+
+    #[interrupt]
+    void page_fault(u8* frame, u64 code)
+    {
+        ...
+    }"),
+
+    ("E0039", "Bad constructor",
+"A function marked '#[constructor]' cannot be run before main. A constructor returns void and takes
+no arguments, and cannot also be an interrupt handler. Constructors are run by the C runtime, so
+they can only be used in a hosted environment, not with --freestanding or a target without the C
+library.
+
+    #[constructor]
+    void setup()
+    {
+        ...
+    }"),
+
+    ("E0040", "Bad struct",
+"A struct is declared or used wrongly. Each struct is declared once per library, with fields of
+distinct names which are not void, and cannot hold itself other than through a pointer, as it
+would never end. Its fields are read with '.' on a struct and '->' on a pointer to one, only by
+the names it declares. A struct can be copied, passed and returned whole, but not used in
+arithmetic, compared, cast or tested as a condition.
+
+    struct Node
+    {
+        i32 value;
+        struct Node* next;
+    }"),
+
+    ("E0041", "Bad global variable",
+"A global variable is declared wrongly. Each global variable is declared once per library, is not
+void and does not share its name with a function. The value it starts with must be a constant
+expression, a pointer can only start as null or as a constant cast to a pointer, and a struct
+always starts zeroed, so it cannot be given a value.
+
+    i32 counter = 0;
+    u8* port = 1024 as u8*;"),
+
+    ("E0042", "Recursion forbidden",
+"A function calls itself, directly or through other functions, while recursion is forbidden with
+--forbid-recursion. Without recursion the stack a program needs can be bounded, which matters on
+small embedded targets. The error gives the cycle of calls, which can be broken by turning the
+recursion into a loop.
+
+    u64 factorial(u64 n)
+    {
+        u64 result = 1;
+
+        while n > 1
+        {
+            result *= n;
+            n--;
+        }
+
+        return result;
+    }"),
+
+    ("E0043", "Undeclared function",
+"A function is called which is not defined or declared by a prototype in the library, nor imported
+from another module. Check the spelling of the name, and declare a function defined in C with a
+prototype before calling it.
+
+    i32 abs(i32 x);"),
+
+    ("E0100", "Cannot read input",
+"An input file does not exist or cannot be read, or no input files were given."),
+
+    ("E0101", "Cannot write output",
+"An output file or directory could not be created or written to. Check that the directory exists
+and is writable."),
+
+    ("E0102", "Bad command line option",
+"A command line option was given a value it does not accept, such as an unknown code generation
+mode, optimization pass or emit kind, or options were combined which cannot be used together."),
+
+    ("E0103", "Bad target",
+"The target could not be worked out from the triple, data layout or target specification file
+given, or --target named a target which is not built in. Unknown architectures need a data layout
+to give the sizes of types."),
+
+    ("E0104", "Warnings treated as errors",
+"Compilation stopped because warnings were given and -Werror was passed, or a warning passed to
+--deny was given."),
+
+    ("E0105", "Output is not deterministic",
+"--deterministic-check compiled the input twice and the outputs differed. This is a bug in the
+compiler, please report it along with the input."),
+
+    ("E0106", "Smoke test failed",
+"'compiler doctor' could not compile its small test program, the errors from compiling it are
+printed before this one."),
+
+    ("E0107", "Specification check failed",
+"'compiler spec' ran a program from the conformance suite which did not exit with the code given
+by its '// expect: N' line, either when interpreted or when compiled with LLVM and run with lli.
+The failing programs are listed before this error."),
+
+    ("E0108", "External tool failed",
+"An external tool the compiler runs, such as 'llc' or 'ar' when building a static library with
+'--emit staticlib', or 'llvm-nm' when measuring code for '--emit size-report', could not be run
+or failed. The output of the tool is given with the error, and 'compiler doctor' checks which of
+the tools can be found."),
+
+    ("E0109", "Check found errors",
+"'compiler check' found errors in the files it was given, which are reported before this one."),
+
+    ("E0110", "Nothing to rename",
+"'compiler rename' was given a line and column at which no function, argument or variable is
+named, a function which is not defined in the source, such as a builtin, or a new name which is not
+an identifier or is a keyword or a type. Lines and columns count from 1."),
+
+    ("E0111", "Bad IR file",
+"A file given with --from-irb is not an IR file written by --emit irb, was written by a version of
+the compiler using a different version of the format, or is damaged. Write it again from the source
+with --emit irb."),
+
+    ("E0112", "Plugin conflict",
+"A plugin registered a pass or code generation mode under a name which is already taken, by one
+built into the compiler or one registered before it. Passes and modes are selected by name, so
+each name can only be registered once."),
+
+    ("E0113", "Compilation failed",
+"The compiler found errors in the files it was given, which are reported before this one, so no
+output was written for them. The compiler exits with a failing status."),
+
+    ("E0114", "Invalid IR",
+"The IR handed to the code generators is not well formed, as an instruction follows the end of a
+block, a return or branch, without a label to start a new block, so nothing can reach it, or a
+variable is used which is not in the symbol table of its function. This can come from an IR file
+which was changed by hand or from a plugin pass, and otherwise is a bug in the compiler."),
+
+    ("E0200", "Unsupported by the code generator",
+"The code generator for the chosen mode cannot yet generate code for a construct, or has run out
+of registers. The AVR code generator in particular is far from complete."),
+
+    ("W0001", "Unreachable code",
+"A statement follows a 'return', 'break', 'continue' or an infinite loop in the same block, so it
+can never run."),
+
+    ("W0002", "Dangling else",
+"An 'else' follows an 'if' placed without braces as the body of another 'if', and is lined up with
+the outer 'if'. An 'else' always belongs to the nearest 'if' without one, so it is the inner 'if'
+the 'else' belongs to, whatever the indentation suggests. This warning is only given with
+-Wdangling-else.
+
+    if (a)
+        if (b)
+            x = 1;
+    else        // belongs to 'if b'
+        x = 2;
+
+Put braces around the body of the outer 'if' to give it the 'else'."),
+
+    ("W0003", "Assignment used as a condition",
+"The condition of an 'if' or loop is an assignment, which is true whenever the value assigned is
+nonzero. This is usually a comparison written with '=' in place of '=='. This warning is only
+given with -Wassign-in-condition.
+
+    if (x = 0)  // assigns 0 to x, and is never true
+    if (x == 0)"),
+
+    ("W0004", "Shift amount out of range",
+"A value is shifted by a constant amount which is negative or not less than the width of its type,
+which LLVM leaves undefined. Every bit is shifted out instead, so the result is zero, or for a
+right shift of a signed value a copy of its sign bit in every bit."),
+
+    ("W0005", "Division by zero",
+"A value is divided, or its remainder taken, by a constant zero, which LLVM leaves undefined.
+Dividing by zero gives zero instead, and the remainder is the value being divided."),
+
+    ("W0006", "Comparison of integers of different signedness",
+"A signed integer is compared with an unsigned one. Both are converted to a signed type wide
+enough to hold every value of either, so the comparison gives the same result as comparing the
+numbers and a negative value is always less than an unsigned one. This differs from C, where the
+signed value is converted to unsigned. This warning is only given with -Wsign-compare.
+
+    i32 a = -1;
+    u32 b = 1;
+    if a < b    // true, compared as 'i64'"),
+
+    ("W0007", "Variable declared again",
+"A variable is declared with the name of an argument or of a variable declared earlier in the same
+function. A function has a single scope, so the declaration does not make a new variable hiding
+the first, as it would in a block of C, but assigns to the same one. Turned off with --allow
+shadowing.
+
+    i32 total = 0;
+    while (i < n)
+    {
+        i32 total = i;  // the same 'total' as above
+    }"),
+
+    ("W0008", "Constant condition",
+"The condition of an 'if' or loop is a constant, so the branch is always or never taken. For a loop
+which runs until it is broken out of, use 'loop' in place of 'while (1)'. Turned off with --allow
+constant-condition."),
+
+    ("W0009", "Empty body",
+"The body of an 'if', 'while' or 'for' is a lone ';', which is usually a stray ';' after the condition
+making the statement after it run unconditionally, or once after the loop. Write '{}' for a body
+which is meant to be empty. Turned off with --allow empty-body.
+
+    while (x > 0);  // never ends if x is positive
+        x -= 1;")
+];
+
+/// Look up the summary and description of a code
+pub fn explain_code(code: &str) -> Option<(&'static str, &'static str)>
+{
+    ERROR_CODES.iter()
+        .find(|(known, ..)| known.eq_ignore_ascii_case(code))
+        .map(|(_, summary, description)| (*summary, *description))
+}
+
+/// Display the description of a code, for --explain
+pub fn display_explanation(code: &str) -> Result<(), Error>
+{
+    match explain_code(code)
+    {
+        Some((summary, description)) =>
+        {
+            println!("{}: {}\n\n{}", code.to_uppercase(), summary, description);
+            Ok(())
+        },
+        None => Err(Error::fatal_error(&format!("Unknown error code '{}'", code)).with_code("E0102"))
+    }
+}
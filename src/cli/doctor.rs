@@ -0,0 +1,275 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use super::{Options, Error};
+
+/// External tools the compiler's output is fed to, along with the role they play
+static TOOLS: &[(&str, &str)] = &[("clang", "C frontend / driver"), ("llc", "LLVM static compiler"), ("lli", "LLVM interpreter"), ("cc", "linker driver"), ("ld", "linker")];
+
+/// Program compiled end to end by the smoke test, expected to exit with `SMOKE_TEST_RESULT`
+static SMOKE_TEST_SOURCE: &str = "i32 main()\n{\n    i32 a = 40;\n    return a + 2;\n}\n";
+static SMOKE_TEST_RESULT: i32 = 42;
+
+/// Run a command, feeding it the given input, and return its exit code and combined output
+pub(super) fn run_tool(program: &str, args: &[&str], input: Option<&str>) -> Option<(Option<i32>, String)>
+{
+    run_tool_with_timeout(program, args, input, None)
+}
+
+/// Run a command as `run_tool` does, killing it if it runs for longer than the timeout
+pub(super) fn run_tool_with_timeout(program: &str, args: &[&str], input: Option<&str>, timeout: Option<Duration>) -> Option<(Option<i32>, String)>
+{
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn().ok()?;
+
+    if let Some(mut stdin) = child.stdin.take()
+    {
+        if let Some(text) = input
+        {
+            stdin.write_all(text.as_bytes()).ok()?;
+        }
+    }
+
+    if let Some(timeout) = timeout
+    {
+        let start = Instant::now();
+
+        while child.try_wait().ok()?.is_none()
+        {
+            if start.elapsed() > timeout
+            {
+                let _ = child.kill();
+                break;
+            }
+
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    let output = child.wait_with_output().ok()?;
+
+    let mut text = String::from_utf8_lossy(&output.stdout).to_string();
+    text += &String::from_utf8_lossy(&output.stderr);
+
+    Some((output.status.code(), text))
+}
+
+/// Extract a version string from the output of `--version`
+fn find_version(text: &str) -> String
+{
+    for line in text.lines()
+    {
+        if line.to_lowercase().contains("version") || line.chars().any(|c| c.is_ascii_digit())
+        {
+            return String::from(line.trim());
+        }
+    }
+
+    String::from("unknown version")
+}
+
+/// Extract the default target triple reported by `llc --version`
+fn default_triple(text: &str) -> Option<String>
+{
+    for line in text.lines()
+    {
+        if let Some(triple) = line.trim().strip_prefix("Default target:")
+        {
+            return Some(String::from(triple.trim()));
+        }
+    }
+
+    None
+}
+
+/// Check that a target triple is at least shaped like `arch-vendor-os[-env]`
+fn triple_well_formed(triple: &str) -> bool
+{
+    let parts: Vec<&str> = triple.split('-').collect();
+
+    parts.len() >= 2 && parts.len() <= 4 && parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.'))
+}
+
+/// Print a single line of the report
+fn report_line(ok: bool, name: &str, detail: &str)
+{
+    if ok
+    {
+        println!("  \x1b[1m\x1b[32m[ok]\x1b[0m      {:<16} {}", name, detail);
+    }
+    else
+    {
+        println!("  \x1b[1m\x1b[31m[fail]\x1b[0m    {:<16} {}", name, detail);
+    }
+}
+
+/// Check the environment the compiler runs in, reporting any missing pieces
+pub fn doctor(opts: &Options) -> Result<(), Error>
+{
+    let mut problems = 0;
+    let mut llc_output = None;
+    let mut has_lli = false;
+
+    // External tools
+    println!("Tools:");
+
+    for (tool, role) in TOOLS
+    {
+        let args: &[&str] = if *tool == "ld" {&["-v"]} else {&["--version"]};
+
+        match run_tool(tool, args, None)
+        {
+            Some((_, text)) =>
+            {
+                report_line(true, tool, &find_version(&text));
+
+                if *tool == "llc"
+                {
+                    llc_output = Some(text);
+                }
+                else if *tool == "lli"
+                {
+                    has_lli = true;
+                }
+            },
+            None =>
+            {
+                report_line(false, tool, &format!("not found ({})", role));
+                problems += 1;
+            }
+        }
+    }
+
+    // Target triple
+    println!("Target:");
+
+    let triple = if opts.map.contains_key("--target-spec") || opts.map.contains_key("--target")
+    {
+        match crate::codegen::TargetSpec::from_options(opts)
+        {
+            Ok(target) => target.triple,
+            Err(error) =>
+            {
+                report_line(false, "target spec", &format!("{}", error));
+                problems += 1;
+                None
+            }
+        }
+    }
+    else
+    {
+        match opts.map.get("--llvm-target").and_then(|args| args.first())
+        {
+            Some(triple) => Some(triple.clone()),
+            None => llc_output.as_deref().and_then(default_triple)
+        }
+    };
+
+    match triple
+    {
+        Some(triple) =>
+        {
+            let valid = if !triple_well_formed(&triple)
+            {
+                false
+            }
+            else if llc_output.is_some()
+            {
+                let module = format!("target triple = \"{}\"\ndefine i32 @main() {{\n  ret i32 0\n}}\n", triple);
+                matches!(run_tool("llc", &["-o", "/dev/null"], Some(&module)), Some((Some(0), _)))
+            }
+            else
+            {
+                true
+            };
+
+            if valid
+            {
+                report_line(true, "triple", &triple);
+            }
+            else
+            {
+                report_line(false, "triple", &format!("'{}' is not a valid target triple", triple));
+                problems += 1;
+            }
+        },
+        None =>
+        {
+            report_line(false, "triple", "no target given and no default could be determined");
+            problems += 1;
+        }
+    }
+
+    // End to end smoke test
+    println!("Smoke Test:");
+
+    let input = crate::io::InputFile {data: String::from(SMOKE_TEST_SOURCE), filename: String::from("doctor.pc")};
+    let smoke_options = Options::new(vec![String::from("compiler"), String::from("-g"), String::from("llvm")]);
+
+    let module = match crate::compile::generate(&input, &smoke_options, false)
+    {
+        Ok((module, _)) =>
+        {
+            report_line(true, "compile", "generated LLVM IR");
+            module
+        },
+        Err(error) =>
+        {
+            report_line(false, "compile", &format!("{}", error));
+            return Err(Error::fatal_error("The compiler failed to compile the smoke test").with_code("E0106"));
+        }
+    };
+
+    if has_lli
+    {
+        match run_tool("lli", &[], Some(&module))
+        {
+            Some((Some(code), _)) if code == SMOKE_TEST_RESULT =>
+            {
+                report_line(true, "run", &format!("exited with {}", code));
+            },
+            Some((code, text)) =>
+            {
+                let detail = match code
+                {
+                    Some(code) => format!("exited with {}, expected {}", code, SMOKE_TEST_RESULT),
+                    None => String::from("terminated by a signal")
+                };
+
+                report_line(false, "run", &detail);
+
+                for line in text.lines()
+                {
+                    println!("              {}", line);
+                }
+
+                problems += 1;
+            },
+            None =>
+            {
+                report_line(false, "run", "could not start lli");
+                problems += 1;
+            }
+        }
+    }
+    else
+    {
+        report_line(false, "run", "skipped, lli is not available");
+    }
+
+    if problems == 0
+    {
+        println!("\nNo problems found");
+    }
+    else
+    {
+        println!("\n{} problem{} found", problems, if problems == 1 {""} else {"s"});
+    }
+
+    Ok(())
+}
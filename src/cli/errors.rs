@@ -14,7 +14,9 @@ pub enum Severity
 pub struct Error
 {
     message: String,
-    severity: Severity
+    severity: Severity,
+    code: Option<&'static str>,
+    hint: Option<String>
 }
 
 impl Error
@@ -25,7 +27,9 @@ impl Error
         Self
         {
             message: String::from(msg),
-            severity: Severity::Warning
+            severity: Severity::Warning,
+            code: None,
+            hint: None
         }
     }
 
@@ -35,7 +39,19 @@ impl Error
         Self
         {
             message: String::from(msg),
-            severity: Severity::Error
+            severity: Severity::Error,
+            code: None,
+            hint: None
+        }
+    }
+
+    /// Promote a warning to an error, for when warnings are treated as errors
+    pub fn promote(self) -> Self
+    {
+        Self
+        {
+            severity: if self.severity == Severity::Warning {Severity::Error} else {self.severity},
+            ..self
         }
     }
 
@@ -45,34 +61,94 @@ impl Error
         Self
         {
             message: String::from(msg),
-            severity: Severity::FatalError
+            severity: Severity::FatalError,
+            code: None,
+            hint: None
+        }
+    }
+
+    /// Give the error one of the stable codes from `ERROR_CODES`, which `--explain` describes
+    pub fn with_code(self, code: &'static str) -> Self
+    {
+        Self
+        {
+            code: Some(code),
+            ..self
         }
     }
+
+    /// Give the error a hint, shown on its own line after the message
+    pub fn with_hint(self, hint: &str) -> Self
+    {
+        Self
+        {
+            hint: Some(String::from(hint)),
+            ..self
+        }
+    }
+
+    /// Get the hint given with the error, if there is one
+    pub fn hint(&self) -> Option<&str>
+    {
+        self.hint.as_deref()
+    }
+
+    /// Get the stable code of the error, if it has one
+    pub fn code(&self) -> Option<&'static str>
+    {
+        self.code
+    }
+
+    /// Get the severity of the error
+    pub fn severity(&self) -> Severity
+    {
+        self.severity
+    }
+
+    /// Get the message of the error, without the severity or code
+    pub fn message(&self) -> &str
+    {
+        &self.message
+    }
 }
 
 impl fmt::Display for Error
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
     {
-        // Render an error with the given colorings
+        // Render an error with the given colorings, the code being colored along with the severity
         write!(f, "compiler: ")?;
         match self.severity
         {
             Severity::Warning =>
             {
-                write!(f, "\x1b[1m\x1b[33mwarning\x1b[0m")?;
+                write!(f, "\x1b[1m\x1b[33mwarning")?;
             },
             Severity::Error =>
             {
-                write!(f, "\x1b[1m\x1b[31merror\x1b[0m")?;
+                write!(f, "\x1b[1m\x1b[31merror")?;
             },
             Severity::FatalError =>
             {
-                write!(f, "\x1b[1m\x1b[31mfatal error\x1b[0m")?;
+                write!(f, "\x1b[1m\x1b[31mfatal error")?;
             }
         }
 
-        write!(f, ": {}", self.message)
+        if let Some(code) = self.code
+        {
+            write!(f, "[{}]", code)?;
+        }
+
+        write!(f, "\x1b[0m")?;
+
+        write!(f, ": {}", self.message)?;
+
+        if let Some(hint) = &self.hint
+        {
+            write!(f, "\n  \x1b[1m\x1b[36mhelp\x1b[0m: {}", hint)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -122,6 +198,23 @@ impl ErrorRecorder
         }
     }
 
+    /// Get the number of errors recorded, not counting warnings
+    pub fn error_count(&self) -> usize
+    {
+        self.recorded_errors.iter().filter(|error| error.severity != Severity::Warning).count()
+    }
+
+    /// Stop with a fatal error if any errors were recorded, so the compiler exits with a failing
+    /// status even when it carried on past them
+    pub fn finish(&self) -> Result<(), Error>
+    {
+        match self.error_count()
+        {
+            0 => Ok(()),
+            count => Err(Error::fatal_error(&format!("Could not compile, found {} error{}", count, if count == 1 {""} else {"s"})).with_code("E0113"))
+        }
+    }
+
     /// Display any recorded errors
     pub fn dump(&self)
     {
@@ -4,6 +4,13 @@ use super::{Options, Error, ErrorRecorder};
 pub fn execute(opts: &Options) -> Result<(), Error>
 {
     let mut recorder: ErrorRecorder = ErrorRecorder::new();
+
+    // The input files are IR written by --emit irb rather than source
+    if opts.has_long_flag("from-irb")
+    {
+        return execute_irb(opts, recorder);
+    }
+
     let mut input_files: Vec<crate::io::InputFile> = Vec::new();
 
     // Open input files
@@ -20,14 +27,60 @@ pub fn execute(opts: &Options) -> Result<(), Error>
     // If no files are found, error
     if input_files.len() == 0
     {
-        recorder.report_error(Error::fatal_error("No input files"))?;
+        recorder.report_error(Error::fatal_error("No input files").with_code("E0100"))?;
     }
 
     // Loop over input files and compile them
     for input_file in input_files
     {
-        recorder.wrap_return(crate::compile::compile(input_file, opts))?;
+        let _span = tracing::info_span!("compile", file = %input_file.filename).entered();
+
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| crate::compile::compile(&input_file, opts)))
+        {
+            Ok(result) =>
+            {
+                crate::ice::leave_phase();
+                recorder.wrap_return(result)?;
+            },
+            Err(_) =>
+            {
+                crate::ice::report(Some(&input_file), opts);
+                std::process::exit(101);
+            }
+        }
+    }
+
+    recorder.finish()
+}
+
+/// Compile each of the IR files given on the command line
+fn execute_irb(opts: &Options, mut recorder: ErrorRecorder) -> Result<(), Error>
+{
+    let filenames = opts.get_raw_values();
+
+    if filenames.is_empty()
+    {
+        recorder.report_error(Error::fatal_error("No input files").with_code("E0100"))?;
+    }
+
+    for filename in filenames
+    {
+        let _span = tracing::info_span!("compile", file = %filename).entered();
+
+        match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| crate::compile::compile_irb(&filename, opts)))
+        {
+            Ok(result) =>
+            {
+                crate::ice::leave_phase();
+                recorder.wrap_return(result)?;
+            },
+            Err(_) =>
+            {
+                crate::ice::report(None, opts);
+                std::process::exit(101);
+            }
+        }
     }
 
-    Ok(())
-}
\ No newline at end of file
+    recorder.finish()
+}
@@ -0,0 +1,68 @@
+use super::{Options, Error};
+
+/// Get the input file and the position in it given to an editor support subcommand, which are the
+/// first two values after the subcommand
+fn input_and_position(opts: &Options, usage: &str) -> Result<(crate::io::InputFile, String), Error>
+{
+    let values = opts.get_raw_values();
+
+    match (values.get(1), values.get(2))
+    {
+        (Some(filename), Some(position)) => Ok((crate::io::InputFile::new(filename.clone())?, position.clone())),
+        _ => Err(Error::fatal_error(&format!("Expected {}", usage)).with_code("E0102"))
+    }
+}
+
+/// List the names in scope at a byte offset into a file, one per line along with what they are
+/// and their type, for an editor to offer as completions
+pub fn complete(opts: &Options) -> Result<(), Error>
+{
+    let (input, offset) = input_and_position(opts, "'compiler complete FILE OFFSET'")?;
+
+    let offset = match offset.parse::<usize>()
+    {
+        Ok(offset) => offset,
+        Err(_) => {return Err(Error::fatal_error(&format!("Bad offset '{}', expected a number of bytes", offset)).with_code("E0102"));}
+    };
+
+    for completion in crate::ide::completions(&input, offset)
+    {
+        println!("{}", completion);
+    }
+
+    Ok(())
+}
+
+/// Rename the function, argument or variable named at a line and column of a file, rewriting every
+/// file it is named in and listing them
+pub fn rename(opts: &Options) -> Result<(), Error>
+{
+    let usage = "'compiler rename FILE LINE COLUMN NEWNAME'";
+    let (input, line) = input_and_position(opts, usage)?;
+
+    let values = opts.get_raw_values();
+
+    let (column, new_name) = match (values.get(3), values.get(4))
+    {
+        (Some(column), Some(new_name)) => (column, new_name),
+        _ => {return Err(Error::fatal_error(&format!("Expected {}", usage)).with_code("E0102"));}
+    };
+
+    let (line, column) = match (line.parse::<usize>(), column.parse::<usize>())
+    {
+        (Ok(line), Ok(column)) => (line, column),
+        _ => {return Err(Error::fatal_error(&format!("Bad position '{}:{}', expected a line and column", line, column)).with_code("E0102"));}
+    };
+
+    for (file, data) in crate::ide::rename(&input, line, column, new_name)?
+    {
+        if std::fs::write(&file, data).is_err()
+        {
+            return Err(Error::fatal_error(&format!("Could not write to '{}'", file)).with_code("E0101"));
+        }
+
+        println!("{}", file);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use tracing::Level;
+use tracing_subscriber::fmt::format::FmtSpan;
+
+use super::{Options, Error, suggest};
+
+/// The levels which can be passed to --log-level, from the fewest messages to the most
+static LOG_LEVELS: &[(&str, Option<Level>)] = &[
+    ("off", None),
+    ("error", Some(Level::ERROR)),
+    ("warn", Some(Level::WARN)),
+    ("info", Some(Level::INFO)),
+    ("debug", Some(Level::DEBUG)),
+    ("trace", Some(Level::TRACE))
+];
+
+/// Send the tracing of the compiler's phases, functions and optimization passes to stderr at the
+/// level given by --log-level, as JSON with --log-json. Nothing is logged unless one of them is
+/// passed, --log-json alone logging at the info level
+pub fn install_logging(opts: &Options) -> Result<(), Error>
+{
+    let name = match opts.map.get("--log-level").and_then(|args| args.first())
+    {
+        Some(name) => name.as_str(),
+        None if opts.has_long_flag("log-json") => "info",
+        None => {return Ok(());}
+    };
+
+    let level = match LOG_LEVELS.iter().find(|(other, _)| *other == name)
+    {
+        Some((_, Some(level))) => *level,
+        Some((_, None)) => {return Ok(());},
+        None =>
+        {
+            let error = Err(Error::fatal_error(&format!("Unknown log level '{}'", name)).with_code("E0102"));
+            return suggest(error, name, LOG_LEVELS.iter().map(|(name, _)| *name));
+        }
+    };
+
+    // Each span is logged as it closes, along with how long was spent in it
+    let builder = tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE)
+        .with_target(false);
+
+    // Another subscriber is only ever installed by an earlier call, which is left in place
+    let _ = if opts.has_long_flag("log-json")
+    {
+        builder.json().with_span_list(true).try_init()
+    }
+    else
+    {
+        builder.with_ansi(false).try_init()
+    };
+
+    Ok(())
+}
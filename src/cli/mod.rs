@@ -2,13 +2,24 @@ mod pages;
 mod options;
 mod errors;
 mod execute;
+mod doctor;
+mod spec;
+mod check;
+mod ide;
+mod codes;
+mod logging;
+mod suggest;
 
 pub use options::*;
 pub use errors::*;
+pub use codes::*;
+pub use suggest::*;
 
 /// Run the application with the given options
 pub fn run(opts: &Options) -> Result<(), Error>
 {
+    logging::install_logging(opts)?;
+
     // Display the help documentation if the help flag is included
     if opts.has_long_flag("help")
     {
@@ -21,6 +32,56 @@ pub fn run(opts: &Options) -> Result<(), Error>
         pages::display_version();
         Ok(())
     }
+    // List the code generation modes which can be passed to -g
+    else if opts.has_long_flag("list-backends")
+    {
+        for name in crate::codegen::CodegenMode::names()
+        {
+            println!("{}", name);
+        }
+
+        Ok(())
+    }
+    // List the optimization passes which can be passed to --dump-ir-before and --dump-ir-after
+    else if opts.has_long_flag("list-passes")
+    {
+        for name in crate::irgen::pass_names()
+        {
+            println!("{}", name);
+        }
+
+        Ok(())
+    }
+    // Describe an error code
+    else if let Some(code) = opts.map.get("--explain").and_then(|args| args.first())
+    {
+        display_explanation(code)
+    }
+    // Check the environment if the doctor subcommand is given
+    else if opts.get_raw_values().first().map(|v| v == "doctor").unwrap_or(false)
+    {
+        doctor::doctor(opts)
+    }
+    // Run the conformance suite if the spec subcommand is given
+    else if opts.get_raw_values().first().map(|v| v == "spec").unwrap_or(false)
+    {
+        spec::spec(opts)
+    }
+    // Report every error in the input files if the check subcommand is given
+    else if opts.get_raw_values().first().map(|v| v == "check").unwrap_or(false)
+    {
+        check::check(opts)
+    }
+    // List the names in scope at a position in a file if the complete subcommand is given
+    else if opts.get_raw_values().first().map(|v| v == "complete").unwrap_or(false)
+    {
+        ide::complete(opts)
+    }
+    // Rename a function, argument or variable if the rename subcommand is given
+    else if opts.get_raw_values().first().map(|v| v == "rename").unwrap_or(false)
+    {
+        ide::rename(opts)
+    }
     else
     {
         execute::execute(opts)
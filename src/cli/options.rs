@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 
 /// Flags which accept arguments
-static ACCEPT_ARGUMENTS: &[&str] = &["-o", "--out", "-g", "-O", "--llvm-target", "--llvm-layout"];
+static ACCEPT_ARGUMENTS: &[&str] = &["-o", "--out", "-g", "-O", "--llvm-target", "--llvm-layout", "--target", "--target-spec", "--emit", "--allow", "--deny", "--log-level", "--dump-ir-before", "--dump-ir-after", "--function", "--split-output", "--explain"];
 
 /// Struct containing information regarding the command line arguments passed
 /// to the application
@@ -2,19 +2,79 @@
 pub fn display_help()
 {
     println!("Usage: compiler [options] file...");
+    println!("       compiler doctor [options]");
+    println!("       compiler spec [options] [directory]");
+    println!("       compiler check [options] file...");
+    println!("       compiler complete file offset");
+    println!("       compiler rename file line column newname");
     println!("Options:");
+    println!("     --allow       [NAME...]   Never give the named warnings");
+    println!("     --compact-output          Leave comments and blank lines out of LLVM output");
+    println!("     --deterministic           Strip build location dependent information from outputs");
+    println!("     --deterministic-check     Compile twice and fail if the outputs differ");
+    println!("     --deny        [NAME...]   Give the named warnings as errors");
+    println!("     --dump-ir-after  [PASS]   Dump the IR to stderr after each run of the given passes");
+    println!("     --dump-ir-before [PASS]   Dump the IR to stderr before each run of the given passes");
+    println!("     --emit        [KIND...]   Emit additional outputs alongside the compiled code");
+    println!("     --explain     [CODE]      Describe the error with the given code");
+    println!("     --forbid-recursion        Give an error for any function which can call itself");
+    println!("     --freestanding            Leave out the builtins which need the C library");
+    println!("     --function    [NAME...]   Restrict IR dumps to the given functions");
+    println!("     --from-irb                Compile IR files written by --emit irb instead of source");
     println!("     --help                    Display this page");
+    println!("     --hosted                  Provide the builtins which need the C library (the default)");
     println!(" -g                [MODE]      Set the code gen mode to use");
+    println!("     --list-backends           List the code gen modes, one per line");
+    println!("     --list-passes             List the optimization passes, one per line");
     println!("     --llvm-layout [LAYOUT]    Sets the target data layout for LLVM");
     println!("     --llvm-target [TARGET]    Sets the target triple for LLVM");
+    println!("     --log-json                Log as JSON, one object per line");
+    println!("     --log-level   [LEVEL]     Log the phases of the compiler to stderr at the given level");
+    println!("     --no-comments             Leave comments out of LLVM output");
     println!("     --nocomp                  Do not collapse register usage");
     println!(" -o                [FILE]      Redirect the output to the given file");
     println!(" -O                [VAL]       Set the optimization level (defaults to 2)");
+    println!("     --readable-names          Name LLVM values after variables and instructions");
+    println!("     --split-output [DIR]      Write each function to its own file in the given directory");
     println!("     --stdout                  Display the output on stdout");
+    println!("     --target      [NAME]      Use one of the built in bare metal targets");
+    println!("     --target-spec [FILE]      Load the target triple, sizes and CPU from a JSON file");
     println!(" -T  --tree                    Display the parse tree");
+    println!(" -Wassign-in-condition         Warn when an assignment is used as the condition of an if or loop");
+    println!(" -Wdangling-else               Warn when an else is lined up with a different if than it belongs to");
+    println!(" -Werror                       Treat warnings as errors");
+    println!(" -Wsign-compare                Warn when integers of different signedness are compared");
     println!("\nAllowable Codegen Modes:");
-    println!("   ir");
-    println!("   llvm");
+
+    for name in crate::codegen::CodegenMode::names()
+    {
+        println!("   {}", name);
+    }
+
+    println!("\nOptimization Passes (for --dump-ir-before and --dump-ir-after):");
+    println!("   all");
+
+    for name in crate::irgen::pass_names()
+    {
+        println!("   {}", name);
+    }
+
+    println!("\nSubcommands:");
+    println!("   check       Report every error in the given files without compiling them");
+    println!("   complete    List the names in scope at a byte offset into a file, with their types");
+    println!("   rename      Rename a function, argument or variable everywhere it is named");
+    println!("   doctor      Check for external tools, validate the target triple and run a smoke test");
+    println!("   spec        Run the conformance suite, interpreting and compiling each program in spec/");
+    println!("\nAllowable Emit Kinds:");
+    println!("   depfile     Makefile style dependency file (written next to the output)");
+    println!("   apidoc      Markdown documentation of each function and its doc comment (written next to the output)");
+    println!("   header      C header declaring the public functions of the library (written next to the output)");
+    println!("   staticlib   Static library of the compiled modules along with their header, using llc and ar (written next to the output)");
+    println!("   xref-json   Cross reference index of the definition and uses of each name, as JSON (written next to the output)");
+    println!("   irb         IR of the library before optimization, for --from-irb (written next to the output)");
+    println!("   stack-report Stack usage of each function and its deepest chain of calls (written next to the output)");
+    println!("   size-report Size of each function in IR instructions and, with -g llvm, bytes of code, largest first (written next to the output)");
+    println!("   callgraph   [dot] Graphviz graph of the calls between functions (written next to the output)");
 }
 
 /// Display the version page
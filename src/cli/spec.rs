@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use super::{Options, Error};
+use super::doctor::run_tool_with_timeout;
+
+/// Directory the conformance suite is read from when no other is given
+static DEFAULT_SPEC_DIRECTORY: &str = "spec";
+
+/// Longest a compiled program may run before it is taken to be stuck in a loop
+static NATIVE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How one way of running a program turned out
+enum Outcome
+{
+    Passed,
+    Failed(String),
+    Skipped(String)
+}
+
+/// Read the exit code a specification program expects from its `// expect: N` line
+fn expected_exit_code(source: &str) -> Option<i32>
+{
+    source.lines()
+        .filter_map(|line| line.trim().strip_prefix("// expect:"))
+        .find_map(|value| value.trim().parse::<i32>().ok())
+}
+
+/// Compare the exit code a program gave with the one expected, exit codes only keeping their
+/// lowest byte as they do when a program returns from main
+fn check_exit_code(code: i128, expected: i32) -> Outcome
+{
+    if code & 0xFF == expected as i128
+    {
+        Outcome::Passed
+    }
+    else
+    {
+        Outcome::Failed(format!("exited with {}, expected {}", code & 0xFF, expected))
+    }
+}
+
+/// Run a program by evaluating its unoptimized IR, starting from main
+fn run_interpreter(input: &crate::io::InputFile, options: &Options, expected: i32) -> Outcome
+{
+    let functions = match crate::compile::lower_functions(input, options, false)
+    {
+        Ok((functions, _, _, _)) => functions,
+        Err(error) => {return Outcome::Failed(format!("{}", error));}
+    };
+
+    let functions: HashMap<String, crate::irgen::Function> = functions.into_iter().map(|function| (function.name.clone(), function)).collect();
+
+    match functions.get("main").and_then(|main| crate::irgen::interpret(main, &[], &functions))
+    {
+        Some(code) => check_exit_code(code, expected),
+        None => Outcome::Skipped(String::from("uses something the interpreter cannot evaluate"))
+    }
+}
+
+/// Run a program by compiling it to LLVM IR and running it with lli
+fn run_native(input: &crate::io::InputFile, options: &Options, expected: i32) -> Outcome
+{
+    let mut llvm_options = options.clone();
+    llvm_options.map.insert(String::from("-g"), vec![String::from("llvm")]);
+
+    let module = match crate::compile::generate(input, &llvm_options, false)
+    {
+        Ok((module, _)) => module,
+        Err(error) => {return Outcome::Failed(format!("{}", error));}
+    };
+
+    match run_tool_with_timeout("lli", &[], Some(&module), Some(NATIVE_TIMEOUT))
+    {
+        Some((Some(code), _)) => check_exit_code(code as i128, expected),
+        Some((None, _)) => Outcome::Failed(String::from("terminated by a signal, or stopped after running for too long")),
+        None => Outcome::Skipped(String::from("lli is not available"))
+    }
+}
+
+/// Print the outcome of one way of running a program
+fn report_outcome(name: &str, path: &str, outcome: &Outcome)
+{
+    match outcome
+    {
+        Outcome::Passed => println!("  \x1b[1m\x1b[32m[ok]\x1b[0m      {:<24} {}", name, path),
+        Outcome::Failed(detail) => println!("  \x1b[1m\x1b[31m[fail]\x1b[0m    {:<24} {}: {}", name, path, detail),
+        Outcome::Skipped(detail) => println!("  \x1b[1m\x1b[33m[skip]\x1b[0m    {:<24} {}: {}", name, path, detail)
+    }
+}
+
+/// Run the conformance suite, checking every program in the spec directory exits with the code it
+/// expects both when interpreted and when compiled
+pub fn spec(opts: &Options) -> Result<(), Error>
+{
+    let directory = opts.get_raw_values().get(1).cloned().unwrap_or_else(|| String::from(DEFAULT_SPEC_DIRECTORY));
+
+    let mut paths: Vec<std::path::PathBuf> = match std::fs::read_dir(&directory)
+    {
+        Ok(entries) => entries.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map(|extension| extension == "pc").unwrap_or(false))
+            .collect(),
+        Err(_) => {return Err(Error::fatal_error(&format!("{}: No such file or directory", directory)).with_code("E0100"));}
+    };
+
+    paths.sort();
+
+    let mut failures = 0;
+
+    println!("Specification ({}):", directory);
+
+    for path in paths
+    {
+        let input = crate::io::InputFile::new(path.display().to_string())?;
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+        let expected = match expected_exit_code(&input.data)
+        {
+            Some(expected) => expected,
+            None =>
+            {
+                report_outcome(&name, "program", &Outcome::Failed(String::from("no '// expect: N' line giving the exit code")));
+                failures += 1;
+                continue;
+            }
+        };
+
+        for (path, outcome) in [("interpreter", run_interpreter(&input, opts, expected)), ("native", run_native(&input, opts, expected))].iter()
+        {
+            if let Outcome::Failed(_) = outcome
+            {
+                failures += 1;
+            }
+
+            report_outcome(&name, path, outcome);
+        }
+    }
+
+    if failures == 0
+    {
+        println!("\nEvery program behaved as specified");
+        Ok(())
+    }
+    else
+    {
+        println!();
+        Err(Error::error(&format!("{} specification check{} failed", failures, if failures == 1 {""} else {"s"})).with_code("E0107"))
+    }
+}
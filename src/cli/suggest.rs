@@ -0,0 +1,78 @@
+use super::Error;
+
+/// Edit distance between two names, counting insertions, deletions, substitutions and swaps of
+/// neighbouring characters (so `whlie` is a single edit from `while`)
+pub fn edit_distance(a: &str, b: &str) -> usize
+{
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    // Distances between every prefix of a and every prefix of b
+    let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in table.iter_mut().enumerate()
+    {
+        row[0] = i;
+    }
+
+    for (j, cell) in table[0].iter_mut().enumerate()
+    {
+        *cell = j;
+    }
+
+    for i in 1..=a.len()
+    {
+        for j in 1..=b.len()
+        {
+            let cost = if a[i - 1] == b[j - 1] {0} else {1};
+
+            table[i][j] = (table[i - 1][j] + 1)
+                .min(table[i][j - 1] + 1)
+                .min(table[i - 1][j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1]
+            {
+                table[i][j] = table[i][j].min(table[i - 2][j - 2] + 1);
+            }
+        }
+    }
+
+    table[a.len()][b.len()]
+}
+
+/// Find the candidate closest to a name which failed to resolve, if any is close enough to be a
+/// likely typo, ties going to the earliest candidate
+pub fn closest_match<'a>(name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str>
+{
+    // Short names allow a single edit, longer names one edit for every three characters
+    let limit = (name.chars().count() / 3).max(1);
+
+    let mut best: Option<(usize, &str)> = None;
+
+    for candidate in candidates
+    {
+        if candidate == name
+        {
+            continue;
+        }
+
+        let distance = edit_distance(name, candidate);
+
+        if distance <= limit && best.map(|(d, _)| distance < d).unwrap_or(true)
+        {
+            best = Some((distance, candidate));
+        }
+    }
+
+    best.map(|(_, candidate)| candidate)
+}
+
+/// Attach a "did you mean" hint to an error if one of the candidates is close to the name
+pub fn suggest<'a, T>(result: Result<T, Error>, name: &str, candidates: impl IntoIterator<Item = &'a str>) -> Result<T, Error>
+{
+    result.map_err(|error| match closest_match(name, candidates)
+    {
+        Some(candidate) => error.with_hint(&format!("did you mean `{}`?", candidate)),
+        None => error
+    })
+}
@@ -0,0 +1,231 @@
+use crate::irgen::{DataType, NonPtrType};
+
+use super::TargetSpec;
+
+/// Calling convention of a target, giving where arguments and return values are passed, which
+/// registers a call may change, and how the stack is laid out
+#[derive(Debug, Clone, PartialEq)]
+pub struct Abi
+{
+    pub name: &'static str,
+    pub argument_registers: &'static [&'static str],
+    pub return_registers: &'static [&'static str],
+    pub callee_saved: &'static [&'static str],
+    pub caller_saved: &'static [&'static str],
+    pub register_bytes: usize,
+    pub stack_alignment: usize,
+    pub max_register_return: usize // Larger values are returned through a pointer given by the caller
+}
+
+/// Calling conventions of the architectures with known type sizes, the AVR registers are given
+/// as the lower register of each pair, as arguments are passed in pairs
+static ABIS: &[(&[&str], Abi)] = &[
+    (&["x86_64"], Abi
+    {
+        name: "System V AMD64",
+        argument_registers: &["rdi", "rsi", "rdx", "rcx", "r8", "r9"],
+        return_registers: &["rax", "rdx"],
+        callee_saved: &["rbx", "rbp", "r12", "r13", "r14", "r15"],
+        caller_saved: &["rax", "rcx", "rdx", "rsi", "rdi", "r8", "r9", "r10", "r11"],
+        register_bytes: 8,
+        stack_alignment: 16,
+        max_register_return: 16
+    }),
+    (&["i386", "i686"], Abi
+    {
+        name: "System V i386",
+        argument_registers: &[],
+        return_registers: &["eax", "edx"],
+        callee_saved: &["ebx", "esi", "edi", "ebp"],
+        caller_saved: &["eax", "ecx", "edx"],
+        register_bytes: 4,
+        stack_alignment: 16,
+        max_register_return: 8
+    }),
+    (&["aarch64", "aarch64_be"], Abi
+    {
+        name: "AAPCS64",
+        argument_registers: &["x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7"],
+        return_registers: &["x0", "x1"],
+        callee_saved: &["x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x29"],
+        caller_saved: &["x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13", "x14", "x15", "x16", "x17", "x18"],
+        register_bytes: 8,
+        stack_alignment: 16,
+        max_register_return: 16
+    }),
+    (&["arm", "armv7", "thumbv7em"], Abi
+    {
+        name: "AAPCS",
+        argument_registers: &["r0", "r1", "r2", "r3"],
+        return_registers: &["r0", "r1"],
+        callee_saved: &["r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11"],
+        caller_saved: &["r0", "r1", "r2", "r3", "r12"],
+        register_bytes: 4,
+        stack_alignment: 8,
+        max_register_return: 4
+    }),
+    (&["riscv64"], Abi
+    {
+        name: "RISC-V LP64",
+        argument_registers: &["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"],
+        return_registers: &["a0", "a1"],
+        callee_saved: &["s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11"],
+        caller_saved: &["ra", "t0", "t1", "t2", "t3", "t4", "t5", "t6", "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"],
+        register_bytes: 8,
+        stack_alignment: 16,
+        max_register_return: 16
+    }),
+    (&["riscv32"], Abi
+    {
+        name: "RISC-V ILP32",
+        argument_registers: &["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"],
+        return_registers: &["a0", "a1"],
+        callee_saved: &["s0", "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11"],
+        caller_saved: &["ra", "t0", "t1", "t2", "t3", "t4", "t5", "t6", "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"],
+        register_bytes: 4,
+        stack_alignment: 16,
+        max_register_return: 8
+    }),
+    (&["avr"], Abi
+    {
+        name: "avr-gcc",
+        argument_registers: &["r24", "r22", "r20", "r18", "r16", "r14", "r12", "r10", "r8"],
+        return_registers: &["r24", "r22", "r20", "r18"],
+        callee_saved: &["r2", "r3", "r4", "r5", "r6", "r7", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15", "r16", "r17", "r28", "r29"],
+        caller_saved: &["r0", "r18", "r19", "r20", "r21", "r22", "r23", "r24", "r25", "r26", "r27", "r30", "r31"],
+        register_bytes: 2,
+        stack_alignment: 1,
+        max_register_return: 8
+    })
+];
+
+/// Where a single argument is passed
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentLocation
+{
+    Registers(Vec<&'static str>),
+    Stack(usize) // The offset from the stack pointer at the call
+}
+
+/// Where a return value is passed
+#[derive(Debug, Clone, PartialEq)]
+pub enum ReturnLocation
+{
+    Nothing,
+    Registers(Vec<&'static str>),
+    Memory // Through a pointer passed by the caller as a hidden first argument
+}
+
+/// Offsets of the slots in a stack frame, and the size of the frame once aligned
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameLayout
+{
+    pub offsets: Vec<usize>,
+    pub size: usize
+}
+
+impl Abi
+{
+    /// Get the calling convention of an architecture
+    pub fn for_architecture(arch: &str) -> Option<&'static Abi>
+    {
+        ABIS.iter().find(|(names, _)| names.contains(&arch)).map(|(_, abi)| abi)
+    }
+
+    /// Get the calling convention of a target, the host being treated as x86_64
+    pub fn for_target(target: &TargetSpec) -> Option<&'static Abi>
+    {
+        match &target.triple
+        {
+            Some(triple) => Self::for_architecture(triple.split('-').next().unwrap_or_default()),
+            None => Self::for_architecture("x86_64")
+        }
+    }
+
+    /// Check if a register keeps its value across a call
+    pub fn is_callee_saved(&self, register: &str) -> bool
+    {
+        self.callee_saved.contains(&register)
+    }
+
+    /// Number of registers needed to hold a value of a type
+    fn registers_for(&self, datatype: &DataType, target: &TargetSpec) -> usize
+    {
+        target.bytes_size_of(datatype).div_ceil(self.register_bytes).max(1)
+    }
+
+    /// Work out where each argument of a call is passed, arguments are given registers in order
+    /// until one does not fit, after which they all go on the stack
+    pub fn classify_arguments(&self, arguments: &[DataType], target: &TargetSpec) -> Vec<ArgumentLocation>
+    {
+        let mut next_register = 0;
+        let mut stack: usize = 0;
+        let mut registers_full = false;
+
+        arguments.iter().map(|datatype|
+        {
+            let needed = self.registers_for(datatype, target);
+
+            if !registers_full && next_register + needed <= self.argument_registers.len()
+            {
+                let registers = self.argument_registers[next_register..next_register + needed].to_vec();
+                next_register += needed;
+
+                ArgumentLocation::Registers(registers)
+            }
+            else
+            {
+                registers_full = true;
+
+                let align = target.align_of(datatype);
+                let offset = stack.div_ceil(align) * align;
+                stack = offset + target.bytes_size_of(datatype);
+
+                ArgumentLocation::Stack(offset)
+            }
+        }).collect()
+    }
+
+    /// Work out where a value of the given type is returned
+    pub fn classify_return(&self, datatype: &DataType, target: &TargetSpec) -> ReturnLocation
+    {
+        if datatype.raw_type == NonPtrType::Void && datatype.num_ptr == 0
+        {
+            return ReturnLocation::Nothing;
+        }
+
+        let needed = self.registers_for(datatype, target);
+
+        if target.bytes_size_of(datatype) > self.max_register_return || needed > self.return_registers.len()
+        {
+            ReturnLocation::Memory
+        }
+        else
+        {
+            ReturnLocation::Registers(self.return_registers[..needed].to_vec())
+        }
+    }
+
+    /// Lay out slots of the given sizes and alignments in a stack frame, the frame being padded
+    /// to the alignment of the stack
+    pub fn frame_layout(&self, slots: &[(usize, usize)]) -> FrameLayout
+    {
+        let mut offsets = vec![];
+        let mut size: usize = 0;
+
+        for (bytes, align) in slots
+        {
+            let align = (*align).max(1);
+            let offset = size.div_ceil(align) * align;
+
+            offsets.push(offset);
+            size = offset + bytes;
+        }
+
+        FrameLayout
+        {
+            offsets,
+            size: size.div_ceil(self.stack_alignment) * self.stack_alignment
+        }
+    }
+}
@@ -1,116 +1,153 @@
 use crate::cli::Error;
-use crate::irgen::{Function, OpCode, Value, Symbol};
+use crate::irgen::{Function, OpCode, Value, Symbol, get_value_type};
 
-use super::{generate_comment, generate_label, generate_command, get_label, get_size_datatype};
+use super::{generate_comment, generate_label, generate_command, get_label, get_size_datatype, register_count};
+use super::super::{Abi, Allocation, FrameLayout, RegisterFile, allocate_registers, uses_defs};
 
 use std::collections::HashMap;
 
+/// Registers a call may change which are kept back from holding values, r0 is the temporary of
+/// the calling convention and X and Z are used for indexing
+static RESERVED_REGISTERS: &[&str] = &["r0", "r26", "r27", "r30", "r31"];
+
+/// Pairs of registers a spilled value is loaded into for the instruction using it, as an
+/// instruction names at most three values. Z is not otherwise used, and the others are kept by a
+/// call, so are saved by a function which spills
+static SPILL_REGISTERS: &[usize] = &[30, 14, 12];
+
+/// Addresses of the status register and the stack pointer in the I/O space
+const SREG: usize = 0x3f;
+const SPL: usize = 0x3d;
+const SPH: usize = 0x3e;
+
+/// Largest displacement `ldd` and `std` can address from the frame pointer
+const MAX_DISPLACEMENT: usize = 63;
+
 /// A wrapper for giving a context to code generation for an avrasm function
-pub struct FunctionGenerationContext
+pub struct FunctionGenerationContext<'a>
 {
-    free_registers: Vec<usize>,
-    function: Function,
-    symbol_map: HashMap<String, usize>,
+    function: &'a Function,
+    abi: &'static Abi,
+    allocation: Allocation,
+    frame: FrameLayout,
+    reloads: HashMap<String, usize>, // Registers the spilled values of the current instruction are loaded into
     temp_reg: usize,
     last_temp_assignment: String
 }
 
-impl FunctionGenerationContext
+impl<'a> FunctionGenerationContext<'a>
 {
     /// Generate a new FunctionGeneratorContext for the given IR function
-    pub fn new(function: Function) -> Self
+    pub fn new(function: &'a Function) -> Self
     {
+        // Values are only held in registers a call may change, so nothing needs saving
+        let abi = Abi::for_architecture("avr").unwrap();
+
+        let file = RegisterFile
+        {
+            registers: abi.caller_saved.iter()
+                .filter(|reg| !RESERVED_REGISTERS.contains(reg))
+                .map(|reg| reg[1..].parse::<usize>().unwrap())
+                .collect(),
+            aligned: true
+        };
+
+        let allocation = allocate_registers(function, &file, &register_count);
+
+        // Each spilled value is given a slot of its own in the stack frame
+        let mut slots = vec![(0, 1); allocation.slots];
+        for symbol in function.get_all_symbols()
+        {
+            if let Some(slot) = allocation.spilled.get(&symbol.key())
+            {
+                slots[*slot].0 = register_count(&symbol.datatype);
+            }
+        }
+
         Self
         {
+            frame: abi.frame_layout(&slots),
+            allocation,
+            abi,
             function,
-            free_registers: vec![25, 23, 22, 21, 20, 19, 18, 17],
-            symbol_map: HashMap::new(),
+            reloads: HashMap::new(),
             temp_reg: 16,
             last_temp_assignment: String::new()
         }
     }
 
-    /// Request a new 8 bit register
-    pub fn get_u8_reg(&mut self) -> Result<usize, Error>
+    /// Get the register allocated to the given symbol, or the one it is loaded into if it was spilled
+    pub fn get_register(&mut self, symb: &Symbol) -> Result<usize, Error>
     {
-        if self.free_registers.len() == 0
-        {
-            Err(Error::error("No more registers available"))
-        }
-        else
+        match self.allocation.registers.get(&symb.key()).or_else(|| self.reloads.get(&symb.key()))
         {
-            Ok(self.free_registers.pop().unwrap())
+            Some(reg) => Ok(*reg),
+
+            None if self.allocation.spilled.contains_key(&symb.key()) => Err(Error::fatal_error(&format!("Spilled value '{}' was not loaded", symb.key())).with_code("E0200")),
+
+            // Only values of 8 and 16 bits are given registers
+            None => Err(Error::error(&format!("Values of type '{}' are not supported by the AVR assembly backend", symb.datatype)).with_code("E0200"))
         }
     }
 
-    /// Request a new 16 bit register
-    pub fn get_u16_reg(&mut self) -> Result<usize, Error>
+    /// Get the offset from the frame pointer of the slot a spilled symbol is kept in
+    fn slot_offset(&self, key: &str) -> usize
     {
-        if self.free_registers.len() == 0
-        {
-            Err(Error::error("No more registers available"))
-        }
-        else
-        {
-            for i in 0..self.free_registers.len()
-            {
-                // The register must be even
-                if self.free_registers[i] % 2 == 0
-                {
-                    // And have register n + 1 also be available
-                    if self.free_registers.contains(&(self.free_registers[i] + 1))
-                    {
-                        // Remove register n
-                        let v = self.free_registers[i];
-                        self.free_registers.remove(i);
+        // The stack pointer points below the last byte pushed, so the frame starts one above it
+        self.frame.offsets[self.allocation.spilled[key]] + 1
+    }
 
-                        // Remove register n + 1
-                        let j = self.free_registers.iter().position(|&x| x == v + 1).unwrap();
-                        self.free_registers.remove(j);
+    /// Load the spilled values an instruction uses into registers before it, giving the stores
+    /// writing the spilled values it defines back to their slots after it
+    fn reload_spilled(&mut self, index: usize) -> Result<(String, String), Error>
+    {
+        let function = self.function;
+        let (uses, defs) = uses_defs(function, index);
 
-                        return Ok(v)
-                    }
-                }
-            }
+        let mut loads = String::new();
+        let mut stores = String::new();
 
-            Err(Error::error("No more registers available"))
-        }
-    }
+        self.reloads.clear();
 
-    /// Get the register allocated to the given symbol
-    pub fn get_register(&mut self, symb: &Symbol) -> Result<usize, Error>
-    {
-        // If the symbol map contains the symbol, just return the register previously recorded
-        if self.symbol_map.contains_key(&symb.title)
+        for arg in &function.instructions[&index].arguments
         {
-            Ok(self.symbol_map.get(&symb.title).unwrap().clone())
-        }
-        else
-        {
-            // Get an 8 bit register if the datatype is 8 bits
-            if get_size_datatype(symb.datatype) == 1
+            let symbol = match arg
             {
-                let reg = self.get_u8_reg()?;
+                Value::Symbol(symbol) => symbol,
+                _ => continue
+            };
 
-                self.symbol_map.insert(symb.title.clone(), reg.clone());
+            let key = symbol.key();
 
-                Ok(reg)
-            }
-            // Get an 16 bit register if the datatype is 16 bits
-            else if get_size_datatype(symb.datatype) == 2
+            if !self.allocation.spilled.contains_key(&key) || self.reloads.contains_key(&key)
             {
-                let reg = self.get_u16_reg()?;
+                continue;
+            }
 
-                self.symbol_map.insert(symb.title.clone(), reg.clone());
+            let reg = SPILL_REGISTERS[self.reloads.len()];
+            let offset = self.slot_offset(&key);
+            let size = register_count(&symbol.datatype);
 
-                Ok(reg)
+            if uses.contains(&key)
+            {
+                for byte in 0..size
+                {
+                    loads += &generate_command(&format!("ldd r{}, Y+{}", reg + byte, offset + byte))?;
+                }
             }
-            // Otherwise, panic because there should never be a size other that these two so far
-            else
+
+            if defs.contains(&key)
             {
-                panic!()
+                for byte in 0..size
+                {
+                    stores += &generate_command(&format!("std Y+{}, r{}", offset + byte, reg + byte))?;
+                }
             }
+
+            self.reloads.insert(key, reg);
         }
+
+        Ok((loads, stores))
     }
 
     /// Add a move instruction between two values (the force_move flag forces a move, ignoring references)
@@ -118,7 +155,9 @@ impl FunctionGenerationContext
     {
         match value
         {
-            Value::Label(_) => {Err(Error::fatal_error("Cannot use label as a value"))},
+            Value::Label(_) => {Err(Error::fatal_error("Cannot use label as a value").with_code("E0200"))},
+            Value::Data(_) => {Err(Error::error("String literals are not supported by the AVR assembly backend").with_code("E0200"))},
+            Value::Global(_) => {Err(Error::error("Global variables are not supported by the AVR assembly backend").with_code("E0200"))},
             Value::Literal(lit) =>
             {
                 // Moving a literal into a symbol
@@ -146,7 +185,7 @@ impl FunctionGenerationContext
                         let reg = self.get_register(symb)?;
                         
                         // Load the reference into the X index register
-                        result += &generate_command(&format!("movw r26, {}", reg))?;
+                        result += &generate_command(&format!("movw r26, r{}", reg))?;
 
                         let new_temp = format!("{}", lit.value & 0xFF);
                         if self.last_temp_assignment != new_temp
@@ -156,7 +195,7 @@ impl FunctionGenerationContext
                         }
 
                         // Store the low byte
-                        result += &generate_command("st X, r16")?;
+                        result += &generate_command("st X+, r16")?;
 
                         // If needed store the high byte
                         if get_size_datatype(lit.datatype) == 2
@@ -168,7 +207,7 @@ impl FunctionGenerationContext
                                 result += &generate_command(&format!("ldi r16, {}", self.last_temp_assignment))?;
                             }
 
-                            result += &generate_command("st +X, r16")?;
+                            result += &generate_command("st X, r16")?;
                         }
 
                         Ok(result)
@@ -201,7 +240,7 @@ impl FunctionGenerationContext
 
                             if get_size_datatype(lit.datatype) == 2
                             {
-                                return Err(Error::error("Cannot assign 16 bit value to an 8 bit register"))
+                                return Err(Error::error("Cannot assign 16 bit value to an 8 bit register").with_code("E0200"))
                             }
                         }
                         // Otherwise the st command will need to be used
@@ -219,7 +258,7 @@ impl FunctionGenerationContext
                             }
 
                             // Store the low byte
-                            result += &generate_command("st X, r16")?;
+                            result += &generate_command("st X+, r16")?;
 
                             // If needed store the high byte
                             if get_size_datatype(lit.datatype) == 2
@@ -231,7 +270,7 @@ impl FunctionGenerationContext
                                     result += &generate_command(&format!("ldi r16, {}", self.last_temp_assignment))?;
                                 }
 
-                                result += &generate_command("st +X, r16")?;
+                                result += &generate_command("st X, r16")?;
                             }
                         }
 
@@ -278,15 +317,15 @@ impl FunctionGenerationContext
                         let reg = self.get_register(symb)?;
                         
                         // Load the destination into the X register
-                        result += &generate_command(&format!("movw r26, {}", reg))?;
+                        result += &generate_command(&format!("movw r26, r{}", reg))?;
 
                         // Write the low byte
-                        result += &generate_command(&format!("st X, r{}", src_reg))?;
+                        result += &generate_command(&format!("st X+, r{}", src_reg))?;
 
                         // Write the high byte if needed
                         if get_size_datatype(src_symb.datatype) == 2
                         {
-                            result += &generate_command(&format!("st +X, r{}", src_reg + 1))?;
+                            result += &generate_command(&format!("st X, r{}", src_reg + 1))?;
                         }
 
                         Ok(result)
@@ -312,7 +351,7 @@ impl FunctionGenerationContext
 
                             if get_size_datatype(src_symb.datatype) == 2
                             {
-                                return Err(Error::error("Cannot assign 16 bit value to an 8 bit register"))
+                                return Err(Error::error("Cannot assign 16 bit value to an 8 bit register").with_code("E0200"))
                             }
                         }
                         else
@@ -322,12 +361,12 @@ impl FunctionGenerationContext
                             result += &generate_command(&format!("ldi r27, {}", (target_lit.value & 0xFF00) >> 8))?;
 
                             // Store the low byte
-                            result += &generate_command(&format!("st X, r{}", src_reg))?;
+                            result += &generate_command(&format!("st X+, r{}", src_reg))?;
 
                             // Store the high byte if needed
                             if get_size_datatype(src_symb.datatype) == 2
                             {
-                                result += &generate_command(&format!("st +X, r{}", src_reg + 1))?;
+                                result += &generate_command(&format!("st X, r{}", src_reg + 1))?;
                             }
                         }
 
@@ -348,7 +387,9 @@ impl FunctionGenerationContext
     {
         match value
         {
-            Value::Label(_) => {Err(Error::fatal_error("Cannot use label as a value"))},
+            Value::Label(_) => {Err(Error::fatal_error("Cannot use label as a value").with_code("E0200"))},
+            Value::Data(_) => {Err(Error::error("String literals are not supported by the AVR assembly backend").with_code("E0200"))},
+            Value::Global(_) => {Err(Error::error("Global variables are not supported by the AVR assembly backend").with_code("E0200"))},
             Value::Literal(lit) =>
             {
                 // Derefencing a literal into a register
@@ -381,12 +422,12 @@ impl FunctionGenerationContext
                         result += &generate_command(&format!("ldi r27, {}", (lit.value & 0xFF00) >> 8))?;
 
                         // Load the low byte
-                        result += &generate_command(&format!("ld r{}, X", reg))?;
+                        result += &generate_command(&format!("ld r{}, X+", reg))?;
 
                         // Load the high byte if needed
                         if get_size_datatype(symb.datatype) == 2
                         {
-                            result += &generate_command(&format!("ld r{}, +X", reg + 1))?;
+                            result += &generate_command(&format!("ld r{}, X", reg + 1))?;
                         }
 
                         Ok(result)
@@ -411,12 +452,12 @@ impl FunctionGenerationContext
                     result += &generate_command(&format!("movw r26, r{}", src_reg))?;
 
                     // Load the low byte
-                    result += &generate_command(&format!("ld r{}, X", reg))?;
+                    result += &generate_command(&format!("ld r{}, X+", reg))?;
 
                     // Load the high byte if needed
                     if get_size_datatype(symb.datatype) == 2
                     {
-                        result += &generate_command(&format!("ld r{}, +X", reg + 1))?;
+                        result += &generate_command(&format!("ld r{}, X", reg + 1))?;
                     }
 
                     Ok(result)
@@ -440,18 +481,20 @@ impl FunctionGenerationContext
         }
         else
         {
-            return Err(Error::error("Unable to assign to anything but a symbol"));
+            return Err(Error::error("Unable to assign to anything but a symbol").with_code("E0200"));
         };
 
         match v0
         {
-            Value::Label(_) => {Err(Error::fatal_error("Cannot use label as a value"))},
+            Value::Label(_) => {Err(Error::fatal_error("Cannot use label as a value").with_code("E0200"))},
+            Value::Data(_) => {Err(Error::error("String literals are not supported by the AVR assembly backend").with_code("E0200"))},
+            Value::Global(_) => {Err(Error::error("Global variables are not supported by the AVR assembly backend").with_code("E0200"))},
             Value::Literal(_) =>
             {
                 // This should have been cleaned up by the IR gen
                 if let Value::Literal(_) = v1
                 {
-                    Err(Error::error("Add command invoked with two literals"))
+                    Err(Error::error("Add command invoked with two literals").with_code("E0200"))
                 }
                 // If having a literal as the first argument can be solved by reversing the order of the operands, do so
                 else
@@ -510,25 +553,128 @@ impl FunctionGenerationContext
                     }
                     else
                     {
-                        return Err(Error::error("Unable to read value from label"));
+                        return Err(Error::error("Unable to read value from label").with_code("E0200"));
                     }
 
                     Ok(result)
                 }
-                // A standard + (Not yet implemented)
+                // Adding to the second operand in place needs no move
+                else if dest == v1
+                {
+                    self.add_instruction(dest, v1, v0)
+                }
+                // Otherwise move the first operand into the destination and add to it there
                 else
                 {
-                    unimplemented!()
+                    let mut result = self.move_instruction(dest, v0, true)?;
+                    result += &self.add_instruction(dest, dest, v1)?;
+
+                    Ok(result)
                 }
             },
         }
     }
 
+    /// Add an index instruction, stepping a pointer by a number of elements
+    pub fn index_instruction(&mut self, dest: &Value, base: &Value, offset: &Value) -> Result<String, Error>
+    {
+        let dest_reg = if let Value::Symbol(symb) = dest
+        {
+            self.get_register(symb)?
+        }
+        else
+        {
+            return Err(Error::error("Unable to assign to anything but a symbol").with_code("E0200"));
+        };
+
+        let mut element_type = match get_value_type(base)
+        {
+            Some(datatype) if datatype.num_ptr > 0 => datatype,
+            _ => {return Err(Error::error("Expected a pointer to index").with_code("E0200"));}
+        };
+
+        element_type.num_ptr -= 1;
+        element_type.is_ref = false;
+
+        let size = match register_count(&element_type)
+        {
+            0 => {return Err(Error::error(&format!("Pointers to '{}' are not supported by the AVR assembly backend", element_type)).with_code("E0200"));},
+            size => size
+        };
+
+        let mut result = String::new();
+
+        // Start from the base pointer, unless the pointer is stepped in place
+        if dest != base
+        {
+            if offset == dest
+            {
+                return Err(Error::error("Unable to index by the pointer being assigned").with_code("E0200"));
+            }
+
+            match base
+            {
+                Value::Symbol(symb) => {result += &generate_command(&format!("movw r{}, r{}", dest_reg, self.get_register(symb)?))?;},
+                Value::Literal(lit) =>
+                {
+                    result += &generate_command(&format!("ldi r{}, {}", dest_reg, lit.value & 0xFF))?;
+                    result += &generate_command(&format!("ldi r{}, {}", dest_reg + 1, (lit.value & 0xFF00) >> 8))?;
+                },
+                _ => {return Err(Error::error("Unable to index anything but a symbol or a literal").with_code("E0200"));}
+            }
+        }
+
+        match offset
+        {
+            // A constant offset is subtracted negated, as there is no add immediate for a pair
+            Value::Literal(lit) =>
+            {
+                let bytes = -(lit.value * size as i128) & 0xFFFF;
+
+                if bytes != 0
+                {
+                    result += &generate_command(&format!("subi r{}, {}", dest_reg, bytes & 0xFF))?;
+                    result += &generate_command(&format!("sbci r{}, {}", dest_reg + 1, (bytes & 0xFF00) >> 8))?;
+                }
+            },
+            // Otherwise the offset is added once for each byte of an element
+            Value::Symbol(symb) =>
+            {
+                let offset_reg = self.get_register(symb)?;
+
+                let high = match get_size_datatype(symb.datatype)
+                {
+                    2 => format!("r{}", offset_reg + 1),
+                    // r1 always holds zero, and the sign of a signed byte is spread through r16
+                    _ if symb.datatype.is_signed() =>
+                    {
+                        result += &generate_command(&format!("mov r16, r{}", offset_reg))?;
+                        result += &generate_command("lsl r16")?;
+                        result += &generate_command("sbc r16, r16")?;
+                        self.last_temp_assignment = String::new();
+
+                        String::from("r16")
+                    },
+                    _ => String::from("r1")
+                };
+
+                for _ in 0..size
+                {
+                    result += &generate_command(&format!("add r{}, r{}", dest_reg, offset_reg))?;
+                    result += &generate_command(&format!("adc r{}, {}", dest_reg + 1, high))?;
+                }
+            },
+            _ => {return Err(Error::error("Unable to index by anything but a symbol or a literal").with_code("E0200"));}
+        }
+
+        Ok(result)
+    }
+
     // Add a branch operation
     pub fn add_branch(&mut self, inst: &str, v0: &Value, v1: &Value, l0: &Value, l1: &Value) -> Result<String, Error>
     {
-        let label0 = if let Value::Label(s) = l0 {s} else {return Err(Error::error("Expected a label"));};
-        let label1 = if let Value::Label(s) = l1 {s} else {return Err(Error::error("Expected a label"));};
+        let label0 = if let Value::Label(s) = l0 {s} else {return Err(Error::error("Expected a label").with_code("E0200"));};
+        let label1 = if let Value::Label(s) = l1 {s} else {return Err(Error::error("Expected a label").with_code("E0200"));};
 
         if let Value::Symbol(symb0) = v0
         {
@@ -580,21 +726,177 @@ impl FunctionGenerationContext
         }
     }
 
+    /// Get the registers a function which spills must save on entry, the frame pointer and the
+    /// pairs spilled values are loaded into which a call keeps
+    fn frame_saved_registers(&self) -> Vec<usize>
+    {
+        if self.frame.size == 0
+        {
+            return vec![];
+        }
+
+        let mut result = vec![28, 29];
+        result.extend(SPILL_REGISTERS.iter()
+            .filter(|reg| self.abi.is_callee_saved(&format!("r{}", reg)))
+            .flat_map(|reg| [*reg, *reg + 1]));
+
+        result
+    }
+
+    /// Get the registers an interrupt handler must save, as it may interrupt code using any of
+    /// them: every register a value is allocated to (and the one after, for values of two bytes),
+    /// along with the temporary and index registers the lowering uses and those of the frame.
+    /// r0 is saved along with the status register
+    fn interrupt_saved_registers(&self) -> Vec<usize>
+    {
+        let mut result: Vec<usize> = self.allocation.registers.values().flat_map(|reg| [*reg, *reg + 1]).collect();
+        result.extend([self.temp_reg, 26, 27, 30, 31]);
+        result.extend(self.frame_saved_registers());
+
+        result.sort_unstable();
+        result.dedup();
+
+        result
+    }
+
+    /// Save the status register and the registers an interrupt handler uses on entry to it
+    fn interrupt_prologue(&self) -> Result<String, Error>
+    {
+        let mut result = generate_command("push r0")?;
+        result += &generate_command(&format!("in r0, {:#x}", SREG))?;
+        result += &generate_command("push r0")?;
+
+        for reg in self.interrupt_saved_registers()
+        {
+            result += &generate_command(&format!("push r{}", reg))?;
+        }
+
+        Ok(result)
+    }
+
+    /// Restore what the prologue of an interrupt handler saved and return from the interrupt
+    fn interrupt_epilogue(&self) -> Result<String, Error>
+    {
+        let mut result = String::new();
+
+        for reg in self.interrupt_saved_registers().iter().rev()
+        {
+            result += &generate_command(&format!("pop r{}", reg))?;
+        }
+
+        result += &generate_command("pop r0")?;
+        result += &generate_command(&format!("out {:#x}, r0", SREG))?;
+        result += &generate_command("pop r0")?;
+        result += &generate_command("reti")?;
+
+        Ok(result)
+    }
+
+    /// Point Y at the stack frame a function keeps its spilled values in, and move the stack pointer
+    /// below it. The stack pointer is written with interrupts held off, the write to SREG taking
+    /// effect after the instruction following it
+    fn frame_prologue(&self) -> Result<String, Error>
+    {
+        if self.frame.size == 0
+        {
+            return Ok(String::new());
+        }
+
+        let mut result = generate_command(&format!("in r28, {:#x}", SPL))?;
+        result += &generate_command(&format!("in r29, {:#x}", SPH))?;
+        result += &generate_command(&format!("sbiw r28, {}", self.frame.size))?;
+        result += &self.write_stack_pointer()?;
+
+        Ok(result)
+    }
+
+    /// Release the stack frame of a function before it returns
+    fn frame_epilogue(&self) -> Result<String, Error>
+    {
+        if self.frame.size == 0
+        {
+            return Ok(String::new());
+        }
+
+        let mut result = generate_command(&format!("adiw r28, {}", self.frame.size))?;
+        result += &self.write_stack_pointer()?;
+
+        Ok(result)
+    }
+
+    /// Copy Y into the stack pointer
+    fn write_stack_pointer(&self) -> Result<String, Error>
+    {
+        let mut result = generate_command(&format!("in r0, {:#x}", SREG))?;
+        result += &generate_command("cli")?;
+        result += &generate_command(&format!("out {:#x}, r29", SPH))?;
+        result += &generate_command(&format!("out {:#x}, r0", SREG))?;
+        result += &generate_command(&format!("out {:#x}, r28", SPL))?;
+
+        Ok(result)
+    }
+
+    /// Restore what the function saved on entry and return from it
+    fn epilogue(&self) -> Result<String, Error>
+    {
+        let mut result = String::new();
+
+        for reg in self.frame_saved_registers().iter().rev()
+        {
+            result += &generate_command(&format!("pop r{}", reg))?;
+        }
+
+        result += &generate_command("ret")?;
+
+        Ok(result)
+    }
+
     /// Render an IR function in AVR Assembly
     pub fn render_function(&mut self) -> Result<String, Error>
     {
         let mut result = String::new();
 
+        crate::ice::set_function(&self.function.name);
+
+        // Nothing would run a constructor before main in the assembly
+        if self.function.attribute("constructor").is_some()
+        {
+            return Err(Error::error(&format!("Constructor '{}' cannot be run by the AVR assembly backend", self.function.name)).with_code("E0200"));
+        }
+
         // Add the comment at the top of the function
         result += &generate_comment(&format!("Function {}", self.function.render_signature()))?;
 
         // Add the label marking the start of the function
         result += &generate_label(&format!("f{}", self.function.name))?;
 
+        // The displacement of ldd and std limits the size of the frame
+        if self.frame.size > MAX_DISPLACEMENT
+        {
+            return Err(Error::error(&format!("Function '{}' spills more than the {} bytes the AVR assembly backend can address", self.function.name, MAX_DISPLACEMENT)).with_code("E0200"));
+        }
+
+        let interrupt = self.function.attribute("interrupt").is_some();
+
+        if interrupt
+        {
+            result += &self.interrupt_prologue()?;
+        }
+        else
+        {
+            for reg in self.frame_saved_registers()
+            {
+                result += &generate_command(&format!("push r{}", reg))?;
+            }
+        }
+
+        result += &self.frame_prologue()?;
+
         // Iterate over each instruction (in order)
         for i in 0..self.function.instructions.len()
         {
             let inst = self.function.instructions.get(&i).unwrap().clone();
+            crate::ice::set_instruction(format!("{}", inst));
 
             // If there are labels available for a given instruction, write those in
             if let Some(labels) = self.function.labels.get(&i)
@@ -604,7 +906,10 @@ impl FunctionGenerationContext
                     result += &generate_label(&get_label(&self.function, label)?)?;
                 }
             }
-            
+
+            let (loads, stores) = self.reload_spilled(i)?;
+            result += &loads;
+
             match inst.opcode
             {
                 OpCode::Nop => {},
@@ -618,10 +923,11 @@ impl FunctionGenerationContext
                     }
                 },
 
-                // Mov Alloc and Cast are all wrappers for moves
+                // Mov Alloc and Cast are all wrappers for moves, a cast into a reference makes the
+                // reference rather than writing through it
                 OpCode::Mov | OpCode::Alloc | OpCode::Cast =>
                 {
-                    result += self.move_instruction(&inst.arguments[0], &inst.arguments[1], false)?.as_str();
+                    result += self.move_instruction(&inst.arguments[0], &inst.arguments[1], inst.opcode == OpCode::Cast)?.as_str();
                 },
 
                 // Dereference
@@ -636,6 +942,12 @@ impl FunctionGenerationContext
                     result += self.add_instruction(&inst.arguments[0], &inst.arguments[1], &inst.arguments[2])?.as_str();
                 },
 
+                // Index
+                OpCode::Index =>
+                {
+                    result += self.index_instruction(&inst.arguments[0], &inst.arguments[1], &inst.arguments[2])?.as_str();
+                },
+
                 // All of the branches
 
                 // Branch Equal
@@ -675,9 +987,24 @@ impl FunctionGenerationContext
                     // Reverse Branches
                     result += self.add_branch("brlo", &inst.arguments[0], &inst.arguments[1], &inst.arguments[3], &inst.arguments[2])?.as_str();
                 },
+                OpCode::Ret if !inst.arguments.is_empty() =>
+                {
+                    return Err(Error::error("Returning a value is not yet supported").with_code("E0200"));
+                },
+                OpCode::Ret =>
+                {
+                    result += &self.frame_epilogue()?;
+                    result += &if interrupt {self.interrupt_epilogue()?} else {self.epilogue()?};
+                },
+                OpCode::Member =>
+                {
+                    return Err(Error::error("Structs are not supported by the AVR assembly backend").with_code("E0200"));
+                },
                 _ => {panic!("Not yet implemented conversion for\n{}", inst)
                 }
             }
+
+            result += &stores;
         }
 
         Ok(result)
@@ -1,19 +1,22 @@
+use std::fmt;
+
 use crate::cli::Error;
 use crate::irgen::{Function};
 
-use super::FunctionGenerationContext;
+use super::{FunctionGenerationContext, AvrMachineModel};
+use super::super::{ListScheduler, schedule_assembly, output_error};
 
 /// Wrapper for the AVR Assembly Code Generator
 #[derive(Debug, Clone)]
-pub struct AvrAsmGenerator
+pub struct AvrAsmGenerator<'a>
 {
-    functions: Vec<Function>
+    functions: &'a [&'a Function]
 }
 
-impl AvrAsmGenerator
+impl<'a> AvrAsmGenerator<'a>
 {
-    /// Generate a new AvrAsmGenerator from a vector of IR functions
-    pub fn new(functions: Vec<Function>) -> Self
+    /// Generate a new AvrAsmGenerator from the IR functions it borrows
+    pub fn new(functions: &'a [&'a Function]) -> Self
     {
         Self
         {
@@ -21,17 +24,18 @@ impl AvrAsmGenerator
         }
     }
 
-    /// Render each function in turn
-    pub fn render(self) -> Result<String, Error>
+    /// Render each function in turn, then schedule the instructions in it, writing each function
+    /// to `out` once it is scheduled
+    pub fn render(self, out: &mut dyn fmt::Write) -> Result<(), Error>
     {
-        let mut result = String::new();
+        let scheduler = ListScheduler::new();
 
         for function in self.functions
         {
             let mut context = FunctionGenerationContext::new(function);
-            result += &format!("{}", context.render_function()?);
+            out.write_str(&schedule_assembly(&context.render_function()?, &AvrMachineModel, &scheduler)).map_err(output_error)?;
         }
 
-        Ok(result)
+        Ok(())
     }
 }
\ No newline at end of file
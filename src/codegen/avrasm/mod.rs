@@ -1,7 +1,9 @@
 mod generation;
 mod functions;
 mod utils;
+mod schedule;
 
 pub use generation::*;
 pub use functions::*;
 pub use utils::*;
+pub use schedule::*;
@@ -0,0 +1,130 @@
+use super::super::{MachineInstruction, MachineModel};
+
+/// Description of the AVR instructions the code generator emits, for the scheduler
+pub struct AvrMachineModel;
+
+/// Instructions which change the status register
+static SETS_FLAGS: &[&str] = &["add", "adc", "sub", "sbc", "subi", "sbci", "and", "andi", "or", "ori", "eor", "inc", "dec", "cp", "cpc", "cpi", "tst", "neg", "com", "lsl", "lsr", "rol", "ror", "asr"];
+
+/// Instructions which read the status register (the carry flag)
+static USES_FLAGS: &[&str] = &["adc", "sbc", "sbci", "cpc", "rol", "ror"];
+
+/// Instructions which only read their first operand, rather than writing to it
+static COMPARES: &[&str] = &["cp", "cpc", "cpi", "tst"];
+
+impl MachineModel for AvrMachineModel
+{
+    fn describe(&self, text: &str) -> MachineInstruction
+    {
+        let mut parts = text.splitn(2, char::is_whitespace);
+        let mnemonic = parts.next().unwrap_or_default();
+        let operands: Vec<&str> = parts.next().unwrap_or_default().split(',').map(|operand| operand.trim()).filter(|operand| !operand.is_empty()).collect();
+
+        let mut defs = vec![];
+        let mut uses = vec![];
+
+        match mnemonic
+        {
+            // Moves and loads of immediates write their first operand
+            "ldi" | "mov" =>
+            {
+                defs.extend(registers_of(operands[0]));
+                uses.extend(operands[1..].iter().flat_map(|operand| registers_of(operand)));
+            },
+
+            // Word moves write a pair of registers
+            "movw" =>
+            {
+                defs.extend(register_pair(operands[0]));
+                uses.extend(register_pair(operands[1]));
+            },
+
+            // Loads and stores through X, Y or Z, which may move the pointer
+            "ld" =>
+            {
+                defs.extend(registers_of(operands[0]));
+                uses.extend(registers_of(operands[1]));
+                uses.push(String::from("memory"));
+
+                if operands[1].contains('+') || operands[1].contains('-')
+                {
+                    defs.extend(registers_of(operands[1]));
+                }
+            },
+            "st" =>
+            {
+                uses.extend(registers_of(operands[0]));
+                uses.extend(registers_of(operands[1]));
+                defs.push(String::from("memory"));
+
+                if operands[0].contains('+') || operands[0].contains('-')
+                {
+                    defs.extend(registers_of(operands[0]));
+                }
+            },
+
+            // Reading and writing I/O ports are kept in order with each other
+            "in" =>
+            {
+                defs.extend(registers_of(operands[0]));
+                defs.push(String::from("io"));
+            },
+            "out" =>
+            {
+                uses.extend(registers_of(operands[1]));
+                defs.push(String::from("io"));
+            },
+
+            // Arithmetic reads and (except for compares) writes its first operand
+            _ if SETS_FLAGS.contains(&mnemonic) =>
+            {
+                uses.extend(operands.iter().flat_map(|operand| registers_of(operand)));
+
+                if !COMPARES.contains(&mnemonic)
+                {
+                    defs.extend(registers_of(operands[0]));
+                }
+            },
+
+            // Anything else (jumps, branches, calls and returns) is left where it is
+            _ => {return MachineInstruction::barrier(text);}
+        }
+
+        if SETS_FLAGS.contains(&mnemonic)
+        {
+            defs.push(String::from("SREG"));
+        }
+
+        if USES_FLAGS.contains(&mnemonic)
+        {
+            uses.push(String::from("SREG"));
+        }
+
+        MachineInstruction::new(text, defs, uses)
+    }
+}
+
+/// Get the registers named by an operand, the pointer registers X, Y and Z are each made of two
+fn registers_of(operand: &str) -> Vec<String>
+{
+    let name = operand.trim_matches(|c| c == '+' || c == '-');
+
+    match name
+    {
+        "X" => vec![String::from("r26"), String::from("r27")],
+        "Y" => vec![String::from("r28"), String::from("r29")],
+        "Z" => vec![String::from("r30"), String::from("r31")],
+        _ if name.starts_with('r') && name[1..].parse::<usize>().is_ok() => vec![String::from(name)],
+        _ => vec![]
+    }
+}
+
+/// Get the pair of registers starting at an even register, used by word instructions
+fn register_pair(operand: &str) -> Vec<String>
+{
+    match operand.strip_prefix('r').and_then(|number| number.parse::<usize>().ok())
+    {
+        Some(number) => vec![format!("r{}", number), format!("r{}", number + 1)],
+        None => vec![]
+    }
+}
@@ -43,4 +43,17 @@ pub fn get_size_datatype(t: DataType) -> usize
     {
         2
     }
+}
+
+/// Get the number of registers needed to hold a value of a datatype, or zero if it can not be held
+/// in registers, a reference holding the address it refers to
+pub fn register_count(t: &DataType) -> usize
+{
+    match t.raw_type
+    {
+        _ if t.num_ptr > 0 || t.is_ref => 2,
+        NonPtrType::I8 | NonPtrType::U8 => 1,
+        NonPtrType::I16 | NonPtrType::U16 => 2,
+        _ => 0
+    }
 }
\ No newline at end of file
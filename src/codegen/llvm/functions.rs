@@ -1,11 +1,27 @@
 use crate::cli::Error;
 
-use crate::irgen::{Function, DataType, NonPtrType, Symbol, Value, OpCode, get_value_type};
+use crate::irgen::{Function, DataType, NonPtrType, Symbol, Value, OpCode, get_value_type, correct_type_references};
 
-use super::{convert_to_llvm, bytes_size_of};
+use super::{convert_to_llvm, intrinsic_suffix, hosted_runtime, data_name};
+use super::super::TargetSpec;
 
 use std::collections::HashMap;
 
+/// How an interrupt handler is marked on each architecture LLVM supports them for, by the calling
+/// convention and function attributes it is given, and whether it is passed the interrupt frame
+/// and error code
+static INTERRUPT_CONVENTIONS: &[(&str, &str, &str, bool)] = &[
+    ("x86_64", "x86_intrcc ", "", true),
+    ("i386", "x86_intrcc ", "", true),
+    ("i686", "x86_intrcc ", "", true),
+    ("arm", "", " \"interrupt\"=\"IRQ\"", false),
+    ("armv7", "", " \"interrupt\"=\"IRQ\"", false),
+    ("thumbv7em", "", " \"interrupt\"=\"IRQ\"", false),
+    ("riscv32", "", " \"interrupt\"=\"machine\"", false),
+    ("riscv64", "", " \"interrupt\"=\"machine\"", false),
+    ("avr", "avr_intrcc ", "", false)
+];
+
 /// A value held in llvm
 pub struct LLVMValue
 {
@@ -42,19 +58,31 @@ impl LLVMValue
 }
 
 /// A wrapper for giving a context to code generation for an LLVM function
-pub struct FunctionGenerationContext
+pub struct FunctionGenerationContext<'a>
 {
-    func: Function,
+    func: &'a Function,
     values: HashMap<String, LLVMValue>,
     next_temp: usize,
     result: String,
     current_arguments: String,
+    target: &'a TargetSpec,
+    pub intrinsics: Vec<String>,
+    pub metadata: Vec<String>,
+    pub next_metadata: usize,
+    pub strings: Vec<Vec<u8>>,
+    loop_metadata: HashMap<String, usize>,
+    pub readable_names: bool,
+    pub internal_linkage: bool,
+    temp_hint: String,
+    name_counts: HashMap<String, usize>,
+    block_labels: HashMap<String, String>,
+    terminated: bool
 }
 
-impl FunctionGenerationContext
+impl<'a> FunctionGenerationContext<'a>
 {
     /// Generate a new function generation context object
-    pub fn new(func: Function) -> Self
+    pub fn new(func: &'a Function, target: &'a TargetSpec) -> Self
     {
         Self
         {
@@ -62,125 +90,278 @@ impl FunctionGenerationContext
             values: HashMap::new(),
             next_temp: 0,
             result: String::new(),
-            current_arguments: String::new()
+            current_arguments: String::new(),
+            target,
+            intrinsics: vec![],
+            metadata: vec![],
+            next_metadata: 0,
+            strings: vec![],
+            loop_metadata: HashMap::new(),
+            readable_names: false,
+            internal_linkage: true,
+            temp_hint: String::from("arg"),
+            name_counts: HashMap::new(),
+            block_labels: HashMap::new(),
+            terminated: false
         }
     }
 
-    /// Insert a new command
+    /// Insert a new command, which never follows the terminator of a block as the IR is checked to
+    /// start a new block with a label after each one
     pub fn insert_command(&mut self, cmd: &str)
     {
         self.result += &format!("    {}\n", cmd);
+
+        self.terminated = cmd.starts_with("br ") || cmd.starts_with("ret ") || cmd == "unreachable";
+    }
+
+    /// Record an intrinsic which needs declaring in the module
+    pub fn declare_intrinsic(&mut self, declaration: String)
+    {
+        if !self.intrinsics.contains(&declaration)
+        {
+            self.intrinsics.push(declaration);
+        }
+    }
+
+    /// Get the metadata to attach to a branch at the given instruction, which is only given if the
+    /// branch is a back edge to the header of a loop with hints
+    pub fn loop_hints_for(&mut self, index: usize, targets: &[Value]) -> String
+    {
+        for target in targets
+        {
+            let position = match target
+            {
+                Value::Label(label) => match self.func.labels_reverse.get(label)
+                {
+                    Some(position) if *position <= index => *position,
+                    _ => continue
+                },
+                _ => continue
+            };
+
+            // Jumps may have been redirected to another label at the same position as the header
+            let header = self.func.labels.get(&position).and_then(|labels| labels.iter().find(|label| self.func.loop_hints.contains_key(*label))).cloned();
+
+            if let Some(header) = header
+            {
+                if !self.loop_metadata.contains_key(&header)
+                {
+                    // The loop's node refers to itself, followed by a node for each hint
+                    let node = self.next_metadata;
+                    let mut items = vec![format!("!{}", node)];
+                    let mut hints = vec![];
+
+                    for attribute in &self.func.loop_hints[&header]
+                    {
+                        let hint = match attribute.name.as_str()
+                        {
+                            "vectorize" => String::from("!{!\"llvm.loop.vectorize.enable\", i1 true}"),
+                            "unroll" => format!("!{{!\"llvm.loop.unroll.count\", i32 {}}}", attribute.arguments[0]),
+                            _ => continue
+                        };
+
+                        let id = node + 1 + hints.len();
+
+                        items.push(format!("!{}", id));
+                        hints.push(format!("!{} = {}", id, hint));
+                    }
+
+                    self.next_metadata += 1 + hints.len();
+
+                    self.metadata.push(format!("!{} = distinct !{{{}}}", node, items.join(", ")));
+                    self.metadata.extend(hints);
+                    self.loop_metadata.insert(header.clone(), node);
+                }
+
+                return format!(", !llvm.loop !{}", self.loop_metadata[&header]);
+            }
+        }
+
+        String::new()
     }
 
-    /// Insert a label
+    /// Insert a label, branching to it from the block before unless that block already ends in a
+    /// terminator
     pub fn insert_label(&mut self, label: &str)
     {
-        let l = self.render_value(&Value::Label(String::from(label)), false);
-        self.insert_command(&format!("br {}", l));
-        
+        if !self.terminated
+        {
+            let l = format!("label %{}", self.block_labels.get(label).map(String::as_str).unwrap_or(label));
+            self.insert_command(&format!("br {}", l));
+        }
+
         self.result += &format!("\n  {}:\n", label);
+        self.terminated = false;
     }
 
-    /// Get the next temporary variable
+    /// Get the next temporary variable, named after the instruction being generated when
+    /// readable names are used
     pub fn get_next_temp(&mut self) -> String
     {
+        if self.readable_names
+        {
+            let hint = format!("{}.tmp", self.temp_hint);
+            return self.get_named_temp(&hint);
+        }
+
         self.next_temp += 1;
         format!("%V{}", self.next_temp - 1)
     }
 
+    /// Get a temporary variable named after the given hint when readable names are used, a number
+    /// is added for the second and later uses of a name
+    pub fn get_named_temp(&mut self, hint: &str) -> String
+    {
+        if !self.readable_names
+        {
+            return self.get_next_temp();
+        }
+
+        let count = self.name_counts.entry(String::from(hint)).or_insert(0);
+        *count += 1;
+
+        if *count == 1
+        {
+            format!("%{}", hint)
+        }
+        else
+        {
+            format!("%{}.{}", hint, *count - 1)
+        }
+    }
+
     /// Create a new value
-    pub fn create_new_value(&mut self, title: String, datatype: DataType)
+    pub fn create_new_value(&mut self, symbol: &Symbol)
     {
         // Create the raw object
-        let ptr = self.get_next_temp();
-        let new_value = LLVMValue::new(ptr, datatype);
-        self.values.insert(title.clone(), new_value);
+        let ptr = self.get_named_temp(&format!("{}.addr", symbol.title));
+        let new_value = LLVMValue::new(ptr, symbol.datatype);
+        self.values.insert(symbol.key(), new_value);
+
+        let dt = self.values.get(&symbol.key()).unwrap().get_datatype();
+        let ptr = self.values.get(&symbol.key()).unwrap().ptr.clone();
 
-        let dt = self.values.get(&title).unwrap().get_datatype();
-        let ptr = self.values.get(&title).unwrap().ptr.clone();
+        // A declared variable may ask for more than the natural alignment of its type, registers
+        // copy the type of the values they are made from so their alignment is not used
+        let requested = if symbol.is_register() {0} else {self.func.symbol_table.get(&symbol.title).and_then(|declared| declared.datatype.align).unwrap_or(0) as usize};
+        let align = self.target.align_of(&dt).max(requested);
 
         // Add the command
         self.insert_command(
             &format!("{} = alloca {}, align {}", 
                             ptr, 
                             convert_to_llvm(&dt), 
-                            bytes_size_of(&dt)));
+                            align));
     }
 
-    /// Get the reference for a variable
-    pub fn get_reference(&mut self, var: &Symbol, include_type: bool) -> String
+    /// Every variable is allocated at the start of the function from the symbols it uses, so one
+    /// missing here can only come from IR which was not made by the IR generator
+    fn check_allocated(&self, var: &Symbol) -> Result<(), Error>
     {
-        // If the variable is not already stored, create it
-        if !self.values.contains_key(&var.title)
+        if !self.values.contains_key(&var.key())
         {
-            self.create_new_value(var.title.clone(), var.datatype);
+            return Err(Error::error(&format!("Variable '{}' is used in '{}' but was never allocated", var.title, self.func.name)).with_code("E0114"));
         }
-        let ptr = self.values.get(&var.title).unwrap().ptr.clone();
-        let pdt = self.values.get(&var.title).unwrap().get_pointer_datatype();
+
+        Ok(())
+    }
+
+    /// Get the reference for a variable
+    pub fn get_reference(&mut self, var: &Symbol, include_type: bool) -> Result<String, Error>
+    {
+        self.check_allocated(var)?;
+
+        let ptr = self.values.get(&var.key()).unwrap().ptr.clone();
+        let pdt = self.values.get(&var.key()).unwrap().get_pointer_datatype();
 
         if include_type
         {
-            format!("{} {}",convert_to_llvm(&pdt), ptr)
+            Ok(format!("{} {}",convert_to_llvm(&pdt), ptr))
         }
         else
         {
-            ptr
+            Ok(ptr)
         }
     }
 
     /// Get the value for a variable
-    pub fn get_value(&mut self, var: &Symbol, include_type: bool) -> String
+    pub fn get_value(&mut self, var: &Symbol, include_type: bool) -> Result<String, Error>
     {
-        // If the variable is not already stored, create it
-        if !self.values.contains_key(&var.title)
-        {
-            self.create_new_value(var.title.clone(), var.datatype);
-        }
+        self.check_allocated(var)?;
+
         
-        let reg = self.get_next_temp();
-        let dt = self.values.get(&var.title).unwrap().get_datatype();
-        let pdt = self.values.get(&var.title).unwrap().get_pointer_datatype();
-        let ptr = self.values.get(&var.title).unwrap().ptr.clone();
+        let reg = self.get_named_temp(&var.title);
+        let dt = self.values.get(&var.key()).unwrap().get_datatype();
+        let pdt = self.values.get(&var.key()).unwrap().get_pointer_datatype();
+        let ptr = self.values.get(&var.key()).unwrap().ptr.clone();
 
         self.insert_command(&format!("{} = load {}, {} {}, align {}", 
                                         reg, 
                                         convert_to_llvm(&dt),
                                         convert_to_llvm(&pdt),
                                         ptr,
-                                        bytes_size_of(&var.datatype)));
+                                        self.target.align_of(&var.datatype)));
 
         if include_type
         {
             if !(dt.raw_type == NonPtrType::Void && dt.num_ptr == 0)
             {
-                format!("{} {}", convert_to_llvm(&dt), reg)
+                Ok(format!("{} {}", convert_to_llvm(&dt), reg))
             }
             else
             {
-                format!("{}", convert_to_llvm(&dt))
+                Ok(format!("{}", convert_to_llvm(&dt)))
             }
         }
         else
         {
-            reg
+            Ok(reg)
         }
     }
 
     /// Render a value for direct insertion into a command
-    pub fn render_value(&mut self, val: &Value, include_type: bool) -> String
+    pub fn render_value(&mut self, val: &Value, include_type: bool) -> Result<String, Error>
     {
-        match val
+        let rendered = match val
         {
             Value::Label(label) =>
             {
-                format!("label %{}", label)
+                format!("label %{}", self.block_labels.get(label).unwrap_or(label))
             },
             Value::Literal(literal) =>
             {
                 // If the type isn't void
                 if !(literal.datatype.raw_type == NonPtrType::Void && literal.datatype.num_ptr == 0)
                 {
-                    if literal.datatype.num_ptr == 0 && !literal.datatype.is_ref
+                    // A struct is only ever given a literal to start it zeroed
+                    if literal.datatype.is_struct() && !literal.datatype.is_ref
+                    {
+                        if include_type
+                        {
+                            format!("{} zeroinitializer", convert_to_llvm(&literal.datatype))
+                        }
+                        else
+                        {
+                            String::from("zeroinitializer")
+                        }
+                    }
+                    // A literal vector has the value in every lane
+                    else if let Some(element_type) = literal.datatype.element_type()
+                    {
+                        let lane = format!("{} {}", convert_to_llvm(&element_type), literal.value);
+                        let splat = format!("<{}>", vec![lane; literal.datatype.lanes()].join(", "));
+
+                        if include_type
+                        {
+                            format!("{} {}", convert_to_llvm(&literal.datatype), splat)
+                        }
+                        else
+                        {
+                            splat
+                        }
+                    }
+                    else if literal.datatype.num_ptr == 0 && !literal.datatype.is_ref
                     {
                         if include_type
                         {
@@ -191,15 +372,27 @@ impl FunctionGenerationContext
                             format!("{}", literal.value)
                         }
                     }
+                    // Null pointers
+                    else if literal.value == 0
+                    {
+                        if include_type
+                        {
+                            format!("{} null", convert_to_llvm(&literal.datatype))
+                        }
+                        else
+                        {
+                            String::from("null")
+                        }
+                    }
                     else
                     {
                         if include_type
                         {
-                            format!("{0} inttoptr (i64 {1} to {0})", convert_to_llvm(&literal.datatype), literal.value)
+                            format!("{0} inttoptr ({2} {1} to {0})", convert_to_llvm(&literal.datatype), literal.value, self.target.pointer_int())
                         }
                         else
                         {
-                            format!("inttoptr (i64 {1} to {0})", convert_to_llvm(&literal.datatype), literal.value)
+                            format!("inttoptr ({2} {1} to {0})", convert_to_llvm(&literal.datatype), literal.value, self.target.pointer_int())
                         }
                     }
                 }
@@ -217,13 +410,38 @@ impl FunctionGenerationContext
             },
             Value::Symbol(symbol) =>
             {
-                self.get_value(symbol, include_type)
+                self.get_value(symbol, include_type)?
+            },
+            // Constant data is a global of the module, shared by every use of the same bytes
+            Value::Data(bytes) =>
+            {
+                let index = match self.strings.iter().position(|other| other == bytes)
+                {
+                    Some(index) => index,
+                    None =>
+                    {
+                        self.strings.push(bytes.clone());
+                        self.strings.len() - 1
+                    }
+                };
+
+                let array = format!("[{} x i8]", bytes.len() + 1);
+                let pointer = format!("getelementptr inbounds ({0}, {0}* {1}, i64 0, i64 0)", array, data_name(index));
+
+                if include_type {format!("i8* {}", pointer)} else {pointer}
+            },
+            // A global variable is named by its address
+            Value::Global(symbol) =>
+            {
+                if include_type {format!("{} @{}", convert_to_llvm(&symbol.datatype), symbol.title)} else {format!("@{}", symbol.title)}
             }
-        }
+        };
+
+        Ok(rendered)
     }
 
     /// Render a pointer to a value for direct insertion into a command
-    pub fn render_pointer(&mut self, val: &Value) -> String
+    pub fn render_pointer(&mut self, val: &Value) -> Result<String, Error>
     {
         match val
         {
@@ -231,7 +449,7 @@ impl FunctionGenerationContext
             {
                 panic!("The pointer of a label?!")
             },
-            Value::Literal(_) =>
+            Value::Literal(_) | Value::Data(_) | Value::Global(_) =>
             {
                 panic!("The pointer of a literal?!")
             },
@@ -243,14 +461,14 @@ impl FunctionGenerationContext
     }
 
     /// Add a move via the syntax of the 'store' command
-    pub fn add_move(&mut self, dest: &Value, src: String)
+    pub fn add_move(&mut self, dest: &Value, src: String) -> Result<(), Error>
     {
         if let Some(datatype) = get_value_type(dest)
         {
             // If the data type is not a reference, just store the value into a pointer to the first
             if !datatype.is_ref
             {
-                let val0 = self.render_pointer(dest);
+                let val0 = self.render_pointer(dest)?;
                 self.insert_command(
                             &format!("store {}, {}", 
                                         src,
@@ -259,34 +477,79 @@ impl FunctionGenerationContext
             // Otherwise, the target *is* the pointer
             else
             {
-                let val0 = self.render_value(dest, true);
+                let val0 = self.render_value(dest, true)?;
                 self.insert_command(
                             &format!("store {}, {}", 
                                         src,
                                         val0));
             }
         }
+
+        Ok(())
     }
 
     /// Add a compare command
-    pub fn add_compare(&mut self, command: String, dest: String, src0: &Value, src1: &Value)
+    pub fn add_compare(&mut self, command: String, dest: String, src0: &Value, src1: &Value) -> Result<(), Error>
     {
-        let val0 = self.render_value(src0, true);
-        let val1 = self.render_value(src1, false);
+        let val0 = self.render_value(src0, true)?;
+        let val1 = self.render_value(src1, false)?;
 
         self.insert_command(&format!("{} = icmp {} {}, {}", dest,  command, val0, val1));
+
+        Ok(())
+    }
+
+    /// Get the calling convention and attributes marking the function as an interrupt handler on
+    /// the target architecture, and whether it is passed the interrupt frame, making sure it takes
+    /// the arguments the architecture passes
+    fn interrupt_convention(&self) -> Result<(&'static str, &'static str, bool), Error>
+    {
+        let architecture = self.target.architecture();
+
+        let (_, convention, attribute, frame) = match INTERRUPT_CONVENTIONS.iter().find(|(name, ..)| *name == architecture)
+        {
+            Some(convention) => convention,
+            None => {return Err(Error::error(&format!("Interrupt handlers are not supported on the '{}' architecture", architecture)).with_code("E0200"));}
+        };
+
+        let bad = |text: String| Err(Error::error(&format!("Interrupt handler '{}' {}", self.func.name, text)).with_code("E0038"));
+
+        if !frame && !self.func.arguments.is_empty()
+        {
+            return bad(format!("cannot take arguments on the '{}' architecture", architecture));
+        }
+
+        // The error code is pushed as a whole word
+        if let Some((_, code)) = self.func.arguments.get(1)
+        {
+            if self.target.bytes_size_of(code) != self.target.pointer_size
+            {
+                return bad(format!("must take an error code of {} bits on the '{}' architecture", self.target.pointer_size * 8, architecture));
+            }
+        }
+
+        Ok((convention, attribute, *frame))
     }
 
     /// Render an IR function in LLVM IR
     pub fn render_function(&mut self) -> Result<String, Error>
     {
-        // Clone the function to avoid borrow issues later
-        let func = self.func.clone();
+        // Copy out the borrow of the function so it outlives the borrows of the generator below
+        let func = self.func;
+
+        crate::ice::set_function(&func.name);
 
         self.result = String::new();
 
+        // Interrupt handlers are given the calling convention or attribute of the architecture
+        let (convention, interrupt_attribute, frame) = match func.attribute("interrupt")
+        {
+            Some(_) => self.interrupt_convention()?,
+            None => ("", "", false)
+        };
+
         // Function return type and name
-        self.result += &format!("define {} @{}", convert_to_llvm(&func.return_type), func.name);
+        self.result += &format!("define {}{}{} @{}", if func.internal && self.internal_linkage {"internal "} else {""}, convention, convert_to_llvm(&func.return_type), func.name);
 
         // Arguments
         self.result += "(";
@@ -296,7 +559,24 @@ impl FunctionGenerationContext
         for (i, (name, datatype)) in func.arguments.iter().enumerate()
         {
             let s = format!("{} %{}", convert_to_llvm(datatype), name);
-            self.result += &s;
+
+            // The interrupt frame is pushed by the processor, so is passed on the stack
+            if frame && i == 0
+            {
+                let pointee = DataType { num_ptr: datatype.num_ptr - 1, ..*datatype };
+                let pointee = if pointee.is_void() && pointee.num_ptr == 0 {String::from("i8")} else {convert_to_llvm(&pointee)};
+
+                self.result += &format!("{} byval({}) %{}", convert_to_llvm(datatype), pointee, name);
+            }
+            // Restrict pointers promise LLVM that nothing else reaches the memory they point to
+            else if datatype.restrict
+            {
+                self.result += &format!("{} noalias %{}", convert_to_llvm(datatype), name);
+            }
+            else
+            {
+                self.result += &s;
+            }
 
             if i < func.arguments.len() - 1
             {
@@ -306,38 +586,93 @@ impl FunctionGenerationContext
             argument_names.push((name.clone(), s));
         }
 
-        self.result += ")\n";
+        self.result += ")";
+
+        if func.attribute("noreturn").is_some()
+        {
+            self.result += " noreturn";
+        }
+
+        self.result += interrupt_attribute;
+
+        if let Some(cpu) = &self.target.cpu
+        {
+            self.result += &format!(" \"target-cpu\"=\"{}\"", cpu);
+        }
+
+        if let Some(features) = &self.target.features
+        {
+            self.result += &format!(" \"target-features\"=\"{}\"", features);
+        }
+
+        // The section follows every function attribute, and its name is kept with its quotes
+        if let Some(section) = func.attribute("section")
+        {
+            self.result += &format!(" section {}", section.arguments[0]);
+        }
+
+        self.result += "\n";
 
         // Body
 
         self.result += "{\n";
 
-        // Allocate all of the space required for the symbols
-        for symbol in func.get_all_symbols()
+        // Arguments and labels share the names of values, so readable names must avoid them
+        for name in func.arguments.iter().map(|(name, _)| name).chain(func.labels_reverse.keys())
+        {
+            self.name_counts.insert(name.clone(), 1);
+        }
+
+        // Allocate all of the space required for the symbols, in the order they are first used,
+        // variables taking their type from the symbol table
+        for mut symbol in func.get_all_symbols()
         {
-            if symbol.datatype.raw_type == NonPtrType::Void
+            if !symbol.is_register()
+            {
+                match func.symbol_table.get(&symbol.title)
+                {
+                    Some(declared) => {symbol.datatype = declared.datatype;},
+                    None => {return Err(Error::error(&format!("Variable '{}' is used in '{}' but is not in its symbol table", symbol.title, func.name)).with_code("E0114"));}
+                }
+            }
+
+            if symbol.datatype.raw_type == NonPtrType::Void || self.values.contains_key(&symbol.key())
             {
                 continue;
             }
 
-            self.create_new_value(symbol.title.clone(), symbol.datatype);
+            self.create_new_value(&symbol);
 
             // If the symbol is an argument, load the argument into the value
             for arg in &argument_names
             {
-                if symbol.title.clone() == arg.0
+                if !symbol.is_register() && symbol.title == arg.0
+                {
+                    self.add_move(&Value::Symbol(symbol.clone()), arg.1.clone())?;
+                }
+            }
+        }
+
+        // Labels placed at the same instruction all start one block, named after the first of them
+        self.block_labels = HashMap::new();
+
+        for labels in func.labels.values()
+        {
+            if let Some(first) = labels.first()
+            {
+                for label in labels
                 {
-                    self.add_move(&Value::Symbol(symbol.clone()), arg.1.clone());
+                    self.block_labels.insert(label.clone(), first.clone());
                 }
             }
         }
 
+        self.terminated = false;
+
         // Go over every instruction
         for i in 0..self.func.instructions.len()
         {
-            let labels = if let Some(l) = func.labels.get(&i).clone() {l.clone()} else {vec![]};
-            
-            for label in labels
+            if let Some(label) = func.labels.get(&i).and_then(|labels| labels.first())
             {
                 self.insert_label(label.as_str());
             }
@@ -347,21 +682,33 @@ impl FunctionGenerationContext
 
             if let Some(inst) = &func.instructions.get(&i)
             {
-                self.result += &format!("\n; {}\n", inst);
+                let inst_text = format!("{}", inst);
+                crate::ice::set_instruction(inst_text.clone());
+
+                self.result += &format!("\n; {}\n", inst_text);
+
+                self.temp_hint = format!("{:?}", inst.opcode).to_lowercase();
 
                 match &inst.opcode
                 {
                     // Return Command
                     OpCode::Ret =>
                     {
-                        let val = self.render_value(&inst.arguments[0], true).clone();
-                        self.insert_command(&format!("ret {}", val));
+                        if inst.arguments.is_empty()
+                        {
+                            self.insert_command("ret void");
+                        }
+                        else
+                        {
+                            let val = self.render_value(&inst.arguments[0], true)?.clone();
+                            self.insert_command(&format!("ret {}", val));
+                        }
                     },
                     // Move or Allocate
                     OpCode::Mov | OpCode::Alloc =>
                     {
-                        let val = self.render_value(&inst.arguments[1], true);
-                        self.add_move(&inst.arguments[0], val);
+                        let val = self.render_value(&inst.arguments[1], true)?;
+                        self.add_move(&inst.arguments[0], val)?;
                     },
                     // Cast
                     OpCode::Cast =>
@@ -370,108 +717,165 @@ impl FunctionGenerationContext
                         let dest_type = get_value_type(&inst.arguments[0]).unwrap();
                         let src_type = get_value_type(&inst.arguments[1]).unwrap();
 
-                        // Get the sizes of the types
-                        let dest_size = bytes_size_of(&dest_type);
-                        let src_size = bytes_size_of(&src_type);
+                        // Get the widths of the types
+                        let dest_size = self.target.bits_of(&dest_type);
+                        let src_size = self.target.bits_of(&src_type);
+
+                        // Pointers are converted to and from integers the size of a pointer
+                        let pointer_int = self.target.pointer_int();
 
-                        let mut current = self.render_value(&inst.arguments[1], false);
+                        let mut current = self.render_value(&inst.arguments[1], false)?;
 
                         let mut current_type = convert_to_llvm(&src_type);
 
-                        if src_type.num_ptr > 0
+                        // Casting a pointer to a reference makes the reference point to the same place, so the
+                        // pointer is stored as the reference itself rather than through it
+                        if dest_type.is_ref && !src_type.is_ref && src_type.num_ptr > 0
                         {
-                            let next = self.get_next_temp();
-                            self.insert_command(&format!("{} = ptrtoint {} {} to i64", next, current_type, current));
-                            current = next;
-                            current_type = String::from("i64");
+                            let mut ptr_type = dest_type;
+                            ptr_type.is_ref = false;
+                            ptr_type.num_ptr += 1;
+
+                            if convert_to_llvm(&ptr_type) != current_type
+                            {
+                                let next = self.get_next_temp();
+                                self.insert_command(&format!("{} = bitcast {} {} to {}", next, current_type, current, convert_to_llvm(&ptr_type)));
+                                current = next;
+                                current_type = convert_to_llvm(&ptr_type);
+                            }
+
+                            let slot = self.render_pointer(&inst.arguments[0])?;
+                            self.insert_command(&format!("store {} {}, {}", current_type, current, slot));
+
+                            continue;
                         }
+                        // Vectors with as many lanes convert each lane, otherwise they are the same size
+                        // and the bits are reinterpreted
+                        else if dest_type.is_vector() && src_type.is_vector()
+                        {
+                            if convert_to_llvm(&dest_type) != current_type
+                            {
+                                let operation = if dest_type.lanes() != src_type.lanes() || dest_size == src_size
+                                {
+                                    "bitcast"
+                                }
+                                else if dest_size < src_size
+                                {
+                                    "trunc"
+                                }
+                                else if src_type.is_signed()
+                                {
+                                    "sext"
+                                }
+                                else
+                                {
+                                    "zext"
+                                };
 
-                        if convert_to_llvm(&dest_type) != convert_to_llvm(&src_type)
+                                let next = self.get_next_temp();
+                                self.insert_command(&format!("{} = {} {} {} to {}", next, operation, current_type, current, convert_to_llvm(&dest_type)));
+                                current = next;
+                                current_type = convert_to_llvm(&dest_type);
+                            }
+                        }
+                        // Casting between pointers only ever needs a bitcast
+                        else if src_type.num_ptr > 0 && dest_type.num_ptr > 0
                         {
-                            // If the destination is smaller, truncation is necessary
-                            if dest_size < src_size && current_type != if dest_type.num_ptr == 0 {convert_to_llvm(&dest_type)} else {String::from("i64")}
+                            if convert_to_llvm(&dest_type) != current_type
                             {
                                 let next = self.get_next_temp();
-                                let next_type = if dest_type.num_ptr == 0 {convert_to_llvm(&dest_type)} else {String::from("i64")};
-                                self.insert_command(&format!("{} = trunc {} {} to {}", next, current_type, current, next_type));
-                                
+                                self.insert_command(&format!("{} = bitcast {} {} to {}", next, current_type, current, convert_to_llvm(&dest_type)));
                                 current = next;
-                                current_type = next_type;
+                                current_type = convert_to_llvm(&dest_type);
                             }
-                            // If the destination is larger, extension is necessary
-                            else if dest_size > src_size && current_type != if dest_type.num_ptr == 0 {convert_to_llvm(&dest_type)} else {String::from("i64")}
+                        }
+                        else
+                        {
+                            if src_type.num_ptr > 0
                             {
                                 let next = self.get_next_temp();
-                                let next_type = if dest_type.num_ptr == 0 {convert_to_llvm(&dest_type)} else {String::from("i64")};
-                                self.insert_command(&format!("{} = {} {} {} to {}", 
-                                    next, if dest_type.is_signed() {"sext"} else {"zext"},
-                                    current_type, current, next_type));
-
+                                self.insert_command(&format!("{} = ptrtoint {} {} to {}", next, current_type, current, pointer_int));
                                 current = next;
-                                current_type = next_type;
+                                current_type = pointer_int.clone();
                             }
 
-                            if dest_type.num_ptr > 0
+                            if convert_to_llvm(&dest_type) != convert_to_llvm(&src_type)
                             {
-                                // The source is not a pointer
-                                if src_type.num_ptr == 0
+                                // If the destination is smaller, truncation is necessary
+                                if dest_size < src_size && current_type != if dest_type.num_ptr == 0 {convert_to_llvm(&dest_type)} else {pointer_int.clone()}
                                 {
                                     let next = self.get_next_temp();
-                                    self.insert_command(&format!("{} = inttoptr {} {} to {}", next, current_type, current, convert_to_llvm(&dest_type)));
+                                    let next_type = if dest_type.num_ptr == 0 {convert_to_llvm(&dest_type)} else {pointer_int.clone()};
+                                    self.insert_command(&format!("{} = trunc {} {} to {}", next, current_type, current, next_type));
+                                
                                     current = next;
-                                    current_type = convert_to_llvm(&dest_type);
+                                    current_type = next_type;
                                 }
-                                // The source is a pointer
-                                else
+                                // If the destination is larger, extension is necessary, which fills with the sign
+                                // of the source if it is signed
+                                else if dest_size > src_size && current_type != if dest_type.num_ptr == 0 {convert_to_llvm(&dest_type)} else {pointer_int.clone()}
                                 {
                                     let next = self.get_next_temp();
-                                    self.insert_command(&format!("{} = bitcast {} {} to {}", next, current_type, current, convert_to_llvm(&dest_type)));
+                                    let next_type = if dest_type.num_ptr == 0 {convert_to_llvm(&dest_type)} else {pointer_int.clone()};
+                                    self.insert_command(&format!("{} = {} {} {} to {}", 
+                                        next, if src_type.is_signed() {"sext"} else {"zext"},
+                                        current_type, current, next_type));
+
+                                    current = next;
+                                    current_type = next_type;
+                                }
+
+                                // The source is an integer being turned into a pointer
+                                if dest_type.num_ptr > 0
+                                {
+                                    let next = self.get_next_temp();
+                                    self.insert_command(&format!("{} = inttoptr {} {} to {}", next, current_type, current, convert_to_llvm(&dest_type)));
                                     current = next;
                                     current_type = convert_to_llvm(&dest_type);
                                 }
                             }
                         }
 
-                        self.add_move(&inst.arguments[0], format!("{} {}", current_type, current));
+                        self.add_move(&inst.arguments[0], format!("{} {}", current_type, current))?;
                     },
                     // Dereference Command
                     OpCode::Deref =>
                     {
                         if let Value::Symbol(var) = &inst.arguments[0]
                         {
+                            self.check_allocated(var)?;
+
                             let reg = self.get_next_temp();
-                            let dt = self.values.get(&var.title).unwrap().get_datatype();
+                            let dt = self.values.get(&var.key()).unwrap().get_datatype();
 
-                            let val = self.render_value(&inst.arguments[1], true);
+                            let val = self.render_value(&inst.arguments[1], true)?;
 
                             self.insert_command(&format!("{} = load {}, {}, align {}", 
                                             reg, 
                                             convert_to_llvm(&dt),
                                             val,
-                                            bytes_size_of(&var.datatype)));
+                                            self.target.align_of(&var.datatype)));
 
-                            self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&dt), reg));
+                            self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&dt), reg))?;
                         };
                     },
                     // Dereference Command
                     OpCode::Ref =>
                     {
-                        if let Value::Symbol(var0) = &inst.arguments[0]
+                        if let Value::Symbol(var) = &inst.arguments[1]
                         {
-                            if let Value::Symbol(var1) = &inst.arguments[1]
-                            {
-                                let ptr_dt = self.values.get(&var0.title).unwrap().get_pointer_datatype();
-                                let ptr = self.values.get(&var1.title).unwrap().ptr.clone();
+                            self.check_allocated(var)?;
 
-                                self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&ptr_dt), ptr));
-                            }
+                            let ptr_dt = self.values.get(&var.key()).unwrap().get_pointer_datatype();
+                            let ptr = self.values.get(&var.key()).unwrap().ptr.clone();
+
+                            self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&ptr_dt), ptr))?;
                         };
                     },
                     // Compare Commands
                     OpCode::Cne | OpCode::Ceq | OpCode::Cge | OpCode::Cgt | OpCode::Cle | OpCode::Clt =>
                     {
                         let temp = self.get_next_temp();
-                        let temp2 = self.get_next_temp();
 
                         let is_signed = get_value_type(&inst.arguments[1]).unwrap().is_signed();
 
@@ -488,17 +892,29 @@ impl FunctionGenerationContext
                             }
                         );
 
-                        self.add_compare(command, temp.clone(), &inst.arguments[1], &inst.arguments[2]);
-                        self.insert_command(&format!("{} = zext i1 {} to {}", &temp2, &temp, convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap())));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp2));
+                        self.add_compare(command, temp.clone(), &inst.arguments[1], &inst.arguments[2])?;
+
+                        // A comparison giving a bool is already an i1, anything wider is filled with zeros
+                        let dest_type = convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap());
+
+                        if dest_type == "i1"
+                        {
+                            self.add_move(&inst.arguments[0], format!("i1 {}", temp))?;
+                        }
+                        else
+                        {
+                            let temp2 = self.get_next_temp();
+                            self.insert_command(&format!("{} = zext i1 {} to {}", &temp2, &temp, dest_type));
+                            self.add_move(&inst.arguments[0], format!("{} {}", dest_type, temp2))?;
+                        }
                     },
                     // Branch Commands
                     OpCode::Bne | OpCode::Beq | OpCode::Bge | OpCode::Bgt | OpCode::Ble | OpCode::Blt =>
                     {
                         let temp = self.get_next_temp();
 
-                        let label_true = self.render_value(&inst.arguments[2], true);
-                        let label_false = self.render_value(&inst.arguments[3], true);
+                        let label_true = self.render_value(&inst.arguments[2], true)?;
+                        let label_false = self.render_value(&inst.arguments[3], true)?;
 
                         let is_signed = get_value_type(&inst.arguments[0]).unwrap().is_signed();
 
@@ -515,118 +931,157 @@ impl FunctionGenerationContext
                             }
                         );
 
-                        self.add_compare(command, temp.clone(), &inst.arguments[0], &inst.arguments[1]);
-                        self.insert_command(&format!("br i1 {}, {}, {}", &temp, label_true, label_false));
+                        self.add_compare(command, temp.clone(), &inst.arguments[0], &inst.arguments[1])?;
+                        let hints = self.loop_hints_for(i, &inst.arguments[2..4]);
+
+                        self.insert_command(&format!("br i1 {}, {}, {}{}", &temp, label_true, label_false, hints));
                     },
                     // Add Command
                     OpCode::Add =>
                     {
                         let temp = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], false)?;
 
                         self.insert_command(&format!("{} = add {}, {}", temp, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // Sub Command
                     OpCode::Sub =>
                     {
-                        let temp = self.get_next_temp();
+                        let src_type = get_value_type(&inst.arguments[1]).unwrap();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        // Subtracting two pointers gives the number of elements between them
+                        if src_type.num_ptr > 0 && get_value_type(&inst.arguments[2]).unwrap().num_ptr > 0
+                        {
+                            let mut element_type = src_type;
+                            element_type.num_ptr -= 1;
+
+                            let val0 = self.render_value(&inst.arguments[1], true)?;
+                            let val1 = self.render_value(&inst.arguments[2], true)?;
+
+                            let pointer_int = self.target.pointer_int();
+
+                            let int0 = self.get_next_temp();
+                            self.insert_command(&format!("{} = ptrtoint {} to {}", int0, val0, pointer_int));
+                            let int1 = self.get_next_temp();
+                            self.insert_command(&format!("{} = ptrtoint {} to {}", int1, val1, pointer_int));
+
+                            let difference = self.get_next_temp();
+                            self.insert_command(&format!("{} = sub {} {}, {}", difference, pointer_int, int0, int1));
+
+                            let mut temp = self.get_next_temp();
+                            self.insert_command(&format!("{} = sdiv exact {} {}, {}", temp, pointer_int, difference, self.target.bytes_size_of(&element_type).max(1)));
+
+                            // The element count is always an i64
+                            if self.target.pointer_size < 8
+                            {
+                                let extended = self.get_next_temp();
+                                self.insert_command(&format!("{} = sext {} {} to i64", extended, pointer_int, temp));
+                                temp = extended;
+                            }
 
-                        self.insert_command(&format!("{} = sub {}, {}", temp, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                            self.add_move(&inst.arguments[0], format!("i64 {}", temp))?;
+                        }
+                        else
+                        {
+                            let temp = self.get_next_temp();
+
+                            let val0 = self.render_value(&inst.arguments[1], true)?;
+                            let val1 =  self.render_value(&inst.arguments[2], false)?;
+
+                            self.insert_command(&format!("{} = sub {}, {}", temp, val0, val1));
+                            self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
+                        }
                     },
                     // Mul Command
                     OpCode::Mul =>
                     {
                         let temp = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], false)?;
 
                         self.insert_command(&format!("{} = mul {}, {}", temp, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // Div Command
                     OpCode::Div =>
                     {
                         let temp = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], false)?;
 
-                        self.insert_command(&format!("{} = div {}, {}", temp, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                        self.insert_command(&format!("{} = {} {}, {}", temp, if get_value_type(&inst.arguments[1]).unwrap().is_signed() {"sdiv"} else {"udiv"}, val0, val1));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // And Command
                     OpCode::And =>
                     {
                         let temp = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], false)?;
 
                         self.insert_command(&format!("{} = and {}, {}", temp, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // Or Command
                     OpCode::Or =>
                     {
                         let temp = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], false)?;
 
                         self.insert_command(&format!("{} = or {}, {}", temp, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // Xor Command
                     OpCode::Xor =>
                     {
                         let temp = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], false)?;
 
                         self.insert_command(&format!("{} = xor {}, {}", temp, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // Mod Command
                     OpCode::Mod =>
                     {
                         let temp = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], false)?;
 
                         self.insert_command(&format!("{} = {} {}, {}", temp, if get_value_type(&inst.arguments[1]).unwrap().is_signed() {"srem"} else {"urem"}, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // Shl Command
                     OpCode::Shl =>
                     {
                         let temp = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], false)?;
 
                         self.insert_command(&format!("{} = shl {}, {}", temp, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // Shr Command
                     OpCode::Shr =>
                     {
                         let temp = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], false);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], false)?;
 
-                        self.insert_command(&format!("{} = {} {}, {}", temp, if get_value_type(&inst.arguments[1]).unwrap().is_signed() {"lshr"} else {"ashr"}, val0, val1));
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                        self.insert_command(&format!("{} = {} {}, {}", temp, if get_value_type(&inst.arguments[1]).unwrap().is_signed() {"ashr"} else {"lshr"}, val0, val1));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // Array Command
                     OpCode::Array =>
@@ -634,8 +1089,8 @@ impl FunctionGenerationContext
                         let temp = self.get_next_temp();
                         let temp2 = self.get_next_temp();
 
-                        let val0 = self.render_value(&inst.arguments[1], true);
-                        let val1 =  self.render_value(&inst.arguments[2], true);
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], true)?;
 
                         let val_type = convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap());
                         let ptr_type = convert_to_llvm(&get_value_type(&inst.arguments[1]).unwrap());
@@ -643,27 +1098,168 @@ impl FunctionGenerationContext
                         self.insert_command(&format!("{} = getelementptr {}, {}, {}", temp, val_type, val0, val1));
 
                         self.insert_command(&format!("{} = load {}, {} {}, align {}", temp2, val_type, ptr_type, temp,
-                                            bytes_size_of(&get_value_type(&inst.arguments[0]).unwrap())));
+                                            self.target.align_of(&get_value_type(&inst.arguments[0]).unwrap())));
+
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp2))?;
+                    },
+                    OpCode::Index =>
+                    {
+                        let temp = self.get_next_temp();
+
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 =  self.render_value(&inst.arguments[2], true)?;
+
+                        let mut element_type = get_value_type(&inst.arguments[1]).unwrap();
+                        element_type.num_ptr -= 1;
+                        element_type.is_ref = false;
+
+                        self.insert_command(&format!("{} = getelementptr {}, {}, {}", temp, convert_to_llvm(&element_type), val0, val1));
+
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
+                    },
+                    // The field is picked by its position, which is always a constant
+                    OpCode::Member =>
+                    {
+                        let temp = self.get_next_temp();
+
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let field = match &inst.arguments[2]
+                        {
+                            Value::Literal(literal) => literal.value,
+                            _ => unreachable!()
+                        };
+
+                        let mut struct_type = get_value_type(&inst.arguments[1]).unwrap();
+                        struct_type.num_ptr -= 1;
+                        struct_type.is_ref = false;
+
+                        self.insert_command(&format!("{} = getelementptr inbounds {}, {}, i32 0, i32 {}", temp, convert_to_llvm(&struct_type), val0, field));
 
-                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp2));
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
+                    },
+                    // Bit manipulation builtins, lowered to the LLVM intrinsics
+                    OpCode::Clz | OpCode::Ctz | OpCode::Popcnt | OpCode::Bswap =>
+                    {
+                        let dt = get_value_type(&inst.arguments[0]).unwrap();
+                        let llvm_type = convert_to_llvm(&dt);
+                        let val = self.render_value(&inst.arguments[1], true)?;
+
+                        // Swapping the bytes of a single byte does nothing
+                        if inst.opcode == OpCode::Bswap && self.target.bytes_size_of(&dt) < 2
+                        {
+                            self.add_move(&inst.arguments[0], val)?;
+                            continue;
+                        }
+
+                        let (name, extra) = match inst.opcode
+                        {
+                            OpCode::Clz => ("ctlz", ", i1 false"),
+                            OpCode::Ctz => ("cttz", ", i1 false"),
+                            OpCode::Popcnt => ("ctpop", ""),
+                            _ => ("bswap", "")
+                        };
+
+                        let suffix = intrinsic_suffix(&dt);
+
+                        self.declare_intrinsic(format!("declare {0} @llvm.{1}.{2}({0}{3})", llvm_type, name, suffix, if extra.is_empty() {""} else {", i1"}));
+
+                        let temp = self.get_next_temp();
+                        self.insert_command(&format!("{} = call {} @llvm.{}.{}({}{})", temp, llvm_type, name, suffix, val, extra));
+
+                        self.add_move(&inst.arguments[0], format!("{} {}", llvm_type, temp))?;
+                    },
+                    // Rotates, lowered to funnel shifts of the value with itself
+                    OpCode::Rotl | OpCode::Rotr =>
+                    {
+                        let dt = get_value_type(&inst.arguments[0]).unwrap();
+                        let llvm_type = convert_to_llvm(&dt);
+                        let suffix = intrinsic_suffix(&dt);
+                        let name = if inst.opcode == OpCode::Rotl {"fshl"} else {"fshr"};
+
+                        self.declare_intrinsic(format!("declare {0} @llvm.{1}.{2}({0}, {0}, {0})", llvm_type, name, suffix));
+
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 = self.render_value(&inst.arguments[2], true)?;
+
+                        let temp = self.get_next_temp();
+                        self.insert_command(&format!("{} = call {} @llvm.{}.{}({4}, {4}, {5})", temp, llvm_type, name, suffix, val0, val1));
+
+                        self.add_move(&inst.arguments[0], format!("{} {}", llvm_type, temp))?;
+                    },
+                    // Read a lane of a vector
+                    OpCode::Extract =>
+                    {
+                        let vector = self.render_value(&inst.arguments[1], true)?;
+                        let index = self.render_value(&inst.arguments[2], true)?;
+
+                        let temp = self.get_next_temp();
+                        self.insert_command(&format!("{} = extractelement {}, {}", temp, vector, index));
+
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
+                    },
+                    // Replace a lane of a vector
+                    OpCode::Insert =>
+                    {
+                        let vector = self.render_value(&inst.arguments[1], true)?;
+                        let value = self.render_value(&inst.arguments[2], true)?;
+                        let index = self.render_value(&inst.arguments[3], true)?;
+
+                        let temp = self.get_next_temp();
+                        self.insert_command(&format!("{} = insertelement {}, {}, {}", temp, vector, value, index));
+
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&correct_type_references(get_value_type(&inst.arguments[0]).unwrap())), temp))?;
+                    },
+                    // Build a vector from the lanes of two others, the mask is always constant
+                    OpCode::Shuffle =>
+                    {
+                        let val0 = self.render_value(&inst.arguments[1], true)?;
+                        let val1 = self.render_value(&inst.arguments[2], true)?;
+
+                        let mut mask = vec![];
+
+                        for lane in &inst.arguments[3..]
+                        {
+                            mask.push(self.render_value(lane, true)?);
+                        }
+
+                        let temp = self.get_next_temp();
+                        self.insert_command(&format!("{} = shufflevector {}, {}, <{} x i32> <{}>", temp, val0, val1, mask.len(), mask.join(", ")));
+
+                        self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                     },
                     // Push Command
                     OpCode::Push =>
                     {
-                        let arg = self.render_value(&inst.arguments[0], true);
+                        let arg = self.render_value(&inst.arguments[0], true)?;
                         self.current_arguments += &format!("{}, ", arg);
                     },
                     // Call Command
                     OpCode::Call =>
                     {
-                        let temp = self.get_next_temp();
-                        if let Value::Label(func_label) = &inst.arguments[1]
+                        if self.current_arguments.len() > 0
                         {
-                            if self.current_arguments.len() > 0
+                            self.current_arguments.pop();
+                            self.current_arguments.pop();
+                        }
+
+                        // The hosted builtins are defined in the module, calling on to the C library
+                        if let Some(Value::Label(func_label)) = inst.arguments.last()
+                        {
+                            for definition in hosted_runtime(func_label, &self.target)
                             {
-                                self.current_arguments.pop();
-                                self.current_arguments.pop();
+                                self.declare_intrinsic(definition);
                             }
+                        }
+
+                        // A call to a void function has only the function, as there is no value to store
+                        if let [Value::Label(func_label)] = inst.arguments.as_slice()
+                        {
+                            self.insert_command(&format!("call void @{}({})", func_label, self.current_arguments));
+                            self.current_arguments = String::new();
+                        }
+                        else if let Value::Label(func_label) = &inst.arguments[1]
+                        {
+                            let temp = self.get_next_temp();
 
                             self.insert_command(&format!("{} = call {} @{}({})",
                                                     temp, 
@@ -673,14 +1269,16 @@ impl FunctionGenerationContext
 
                             self.current_arguments = String::new();
 
-                            self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp));
+                            self.add_move(&inst.arguments[0], format!("{} {}", convert_to_llvm(&get_value_type(&inst.arguments[0]).unwrap()), temp))?;
                         }
                     },
                     // Unconditional Jump
                     OpCode::Jmp =>
                     {
-                        let label = self.render_value(&inst.arguments[0], true);
-                        self.insert_command(&format!("br {}", label));
+                        let label = self.render_value(&inst.arguments[0], true)?;
+                        let hints = self.loop_hints_for(i, &inst.arguments[0..1]);
+
+                        self.insert_command(&format!("br {}{}", label, hints));
                     },
                     // This should never happen, but if it does, ignore it
                     OpCode::Nop => {}
@@ -688,8 +1286,15 @@ impl FunctionGenerationContext
             }
         }
 
+        // Every block must end in a terminator, and the last can only be left without one when
+        // it never finishes (such as after a call to a noreturn function)
+        if !self.terminated
+        {
+            self.insert_command("unreachable");
+        }
+
         self.result += "}\n";
 
-        Ok(self.result.clone())
+        Ok(std::mem::take(&mut self.result))
     }
 }
\ No newline at end of file
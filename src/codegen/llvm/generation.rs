@@ -1,47 +1,215 @@
+use std::fmt;
+
 use crate::cli::Error;
-use crate::irgen::{Function};
+use crate::irgen::{Context, Function, OpCode, Value};
 
-use super::FunctionGenerationContext;
+use super::{FunctionGenerationContext, convert_to_llvm, strip_output, render_data, render_struct_type, render_global};
+use super::super::{TargetSpec, output_error};
 
 /// Wrapper for the LLVM IR Code Generator
 #[derive(Debug, Clone)]
-pub struct LLVMGenerator
+pub struct LLVMGenerator<'a>
 {
-    functions: Vec<Function>
+    functions: &'a [&'a Function],
+    context: &'a Context,
+    readable_names: bool,
+    internal_linkage: bool,
+    defines_globals: bool,
+    strip: Option<bool>
 }
 
-impl LLVMGenerator
+impl<'a> LLVMGenerator<'a>
 {
-    /// Generate a new AvrAsmGenerator from a vector of IR functions
-    pub fn new(functions: Vec<Function>) -> Self
+    /// Generate a new LLVMGenerator from the IR functions it borrows and the context of their
+    /// library, giving the functions private to their module internal linkage if `internal_linkage`
+    /// is set
+    pub fn new(functions: &'a [&'a Function], context: &'a Context, readable_names: bool, internal_linkage: bool) -> Self
     {
         Self
         {
-            functions
+            functions,
+            context,
+            readable_names,
+            internal_linkage,
+            defines_globals: true,
+            strip: None
         }
     }
 
-    /// Render each function in turn
-    pub fn render(self, target: Option<&str>, datalayout: Option<&str>) -> Result<String, Error>
+    /// Declare the global variables of the library rather than defining them, as they are defined
+    /// in another output linked with this one
+    pub fn with_external_globals(self) -> Self
+    {
+        Self
+        {
+            defines_globals: false,
+            ..self
+        }
+    }
+
+    /// Leave the comments out of the output, along with the blank lines if `blank_lines` is set
+    pub fn with_stripping(self, blank_lines: bool) -> Self
+    {
+        Self
+        {
+            strip: Some(blank_lines),
+            ..self
+        }
+    }
+
+    /// Write a part of the module made of whole lines, stripping it if asked to
+    fn emit(&self, out: &mut dyn fmt::Write, text: &str) -> Result<(), Error>
+    {
+        match self.strip
+        {
+            Some(blank_lines) => out.write_str(&strip_output(text, blank_lines)),
+            None => out.write_str(text)
+        }.map_err(output_error)
+    }
+
+    /// Render each function in turn, declaring any of the external functions which are called.
+    /// Each function is written to `out` once it is rendered, and the declarations and metadata
+    /// after the last
+    pub fn render(self, target: &TargetSpec, external: &[&Function], out: &mut dyn fmt::Write) -> Result<(), Error>
     {
-        let mut result = String::new();
+        if let Some(datalayout_str) = &target.datalayout
+        {
+            self.emit(out, &format!("target datalayout = \"{}\"\n", datalayout_str))?;
+        }
 
-        if let Some(datalayout_str) = datalayout
+        if let Some(target_str) = &target.triple
         {
-            result += &format!("target datalayout = \"{}\"\n", datalayout_str);
+            self.emit(out, &format!("target triple = \"{}\"\n", target_str))?;
         }
 
-        if let Some(target_str) = target
+        // Every struct of the library is defined before the functions using it
+        let structs: Vec<String> = self.context.structs.in_scope().iter().copied().map(render_struct_type).collect();
+
+        if !structs.is_empty()
         {
-            result += &format!("target triple = \"{}\"\n", target_str);
+            self.emit(out, &format!("{}\n\n", structs.join("\n")))?;
         }
 
+        // Every global variable of the library, defined in only one output
+        let globals: Vec<String> = self.context.globals.iter()
+            .map(|global| render_global(global, self.defines_globals, global.internal && self.internal_linkage, &target.pointer_int(), target.align_of(&global.datatype).max(global.datatype.align.unwrap_or(0) as usize)))
+            .collect();
+
+        if !globals.is_empty()
+        {
+            self.emit(out, &format!("{}\n\n", globals.join("\n")))?;
+        }
+
+        // Intrinsics used by any of the functions, declared once at the end of the module
+        let mut intrinsics: Vec<String> = vec![];
+
+        // Metadata nodes are numbered across the whole module
+        let mut metadata: Vec<String> = vec![];
+
+        // Constant data is numbered across the whole module, in the order it is first used
+        let mut strings: Vec<Vec<u8>> = vec![];
+
+        let declarations = render_declarations(self.functions, external);
+
         for function in self.functions
         {
-            let mut context = FunctionGenerationContext::new(function);
-            result += &format!("{}", context.render_function()?);
+            let mut context = FunctionGenerationContext::new(function, target);
+            context.next_metadata = metadata.len();
+            context.strings = std::mem::take(&mut strings);
+            context.readable_names = self.readable_names;
+            context.internal_linkage = self.internal_linkage;
+            self.emit(out, &context.render_function()?)?;
+
+            metadata.extend(context.metadata);
+            strings = context.strings;
+
+            for declaration in context.intrinsics
+            {
+                if !intrinsics.contains(&declaration)
+                {
+                    intrinsics.push(declaration);
+                }
+            }
+        }
+
+        for declaration in declarations.into_iter().chain(intrinsics).chain(render_used(self.functions)).chain(render_constructors(self.functions))
+            .chain(strings.iter().enumerate().map(|(index, bytes)| render_data(index, bytes)))
+        {
+            self.emit(out, &format!("\n{}\n", declaration))?;
         }
 
-        Ok(result)
+        // Position independent code is recorded in the module, so it is kept when linking modules
+        if target.relocation_model.as_deref() == Some("pic")
+        {
+            let node = metadata.len();
+            metadata.push(format!("!llvm.module.flags = !{{!{}}}", node));
+            metadata.push(format!("!{} = !{{i32 7, !\"PIC Level\", i32 2}}", node));
+        }
+
+        if !metadata.is_empty()
+        {
+            self.emit(out, "\n")?;
+
+            for node in metadata
+            {
+                self.emit(out, &format!("{}\n", node))?;
+            }
+        }
+
+        Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// List the functions marked #[used] in `llvm.used`, so neither LLVM nor the linker removes them
+/// however unused they look, if there are any
+fn render_used(functions: &[&Function]) -> Option<String>
+{
+    let used: Vec<String> = functions.iter().filter(|func| func.attribute("used").is_some()).map(|func|
+    {
+        let arguments: Vec<String> = func.arguments.iter().map(|(_, datatype)| convert_to_llvm(datatype)).collect();
+        format!("i8* bitcast ({} ({})* @{} to i8*)", convert_to_llvm(&func.return_type), arguments.join(", "), func.name)
+    }).collect();
+
+    if used.is_empty()
+    {
+        return None;
+    }
+
+    Some(format!("@llvm.used = appending global [{} x i8*] [{}], section \"llvm.metadata\"", used.len(), used.join(", ")))
+}
+
+/// List the functions marked #[constructor] in `llvm.global_ctors`, so the C runtime runs them
+/// before main in the order they are defined, if there are any
+fn render_constructors(functions: &[&Function]) -> Option<String>
+{
+    let constructors: Vec<String> = functions.iter().filter(|func| func.attribute("constructor").is_some())
+        .map(|func| format!("{{ i32, void ()*, i8* }} {{ i32 65535, void ()* @{}, i8* null }}", func.name)).collect();
+
+    if constructors.is_empty()
+    {
+        return None;
+    }
+
+    Some(format!("@llvm.global_ctors = appending global [{} x {{ i32, void ()*, i8* }}] [{}]", constructors.len(), constructors.join(", ")))
+}
+
+/// Declare the external functions called by any of the given functions
+fn render_declarations(functions: &[&Function], external: &[&Function]) -> Vec<String>
+{
+    let mut result = vec![];
+
+    for callee in external
+    {
+        let called = functions.iter().any(|func| func.instructions.values().any(|inst|
+            inst.opcode == OpCode::Call && inst.arguments.last() == Some(&Value::Label(callee.name.clone()))));
+
+        if called
+        {
+            let arguments: Vec<String> = callee.arguments.iter().map(|(_, datatype)| convert_to_llvm(datatype)).collect();
+
+            result.push(format!("declare {} @{}({})", convert_to_llvm(&callee.return_type), callee.name, arguments.join(", ")));
+        }
+    }
+
+    result
+}
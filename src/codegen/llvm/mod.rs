@@ -1,7 +1,9 @@
 mod functions;
 mod generation;
 mod utils;
+mod runtime;
 
 pub use functions::*;
 pub use generation::*;
 pub use utils::*;
+pub use runtime::*;
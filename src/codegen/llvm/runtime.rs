@@ -0,0 +1,43 @@
+use super::super::TargetSpec;
+
+/// Get the definitions the module needs for a call to one of the hosted builtins, along with the
+/// C library functions they call, giving nothing for any other function
+pub fn hosted_runtime(name: &str, target: &TargetSpec) -> Vec<String>
+{
+    match name
+    {
+        "__builtin_print" => vec![
+            String::from("@__builtin_print.format = private unnamed_addr constant [6 x i8] c\"%lld\\0A\\00\""),
+            String::from("declare i32 @printf(i8*, ...)"),
+            String::from("define internal void @__builtin_print(i64 %value)\n{\n    %format = getelementptr inbounds [6 x i8], [6 x i8]* @__builtin_print.format, i64 0, i64 0\n    call i32 (i8*, ...) @printf(i8* %format, i64 %value)\n    ret void\n}")
+        ],
+        "__builtin_alloc" =>
+        {
+            let size_type = target.pointer_int();
+
+            // malloc takes a size_t, which is narrower than the u64 the builtin takes on 32 bit targets
+            let size = if target.pointer_size < 8
+            {
+                format!("    %size.trunc = trunc i64 %size to {}\n    %pointer = call i8* @malloc({} %size.trunc)\n", size_type, size_type)
+            }
+            else
+            {
+                String::from("    %pointer = call i8* @malloc(i64 %size)\n")
+            };
+
+            vec![
+                format!("declare i8* @malloc({})", size_type),
+                format!("define internal i8* @__builtin_alloc(i64 %size)\n{{\n{}    ret i8* %pointer\n}}", size)
+            ]
+        },
+        "__builtin_assert" => vec![
+            String::from("declare void @abort() noreturn"),
+            String::from("define internal void @__builtin_assert(i1 %condition)\n{\n    br i1 %condition, label %pass, label %fail\nfail:\n    call void @abort() noreturn\n    unreachable\npass:\n    ret void\n}")
+        ],
+        "__builtin_abort" => vec![
+            String::from("declare void @abort() noreturn"),
+            String::from("define internal void @__builtin_abort() noreturn\n{\n    call void @abort() noreturn\n    unreachable\n}")
+        ],
+        _ => vec![]
+    }
+}
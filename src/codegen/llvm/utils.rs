@@ -1,29 +1,117 @@
-use crate::irgen::{DataType, NonPtrType};
+use crate::irgen::{DataType, NonPtrType, StructType, GlobalVariable};
 
 /// Convert a type to a string in the format llvm uses (no u32 or u64, just i32, i64 etc.)
 pub fn convert_to_llvm(datatype: &DataType) -> String
 {
+    if let NonPtrType::Vector(bits, _, lanes) = datatype.raw_type
+    {
+        return format!("<{} x i{}>{}{}", lanes, bits, "*".repeat(datatype.num_ptr), if datatype.is_ref {"*"} else {""});
+    }
+
+    // Structs are named types of the module, whose names are left as they are
+    if let NonPtrType::Struct(structure) = datatype.raw_type
+    {
+        return format!("%struct.{}{}{}", structure.name(), "*".repeat(datatype.num_ptr), if datatype.is_ref {"*"} else {""});
+    }
+
+    if datatype.raw_type == NonPtrType::Bool
+    {
+        return format!("i1{}{}", "*".repeat(datatype.num_ptr), if datatype.is_ref {"*"} else {""});
+    }
+
     format!("{}{}", datatype, if datatype.is_ref {"*"} else {""}).replace("u", "i").replace("&", "")
 }
 
-/// Gets the number of bytes in a type
-pub fn bytes_size_of(datatype: &DataType) -> usize
+/// Render the definition of the type of a struct, its fields in the order they are declared
+pub fn render_struct_type(structure: StructType) -> String
+{
+    let fields: Vec<String> = structure.fields().iter().map(|(_, datatype)| convert_to_llvm(datatype)).collect();
+
+    format!("%struct.{} = type {{ {} }}", structure.name(), fields.join(", "))
+}
+
+/// Render a global variable of the library, defining it with the value it starts with, with
+/// internal linkage if `internal` is set, or if it is defined in another output, declaring it
+pub fn render_global(global: &GlobalVariable, defined: bool, internal: bool, pointer_int: &str, align: usize) -> String
 {
-    if datatype.num_ptr > 0
+    let datatype = convert_to_llvm(&global.datatype);
+
+    if !defined
+    {
+        return format!("@{} = external global {}, align {}", global.name, datatype, align);
+    }
+
+    let value = if global.value == 0 && (global.datatype.num_ptr > 0 || global.datatype.is_struct() || global.datatype.is_vector())
+    {
+        String::from(if global.datatype.num_ptr > 0 {"null"} else {"zeroinitializer"})
+    }
+    else if global.datatype.num_ptr > 0
+    {
+        format!("inttoptr ({} {} to {})", pointer_int, global.value, datatype)
+    }
+    else if let Some(element_type) = global.datatype.element_type()
     {
-        8
+        format!("<{}>", vec![format!("{} {}", convert_to_llvm(&element_type), global.value); global.datatype.lanes()].join(", "))
     }
     else
     {
-        match datatype.raw_type
+        global.value.to_string()
+    };
+
+    format!("@{} = {}global {} {}, align {}", global.name, if internal {"internal "} else {""}, datatype, value, align)
+}
+
+/// Get the suffix given to an intrinsic for the type it operates on, `i32` or `v4i32`
+pub fn intrinsic_suffix(datatype: &DataType) -> String
+{
+    match datatype.raw_type
+    {
+        NonPtrType::Vector(bits, _, lanes) => format!("v{}i{}", lanes, bits),
+        _ => convert_to_llvm(datatype)
+    }
+}
+
+/// Get the name of the global holding the constant data with the given index in the module,
+/// `@.str` for the first and `@.str.1` for the next
+pub fn data_name(index: usize) -> String
+{
+    if index == 0 {String::from("@.str")} else {format!("@.str.{}", index)}
+}
+
+/// Render the global holding constant data, with a terminating zero byte so string literals can
+/// be passed on to C
+pub fn render_data(index: usize, bytes: &[u8]) -> String
+{
+    let mut contents = String::new();
+
+    for byte in bytes.iter().chain(&[0])
+    {
+        match byte
         {
-            NonPtrType::I8 | NonPtrType::U8 => 1,
-            NonPtrType::I16 | NonPtrType::U16 => 2,
-            NonPtrType::I32 | NonPtrType::U32 => 4,
-            NonPtrType::I64 | NonPtrType::U64 => 8,
-            NonPtrType::Void => 0,
-            NonPtrType::Bool => 1,
-            NonPtrType::Unknown => {panic!()}
+            b' '..=b'~' if *byte != b'"' && *byte != b'\\' => {contents.push(*byte as char);},
+            _ => {contents += &format!("\\{:02X}", byte);}
         }
     }
-}
\ No newline at end of file
+
+    format!("{} = private unnamed_addr constant [{} x i8] c\"{}\", align 1", data_name(index), bytes.len() + 1, contents)
+}
+
+/// Remove the comments giving the instruction each group of LLVM lines was generated from, along
+/// with the blank lines if asked for
+pub fn strip_output(output: &str, blank_lines: bool) -> String
+{
+    let mut result = String::new();
+
+    for line in output.lines()
+    {
+        if line.trim_start().starts_with(';') || (blank_lines && line.trim().is_empty())
+        {
+            continue;
+        }
+
+        result += line;
+        result += "\n";
+    }
+
+    result
+}
@@ -1,9 +1,24 @@
+mod abi;
 mod avrasm;
 mod llvm;
+mod output;
+mod regalloc;
+mod schedule;
+mod target;
 
-use crate::cli::{Error, Options};
+pub use abi::*;
+pub use output::*;
+pub use regalloc::*;
+pub use schedule::*;
+pub use target::*;
 
-use crate::irgen::Function;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::cli::{Error, Options, suggest};
+
+use crate::irgen::{Context, Function};
+use crate::plugin::{find_backend, backend_names};
 
 /// Code Generation Mode
 /// What language the output will be in
@@ -13,94 +28,243 @@ pub enum CodegenMode
     IntermediateRepresentation,
     AvrAssembly,
     LLVM,
-    Unknown
+    Custom(&'static str) // Code generator added by a plugin, by the name it was registered with
 }
 
+/// Every code generation mode built into the compiler, by the name passed to -g
+static CODEGEN_MODES: &[(&str, CodegenMode)] = &[
+    ("ir", CodegenMode::IntermediateRepresentation),
+    ("llvm", CodegenMode::LLVM),
+    ("avrasm", CodegenMode::AvrAssembly)
+];
+
 impl CodegenMode
 {
-    /// Generate a new CodegenMode object from a mode passed as an argument
-    pub fn from_mode(mode: &str) -> CodegenMode
+    /// Generate a new CodegenMode object from a mode passed as an argument, failing with the
+    /// modes which are available if there is no mode by that name
+    pub fn from_mode(mode: &str) -> Result<CodegenMode, Error>
     {
-        match mode
+        if let Some((_, result)) = CODEGEN_MODES.iter().find(|(name, _)| *name == mode)
+        {
+            return Ok(*result);
+        }
+
+        if let Some((name, _)) = find_backend(mode)
         {
-            "ir" => CodegenMode::IntermediateRepresentation,
-            "avrasm" => CodegenMode::AvrAssembly,
-            "llvm" => CodegenMode::LLVM,
-            _ => CodegenMode::Unknown
+            return Ok(CodegenMode::Custom(name));
         }
+
+        let names = Self::names();
+        let error = Err(Error::fatal_error(&format!("Unknown code generation mode '{}' (expected one of {})", mode, names.join(", "))).with_code("E0102"));
+
+        suggest(error, mode, names.iter().copied())
+    }
+
+    /// Get the name of every code generation mode, those built in followed by those added by
+    /// plugins
+    pub fn names() -> Vec<&'static str>
+    {
+        CODEGEN_MODES.iter().map(|(name, _)| *name).chain(backend_names()).collect()
     }
 }
 
-/// Wrapper which compiles the individually translated functions
+/// Wrapper which compiles the individually translated functions, borrowing them
 #[derive(Debug, Clone)]
-pub struct CodeGenerator
+pub struct CodeGenerator<'a>
 {
     mode: CodegenMode,
-    functions: Vec<Function>,
-    options: Options
+    functions: &'a [Function],
+    external: &'a [Function], // Functions in other outputs, which are declared where they are called
+    context: Rc<Context>, // Structs and global variables of the library the functions belong to
+    options: &'a Options
 }
 
-impl CodeGenerator
+impl<'a> CodeGenerator<'a>
 {
     /// Generate a new CodeGenerator object
-    pub fn new(mode: CodegenMode, functions: Vec<Function>, options: Options) -> Self
+    pub fn new(mode: CodegenMode, functions: &'a [Function], options: &'a Options) -> Self
     {
         Self
         {
             mode,
             functions,
+            external: &[],
+            context: Rc::default(),
             options
         }
     }
 
+    /// Declare functions which are called but not generated here, such as those only given by a
+    /// prototype
+    pub fn with_external(self, external: &'a [Function]) -> Self
+    {
+        Self
+        {
+            external,
+            ..self
+        }
+    }
+
+    /// Generate the functions of a library with the given context, which gives its structs and
+    /// global variables
+    pub fn with_context(self, context: &Rc<Context>) -> Self
+    {
+        Self
+        {
+            context: Rc::clone(context),
+            ..self
+        }
+    }
+
+    /// Get the extension for files holding the output of the code generator
+    pub fn extension(&self) -> &'static str
+    {
+        match self.mode
+        {
+            CodegenMode::AvrAssembly => "s",
+            CodegenMode::LLVM => "ll",
+            CodegenMode::Custom(name) => find_backend(name).map(|(_, backend)| backend.extension()).unwrap_or("ir"),
+            _ => "ir"
+        }
+    }
+
+    /// Generate code for each function on its own, passing the name of each function along with
+    /// its code to `write` as soon as it is generated, so only one function's code is held at once
+    pub fn render_split(&self, mut write: impl FnMut(&str, &str) -> Result<(), Error>) -> Result<(), Error>
+    {
+        for (i, func) in self.functions.iter().enumerate()
+        {
+            let external: Vec<&Function> = self.functions.iter().enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other)| other)
+                .chain(self.external)
+                .collect();
+
+            // Each file is linked with the others, so none of the functions can be internal to it,
+            // and the global variables are defined in the first
+            let mut output = String::new();
+            self.render_functions(&[func], &external, false, i == 0, &mut output)?;
+
+            write(&func.name, &output)?;
+        }
+
+        Ok(())
+    }
+
+    /// Generate code for the functions in groups, the functions given the same key by `group` being
+    /// generated together, giving the key of each group along with its code
+    pub fn render_grouped(&self, group: impl Fn(&Function) -> String) -> Result<Vec<(String, String)>, Error>
+    {
+        let mut keys: Vec<String> = vec![];
+
+        for func in self.functions
+        {
+            if !keys.contains(&group(func))
+            {
+                keys.push(group(func));
+            }
+        }
+
+        let mut result = vec![];
+
+        for (i, key) in keys.into_iter().enumerate()
+        {
+            let (functions, mut external): (Vec<&Function>, Vec<&Function>) = self.functions.iter().partition(|func| group(func) == key);
+            external.extend(self.external);
+
+            // The global variables are defined in the first group
+            let mut output = String::new();
+            self.render_functions(&functions, &external, true, i == 0, &mut output)?;
+
+            result.push((key, output));
+        }
+
+        Ok(result)
+    }
+
     /// Generate code for the given functions
     pub fn render(&self) -> Result<String, Error>
     {
         let mut result = String::new();
+        self.render_to(&mut result)?;
 
+        Ok(result)
+    }
+
+    /// Generate code for the given functions, writing the code for each function to `out` as soon
+    /// as it is generated rather than building the whole output first
+    pub fn render_to(&self, out: &mut dyn fmt::Write) -> Result<(), Error>
+    {
+        let functions: Vec<&Function> = self.functions.iter().collect();
+        let external: Vec<&Function> = self.external.iter().collect();
+
+        self.render_functions(&functions, &external, true, true, out)
+    }
+
+    /// Generate code for some of the functions, declaring the external functions they call. Functions
+    /// private to their module are only given internal linkage if `internal` is set, as they cannot
+    /// be when each function is linked with the others. The global variables of the library are
+    /// defined if `globals` is set, and otherwise only declared
+    fn render_functions(&self, functions: &[&Function], external: &[&Function], internal: bool, globals: bool, out: &mut dyn fmt::Write) -> Result<(), Error>
+    {
         match self.mode
         {
-            CodegenMode::Unknown => {return Err(Error::fatal_error("Unknown Codegen Mode"));},
             CodegenMode::IntermediateRepresentation =>
             {
+                // The global variables come first, with the values they start with
+                let globals: Vec<String> = self.context.globals.iter().filter(|_| globals)
+                    .map(|global| format!("global @{} ({}) = {}", global.name, global.datatype, global.value))
+                    .collect();
+
+                if !globals.is_empty()
+                {
+                    writeln!(out, "{}\n", globals.join("\n")).map_err(output_error)?;
+                }
+
                 // Render each function of intermediate representation
-                for func in &self.functions
+                for func in functions
                 {
-                    result += &format!("{}\n", func);
+                    writeln!(out, "{}", func).map_err(output_error)?;
                 }
             },
             CodegenMode::AvrAssembly =>
             {
                 // Invoke the renderer for the AvrAsm code generator
-                result = format!("{}", avrasm::AvrAsmGenerator::new(self.functions.clone()).render()?)
+                avrasm::AvrAsmGenerator::new(functions).render(out)?;
             },
             CodegenMode::LLVM =>
             {
-                // Extract the target triple if passed
-                let target = if let Some(args) = self.options.map.get("--llvm-target")
+                // Work out the sizes of types from the target specification, triple and data layout if passed
+                let target = TargetSpec::from_options(&self.options)?;
+
+                let mut generator = llvm::LLVMGenerator::new(functions, &self.context, self.options.has_long_flag("readable-names"), internal);
+
+                // Comments and blank lines only make the output easier to read
+                let compact = self.options.has_long_flag("compact-output");
+
+                if compact || self.options.has_long_flag("no-comments")
                 {
-                    Some(args[0].as_str())
+                    generator = generator.with_stripping(compact);
                 }
-                else
-                {
-                    None
-                };
 
-                // Extract the target data layout if passed
-                let layout = if let Some(args) = self.options.map.get("--llvm-layout")
+                if !globals
                 {
-                    Some(args[0].as_str())
+                    generator = generator.with_external_globals();
                 }
-                else
-                {
-                    None
-                };
 
                 // Invoke the renderer for the LLVM code generaor
-                result = format!("{}", llvm::LLVMGenerator::new(self.functions.clone()).render(target, layout)?)
+                generator.render(&target, external, out)?;
+            },
+            CodegenMode::Custom(name) =>
+            {
+                // Invoke the renderer of the code generator added by a plugin
+                if let Some((_, backend)) = find_backend(name)
+                {
+                    backend.render(functions, external, self.options, out)?;
+                }
             }
         }
 
-        Ok(result)
+        Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use crate::cli::Error;
+
+/// Sink writing generated code through to a file or other `io::Write` as it is rendered, keeping
+/// the first error it meets so the caller can report it with the name of what it was writing to
+pub struct OutputWriter<W: io::Write>
+{
+    inner: io::BufWriter<W>,
+    error: Option<io::Error>
+}
+
+impl<W: io::Write> OutputWriter<W>
+{
+    /// Generate a new OutputWriter, buffering the writes to the given writer
+    pub fn new(inner: W) -> Self
+    {
+        Self
+        {
+            inner: io::BufWriter::new(inner),
+            error: None
+        }
+    }
+
+    /// Flush everything written so far, giving the first error met while writing or flushing
+    pub fn finish(mut self) -> io::Result<()>
+    {
+        if let Some(error) = self.error.take()
+        {
+            return Err(error);
+        }
+
+        self.inner.flush()
+    }
+}
+
+impl<W: io::Write> fmt::Write for OutputWriter<W>
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result
+    {
+        // Once a write has failed, nothing more is written
+        if self.error.is_some()
+        {
+            return Err(fmt::Error);
+        }
+
+        self.inner.write_all(s.as_bytes()).map_err(|error|
+        {
+            self.error = Some(error);
+            fmt::Error
+        })
+    }
+}
+
+/// Generate the error for a sink refusing the generated code
+pub fn output_error(_error: fmt::Error) -> Error
+{
+    Error::fatal_error("Could not write the generated code").with_code("E0101")
+}
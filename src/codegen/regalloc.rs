@@ -0,0 +1,231 @@
+use crate::irgen::{Function, OpCode, Value, DataType};
+
+use std::collections::{HashMap, HashSet};
+
+/// The range of instructions over which a symbol holds a value which is still needed
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveInterval
+{
+    pub symbol: String,
+    pub datatype: DataType,
+    pub start: usize,
+    pub end: usize
+}
+
+/// Registers a backend makes available to the allocator
+#[derive(Debug, Clone)]
+pub struct RegisterFile
+{
+    pub registers: Vec<usize>,
+    pub aligned: bool // Values taking several registers must start at a multiple of their size
+}
+
+/// Result of allocating registers for a function, symbols which did not fit in registers are
+/// given a stack slot instead
+#[derive(Debug, Clone, Default)]
+pub struct Allocation
+{
+    pub registers: HashMap<String, usize>,
+    pub spilled: HashMap<String, usize>,
+    pub slots: usize
+}
+
+/// Get the instructions which may run after the instruction at the given index
+fn successors(func: &Function, index: usize) -> Vec<usize>
+{
+    match func.instructions[&index].opcode
+    {
+        OpCode::Ret => vec![],
+        OpCode::Jmp | OpCode::Beq | OpCode::Bne | OpCode::Blt | OpCode::Bgt | OpCode::Ble | OpCode::Bge =>
+            func.get_jump_values(index).unwrap_or_default(),
+        _ if index + 1 < func.instructions.len() => vec![index + 1],
+        _ => vec![]
+    }
+}
+
+/// Get the symbols an instruction reads and writes, following the same rules as
+/// `Function::get_reads_writes_for`
+pub fn uses_defs(func: &Function, index: usize) -> (Vec<String>, Vec<String>)
+{
+    let inst = &func.instructions[&index];
+    let mut uses = vec![];
+    let mut defs = vec![];
+
+    for (i, arg) in inst.arguments.iter().enumerate()
+    {
+        let symbol = match arg
+        {
+            Value::Symbol(symbol) => symbol,
+            _ => continue
+        };
+
+        let reads_only = matches!(inst.opcode, OpCode::Beq | OpCode::Bge | OpCode::Bgt | OpCode::Ble | OpCode::Blt | OpCode::Bne | OpCode::Push | OpCode::Ret);
+
+        // Writing through a reference reads the address it holds
+        if i == 0 && !reads_only && (!symbol.datatype.is_ref || inst.opcode == OpCode::Cast)
+        {
+            defs.push(symbol.key());
+        }
+        else
+        {
+            uses.push(symbol.key());
+        }
+    }
+
+    (uses, defs)
+}
+
+/// Work out the live interval of every symbol used in a function, from the first instruction it
+/// is live at to the last
+pub fn live_intervals(func: &Function) -> Vec<LiveInterval>
+{
+    let count = func.instructions.len();
+    let effects: Vec<(Vec<String>, Vec<String>)> = (0..count).map(|i| uses_defs(func, i)).collect();
+
+    let mut live_in: Vec<HashSet<String>> = vec![HashSet::new(); count];
+    let mut live_out: Vec<HashSet<String>> = vec![HashSet::new(); count];
+
+    // Propagate liveness backwards until nothing changes
+    let mut changed = true;
+    while changed
+    {
+        changed = false;
+
+        for index in (0..count).rev()
+        {
+            let out: HashSet<String> = successors(func, index).iter().flat_map(|next| live_in[*next].iter().cloned()).collect();
+
+            let (uses, defs) = &effects[index];
+            let mut inside: HashSet<String> = out.iter().filter(|symbol| !defs.contains(symbol)).cloned().collect();
+            inside.extend(uses.iter().cloned());
+
+            if inside != live_in[index] || out != live_out[index]
+            {
+                live_in[index] = inside;
+                live_out[index] = out;
+                changed = true;
+            }
+        }
+    }
+
+    // Each symbol covers every instruction it is live into, out of, or mentioned by
+    let mut ranges: HashMap<String, (usize, usize)> = HashMap::new();
+
+    for index in 0..count
+    {
+        let (uses, defs) = &effects[index];
+
+        for symbol in live_in[index].iter().chain(live_out[index].iter()).chain(uses.iter()).chain(defs.iter())
+        {
+            let range = ranges.entry(symbol.clone()).or_insert((index, index));
+            range.1 = index;
+        }
+    }
+
+    let mut result: Vec<LiveInterval> = func.get_all_symbols().into_iter().filter_map(|symbol|
+    {
+        let (start, end) = *ranges.get(&symbol.key())?;
+
+        Some(LiveInterval
+        {
+            datatype: symbol.datatype,
+            symbol: symbol.key(),
+            start,
+            end
+        })
+    }).collect();
+
+    result.sort_by_key(|interval| (interval.start, interval.end));
+
+    result
+}
+
+/// Allocate registers to the live intervals of a function with a linear scan, the number of
+/// registers each value takes is given by the backend (values taking none are left in memory),
+/// and when registers run out the interval ending last is spilled to the stack
+pub fn linear_scan(intervals: &[LiveInterval], file: &RegisterFile, size_of: &dyn Fn(&DataType) -> usize) -> Allocation
+{
+    let mut allocation = Allocation::default();
+
+    // Intervals holding registers, along with the first register and the number taken
+    let mut active: Vec<(&LiveInterval, usize, usize)> = vec![];
+    let mut free: Vec<usize> = file.registers.clone();
+
+    for interval in intervals
+    {
+        let size = size_of(&interval.datatype);
+
+        if size == 0
+        {
+            continue;
+        }
+
+        // Release the registers of intervals which ended before this one starts
+        active.retain(|(other, first, taken)|
+        {
+            if other.end < interval.start
+            {
+                free.extend(*first..*first + *taken);
+                false
+            }
+            else
+            {
+                true
+            }
+        });
+
+        if let Some(first) = find_registers(&free, file, size)
+        {
+            free.retain(|reg| *reg < first || *reg >= first + size);
+            active.push((interval, first, size));
+            allocation.registers.insert(interval.symbol.clone(), first);
+            continue;
+        }
+
+        // Steal the registers of the active interval of the same size which ends last, if it ends
+        // after this one does
+        let victim = active.iter().enumerate()
+            .filter(|(_, (other, _, taken))| *taken == size && other.end > interval.end)
+            .max_by_key(|(_, (other, _, _))| other.end)
+            .map(|(i, _)| i);
+
+        let spilled = match victim
+        {
+            Some(i) =>
+            {
+                let (other, first, _) = active.remove(i);
+
+                allocation.registers.remove(&other.symbol);
+                allocation.registers.insert(interval.symbol.clone(), first);
+                active.push((interval, first, size));
+
+                other
+            },
+            None => interval
+        };
+
+        allocation.spilled.insert(spilled.symbol.clone(), allocation.slots);
+        allocation.slots += 1;
+    }
+
+    allocation
+}
+
+/// Find the first of a run of free registers big enough to hold a value
+fn find_registers(free: &[usize], file: &RegisterFile, size: usize) -> Option<usize>
+{
+    let mut candidates: Vec<usize> = free.iter().cloned()
+        .filter(|first| !file.aligned || first % size == 0)
+        .filter(|first| (*first..*first + size).all(|reg| free.contains(&reg)))
+        .collect();
+
+    candidates.sort_unstable();
+
+    candidates.first().cloned()
+}
+
+/// Compute the live intervals of a function and allocate registers to them
+pub fn allocate_registers(func: &Function, file: &RegisterFile, size_of: &dyn Fn(&DataType) -> usize) -> Allocation
+{
+    linear_scan(&live_intervals(func), file, size_of)
+}
@@ -0,0 +1,185 @@
+/// A machine instruction as seen by the scheduler, the line of assembly along with the resources
+/// (registers, flags, memory) it reads and writes
+#[derive(Debug, Clone, PartialEq)]
+pub struct MachineInstruction
+{
+    pub text: String,
+    pub defs: Vec<String>,
+    pub uses: Vec<String>,
+    pub barrier: bool // Instructions which end a region (branches, calls, returns)
+}
+
+impl MachineInstruction
+{
+    /// Generate a new machine instruction which may be reordered with the others in its region
+    pub fn new(text: &str, defs: Vec<String>, uses: Vec<String>) -> Self
+    {
+        Self
+        {
+            text: String::from(text),
+            defs,
+            uses,
+            barrier: false
+        }
+    }
+
+    /// Generate a new machine instruction which nothing may be moved across
+    pub fn barrier(text: &str) -> Self
+    {
+        Self
+        {
+            text: String::from(text),
+            defs: vec![],
+            uses: vec![],
+            barrier: true
+        }
+    }
+}
+
+/// Description of a machine supplied by a backend, used to work out which instructions depend on
+/// each other
+pub trait MachineModel
+{
+    /// Describe a line of assembly (without surrounding whitespace) which is an instruction
+    fn describe(&self, text: &str) -> MachineInstruction;
+
+    /// Number of cycles before the result of an instruction can be used
+    fn latency(&self, _instruction: &MachineInstruction) -> usize
+    {
+        1
+    }
+}
+
+/// Orders the instructions in a region, which have no branches into or out of them
+pub trait Scheduler
+{
+    /// Give the order to emit the instructions in, as indexes into the region, which must respect
+    /// the dependencies given for each instruction (the indexes of the instructions it must follow)
+    fn schedule(&self, region: &[MachineInstruction], dependencies: &[Vec<usize>], model: &dyn MachineModel) -> Vec<usize>;
+}
+
+/// List scheduler, which repeatedly picks the ready instruction with the highest priority, the
+/// default priority is the original position so the order is kept
+pub struct ListScheduler
+{
+    priority: fn(usize, &MachineInstruction, &dyn MachineModel) -> isize
+}
+
+impl Default for ListScheduler
+{
+    fn default() -> Self
+    {
+        Self::new()
+    }
+}
+
+impl ListScheduler
+{
+    /// Generate a new list scheduler which keeps the original order
+    pub fn new() -> Self
+    {
+        Self
+        {
+            priority: |index, _, _| -(index as isize)
+        }
+    }
+
+    /// Generate a new list scheduler with the given priority for each instruction
+    pub fn with_priority(priority: fn(usize, &MachineInstruction, &dyn MachineModel) -> isize) -> Self
+    {
+        Self
+        {
+            priority
+        }
+    }
+}
+
+impl Scheduler for ListScheduler
+{
+    fn schedule(&self, region: &[MachineInstruction], dependencies: &[Vec<usize>], model: &dyn MachineModel) -> Vec<usize>
+    {
+        let mut order = vec![];
+        let mut scheduled = vec![false; region.len()];
+
+        while order.len() < region.len()
+        {
+            // Pick the ready instruction with the highest priority, ties going to the earliest
+            let next = (0..region.len())
+                .filter(|i| !scheduled[*i] && dependencies[*i].iter().all(|dep| scheduled[*dep]))
+                .max_by_key(|i| ((self.priority)(*i, &region[*i], model), -(*i as isize)))
+                .unwrap();
+
+            scheduled[next] = true;
+            order.push(next);
+        }
+
+        order
+    }
+}
+
+/// Work out which earlier instructions in a region each instruction must follow, because it reads
+/// what they write, writes what they read, or writes what they write
+pub fn dependencies(region: &[MachineInstruction]) -> Vec<Vec<usize>>
+{
+    let overlaps = |a: &[String], b: &[String]| a.iter().any(|resource| b.contains(resource));
+
+    (0..region.len()).map(|j|
+    {
+        (0..j).filter(|i|
+        {
+            let (first, second) = (&region[*i], &region[j]);
+
+            overlaps(&first.defs, &second.uses) || overlaps(&first.uses, &second.defs) || overlaps(&first.defs, &second.defs)
+        }).collect()
+    }).collect()
+}
+
+/// Run a scheduler over the assembly for a function, reordering the instructions within each run
+/// of lines which has no labels, comments or barriers
+pub fn schedule_assembly(assembly: &str, model: &dyn MachineModel, scheduler: &dyn Scheduler) -> String
+{
+    let mut result = String::new();
+    let mut region: Vec<(&str, MachineInstruction)> = vec![];
+
+    for line in assembly.lines()
+    {
+        let text = line.trim();
+        let is_instruction = line.starts_with(char::is_whitespace) && !text.is_empty() && !text.starts_with(';');
+
+        let instruction = if is_instruction {Some(model.describe(text))} else {None};
+
+        match instruction
+        {
+            Some(instruction) if !instruction.barrier =>
+            {
+                region.push((line, instruction));
+            },
+            _ =>
+            {
+                flush_region(&mut result, &mut region, model, scheduler);
+
+                result += line;
+                result += "\n";
+            }
+        }
+    }
+
+    flush_region(&mut result, &mut region, model, scheduler);
+
+    result
+}
+
+/// Emit the instructions of a region in the order given by the scheduler
+fn flush_region(result: &mut String, region: &mut Vec<(&str, MachineInstruction)>, model: &dyn MachineModel, scheduler: &dyn Scheduler)
+{
+    let instructions: Vec<MachineInstruction> = region.iter().map(|(_, instruction)| instruction.clone()).collect();
+    let order = scheduler.schedule(&instructions, &dependencies(&instructions), model);
+
+    for index in order
+    {
+        *result += region[index].0;
+        *result += "\n";
+    }
+
+    region.clear();
+}
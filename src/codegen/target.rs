@@ -0,0 +1,464 @@
+use crate::cli::{Error, Options, suggest};
+use crate::irgen::{DataType, NonPtrType, StructType};
+
+use serde::Deserialize;
+
+/// Architectures with known type sizes, along with their pointer size and alignment in bytes,
+/// endianness and data layout (for ELF targets)
+static ARCHITECTURES: &[(&str, usize, usize, bool, &str)] = &[
+    ("x86_64", 8, 8, false, "e-m:e-p270:32:32-p271:32:32-p272:64:64-i64:64-f80:128-n8:16:32:64-S128"),
+    ("i386", 4, 4, false, "e-m:e-p:32:32-p270:32:32-p271:32:32-p272:64:64-f64:32:64-f80:32-n8:16:32-S128"),
+    ("i686", 4, 4, false, "e-m:e-p:32:32-p270:32:32-p271:32:32-p272:64:64-f64:32:64-f80:32-n8:16:32-S128"),
+    ("aarch64", 8, 8, false, "e-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128"),
+    ("aarch64_be", 8, 8, true, "E-m:e-i8:8:32-i16:16:32-i64:64-i128:128-n32:64-S128"),
+    ("arm", 4, 4, false, "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64"),
+    ("armv7", 4, 4, false, "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64"),
+    ("thumbv7em", 4, 4, false, "e-m:e-p:32:32-Fi8-i64:64-v128:64:128-a:0:32-n32-S64"),
+    ("riscv32", 4, 4, false, "e-m:e-p:32:32-i64:64-n32-S128"),
+    ("riscv64", 8, 8, false, "e-m:e-p:64:64-i64:64-i128:128-n64-S128"),
+    ("wasm32", 4, 4, false, "e-m:e-p:32:32-i64:64-n32:64-S128"),
+    ("wasm64", 8, 8, false, "e-m:e-p:64:64-i64:64-n32:64-S128"),
+    ("mips", 4, 4, true, "E-m:m-p:32:32-i8:8:32-i16:16:32-i64:64-n32-S64"),
+    ("avr", 2, 1, false, "e-P1-p:16:8-i8:8-i16:8-i32:8-i64:8-f32:8-f64:8-n8-a:8")
+];
+
+/// A built in target for bare metal work, selected with --target, none of them have the hosted
+/// builtins
+struct Preset
+{
+    /// The name given to --target
+    name: &'static str,
+    /// The triple given to LLVM
+    triple: &'static str,
+    cpu: Option<&'static str>,
+    features: Option<&'static str>,
+    relocation_model: &'static str
+}
+
+/// Built in targets for bare metal work
+static PRESETS: &[Preset] = &[
+    Preset {name: "x86_64-unknown-none", triple: "x86_64-unknown-none", cpu: None, features: Some("-mmx,-sse,+soft-float"), relocation_model: "pic"},
+    Preset {name: "riscv64gc-unknown-none-elf", triple: "riscv64-unknown-none-elf", cpu: Some("generic-rv64"), features: Some("+m,+a,+f,+d,+c"), relocation_model: "static"},
+    Preset {name: "wasm32-unknown-unknown", triple: "wasm32-unknown-unknown", cpu: Some("generic"), features: None, relocation_model: "static"}
+];
+
+/// Relocation models accepted by `llc`
+static RELOCATION_MODELS: &[&str] = &["static", "pic", "dynamic-no-pic", "ropi", "rwpi", "ropi-rwpi"];
+
+/// Operating systems which do not use ELF, so the data layouts above do not apply to them
+static NON_ELF_SYSTEMS: &[&str] = &["darwin", "macos", "ios", "windows", "win32"];
+
+/// The keys read from a JSON target specification file, named as in rustc's target specifications
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct Specification
+{
+    llvm_target: String,
+    data_layout: Option<String>,
+    /// Given as either a string or a number of bits
+    target_pointer_width: Option<serde_json::Value>,
+    target_endian: Option<String>,
+    cpu: Option<String>,
+    features: Option<String>,
+    relocation_model: Option<String>
+}
+
+/// Description of the machine code is generated for, giving the sizes of types
+#[derive(Debug, Clone)]
+pub struct TargetSpec
+{
+    pub triple: Option<String>,
+    pub datalayout: Option<String>,
+    pub pointer_size: usize,
+    pub pointer_align: usize,
+    pub big_endian: bool,
+    pub cpu: Option<String>,
+    pub features: Option<String>,
+    pub relocation_model: Option<String>,
+    pub hosted: bool
+}
+
+impl TargetSpec
+{
+    /// The target used when no triple is given, a 64 bit little endian machine
+    pub fn host() -> Self
+    {
+        Self
+        {
+            triple: None,
+            datalayout: None,
+            pointer_size: 8,
+            pointer_align: 8,
+            big_endian: false,
+            cpu: None,
+            features: None,
+            relocation_model: None,
+            hosted: true
+        }
+    }
+
+    /// Get the architecture of the target, that of the machine the compiler runs on if there is no
+    /// triple, as LLVM compiles for the host without one
+    pub fn architecture(&self) -> &str
+    {
+        match &self.triple
+        {
+            Some(triple) => architecture_of(triple),
+            None => std::env::consts::ARCH
+        }
+    }
+
+    /// Generate the target for one of the built in presets
+    pub fn preset(name: &str) -> Result<Self, Error>
+    {
+        match PRESETS.iter().find(|preset| preset.name == name)
+        {
+            Some(preset) =>
+            {
+                let mut target = Self::new(Some(preset.triple), None)?;
+                target.cpu = preset.cpu.map(String::from);
+                target.features = preset.features.map(String::from);
+                target.relocation_model = Some(String::from(preset.relocation_model));
+                target.hosted = false;
+
+                Ok(target)
+            },
+            None =>
+            {
+                let error = Err(Error::fatal_error(&format!("Unknown target '{}', use one of {}", name, known_presets())).with_code("E0103"));
+                suggest(error, name, PRESETS.iter().map(|preset| preset.name))
+            }
+        }
+    }
+
+    /// Take the sizes and data layout from the architecture of a triple, returning false if the
+    /// architecture is not known
+    fn apply_architecture(&mut self, triple: &str) -> bool
+    {
+        let arch = triple.split('-').next().unwrap_or_default();
+
+        match ARCHITECTURES.iter().find(|(name, ..)| *name == arch)
+        {
+            Some((_, size, align, big_endian, layout)) =>
+            {
+                self.pointer_size = *size;
+                self.pointer_align = *align;
+                self.big_endian = *big_endian;
+
+                if !triple.split('-').any(|part| NON_ELF_SYSTEMS.iter().any(|system| part.starts_with(system)))
+                {
+                    self.datalayout = Some(String::from(*layout));
+                }
+
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Generate the target for a triple, with an optional data layout which overrides the one
+    /// for the triple's architecture
+    pub fn new(triple: Option<&str>, datalayout: Option<&str>) -> Result<Self, Error>
+    {
+        let mut target = Self::host();
+
+        if let Some(triple) = triple
+        {
+            target.triple = Some(String::from(triple));
+
+            // Without a data layout the sizes cannot be worked out
+            if !target.apply_architecture(triple) && datalayout.is_none()
+            {
+                return Err(Error::fatal_error(&format!("Unknown architecture '{}' in target triple '{}', pass --llvm-layout or use one of {}", architecture_of(triple), triple, known_architectures())).with_code("E0103"));
+            }
+        }
+
+        if let Some(layout) = datalayout
+        {
+            target.apply_datalayout(layout).map_err(|message| Error::fatal_error(&message).with_code("E0103"))?;
+        }
+
+        Ok(target)
+    }
+
+    /// Load a target from a JSON specification file, which gives the triple (`llvm-target`) and
+    /// optionally `target-pointer-width`, `target-endian`, `data-layout`, `cpu`, `features` and
+    /// `relocation-model`
+    pub fn from_file(filename: &str) -> Result<Self, Error>
+    {
+        let bad = |message: String| Error::fatal_error(&format!("{}: {}", filename, message)).with_code("E0103");
+
+        let spec: Specification = serde_json::from_str(&crate::io::read_from_file(String::from(filename))?)
+            .map_err(|error| bad(format!("Malformed target specification, {}", error)))?;
+
+        let triple = spec.llvm_target;
+        let layout = spec.data_layout;
+
+        let width = match spec.target_pointer_width
+        {
+            Some(serde_json::Value::String(text)) => Some(text.parse::<usize>().unwrap_or(0)),
+            Some(serde_json::Value::Number(value)) => Some(value.as_u64().unwrap_or(0) as usize),
+            Some(_) => Some(0),
+            None => None
+        };
+
+        let big_endian = match spec.target_endian.as_deref()
+        {
+            Some("little") => Some(false),
+            Some("big") => Some(true),
+            Some(other) => {return Err(bad(format!("Unknown endianness '{}', expected 'little' or 'big'", other)));},
+            None => None
+        };
+
+        let mut target = Self::host();
+        target.triple = Some(triple.clone());
+        target.cpu = spec.cpu;
+        target.features = spec.features;
+        target.relocation_model = spec.relocation_model;
+
+        if let Some(model) = &target.relocation_model
+        {
+            if !RELOCATION_MODELS.contains(&model.as_str())
+            {
+                return Err(bad(format!("Unknown relocation model '{}', use one of {}", model, RELOCATION_MODELS.join(", "))));
+            }
+        }
+
+        let known = target.apply_architecture(&triple);
+
+        if let Some(layout) = &layout
+        {
+            target.apply_datalayout(layout).map_err(bad)?;
+        }
+
+        if let Some(width) = width
+        {
+            if width == 0 || width % 8 != 0
+            {
+                return Err(bad(String::from("'target-pointer-width' must be a whole number of bytes in bits")));
+            }
+
+            if width / 8 != target.pointer_size
+            {
+                if layout.is_some()
+                {
+                    return Err(bad(format!("'target-pointer-width' is {} but the data layout has {} bit pointers", width, target.pointer_size * 8)));
+                }
+
+                target.pointer_size = width / 8;
+                target.pointer_align = width / 8;
+                target.rewrite_datalayout();
+            }
+        }
+        else if !known && layout.is_none()
+        {
+            return Err(bad(format!("Unknown architecture '{}' in target triple '{}', give 'target-pointer-width' or 'data-layout' or use one of {}", architecture_of(&triple), triple, known_architectures())));
+        }
+
+        if let Some(big_endian) = big_endian
+        {
+            if big_endian != target.big_endian
+            {
+                if layout.is_some()
+                {
+                    return Err(bad(String::from("'target-endian' does not match the data layout")));
+                }
+
+                target.big_endian = big_endian;
+                target.rewrite_datalayout();
+            }
+        }
+
+        Ok(target)
+    }
+
+    /// Generate the target from the --target, --target-spec, --llvm-target and --llvm-layout options
+    pub fn from_options(options: &Options) -> Result<Self, Error>
+    {
+        if let Some(name) = options.map.get("--target").and_then(|args| args.first())
+        {
+            if options.map.contains_key("--target-spec") || options.map.contains_key("--llvm-target") || options.map.contains_key("--llvm-layout")
+            {
+                return Err(Error::fatal_error("--target cannot be combined with --target-spec, --llvm-target or --llvm-layout").with_code("E0102"));
+            }
+
+            return Self::preset(name);
+        }
+
+        if let Some(filename) = options.map.get("--target-spec").and_then(|args| args.first())
+        {
+            if options.map.contains_key("--llvm-target") || options.map.contains_key("--llvm-layout")
+            {
+                return Err(Error::fatal_error("--target-spec cannot be combined with --llvm-target or --llvm-layout").with_code("E0102"));
+            }
+
+            return Self::from_file(filename);
+        }
+
+        let triple = options.map.get("--llvm-target").and_then(|args| args.first());
+        let layout = options.map.get("--llvm-layout").and_then(|args| args.first());
+
+        Self::new(triple.map(|s| s.as_str()), layout.map(|s| s.as_str()))
+    }
+
+    /// Read the endianness and pointer size and alignment out of a data layout string
+    fn apply_datalayout(&mut self, layout: &str) -> Result<(), String>
+    {
+        self.datalayout = Some(String::from(layout));
+
+        for spec in layout.split('-')
+        {
+            match spec
+            {
+                "e" => {self.big_endian = false;},
+                "E" => {self.big_endian = true;},
+                _ =>
+                {
+                    // Pointers in the default address space, p:size:abi[:pref] or p0:size:abi[:pref]
+                    let fields: Vec<&str> = spec.split(':').collect();
+
+                    if fields[0] == "p" || fields[0] == "p0"
+                    {
+                        let bits: Vec<usize> = fields[1..].iter().filter_map(|field| field.parse::<usize>().ok()).collect();
+
+                        if bits.len() != fields.len() - 1 || bits.is_empty() || bits.iter().any(|bits| bits % 8 != 0 || *bits == 0)
+                        {
+                            return Err(format!("Bad pointer specification '{}' in data layout '{}'", spec, layout));
+                        }
+
+                        self.pointer_size = bits[0] / 8;
+                        self.pointer_align = bits.get(1).copied().unwrap_or(bits[0]) / 8;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Update the endianness and pointer specification in the data layout to match the target,
+    /// so LLVM does not fall back to the defaults for the triple when laying out memory
+    fn rewrite_datalayout(&mut self)
+    {
+        let pointer = format!("p:{}:{}", self.pointer_size * 8, self.pointer_align * 8);
+        let endianness = String::from(if self.big_endian {"E"} else {"e"});
+
+        let mut specs: Vec<String> = match &self.datalayout
+        {
+            Some(layout) => layout.split('-')
+                .filter(|spec| !spec.is_empty() && *spec != "e" && *spec != "E" && !spec.starts_with("p:") && !spec.starts_with("p0:"))
+                .map(String::from)
+                .collect(),
+            None => vec![]
+        };
+
+        specs.insert(0, pointer);
+        specs.insert(0, endianness);
+
+        self.datalayout = Some(specs.join("-"));
+    }
+
+    /// Gets the number of bytes in a type
+    pub fn bytes_size_of(&self, datatype: &DataType) -> usize
+    {
+        if datatype.num_ptr > 0 || datatype.is_ref
+        {
+            self.pointer_size
+        }
+        else
+        {
+            match datatype.raw_type
+            {
+                NonPtrType::I8 | NonPtrType::U8 => 1,
+                NonPtrType::I16 | NonPtrType::U16 => 2,
+                NonPtrType::I32 | NonPtrType::U32 => 4,
+                NonPtrType::I64 | NonPtrType::U64 => 8,
+                // Other widths take up the next power of two bytes, as LLVM lays them out
+                NonPtrType::Int(bits) | NonPtrType::UInt(bits) => (bits as usize).div_ceil(8).next_power_of_two(),
+                NonPtrType::Vector(bits, _, lanes) => bits as usize / 8 * lanes as usize,
+                NonPtrType::Struct(structure) => self.struct_layout(structure).0,
+                NonPtrType::Void => 0,
+                NonPtrType::Bool => 1,
+                NonPtrType::Unknown => {panic!()}
+            }
+        }
+    }
+
+    /// Gets the number of bits in the value of a type
+    pub fn bits_of(&self, datatype: &DataType) -> usize
+    {
+        if datatype.num_ptr > 0 || datatype.is_ref
+        {
+            self.pointer_size * 8
+        }
+        else if let NonPtrType::Vector(bits, _, lanes) = datatype.raw_type
+        {
+            bits as usize * lanes as usize
+        }
+        else
+        {
+            crate::irgen::bits_of(datatype.raw_type).unwrap_or(0) as usize
+        }
+    }
+
+    /// Gets the alignment of a type in bytes
+    pub fn align_of(&self, datatype: &DataType) -> usize
+    {
+        if datatype.num_ptr > 0 || datatype.is_ref
+        {
+            self.pointer_align
+        }
+        else if let NonPtrType::Struct(structure) = datatype.raw_type
+        {
+            self.struct_layout(structure).1
+        }
+        else
+        {
+            self.bytes_size_of(datatype).max(1)
+        }
+    }
+
+    /// Gets the size and alignment of a struct in bytes, each field aligned in turn and the whole
+    /// padded to the alignment of its most aligned field, as LLVM lays them out
+    fn struct_layout(&self, structure: StructType) -> (usize, usize)
+    {
+        let mut size: usize = 0;
+        let mut largest_align = 1;
+
+        for (_, field) in structure.fields()
+        {
+            let align = self.align_of(&field);
+
+            size = size.next_multiple_of(align) + self.bytes_size_of(&field);
+            largest_align = largest_align.max(align);
+        }
+
+        (size.next_multiple_of(largest_align), largest_align)
+    }
+
+    /// The LLVM integer type the size of a pointer
+    pub fn pointer_int(&self) -> String
+    {
+        format!("i{}", self.pointer_size * 8)
+    }
+}
+
+/// Get the architecture from a target triple
+fn architecture_of(triple: &str) -> &str
+{
+    triple.split('-').next().unwrap_or_default()
+}
+
+/// List the architectures with known type sizes
+fn known_architectures() -> String
+{
+    let known: Vec<&str> = ARCHITECTURES.iter().map(|(name, ..)| *name).collect();
+    known.join(", ")
+}
+
+/// List the built in target presets
+fn known_presets() -> String
+{
+    let known: Vec<&str> = PRESETS.iter().map(|preset| preset.name).collect();
+    known.join(", ")
+}
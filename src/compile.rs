@@ -1,38 +1,52 @@
-use std::io::Write;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-use super::io::InputFile;
-use super::cli::{Error, ErrorRecorder, Options};
-use super::tokenizer::tokenize;
+use super::io::{InputFile, relative_to_current_dir};
+use super::cli::{Error, ErrorRecorder, Options, suggest};
+use super::import::{parse_with_imports, module_of};
 use super::irgen;
-use super::codegen::{CodeGenerator, CodegenMode};
+use super::codegen::{CodeGenerator, CodegenMode, OutputWriter, TargetSpec, output_error};
+use super::emit::{EmitKind, requested_emits, depfile_path, render_depfile, apidoc_path, render_apidoc, header_path, render_header, staticlib_path, write_staticlib, xref_path, render_xref, irb_path, render_irb, parse_irb, IrbLibrary, stack_report_path, render_stack_report, size_report_path, render_size_report, assembled_sizes, callgraph_path, render_callgraph_dot, CallgraphFormat, find_cycle};
+use super::ide::cross_references;
+use super::lint;
 
-use super::parser::{ParseTreeNode, display_parse_tree};
+use super::tokenizer::tokenize;
+use super::parser::{ParseTreeNode, NodeArena, display_parse_tree, parse_recovering};
+use super::ice;
 
-/// Compile the given input file
-pub fn compile(input: InputFile, options: &Options) -> Result<(), Error>
+/// Generate the output for the given input file, along with the source files it depends upon
+pub fn generate(input: &InputFile, options: &Options, display: bool) -> Result<(String, Vec<String>), Error>
 {
-    let mut recorder: ErrorRecorder = ErrorRecorder::new();
-    let data = input.data;
-    let filename = input.filename;
+    let (functions, external, context, dependencies) = generate_functions(input, options, display)?;
 
-    // Tokenization
-    let tokens = tokenize(data, filename);
+    let output = code_generator(&functions, &external, &context, options)?.render()?;
 
-    // Parsing
-    let node = recorder.wrap_return(super::parser::parse(tokens))?;
+    Ok((output, dependencies))
+}
 
-    if node.is_none()
-    {
-        Err(Error::fatal_error("No Parse Tree Returned"))?
-    }
+/// The functions generated from an input file, the functions only given by a prototype, the context
+/// of the library they belong to and the source files the input depends upon
+type GeneratedFunctions = (Vec<irgen::Function>, Vec<irgen::Function>, Rc<irgen::Context>, Vec<String>);
 
-    if options.has_long_flag("tree") || options.has_short_flag("T")
-    {
-        println!("Parse Tree:");
+/// Count the calls made by a function
+fn count_calls(function: &irgen::Function) -> usize
+{
+    function.instructions.values().filter(|instruction| instruction.opcode == irgen::OpCode::Call).count()
+}
 
-        display_parse_tree(node.clone().unwrap(), String::new(), false);
-    }
+/// Generate the optimized IR for each function in the given input file, along with the functions
+/// only given by a prototype and the source files it depends upon
+fn generate_functions(input: &InputFile, options: &Options, display: bool) -> Result<GeneratedFunctions, Error>
+{
+    let (functions, external, mut context, dependencies) = lower_functions(input, options, display)?;
 
+    Ok((optimize_functions(functions, &mut context, options)?, external, context, dependencies))
+}
+
+/// Optimize the IR of each function at the optimization level given in the options, dropping the
+/// global variables of the library's context which optimizing leaves unused
+fn optimize_functions(mut functions: Vec<irgen::Function>, context: &mut Rc<irgen::Context>, options: &Options) -> Result<Vec<irgen::Function>, Error>
+{
     // Determine Optimization Level
     let mut optimization_level = 0;
 
@@ -44,24 +58,162 @@ pub fn compile(input: InputFile, options: &Options) -> Result<(), Error>
         }
         else
         {
-            Err(Error::fatal_error(&format!("Bad optimization level '{}'", level[0])))?
+            Err(Error::fatal_error(&format!("Bad optimization level '{}'", level[0])).with_code("E0102"))?
+        }
+    }
+
+    let pass_manager = irgen::PassManager::from_options(optimization_level, !options.has_long_flag("nocomp"), options)?;
+
+    // Global variables which never change are known across the whole program, so are worked out
+    // before any function is optimized on its own
+    if optimization_level >= 2
+    {
+        irgen::propagate_constant_globals(&mut functions, &mut Rc::make_mut(context).globals);
+    }
+
+    // Const functions are evaluated as they were written, before any of them are optimized
+    let const_functions: HashMap<String, irgen::Function> = functions.iter()
+        .filter(|function| function.attribute("const").is_some())
+        .map(|function| (function.name.clone(), function.clone()))
+        .collect();
+
+    ice::enter_phase("optimization");
+
+    for function in &mut functions
+    {
+        ice::set_function(&format!("{} (optimization)", function.name));
+
+        irgen::fold_const_calls(function, &const_functions);
+        pass_manager.run(function);
+
+        // Optimizing may have worked out more arguments of calls to const functions
+        let calls = count_calls(function);
+        irgen::fold_const_calls(function, &const_functions);
+
+        if count_calls(function) != calls
+        {
+            pass_manager.run(function);
         }
+
+        function.check_blocks()?;
+    }
+
+    Ok(functions)
+}
+
+/// Generate the IR for each function in the given input file with its types corrected but before
+/// it is optimized, along with the functions only given by a prototype and the source files it
+/// depends upon
+pub fn lower_functions(input: &InputFile, options: &Options, display: bool) -> Result<GeneratedFunctions, Error>
+{
+    let mut recorder: ErrorRecorder = ErrorRecorder::new();
+
+    // Every source file the output depends upon
+    let mut dependencies = vec![input.filename.clone()];
+
+    // Tokenization and parsing, of the input and every file it imports
+    let arena = NodeArena::new();
+    let (node, imported, private) = match recorder.wrap_return(parse_with_imports(input, &arena))?
+    {
+        Some((node, imported, private)) => (Some(node), imported, private),
+        None => (None, vec![], vec![])
+    };
+
+    if node.is_none()
+    {
+        Err(Error::fatal_error("No Parse Tree Returned").with_code("E0005"))?
+    }
+
+    dependencies.extend(imported);
+
+    if let Some(spec) = options.map.get("--target-spec").and_then(|args| args.first())
+    {
+        dependencies.push(spec.clone());
+    }
+
+    if display && (options.has_long_flag("tree") || options.has_short_flag("T"))
+    {
+        println!("Parse Tree:");
+
+        display_parse_tree(node.as_ref().unwrap(), String::new(), false);
     }
 
     // Convert parse tree to IR
+    ice::enter_phase("IR generation");
     let mut functions = vec![];
+    let mut external = vec![];
 
-    match node.unwrap()
+    let warnings_as_errors = options.has_short_flag("Werror");
+    let mut warned = false;
+    let mut denied = false;
+
+    check_warning_names(options)?;
+
+    let node = node.unwrap();
+
+    // Structs and global variables are declared first, so any function may use them wherever they
+    // are declared
+    let mut context = irgen::Context::new(TargetSpec::from_options(options)?);
+    irgen::declare_structs(node.children(), &mut context)?;
+    irgen::declare_globals(node.children(), &mut context)?;
+
+    let context = Rc::new(context);
+
+    for warning in lint::lint(&node, &context).iter().filter(|warning| warning_enabled(warning, options))
+    {
+        report_warning(warning, options, &mut recorder, &mut warned, &mut denied)?;
+    }
+
+    match node
     {
         ParseTreeNode::Library(children) =>
         {
+            let mut signatures = gather_signatures(&children, &mut external, &context)?;
+            let hosted = hosted(options)?;
+
+            if hosted
+            {
+                signatures.extend(irgen::hosted_signatures());
+            }
+
+            let signatures = Rc::new(signatures);
+
             for child in children
             {
-                let mut function = irgen::Function::from_parse_tree_node(child)?;
+                // Static assertions at the top level are checked in order with the functions
+                if let ParseTreeNode::StaticAssert(_) = child
+                {
+                    irgen::check_static_assert(&child, &context)?;
+                    continue;
+                }
 
-                function = irgen::correct_types(function);
+                // Prototypes only give a signature, structs their fields and global variables their
+                // values, which have already been gathered
+                if irgen::Function::is_prototype(&child) || matches!(child, ParseTreeNode::Struct(_) | ParseTreeNode::GlobalVariable(_))
+                {
+                    continue;
+                }
 
-                function = irgen::optimize_function(function, optimization_level, !options.has_long_flag("nocomp"));
+                if let Some(name) = child.children().get(1).and_then(|node| node.first_token())
+                {
+                    ice::set_function(&name.data);
+                }
+
+                let mut function = irgen::Function::from_parse_tree_node(child, &signatures, &context)?;
+                function.internal = private.contains(&function.name);
+
+                // Constructors are run by the C runtime, which a freestanding program goes without
+                if function.attribute("constructor").is_some() && !hosted
+                {
+                    irgen::compiler_error_loc("E0039", format!("Constructor '{}' is never run, as constructors are only run before main in a hosted environment", function.name), &function.pos)?;
+                }
+
+                for warning in function.warnings.iter().filter(|warning| warning_enabled(warning, options))
+                {
+                    report_warning(warning, options, &mut recorder, &mut warned, &mut denied)?;
+                }
+
+                irgen::correct_types(&mut function)?;
 
                 functions.push(function);
             }
@@ -69,46 +221,749 @@ pub fn compile(input: InputFile, options: &Options) -> Result<(), Error>
         _ => {}
     }
 
+    if warned && warnings_as_errors
+    {
+        Err(Error::error("Stopping as warnings are treated as errors (-Werror)").with_code("E0104"))?
+    }
+
+    if denied
+    {
+        Err(Error::error("Stopping as a warning turned into an error with --deny was given").with_code("E0104"))?
+    }
+
+    check_recursion(&functions, options)?;
+
+    Ok((functions, external, context, dependencies))
+}
+
+/// Check the given input file for errors without generating any output, carrying on past syntax
+/// errors and past the errors in each function to report as many as possible, giving the number
+/// of errors found
+pub fn check(input: &InputFile, options: &Options) -> Result<usize, Error>
+{
+    let mut recorder: ErrorRecorder = ErrorRecorder::new();
+
+    ice::enter_phase("parsing");
+    let arena = NodeArena::new();
+    let (node, syntax_errors) = parse_recovering(tokenize(input.data.clone(), input.filename.clone()), &arena);
+
+    for error in &syntax_errors
+    {
+        recorder.report_error(error.clone())?;
+    }
+
+    if options.has_long_flag("tree") || options.has_short_flag("T")
+    {
+        println!("Parse Tree:");
+
+        display_parse_tree(&node, String::new(), false);
+    }
+
+    // A file which parses is checked as it is compiled, along with everything it imports
+    if syntax_errors.is_empty()
+    {
+        return match lower_functions(input, options, false)
+        {
+            Ok(_) => Ok(0),
+            Err(error) =>
+            {
+                recorder.report_error(error)?;
+                Ok(1)
+            }
+        };
+    }
+
+    // The functions of imported modules cannot be resolved without parsing the whole file
+    if node.children().iter().any(|child| matches!(child, ParseTreeNode::Import(_) | ParseTreeNode::Use(_)))
+    {
+        return Ok(syntax_errors.len());
+    }
+
+    ice::enter_phase("IR generation");
+
+    // Functions, structs and global variables with syntax errors are left out, and each of the
+    // functions is checked alone
+    let intact: Vec<ParseTreeNode> = node.children().iter()
+        .filter(|child| matches!(child, ParseTreeNode::Function(_) | ParseTreeNode::Struct(_) | ParseTreeNode::GlobalVariable(_)) && !child.has_error())
+        .cloned()
+        .collect();
+
+    let mut context = irgen::Context::new(TargetSpec::from_options(options)?);
+
+    let declared = irgen::declare_structs(&intact, &mut context).and_then(|_| irgen::declare_globals(&intact, &mut context));
+    let context = Rc::new(context);
+
+    let mut signatures = match declared.and_then(|_| gather_signatures(&intact, &mut vec![], &context))
+    {
+        Ok(signatures) => signatures,
+        Err(error) =>
+        {
+            recorder.report_error(error)?;
+            return Ok(syntax_errors.len() + 1);
+        }
+    };
+
+    // A function with a syntax error in its body can still be called by the others
+    for child in node.children().iter().filter(|child| matches!(child, ParseTreeNode::Function(_)) && child.has_error())
+    {
+        if let Ok(function) = irgen::Function::prototype_from_parse_tree_node(child, &context)
+        {
+            signatures.entry(function.name.clone()).or_insert_with(|| function.signature());
+        }
+    }
+
+    let signatures = Rc::new(signatures);
+
+    let mut count = syntax_errors.len();
+
+    for child in intact.into_iter().filter(|child| matches!(child, ParseTreeNode::Function(_)) && !irgen::Function::is_prototype(child))
+    {
+        let result = irgen::Function::from_parse_tree_node(&child, &signatures, &context).and_then(|mut function| irgen::correct_types(&mut function));
+
+        if let Err(error) = result
+        {
+            recorder.report_error(error)?;
+            count += 1;
+        }
+    }
+
+    Ok(count)
+}
+
+/// Check no function of the library can call itself, directly or through other functions, when
+/// recursion is forbidden with --forbid-recursion, so the stack a program needs is always bounded
+fn check_recursion(functions: &[irgen::Function], options: &Options) -> Result<(), Error>
+{
+    if !options.has_long_flag("forbid-recursion")
+    {
+        return Ok(());
+    }
+
+    match find_cycle(functions)
+    {
+        Some(cycle) =>
+        {
+            let pos = functions.iter().find(|function| function.name == cycle[0]).and_then(|function| function.pos.clone());
+            irgen::compiler_error_loc("E0042", format!("Function '{}' is recursive, which is forbidden by --forbid-recursion: {}", cycle[0], cycle.join(" -> ")), &pos)
+        },
+        None => Ok(())
+    }
+}
+
+/// Check if the builtins needing the C library are available, which they are unless asked not to
+/// be with --freestanding or the target is a bare metal one
+fn hosted(options: &Options) -> Result<bool, Error>
+{
+    match (options.has_long_flag("hosted"), options.has_long_flag("freestanding"))
+    {
+        (true, true) => Err(Error::fatal_error("--hosted cannot be combined with --freestanding").with_code("E0102")),
+        (true, false) => Ok(true),
+        (false, true) => Ok(false),
+        (false, false) => Ok(TargetSpec::from_options(options)?.hosted)
+    }
+}
+
+/// Warnings which are only given when asked for with a -W flag, along with the name of the flag
+static OPTIONAL_WARNINGS: &[(&str, &str)] = &[
+    ("W0002", "dangling-else"),
+    ("W0003", "assign-in-condition"),
+    ("W0006", "sign-compare")
+];
+
+/// Get the warnings named in a list of warnings passed to --allow or --deny
+fn named_warnings<'a>(options: &'a Options, flag: &str) -> impl Iterator<Item = &'a String>
+{
+    options.map.get(flag).into_iter().flatten()
+}
+
+/// Make sure every warning passed to --allow or --deny is one which has a name
+fn check_warning_names(options: &Options) -> Result<(), Error>
+{
+    let names = || OPTIONAL_WARNINGS.iter().chain(lint::LINTS).map(|(_, name)| *name);
+
+    for flag in ["--allow", "--deny"]
+    {
+        for name in named_warnings(options, flag)
+        {
+            if !names().any(|other| other == name)
+            {
+                suggest(Err(Error::fatal_error(&format!("Unknown warning '{}' passed to {}", name, flag)).with_code("E0102")), name, names())?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Check if a warning was named by a flag taking a list of warnings
+fn warning_named(warning: &Error, options: &Options, flag: &str) -> bool
+{
+    OPTIONAL_WARNINGS.iter().chain(lint::LINTS)
+        .find(|(code, _)| warning.code() == Some(*code))
+        .map(|(_, name)| named_warnings(options, flag).any(|other| other == name))
+        .unwrap_or(false)
+}
+
+/// Check if a warning should be given, those which are optional needing their -W flag, and those
+/// passed to --allow never being given
+fn warning_enabled(warning: &Error, options: &Options) -> bool
+{
+    if warning_named(warning, options, "--allow")
+    {
+        return false;
+    }
+
+    match OPTIONAL_WARNINGS.iter().find(|(code, _)| warning.code() == Some(*code))
+    {
+        Some((_, name)) => options.has_short_flag(&format!("W{}", name)) || warning_named(warning, options, "--deny"),
+        None => true
+    }
+}
+
+/// Report a warning, as an error if warnings are treated as errors or it was passed to --deny
+fn report_warning(warning: &Error, options: &Options, recorder: &mut ErrorRecorder, warned: &mut bool, denied: &mut bool) -> Result<(), Error>
+{
+    *warned = true;
+
+    if warning_named(warning, options, "--deny")
+    {
+        *denied = true;
+        return recorder.report_error(warning.clone().promote());
+    }
+
+    recorder.report_error(if options.has_short_flag("Werror") {warning.clone().promote()} else {warning.clone()})
+}
+
+/// Gather the signature of every function in a library before any function body is generated,
+/// so functions can be called before they are defined, adding the functions which are only given
+/// by a prototype to the external functions
+fn gather_signatures(children: &[ParseTreeNode], external: &mut Vec<irgen::Function>, context: &Rc<irgen::Context>) -> Result<HashMap<String, irgen::Signature>, Error>
+{
+    let mut signatures: HashMap<String, irgen::Signature> = HashMap::new();
+    let mut defined = vec![];
+
+    for child in children
+    {
+        if let ParseTreeNode::Function(_) = child
+        {
+            let function = irgen::Function::prototype_from_parse_tree_node(child, context)?;
+            let location = child.children().get(1).and_then(|node| node.first_token()).map(|token| token.location.clone());
+
+            if signatures.get(&function.name).map(|signature| signature != &function.signature()).unwrap_or(false)
+            {
+                irgen::compiler_error_loc("E0026", format!("Conflicting declarations of function '{}'", function.name), &location)?
+            }
+
+            if !irgen::Function::is_prototype(child)
+            {
+                if defined.contains(&function.name)
+                {
+                    irgen::compiler_error_loc("E0026", format!("Function '{}' is defined more than once", function.name), &location)?
+                }
+
+                defined.push(function.name.clone());
+            }
+
+            signatures.insert(function.name.clone(), function.signature());
+
+            if !external.iter().any(|other: &irgen::Function| other.name == function.name)
+            {
+                external.push(function);
+            }
+        }
+    }
+
+    // Only the functions never defined in the library are external
+    external.retain(|function| !defined.contains(&function.name));
+
+    Ok(signatures)
+}
+
+/// Set up the code generator for the mode given in the options, declaring the external functions
+/// where they are called
+fn code_generator<'a>(functions: &'a [irgen::Function], external: &'a [irgen::Function], context: &Rc<irgen::Context>, options: &'a Options) -> Result<CodeGenerator<'a>, Error>
+{
     // Code Generation
-    let mut codegen_mode = CodegenMode::IntermediateRepresentation;
+    ice::enter_phase("code generation");
+
+    Ok(CodeGenerator::new(codegen_mode(options)?, functions, options).with_external(external).with_context(context))
+}
+
+/// Get the code generation mode given in the options
+fn codegen_mode(options: &Options) -> Result<CodegenMode, Error>
+{
+    match options.map.get("-g")
+    {
+        Some(name) => CodegenMode::from_mode(&name[0]),
+        None => Ok(CodegenMode::IntermediateRepresentation)
+    }
+}
+
+/// An output file being written as its code is generated, which is removed if it is dropped before
+/// it is finished, so neither an error nor a crash part way through leaves partial output behind
+struct PartialOutput<'p>
+{
+    path: &'p str,
+    finished: bool
+}
 
-    if let Some(name) = options.map.get("-g")
+impl Drop for PartialOutput<'_>
+{
+    fn drop(&mut self)
     {
-        codegen_mode = CodegenMode::from_mode(&name[0]);
+        // Devices and links, such as /dev/stdout, are written through rather than replaced
+        let regular = std::fs::symlink_metadata(self.path).map(|metadata| metadata.file_type().is_file()).unwrap_or(false);
+
+        if !self.finished && regular
+        {
+            let _ = std::fs::remove_file(self.path);
+        }
     }
+}
+
+/// Write the generated code to a file or stdout as each function is generated, between the text
+/// given to go before and after it, naming what is written to if writing fails
+fn stream_output(codegen: &CodeGenerator, writer: impl std::io::Write, before: &str, after: &str, name: &str) -> Result<(), Error>
+{
+    let mut writer = OutputWriter::new(writer);
 
-    let output = CodeGenerator::new(codegen_mode, functions, options.clone()).render()?;
+    let rendered = std::fmt::Write::write_str(&mut writer, before).map_err(output_error)
+        .and_then(|_| codegen.render_to(&mut writer))
+        .and_then(|_| std::fmt::Write::write_str(&mut writer, after).map_err(output_error));
+
+    // A failure to write is reported over the error it makes generating the code
+    if writer.finish().is_err()
+    {
+        Err(Error::fatal_error(&format!("Could not write to {}", name)).with_code("E0101"))?;
+    }
+
+    rendered
+}
+
+/// Write the output for each function to its own file in the given directory, along with an
+/// index giving the file for each function, returning the path of the index
+fn write_split_output(codegen: &CodeGenerator, directory: &str) -> Result<String, Error>
+{
+    if std::fs::create_dir_all(directory).is_err()
+    {
+        Err(Error::fatal_error(&format!("Could not create output directory '{}'", directory)).with_code("E0101"))?;
+    }
+
+    let mut index = String::new();
+
+    codegen.render_split(|name, output|
+    {
+        let filename = format!("{}.{}", name, codegen.extension());
+        let path = std::path::Path::new(directory).join(&filename);
+
+        if std::fs::write(&path, output).is_err()
+        {
+            Err(Error::fatal_error(&format!("Could not write to output file '{}'", path.display())).with_code("E0101"))?;
+        }
+
+        index += &format!("{} {}\n", name, filename);
+
+        Ok(())
+    })?;
+
+    let index_path = std::path::Path::new(directory).join("index");
+
+    if std::fs::write(&index_path, index).is_err()
+    {
+        Err(Error::fatal_error(&format!("Could not write to index file '{}'", index_path.display())).with_code("E0101"))?;
+    }
+
+    Ok(index_path.display().to_string())
+}
+
+/// Write the generated code to the output the options ask for, giving the path of the file
+/// written, or of the index when each function is written to its own file
+fn write_output(codegen: &CodeGenerator, options: &Options) -> Result<String, Error>
+{
+    let mut output_filename = String::from("out.ll");
+
+    if let Some(name) = options.map.get("-o")
+    {
+        output_filename = name[0].clone();
+    }
+
+    // Write each function to its own file
+    if let Some(directory) = options.map.get("--split-output").and_then(|args| args.first())
+    {
+        output_filename = write_split_output(codegen, directory)?;
+    }
 
     // Display Output to stdout
-    if options.has_long_flag("stdout")
+    else if options.has_long_flag("stdout")
     {
-        println!("Output:\n{}", output);
+        stream_output(codegen, std::io::stdout().lock(), "Output:\n", "\n", "stdout")?;
     }
 
     // Output to a file
     else
     {
-        let mut output_filename = "out.ll";
+        // Write to the output file
+        let file = match std::fs::File::create(&output_filename)
+        {
+            Ok(file) => file,
+            Err(_) => {return Err(Error::fatal_error(&format!("Could not create output file '{}'", output_filename)).with_code("E0101"));}
+        };
+
+        let mut partial = PartialOutput { path: &output_filename, finished: false };
+        stream_output(codegen, file, "", "", &format!("output file '{}'", output_filename))?;
+        partial.finished = true;
+    }
+
+    Ok(output_filename)
+}
+
+/// Write the dependency file for an output, listing the files it was built from
+fn write_depfile(output_filename: &str, dependencies: &[String], options: &Options) -> Result<(), Error>
+{
+    let depfile_filename = depfile_path(output_filename);
+
+    let target = if options.has_long_flag("deterministic") {relative_to_current_dir(output_filename)} else {output_filename.to_string()};
+
+    if std::fs::write(&depfile_filename, render_depfile(&target, dependencies)).is_err()
+    {
+        Err(Error::fatal_error(&format!("Could not write to dependency file '{}'", depfile_filename)).with_code("E0101"))?;
+    }
+
+    Ok(())
+}
+
+/// Compile the given input file
+pub fn compile(input: &InputFile, options: &Options) -> Result<(), Error>
+{
+    let emits = requested_emits(options)?;
+
+    let (functions, external, mut context, mut dependencies) = lower_functions(input, options, true)?;
+
+    // The IR is written before it is optimized, so it can be compiled at any optimization level
+    let irb = if emits.contains(&EmitKind::Irb)
+    {
+        Some(render_irb(&functions, &external, &context)?)
+    }
+    else
+    {
+        None
+    };
 
-        if let Some(name) = options.map.get("-o")
+    let functions = optimize_functions(functions, &mut context, options)?;
+
+    let source_filename = if options.has_long_flag("deterministic") {relative_to_current_dir(&input.filename)} else {input.filename.clone()};
+
+    // The cross reference index is of the source as it was written
+    let xref = if emits.contains(&EmitKind::XrefJson)
+    {
+        Some(render_xref(&cross_references(input)?))
+    }
+    else
+    {
+        None
+    };
+
+    // The documentation, header and library are made before code generation takes the functions
+    let apidoc = if emits.contains(&EmitKind::Apidoc)
+    {
+        Some(render_apidoc(&source_filename, &functions, &external))
+    }
+    else
+    {
+        None
+    };
+
+    let stack_report = if emits.contains(&EmitKind::StackReport)
+    {
+        Some(render_stack_report(&source_filename, &functions, &TargetSpec::from_options(options)?))
+    }
+    else
+    {
+        None
+    };
+
+    let size_report = if emits.contains(&EmitKind::SizeReport)
+    {
+        Some(size_report(&source_filename, &functions, &external, &context, options)?)
+    }
+    else
+    {
+        None
+    };
+
+    let callgraph = callgraph(&source_filename, &functions, &emits);
+
+    let header = if emits.contains(&EmitKind::Header) || emits.contains(&EmitKind::Staticlib)
+    {
+        Some(render_header(&source_filename, &functions, &context))
+    }
+    else
+    {
+        None
+    };
+
+    // A static library has an object file for each module, whatever the code generation mode
+    let staticlib = if emits.contains(&EmitKind::Staticlib)
+    {
+        let stem = std::path::Path::new(&input.filename).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        let codegen = CodeGenerator::new(CodegenMode::LLVM, &functions, options).with_external(&external).with_context(&context);
+
+        Some(codegen.render_grouped(|function| module_of(&function.name).unwrap_or(&stem).to_string())?)
+    }
+    else
+    {
+        None
+    };
+
+    let codegen = code_generator(&functions, &external, &context, options)?;
+
+    // Compile a second time and make sure the output is byte for byte identical
+    if options.has_long_flag("deterministic-check")
+    {
+        let output = codegen.render()?;
+        let (second_output, _) = generate(input, options, false)?;
+
+        if second_output != output
         {
-            output_filename = &name[0];
+            Err(Error::fatal_error(&format!("Output for '{}' differs between two identical compilations", input.filename)).with_code("E0105"))?
         }
+    }
+
+    // Strip the current directory from any paths so the outputs do not depend on where the build happens
+    if options.has_long_flag("deterministic")
+    {
+        dependencies = dependencies.iter().map(|path| relative_to_current_dir(path)).collect();
+    }
 
+    let output_filename = write_output(&codegen, options)?;
 
-        // Write to the output file
-        let file = std::fs::File::create(output_filename);
+    // Write the dependency file
+    if emits.contains(&EmitKind::Depfile)
+    {
+        write_depfile(&output_filename, &dependencies, options)?;
+    }
+
+    // Write the API documentation
+    if let Some(apidoc) = apidoc
+    {
+        let apidoc_filename = apidoc_path(&output_filename);
+
+        if std::fs::write(&apidoc_filename, apidoc).is_err()
+        {
+            Err(Error::fatal_error(&format!("Could not write to API documentation file '{}'", apidoc_filename)).with_code("E0101"))?;
+        }
+    }
+
+    // Write the C header
+    if let Some(header) = header
+    {
+        let header_filename = header_path(&output_filename);
 
-        if file.is_err()
+        if std::fs::write(&header_filename, header).is_err()
         {
-            Err(Error::fatal_error(&format!("Could not create output file '{}'", output_filename)))?;
+            Err(Error::fatal_error(&format!("Could not write to header file '{}'", header_filename)).with_code("E0101"))?;
         }
+    }
+
+    if let Some(stack_report) = stack_report
+    {
+        write_stack_report(&output_filename, &stack_report)?;
+    }
+
+    if let Some(size_report) = size_report
+    {
+        write_size_report(&output_filename, &size_report)?;
+    }
+
+    if let Some(callgraph) = callgraph
+    {
+        write_callgraph(&output_filename, &callgraph)?;
+    }
+
+    // Write the IR
+    if let Some(irb) = irb
+    {
+        let irb_filename = irb_path(&output_filename);
+
+        if std::fs::write(&irb_filename, irb).is_err()
+        {
+            Err(Error::fatal_error(&format!("Could not write to IR file '{}'", irb_filename)).with_code("E0101"))?;
+        }
+    }
+
+    // Write the cross reference index
+    if let Some(xref) = xref
+    {
+        let xref_filename = xref_path(&output_filename);
+
+        if std::fs::write(&xref_filename, xref).is_err()
+        {
+            Err(Error::fatal_error(&format!("Could not write to cross reference file '{}'", xref_filename)).with_code("E0101"))?;
+        }
+    }
+
+    // Build the static library
+    if let Some(modules) = staticlib
+    {
+        let target = TargetSpec::from_options(options)?;
+        write_staticlib(&staticlib_path(&output_filename), &modules, target.relocation_model.as_deref())?;
+    }
+
+    Ok(())
+}
+
+/// Write the stack usage report for the given output file
+fn write_stack_report(output_filename: &str, report: &str) -> Result<(), Error>
+{
+    let stack_report_filename = stack_report_path(output_filename);
+
+    if std::fs::write(&stack_report_filename, report).is_err()
+    {
+        Err(Error::fatal_error(&format!("Could not write to stack usage report '{}'", stack_report_filename)).with_code("E0101"))?;
+    }
+
+    Ok(())
+}
+
+/// Render the code size report for the given functions, assembling them to find the bytes each
+/// takes when generating LLVM IR
+fn size_report(source_filename: &str, functions: &[irgen::Function], external: &[irgen::Function], context: &Rc<irgen::Context>, options: &Options) -> Result<String, Error>
+{
+    if codegen_mode(options)? != CodegenMode::LLVM
+    {
+        return Ok(render_size_report(source_filename, functions, None));
+    }
+
+    let module = CodeGenerator::new(CodegenMode::LLVM, functions, options).with_external(external).with_context(context).render()?;
+    let target = TargetSpec::from_options(options)?;
+    let sizes = assembled_sizes(&module, target.relocation_model.as_deref())?;
+
+    Ok(render_size_report(source_filename, functions, Some(&sizes)))
+}
+
+/// Write the code size report for the given output file
+fn write_size_report(output_filename: &str, report: &str) -> Result<(), Error>
+{
+    let size_report_filename = size_report_path(output_filename);
+
+    if std::fs::write(&size_report_filename, report).is_err()
+    {
+        Err(Error::fatal_error(&format!("Could not write to code size report '{}'", size_report_filename)).with_code("E0101"))?;
+    }
+
+    Ok(())
+}
+
+/// Render the call graph of the given functions in the format asked for, if one was
+fn callgraph(source_filename: &str, functions: &[irgen::Function], emits: &[EmitKind]) -> Option<String>
+{
+    emits.iter().find_map(|kind| match kind
+    {
+        EmitKind::Callgraph(CallgraphFormat::Dot) => Some(render_callgraph_dot(source_filename, functions)),
+        _ => None
+    })
+}
 
-        if let Err(_error) = write!(file.unwrap(), "{}", output)
+/// Write the call graph for the given output file
+fn write_callgraph(output_filename: &str, callgraph: &str) -> Result<(), Error>
+{
+    let callgraph_filename = callgraph_path(output_filename);
+
+    if std::fs::write(&callgraph_filename, callgraph).is_err()
+    {
+        Err(Error::fatal_error(&format!("Could not write to call graph '{}'", callgraph_filename)).with_code("E0101"))?;
+    }
+
+    Ok(())
+}
+
+/// Read the functions of a library from an IR file written by --emit irb, along with the context
+/// of the library for the target given in the options
+fn read_irb(filename: &str, options: &Options) -> Result<IrbLibrary, Error>
+{
+    match std::fs::read(filename)
+    {
+        Ok(bytes) => parse_irb(&bytes, filename, TargetSpec::from_options(options)?),
+        Err(_) => Err(Error::error(&format!("{}: No such file or directory", filename)).with_code("E0100"))
+    }
+}
+
+/// Compile the IR file written by --emit irb with the given name, optimizing and generating code
+/// for it as if it had just been lowered from its source
+pub fn compile_irb(filename: &str, options: &Options) -> Result<(), Error>
+{
+    let emits = requested_emits(options)?;
+
+    // The other outputs are made from the source, which the IR file does not hold
+    if emits.iter().any(|kind| !matches!(kind, EmitKind::Depfile | EmitKind::StackReport | EmitKind::SizeReport | EmitKind::Callgraph(_)))
+    {
+        Err(Error::fatal_error("Only --emit depfile, --emit stack-report, --emit size-report and --emit callgraph can be used with --from-irb").with_code("E0102"))?;
+    }
+
+    ice::enter_phase("reading IR");
+    let (functions, external, mut context) = read_irb(filename, options)?;
+    check_recursion(&functions, options)?;
+    let functions = optimize_functions(functions, &mut context, options)?;
+
+    let stack_report = if emits.contains(&EmitKind::StackReport)
+    {
+        let source_filename = if options.has_long_flag("deterministic") {relative_to_current_dir(filename)} else {filename.to_string()};
+        Some(render_stack_report(&source_filename, &functions, &TargetSpec::from_options(options)?))
+    }
+    else
+    {
+        None
+    };
+
+    let size_report = if emits.contains(&EmitKind::SizeReport)
+    {
+        let source_filename = if options.has_long_flag("deterministic") {relative_to_current_dir(filename)} else {filename.to_string()};
+        Some(size_report(&source_filename, &functions, &external, &context, options)?)
+    }
+    else
+    {
+        None
+    };
+
+    let callgraph = callgraph(&if options.has_long_flag("deterministic") {relative_to_current_dir(filename)} else {filename.to_string()}, &functions, &emits);
+
+    let codegen = code_generator(&functions, &external, &context, options)?;
+
+    // Read and compile the file a second time and make sure the output is byte for byte identical
+    if options.has_long_flag("deterministic-check")
+    {
+        let output = codegen.render()?;
+        let (second_functions, second_external, mut second_context) = read_irb(filename, options)?;
+        let second_functions = optimize_functions(second_functions, &mut second_context, options)?;
+
+        if code_generator(&second_functions, &second_external, &second_context, options)?.render()? != output
         {
-            Err(Error::fatal_error(&format!("Could not write to output file '{}'", output_filename)))?;
+            Err(Error::fatal_error(&format!("Output for '{}' differs between two identical compilations", filename)).with_code("E0105"))?
         }
     }
-    
+
+    let output_filename = write_output(&codegen, options)?;
+
+    if emits.contains(&EmitKind::Depfile)
+    {
+        let dependency = if options.has_long_flag("deterministic") {relative_to_current_dir(filename)} else {filename.to_string()};
+        write_depfile(&output_filename, &[dependency], options)?;
+    }
+
+    if let Some(stack_report) = stack_report
+    {
+        write_stack_report(&output_filename, &stack_report)?;
+    }
+
+    if let Some(size_report) = size_report
+    {
+        write_size_report(&output_filename, &size_report)?;
+    }
+
+    if let Some(callgraph) = callgraph
+    {
+        write_callgraph(&output_filename, &callgraph)?;
+    }
+
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use crate::irgen::Function;
+
+/// Get the path of the API documentation for a given output file
+pub fn apidoc_path(output_filename: &str) -> String
+{
+    Path::new(output_filename).with_extension("md").to_string_lossy().to_string()
+}
+
+/// Render the signature of a function as it is written in the source
+fn render_signature(function: &Function) -> String
+{
+    let arguments: Vec<String> = function.arguments.iter().zip(&function.defaults)
+        .map(|((name, datatype), default)| match default
+        {
+            Some(value) => format!("{} {} = {}", datatype, name, value),
+            None => format!("{} {}", datatype, name)
+        })
+        .collect();
+
+    format!("{} {}({})", function.return_type, function.name, arguments.join(", "))
+}
+
+/// Render the documentation of a single function
+fn render_function(function: &Function) -> String
+{
+    let mut result = format!("### `{}`\n\n```\n", function.name);
+
+    for attribute in &function.attributes
+    {
+        result += &format!("{}\n", attribute);
+    }
+
+    result += &format!("{}\n```\n", render_signature(function));
+
+    if let Some(doc) = &function.doc
+    {
+        result += &format!("\n{}\n", doc);
+    }
+
+    result
+}
+
+/// Render Markdown documentation of the interface of a library, giving the signature, attributes
+/// and doc comments of each function defined in it followed by those it only declares
+pub fn render_apidoc(filename: &str, functions: &[Function], external: &[Function]) -> String
+{
+    let mut result = format!("# `{}`\n", filename);
+
+    for (title, group) in [("Functions", functions), ("External Functions", external)].iter()
+    {
+        if group.is_empty()
+        {
+            continue;
+        }
+
+        result += &format!("\n## {}\n", title);
+
+        for function in group.iter()
+        {
+            result += &format!("\n{}", render_function(function));
+        }
+    }
+
+    result
+}
@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::irgen::{Function, OpCode, Value};
+
+/// Formats the call graph can be written in, given after `callgraph` to --emit
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallgraphFormat
+{
+    Dot
+}
+
+impl CallgraphFormat
+{
+    /// Get the format with the given name
+    pub fn from_name(name: &str) -> Option<CallgraphFormat>
+    {
+        match name
+        {
+            "dot" => Some(CallgraphFormat::Dot),
+            _ => None
+        }
+    }
+}
+
+/// Get the path of the call graph for a given output file
+pub fn callgraph_path(output_filename: &str) -> String
+{
+    Path::new(output_filename).with_extension("dot").to_string_lossy().to_string()
+}
+
+/// The functions a function calls, each with the number of places it is called from, `None`
+/// standing for indirect calls
+pub type CallSites = Vec<(Option<String>, usize)>;
+
+/// Get the functions a function calls, in the order they are first called, along with the number
+/// of places each is called from. A call to anything other than a named function is indirect, and
+/// is given as `None`
+pub fn call_sites(function: &Function) -> CallSites
+{
+    let mut result: CallSites = vec![];
+
+    for i in 0..function.instructions.len()
+    {
+        let instruction = &function.instructions[&i];
+
+        if instruction.opcode != OpCode::Call
+        {
+            continue;
+        }
+
+        let callee = match instruction.arguments.last()
+        {
+            Some(Value::Label(name)) => Some(name.clone()),
+            _ => None
+        };
+
+        match result.iter_mut().find(|(other, _)| *other == callee)
+        {
+            Some((_, count)) => {*count += 1;},
+            None => {result.push((callee, 1));}
+        }
+    }
+
+    result
+}
+
+/// Get the names of the functions a function calls directly, in the order they are first called
+pub fn callees(function: &Function) -> Vec<String>
+{
+    call_sites(function).into_iter().filter_map(|(callee, _)| callee).collect()
+}
+
+/// Order the functions of a library so each comes after the functions it calls, which is the order
+/// an inliner visits them in so a function is inlined once it has had its own calls inlined. The
+/// last call of a cycle, which cannot be put in this order, is passed over
+pub fn bottom_up_order(functions: &[Function]) -> Vec<String>
+{
+    let calls: HashMap<&str, Vec<String>> = functions.iter().map(|function| (function.name.as_str(), callees(function))).collect();
+
+    let mut result = vec![];
+    let mut visited = vec![];
+
+    for function in functions
+    {
+        visit(&function.name, &calls, &mut visited, &mut result);
+    }
+
+    result
+}
+
+/// Add a function to the bottom up order after the functions it calls which are not yet visited
+fn visit(name: &str, calls: &HashMap<&str, Vec<String>>, visited: &mut Vec<String>, result: &mut Vec<String>)
+{
+    if visited.iter().any(|other| other == name) || !calls.contains_key(name)
+    {
+        return;
+    }
+
+    visited.push(name.to_string());
+
+    for callee in &calls[name]
+    {
+        visit(callee, calls, visited, result);
+    }
+
+    result.push(name.to_string());
+}
+
+/// Find a cycle of calls between the functions of a library, giving the functions along it from
+/// the first called back into, ending with that function again. Functions are searched in the order
+/// they are defined, so the same library always gives the same cycle
+pub fn find_cycle(functions: &[Function]) -> Option<Vec<String>>
+{
+    let calls: HashMap<&str, Vec<String>> = functions.iter().map(|function| (function.name.as_str(), callees(function))).collect();
+
+    let mut finished = vec![];
+
+    functions.iter().find_map(|function| follow(&function.name, &calls, &mut vec![], &mut finished))
+}
+
+/// Follow the calls from a function looking for one back into a function whose calls are still
+/// being followed, which are held in `active`. A function is finished once every call from it has
+/// been followed without finding a cycle
+fn follow(name: &str, calls: &HashMap<&str, Vec<String>>, active: &mut Vec<String>, finished: &mut Vec<String>) -> Option<Vec<String>>
+{
+    if let Some(position) = active.iter().position(|other| other == name)
+    {
+        let mut cycle = active[position..].to_vec();
+        cycle.push(name.to_string());
+        return Some(cycle);
+    }
+
+    if finished.iter().any(|other| other == name) || !calls.contains_key(name)
+    {
+        return None;
+    }
+
+    active.push(name.to_string());
+
+    for callee in &calls[name]
+    {
+        if let Some(cycle) = follow(callee, calls, active, finished)
+        {
+            return Some(cycle);
+        }
+    }
+
+    active.pop();
+    finished.push(name.to_string());
+
+    None
+}
+
+/// Render the call graph of a library as a Graphviz digraph. Each function defined is a box, each
+/// function called which is not defined, such as one only declared with a prototype or a builtin,
+/// is a dashed box, and indirect calls lead to a single placeholder node. An edge called from more
+/// than one place is labeled with the number of calls
+pub fn render_callgraph_dot(filename: &str, functions: &[Function]) -> String
+{
+    let mut result = format!("// Call graph of '{}'\ndigraph callgraph\n{{\n    node [shape=box];\n\n", filename);
+
+    let sites: Vec<(&str, CallSites)> = functions.iter().map(|function| (function.name.as_str(), call_sites(function))).collect();
+
+    for function in functions
+    {
+        result += &format!("    \"{}\";\n", function.name);
+    }
+
+    let mut undefined: Vec<&str> = vec![];
+
+    for (_, calls) in &sites
+    {
+        for (callee, _) in calls
+        {
+            if let Some(callee) = callee
+            {
+                if !functions.iter().any(|function| function.name == *callee) && !undefined.contains(&callee.as_str())
+                {
+                    undefined.push(callee);
+                }
+            }
+        }
+    }
+
+    for name in &undefined
+    {
+        result += &format!("    \"{}\" [style=dashed];\n", name);
+    }
+
+    if sites.iter().any(|(_, calls)| calls.iter().any(|(callee, _)| callee.is_none()))
+    {
+        result += "    \"<indirect>\" [shape=ellipse, style=dashed, label=\"indirect call\"];\n";
+    }
+
+    result += "\n";
+
+    for (caller, calls) in &sites
+    {
+        for (callee, count) in calls
+        {
+            let callee = callee.as_deref().unwrap_or("<indirect>");
+
+            if *count > 1
+            {
+                result += &format!("    \"{}\" -> \"{}\" [label=\"{}\"];\n", caller, callee, count);
+            }
+            else
+            {
+                result += &format!("    \"{}\" -> \"{}\";\n", caller, callee);
+            }
+        }
+    }
+
+    result += "}\n";
+
+    result
+}
@@ -0,0 +1,46 @@
+use std::path::Path;
+
+/// Escape a path for use within a Makefile rule
+fn escape_make_path(path: &str) -> String
+{
+    let mut result = String::new();
+
+    for c in path.chars()
+    {
+        match c
+        {
+            ' ' | '#' => {result.push('\\'); result.push(c);},
+            '$' => {result.push_str("$$");},
+            _ => {result.push(c);}
+        }
+    }
+
+    result
+}
+
+/// Get the path of the dependency file for a given output file
+pub fn depfile_path(output_filename: &str) -> String
+{
+    Path::new(output_filename).with_extension("d").to_string_lossy().to_string()
+}
+
+/// Render a Makefile style dependency file listing every source file the target was built from
+pub fn render_depfile(target: &str, dependencies: &[String]) -> String
+{
+    let mut result = format!("{}:", escape_make_path(target));
+
+    for dependency in dependencies
+    {
+        result += &format!(" \\\n  {}", escape_make_path(dependency));
+    }
+
+    result += "\n";
+
+    // Add an empty rule for each dependency so removed files do not break the build
+    for dependency in dependencies
+    {
+        result += &format!("\n{}:\n", escape_make_path(dependency));
+    }
+
+    result
+}
@@ -0,0 +1,179 @@
+use std::path::Path;
+
+use crate::irgen::{Context, Function, DataType, NonPtrType, StructType};
+
+/// Get the path of the C header for a given output file
+pub fn header_path(output_filename: &str) -> String
+{
+    Path::new(output_filename).with_extension("h").to_string_lossy().to_string()
+}
+
+/// Get the C type for a type, if there is one
+fn c_type(datatype: &DataType) -> Option<String>
+{
+    let base = match datatype.raw_type
+    {
+        NonPtrType::I8 => String::from("int8_t"),
+        NonPtrType::U8 => String::from("uint8_t"),
+        NonPtrType::I16 => String::from("int16_t"),
+        NonPtrType::U16 => String::from("uint16_t"),
+        NonPtrType::I32 => String::from("int32_t"),
+        NonPtrType::U32 => String::from("uint32_t"),
+        NonPtrType::I64 => String::from("int64_t"),
+        NonPtrType::U64 => String::from("uint64_t"),
+        NonPtrType::Int(bits) => format!("_BitInt({})", bits),
+        NonPtrType::UInt(bits) => format!("unsigned _BitInt({})", bits),
+        NonPtrType::Bool => String::from("bool"),
+        NonPtrType::Void => String::from("void"),
+        NonPtrType::Struct(structure) => structure.to_string(),
+        NonPtrType::Vector(..) | NonPtrType::Unknown => {return None;}
+    };
+
+    let mut result = base + &"*".repeat(datatype.num_ptr);
+
+    // Written so it is understood by C++ compilers as well
+    if datatype.restrict
+    {
+        result += " __restrict";
+    }
+
+    Some(result)
+}
+
+/// Get the C type for a type passed to or returned from a function, structs are only passed by
+/// pointer as they are not passed as C passes them
+fn c_argument_type(datatype: &DataType) -> Option<String>
+{
+    match datatype.raw_type
+    {
+        NonPtrType::Struct(_) if datatype.num_ptr == 0 => None,
+        _ => c_type(datatype)
+    }
+}
+
+/// Render the C definition of a struct after the definitions of the structs it holds, leaving out
+/// those already rendered
+fn render_struct(structure: StructType, rendered: &mut Vec<StructType>, result: &mut String)
+{
+    if rendered.contains(&structure)
+    {
+        return;
+    }
+
+    rendered.push(structure);
+
+    let mut fields = String::new();
+
+    for (name, datatype) in structure.fields()
+    {
+        if let NonPtrType::Struct(inner) = datatype.raw_type
+        {
+            if datatype.num_ptr == 0
+            {
+                render_struct(inner, rendered, result);
+            }
+        }
+
+        match c_type(&datatype)
+        {
+            Some(field) => {fields += &format!("    {} {};\n", field, name);},
+            None =>
+            {
+                *result += &format!("\n/* '{}' holds a vector, which has no C type */\n{};\n", structure.name(), structure);
+                return;
+            }
+        }
+    }
+
+    *result += &format!("\n{}\n{{\n{}}};\n", structure, fields);
+}
+
+/// Render the C prototype of a function, naming functions of imported modules after the module
+/// and giving the name of their symbol with an asm label, as `math.square` is not a C identifier
+fn render_prototype(function: &Function) -> Option<String>
+{
+    let mut arguments = vec![];
+
+    for (name, datatype) in &function.arguments
+    {
+        arguments.push(format!("{} {}", c_argument_type(datatype)?, name));
+    }
+
+    if arguments.is_empty()
+    {
+        arguments.push(String::from("void"));
+    }
+
+    let c_name = function.name.replace('.', "_");
+    let label = if c_name != function.name {format!(" __asm__(\"{}\")", function.name)} else {String::new()};
+
+    Some(format!("{} {}({}){};", c_argument_type(&function.return_type)?, c_name, arguments.join(", "), label))
+}
+
+/// Render a doc comment as a C comment
+fn render_doc(doc: &str) -> String
+{
+    let mut result = String::from("/**\n");
+
+    for line in doc.lines()
+    {
+        result += &format!("{}\n", if line.is_empty() {String::from(" *")} else {format!(" * {}", line)});
+    }
+
+    result + " */\n"
+}
+
+/// Render a C header declaring the global variables and public functions of a library, along with
+/// their doc comments, leaving out `main`, interrupt handlers and the private functions of imported
+/// modules. The structs and global variables are those of the library's context
+pub fn render_header(source_filename: &str, functions: &[Function], context: &Context) -> String
+{
+    let stem = Path::new(source_filename).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let guard: String = format!("{}_H", stem).chars().map(|c| if c.is_ascii_alphanumeric() {c.to_ascii_uppercase()} else {'_'}).collect();
+
+    let mut result = format!("/* Generated from '{}', do not edit */\n\n#ifndef {}\n#define {}\n\n", source_filename, guard, guard);
+    result += "#include <stdbool.h>\n#include <stdint.h>\n\n#ifdef __cplusplus\nextern \"C\" {\n#endif\n";
+
+    let mut rendered = vec![];
+
+    for structure in context.structs.in_scope().iter().copied()
+    {
+        render_struct(structure, &mut rendered, &mut result);
+    }
+
+    for global in context.globals.iter().filter(|global| !global.internal)
+    {
+        result += "\n";
+
+        if let Some(doc) = &global.doc
+        {
+            result += &render_doc(doc);
+        }
+
+        match c_type(&global.datatype)
+        {
+            Some(datatype) => {result += &format!("extern {} {};\n", datatype, global.name);},
+            None => {result += &format!("/* '{}' is a vector, which has no C type */\n", global.name);}
+        }
+    }
+
+    for function in functions.iter().filter(|function| !function.internal && function.name != "main" && function.attribute("interrupt").is_none())
+    {
+        result += "\n";
+
+        if let Some(doc) = &function.doc
+        {
+            result += &render_doc(doc);
+        }
+
+        match render_prototype(function)
+        {
+            Some(prototype) => {result += &format!("{}\n", prototype);},
+            None => {result += &format!("/* '{}' takes or returns a vector or a struct by value, which have no C type */\n", function.name);}
+        }
+    }
+
+    result += "\n#ifdef __cplusplus\n}\n#endif\n\n#endif\n";
+
+    result
+}
@@ -0,0 +1,120 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::rc::Rc;
+
+use bincode::Options;
+use serde::{Serialize, Deserialize};
+
+use crate::cli::Error;
+use crate::codegen::TargetSpec;
+use crate::irgen::{Context, Function, DataType, Signature, StructTable, GlobalVariable};
+
+/// Bytes every IR file starts with, followed by the version of its format
+static IRB_MAGIC: &[u8; 4] = b"IRB\0";
+
+/// Version of the format of IR files, changed whenever the IR changes shape so older files are
+/// refused rather than misread
+const IRB_VERSION: u32 = 8;
+
+/// Length of the header before the serialized functions
+const IRB_HEADER: usize = 8;
+
+/// The functions of a library as written to an IR file, those it defines and those it only gives
+/// a prototype for, after the structs and global variables they use so each struct is declared
+/// before it is used. The signatures the functions were lowered with are shared by all of them, so
+/// they are written once rather than with each function
+#[derive(Serialize, Deserialize)]
+struct IrbFile
+{
+    structs: Vec<(String, Vec<(String, DataType)>)>,
+    globals: Vec<GlobalVariable>,
+    signatures: BTreeMap<String, Signature>,
+    functions: Vec<Function>,
+    external: Vec<Function>
+}
+
+/// The functions read from an IR file, the functions only given by a prototype and the context of
+/// the library they belong to
+pub type IrbLibrary = (Vec<Function>, Vec<Function>, Rc<Context>);
+
+/// Get the path of the IR file for a given output file
+pub fn irb_path(output_filename: &str) -> String
+{
+    Path::new(output_filename).with_extension("irb").to_string_lossy().to_string()
+}
+
+/// Encoding of the functions, with integers taking only as many bytes as their values need
+fn irb_options() -> impl Options
+{
+    bincode::DefaultOptions::new()
+}
+
+/// Render the functions of a library, and the functions it only gives a prototype for, to the
+/// bytes of an IR file along with the structs and global variables of the library given by its
+/// context
+pub fn render_irb(functions: &[Function], external: &[Function], context: &Context) -> Result<Vec<u8>, Error>
+{
+    let structs = context.structs.in_scope().iter().map(|structure| (structure.name().to_string(), structure.fields())).collect();
+    let signatures = functions.iter().chain(external).flat_map(|function| function.signatures.iter()).map(|(name, signature)| (name.clone(), signature.clone())).collect();
+    let file = IrbFile { structs, globals: context.globals.clone(), signatures, functions: functions.to_vec(), external: external.to_vec() };
+
+    let mut result = IRB_MAGIC.to_vec();
+    result.extend(IRB_VERSION.to_le_bytes());
+
+    match irb_options().serialize(&file)
+    {
+        Ok(bytes) => result.extend(bytes),
+        Err(error) => {return Err(Error::fatal_error(&format!("Could not serialize the IR: {}", error)).with_code("E0101"));}
+    }
+
+    Ok(result)
+}
+
+/// Read the functions of a library, and the functions it only gives a prototype for, from the
+/// bytes of an IR file along with the context of the library compiled for the given target, naming
+/// the file in the error if they are not one
+pub fn parse_irb(bytes: &[u8], filename: &str, target: TargetSpec) -> Result<IrbLibrary, Error>
+{
+    let bad = |text: String| Err(Error::fatal_error(&format!("'{}' {}", filename, text)).with_code("E0111"));
+
+    if bytes.len() < IRB_HEADER || &bytes[..4] != IRB_MAGIC
+    {
+        return bad(String::from("is not an IR file written by --emit irb"));
+    }
+
+    let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+
+    if version != IRB_VERSION
+    {
+        return bad(format!("was written in version {} of the IR format, but version {} is expected", version, IRB_VERSION));
+    }
+
+    // Structs are named in the file, and stand for the structs of the library it was written from.
+    // The limit keeps a damaged length from asking for more memory than the file could fill
+    let (file, mut structs) = StructTable::reading(|| irb_options().with_limit(bytes.len() as u64).deserialize::<IrbFile>(&bytes[IRB_HEADER..]));
+
+    match file
+    {
+        Ok(file) =>
+        {
+            for (name, fields) in file.structs
+            {
+                structs.named(&name).define(fields);
+            }
+
+            let context = Rc::new(Context { structs, globals: file.globals, target });
+
+            let signatures: Rc<HashMap<String, Signature>> = Rc::new(file.signatures.into_iter().collect());
+            let (mut functions, mut external) = (file.functions, file.external);
+
+            for function in functions.iter_mut().chain(external.iter_mut())
+            {
+                function.signatures = Rc::clone(&signatures);
+                function.context = Rc::clone(&context);
+            }
+
+            Ok((functions, external, context))
+        },
+        Err(error) => bad(format!("is damaged: {}", error))
+    }
+}
@@ -0,0 +1,117 @@
+mod depfile;
+mod apidoc;
+mod header;
+mod staticlib;
+mod xref;
+mod irb;
+mod stack;
+mod size;
+mod callgraph;
+
+pub use depfile::*;
+pub use apidoc::*;
+pub use header::*;
+pub use staticlib::*;
+pub use xref::*;
+pub use irb::*;
+pub use stack::*;
+pub use size::*;
+pub use callgraph::*;
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use crate::cli::{Error, Options};
+
+/// Additional outputs which can be requested with the --emit flag
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmitKind
+{
+    Depfile,
+    Apidoc,
+    Header,
+    Staticlib,
+    XrefJson,
+    Irb,
+    StackReport,
+    SizeReport,
+    Callgraph(CallgraphFormat)
+}
+
+impl EmitKind
+{
+    /// Generate a new EmitKind object from a value passed to --emit
+    pub fn from_name(name: &str) -> Option<EmitKind>
+    {
+        match name
+        {
+            "depfile" => Some(EmitKind::Depfile),
+            "apidoc" => Some(EmitKind::Apidoc),
+            "header" => Some(EmitKind::Header),
+            "staticlib" => Some(EmitKind::Staticlib),
+            "xref-json" => Some(EmitKind::XrefJson),
+            "irb" => Some(EmitKind::Irb),
+            "stack-report" => Some(EmitKind::StackReport),
+            "size-report" => Some(EmitKind::SizeReport),
+            "callgraph" => Some(EmitKind::Callgraph(CallgraphFormat::Dot)),
+            _ => None
+        }
+    }
+}
+
+/// Get all of the emit kinds requested on the command line
+pub fn requested_emits(options: &Options) -> Result<Vec<EmitKind>, Error>
+{
+    let mut result = vec![];
+
+    if let Some(names) = options.map.get("--emit")
+    {
+        let mut names = names.iter().peekable();
+
+        while let Some(name) = names.next()
+        {
+            match EmitKind::from_name(name)
+            {
+                // The call graph can be followed by its format, which is dot if it is not
+                Some(EmitKind::Callgraph(_)) =>
+                {
+                    let format = names.next_if(|next| CallgraphFormat::from_name(next).is_some()).and_then(|next| CallgraphFormat::from_name(next));
+                    result.push(EmitKind::Callgraph(format.unwrap_or(CallgraphFormat::Dot)));
+                },
+                Some(kind) => result.push(kind),
+                None => {return Err(Error::fatal_error(&format!("Unknown emit kind '{}'", name)).with_code("E0102"));}
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Run an external tool, feeding it the given input and giving what it writes to its standard
+/// output, failing with its output if it does not succeed. `purpose` says what the tool was being
+/// run for, as in "building the static library"
+fn run_tool(program: &str, args: &[&str], input: &str, purpose: &str) -> Result<String, Error>
+{
+    let failed = |text: String| Err(Error::fatal_error(&format!("'{}' failed while {}: {}", program, purpose, text.trim())).with_code("E0108"));
+
+    let mut child = match Command::new(program).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()
+    {
+        Ok(child) => child,
+        Err(error) => {return failed(error.to_string());}
+    };
+
+    if let Some(mut stdin) = child.stdin.take()
+    {
+        if let Err(error) = stdin.write_all(input.as_bytes())
+        {
+            return failed(error.to_string());
+        }
+    }
+
+    match child.wait_with_output()
+    {
+        Ok(output) if output.status.success() => Ok(String::from_utf8_lossy(&output.stdout).to_string()),
+        Ok(output) => failed(String::from_utf8_lossy(&output.stderr).to_string()),
+        Err(error) => failed(error.to_string())
+    }
+}
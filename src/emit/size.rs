@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::cli::Error;
+use crate::irgen::Function;
+
+use super::run_tool;
+
+/// Get the path of the code size report for a given output file
+pub fn size_report_path(output_filename: &str) -> String
+{
+    Path::new(output_filename).with_extension("size").to_string_lossy().to_string()
+}
+
+/// Assemble an LLVM module with `llc` and read the size in bytes of the code of each function it
+/// defines from the symbols of the object file with `llvm-nm`
+pub fn assembled_sizes(module: &str, relocation_model: Option<&str>) -> Result<HashMap<String, u64>, Error>
+{
+    let object = std::env::temp_dir().join(format!("compiler-size-{}.o", std::process::id()));
+    let object_name = object.to_string_lossy().to_string();
+
+    let mut arguments = vec![String::from("-filetype=obj"), String::from("-o"), object_name.clone(), String::from("-")];
+
+    if let Some(model) = relocation_model
+    {
+        arguments.insert(0, format!("-relocation-model={}", model));
+    }
+
+    let symbols = run_tool("llc", &arguments.iter().map(|argument| argument.as_str()).collect::<Vec<&str>>(), module, "assembling for the size report")
+        .and_then(|_| run_tool("llvm-nm", &["--print-size", "--defined-only", &object_name], "", "reading the sizes for the size report"));
+
+    let _ = std::fs::remove_file(&object);
+
+    let mut result = HashMap::new();
+
+    // Each line gives the address, size, kind and name of a symbol, only the code is counted
+    for line in symbols?.lines()
+    {
+        if let [_, size, kind, name] = line.split_whitespace().collect::<Vec<&str>>().as_slice()
+        {
+            if matches!(*kind, "T" | "t")
+            {
+                if let Ok(size) = u64::from_str_radix(size, 16)
+                {
+                    result.insert(name.to_string(), size);
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Render a report of the size of each function of a library, largest first, giving the number of
+/// instructions in its optimized IR and, if the code was assembled, the bytes of machine code
+/// generated for it
+pub fn render_size_report(filename: &str, functions: &[Function], bytes: Option<&HashMap<String, u64>>) -> String
+{
+    let mut sizes: Vec<(&str, usize, Option<u64>)> = functions.iter()
+        .map(|function| (function.name.as_str(), function.instructions.len(), bytes.map(|bytes| bytes.get(&function.name).copied().unwrap_or(0))))
+        .collect();
+
+    // Sorting is stable, so functions of the same size stay in the order they are defined
+    sizes.sort_by_key(|size| std::cmp::Reverse((size.2, size.1)));
+
+    let total = (sizes.iter().map(|size| size.1).sum(), bytes.map(|_| sizes.iter().filter_map(|size| size.2).sum::<u64>()));
+
+    let mut rows = vec![(String::from("function"), String::from("instructions"), bytes.map(|_| String::from("bytes")))];
+
+    for (name, instructions, bytes) in sizes.into_iter().chain(std::iter::once(("total", total.0, total.1)))
+    {
+        rows.push((name.to_string(), instructions.to_string(), bytes.map(|bytes| bytes.to_string())));
+    }
+
+    let name_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0);
+    let instructions_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+    let bytes_width = rows.iter().filter_map(|row| row.2.as_ref().map(|bytes| bytes.len())).max().unwrap_or(0);
+
+    let mut result = format!("# Code size of '{}', largest first\n\n", filename);
+
+    for (name, instructions, bytes) in rows
+    {
+        match bytes
+        {
+            Some(bytes) => {result += &format!("{:<name_width$}  {:>instructions_width$}  {:>bytes_width$}\n", name, instructions, bytes);},
+            None => {result += &format!("{:<name_width$}  {:>instructions_width$}\n", name, instructions);}
+        }
+    }
+
+    result
+}
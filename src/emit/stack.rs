@@ -0,0 +1,167 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::codegen::TargetSpec;
+use crate::irgen::{Function, NonPtrType};
+
+use super::callees;
+
+/// Get the path of the stack usage report for a given output file
+pub fn stack_report_path(output_filename: &str) -> String
+{
+    Path::new(output_filename).with_extension("stack").to_string_lossy().to_string()
+}
+
+/// Deepest use of the stack from the start of a function
+#[derive(Debug, Clone)]
+enum Usage
+{
+    /// Bytes used, the chain of calls using them, and whether a function the report cannot see
+    /// into is called somewhere below
+    Bounded(usize, Vec<String>, bool),
+    /// The cycle of calls which can use any amount of stack
+    Recursive(Vec<String>)
+}
+
+/// Get the number of bytes of stack a function allocates for its variables and temporaries, laid
+/// out as the LLVM backend allocates them, each aligned in the order it is first used
+fn frame_size(function: &Function, target: &TargetSpec) -> usize
+{
+    let mut size: usize = 0;
+    let mut largest_align = 1;
+    let mut seen: Vec<String> = vec![];
+
+    for mut symbol in function.get_all_symbols()
+    {
+        if !symbol.is_register()
+        {
+            if let Some(declared) = function.symbol_table.get(&symbol.title)
+            {
+                symbol.datatype = declared.datatype;
+            }
+        }
+
+        if symbol.datatype.raw_type == NonPtrType::Void || seen.contains(&symbol.key())
+        {
+            continue;
+        }
+
+        let requested = if symbol.is_register() {0} else {function.symbol_table.get(&symbol.title).and_then(|declared| declared.datatype.align).unwrap_or(0) as usize};
+        let align = target.align_of(&symbol.datatype).max(requested);
+
+        size = size.next_multiple_of(align) + target.bytes_size_of(&symbol.datatype);
+        largest_align = largest_align.max(align);
+
+        seen.push(symbol.key());
+    }
+
+    size.next_multiple_of(largest_align)
+}
+
+/// Work out the deepest use of the stack from the start of a function, following its calls to the
+/// other functions of the library. `active` holds the calls currently being followed, so a call
+/// back into one of them is recursion
+fn worst_case(name: &str, frames: &HashMap<&str, usize>, calls: &HashMap<&str, Vec<String>>, memo: &mut HashMap<String, Usage>, active: &mut Vec<String>) -> Usage
+{
+    if let Some(usage) = memo.get(name)
+    {
+        return usage.clone();
+    }
+
+    if let Some(position) = active.iter().position(|other| other == name)
+    {
+        let mut cycle = active[position..].to_vec();
+        cycle.push(name.to_string());
+        return Usage::Recursive(cycle);
+    }
+
+    active.push(name.to_string());
+
+    let mut deepest = (0, vec![]);
+    let mut external = false;
+    let mut recursive = None;
+
+    for callee in &calls[name]
+    {
+        if !frames.contains_key(callee.as_str())
+        {
+            // Nothing is known of the stack an external function uses
+            external = true;
+            continue;
+        }
+
+        match worst_case(callee, frames, calls, memo, active)
+        {
+            Usage::Bounded(bytes, chain, calls_external) =>
+            {
+                external |= calls_external;
+
+                if bytes > deepest.0
+                {
+                    deepest = (bytes, chain);
+                }
+            },
+            Usage::Recursive(cycle) =>
+            {
+                recursive = Some(cycle);
+                break;
+            }
+        }
+    }
+
+    active.pop();
+
+    let usage = match recursive
+    {
+        Some(cycle) => Usage::Recursive(cycle),
+        None =>
+        {
+            let mut chain = vec![name.to_string()];
+            chain.extend(deepest.1);
+
+            Usage::Bounded(frames[name] + deepest.0, chain, external)
+        }
+    };
+
+    memo.insert(name.to_string(), usage.clone());
+
+    usage
+}
+
+/// Render a report of the stack used by each function of a library, giving the bytes its own
+/// variables take and the most taken by it and the functions it calls, along with the chain of
+/// calls reaching that depth
+pub fn render_stack_report(filename: &str, functions: &[Function], target: &TargetSpec) -> String
+{
+    let frames: HashMap<&str, usize> = functions.iter().map(|function| (function.name.as_str(), frame_size(function, target))).collect();
+    let calls: HashMap<&str, Vec<String>> = functions.iter().map(|function| (function.name.as_str(), callees(function))).collect();
+
+    let mut memo = HashMap::new();
+
+    let mut rows = vec![(String::from("function"), String::from("frame"), String::from("worst case"), String::from("deepest call chain"))];
+
+    for function in functions
+    {
+        let (worst, chain) = match worst_case(&function.name, &frames, &calls, &mut memo, &mut vec![])
+        {
+            Usage::Bounded(bytes, chain, false) => (bytes.to_string(), chain.join(" -> ")),
+            Usage::Bounded(bytes, chain, true) => (format!("{}+", bytes), format!("{} (and external functions)", chain.join(" -> "))),
+            Usage::Recursive(cycle) => (String::from("unbounded"), format!("{} (recursive)", cycle.join(" -> ")))
+        };
+
+        rows.push((function.name.clone(), frames[function.name.as_str()].to_string(), worst, chain));
+    }
+
+    let name_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0);
+    let frame_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+    let worst_width = rows.iter().map(|row| row.2.len()).max().unwrap_or(0);
+
+    let mut result = format!("# Stack usage of '{}' in bytes\n\n", filename);
+
+    for (name, frame, worst, chain) in rows
+    {
+        result += &format!("{:<name_width$}  {:>frame_width$}  {:>worst_width$}  {}\n", name, frame, worst, chain);
+    }
+
+    result
+}
@@ -0,0 +1,46 @@
+use std::path::{Path, PathBuf};
+
+use crate::cli::Error;
+
+use super::run_tool;
+
+/// Get the path of the static library archive for a given output file
+pub fn staticlib_path(output_filename: &str) -> String
+{
+    Path::new(output_filename).with_extension("a").to_string_lossy().to_string()
+}
+
+/// Compile each of the given LLVM modules to an object file with `llc`, using the relocation model
+/// of the target or position independent code so the library can be linked into any executable,
+/// and bundle them into a static library archive with `ar`
+pub fn write_staticlib(archive: &str, modules: &[(String, String)], relocation_model: Option<&str>) -> Result<(), Error>
+{
+    let directory = std::env::temp_dir().join(format!("compiler-staticlib-{}", std::process::id()));
+
+    if std::fs::create_dir_all(&directory).is_err()
+    {
+        Err(Error::fatal_error(&format!("Could not create directory '{}' for the object files", directory.display())).with_code("E0101"))?;
+    }
+
+    let mut objects: Vec<PathBuf> = vec![];
+    let relocation_model = format!("-relocation-model={}", relocation_model.unwrap_or("pic"));
+
+    for (name, module) in modules
+    {
+        let object = directory.join(format!("{}.o", name));
+        run_tool("llc", &["-filetype=obj", &relocation_model, "-o", &object.to_string_lossy(), "-"], module, "building the static library")?;
+        objects.push(object);
+    }
+
+    // Members left from an earlier build would otherwise stay in the archive
+    let _ = std::fs::remove_file(archive);
+
+    let mut arguments = vec![String::from("rcs"), String::from(archive)];
+    arguments.extend(objects.iter().map(|object| object.to_string_lossy().to_string()));
+
+    let result = run_tool("ar", &arguments.iter().map(|argument| argument.as_str()).collect::<Vec<&str>>(), "", "building the static library").map(|_| ());
+
+    let _ = std::fs::remove_dir_all(&directory);
+
+    result
+}
@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use crate::ide::{CrossReference, Span};
+
+/// Get the path of the cross reference index for a given output file
+pub fn xref_path(output_filename: &str) -> String
+{
+    Path::new(output_filename).with_extension("xref.json").to_string_lossy().to_string()
+}
+
+/// Write a string as a JSON string literal
+fn json_string(value: &str) -> String
+{
+    let mut result = String::from("\"");
+
+    for c in value.chars()
+    {
+        match c
+        {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c)
+        }
+    }
+
+    result.push('"');
+    result
+}
+
+/// Write a span as a JSON object
+fn json_span(span: &Span) -> String
+{
+    format!("{{\"file\": {}, \"line\": {}, \"column\": {}, \"length\": {}}}", json_string(&span.file), span.line, span.column, span.length)
+}
+
+/// Render the cross reference index of a library as JSON, giving every function, argument and
+/// variable along with where it is defined and every place it is named
+pub fn render_xref(references: &[CrossReference]) -> String
+{
+    let symbols: Vec<String> = references.iter().map(|reference|
+    {
+        let scope = reference.scope.as_deref().map(json_string).unwrap_or_else(|| String::from("null"));
+        let definition = reference.definition.as_ref().map(json_span).unwrap_or_else(|| String::from("null"));
+        let spans: Vec<String> = reference.references.iter().map(|span| format!("\n        {}", json_span(span))).collect();
+        let spans = if spans.is_empty() {String::new()} else {format!("{}\n      ", spans.join(","))};
+
+        format!("    {{\n      \"name\": {},\n      \"symbol\": {},\n      \"kind\": {},\n      \"scope\": {},\n      \"definition\": {},\n      \"references\": [{}]\n    }}",
+            json_string(&reference.name), json_string(&reference.symbol), json_string(reference.kind.name()), scope, definition, spans)
+    }).collect();
+
+    let symbols = if symbols.is_empty() {String::new()} else {format!("\n{}\n  ", symbols.join(",\n"))};
+
+    format!("{{\n  \"symbols\": [{}]\n}}\n", symbols)
+}
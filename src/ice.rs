@@ -0,0 +1,266 @@
+use std::cell::RefCell;
+use std::panic;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::cli::Options;
+use crate::io::InputFile;
+use crate::parser::{ParseTreeNode, NodeArena};
+use crate::tokenizer::tokenize;
+
+static BUG_REPORT_URL: &str = "https://github.com/CarterTS/LLVMCompiler/issues";
+
+/// Record of what the compiler was doing, displayed if an internal compiler error occurs
+struct IceState
+{
+    phase: String,
+    function: Option<String>,
+    instruction: Option<String>,
+    message: Option<String>,
+    quiet: bool
+}
+
+lazy_static!
+{
+    static ref STATE: Mutex<IceState> = Mutex::new(IceState
+    {
+        phase: String::from("startup"),
+        function: None,
+        instruction: None,
+        message: None,
+        quiet: false
+    });
+}
+
+thread_local!
+{
+    /// The tracing spans of the current phase and function, the function span within the phase span
+    static SPANS: RefCell<(Option<tracing::span::EnteredSpan>, Option<tracing::span::EnteredSpan>)> = const { RefCell::new((None, None)) };
+}
+
+/// Run a closure on the current state, ignoring a poisoned lock as the state is only informational
+fn with_state<T>(f: impl FnOnce(&mut IceState) -> T) -> T
+{
+    match STATE.lock()
+    {
+        Ok(mut state) => f(&mut state),
+        Err(poisoned) => f(&mut poisoned.into_inner())
+    }
+}
+
+/// Record the phase the compiler is currently in, ending the tracing span of the last phase and
+/// starting one for this phase
+pub fn enter_phase(phase: &str)
+{
+    with_state(|state|
+    {
+        state.phase = String::from(phase);
+        state.function = None;
+        state.instruction = None;
+    });
+
+    SPANS.with(|spans|
+    {
+        let mut spans = spans.borrow_mut();
+
+        // The function span is left before the phase span it is within
+        spans.1 = None;
+        spans.0 = None;
+        spans.0 = Some(tracing::info_span!("phase", phase).entered());
+    });
+}
+
+/// Record that the compiler has finished with an input file, ending the tracing span of the last
+/// phase
+pub fn leave_phase()
+{
+    SPANS.with(|spans|
+    {
+        let mut spans = spans.borrow_mut();
+
+        spans.1 = None;
+        spans.0 = None;
+    });
+}
+
+/// Record the function currently being processed, within a tracing span of its own
+pub fn set_function(name: &str)
+{
+    with_state(|state|
+    {
+        state.function = Some(String::from(name));
+        state.instruction = None;
+    });
+
+    SPANS.with(|spans|
+    {
+        let mut spans = spans.borrow_mut();
+
+        spans.1 = None;
+        spans.1 = Some(tracing::debug_span!("function", function = name).entered());
+    });
+}
+
+/// Record the instruction currently being processed
+pub fn set_instruction(inst: String)
+{
+    with_state(|state| state.instruction = Some(inst));
+}
+
+/// Install the panic hook which records the panic message instead of printing a bare backtrace
+pub fn install_panic_hook()
+{
+    panic::set_hook(Box::new(|info|
+    {
+        let payload = if let Some(s) = info.payload().downcast_ref::<&str>()
+        {
+            String::from(*s)
+        }
+        else if let Some(s) = info.payload().downcast_ref::<String>()
+        {
+            s.clone()
+        }
+        else
+        {
+            String::from("unknown panic")
+        };
+
+        let message = match info.location()
+        {
+            Some(loc) => format!("{} ({}:{})", payload, loc.file(), loc.line()),
+            None => payload
+        };
+
+        with_state(|state|
+        {
+            if state.message.is_none() && !state.quiet
+            {
+                state.message = Some(message);
+            }
+        });
+    }));
+}
+
+/// Check if compiling the given source panics
+fn source_panics(data: &str, filename: &str, options: &Options) -> bool
+{
+    let input = InputFile{data: String::from(data), filename: String::from(filename)};
+
+    panic::catch_unwind(panic::AssertUnwindSafe(|| crate::compile::generate(&input, options, false))).is_err()
+}
+
+/// Attempt to reduce a source file to the smallest set of top level items which still causes the panic
+fn minimize(data: &str, filename: &str, options: &Options) -> String
+{
+    // Find the first line of each top level item
+    let starts: Option<Vec<usize>> = panic::catch_unwind(||
+    {
+        let arena = NodeArena::new();
+
+        match crate::parser::parse(tokenize(String::from(data), String::from(filename)), &arena)
+        {
+            Ok(ParseTreeNode::Library(children)) =>
+                Some(children.iter().filter_map(|child| child.first_token().map(|token| token.location.row - 1)).collect()),
+            _ => None
+        }
+    }).ok().flatten();
+
+    let starts = match starts
+    {
+        Some(starts) => starts,
+        None => {return String::from(data);}
+    };
+
+    let lines: Vec<&str> = data.lines().collect();
+
+    // Split the file into chunks, one per top level item (the first chunk holding anything before the first item)
+    let mut chunks = vec![];
+    let mut last = 0;
+
+    for start in starts.iter().chain(std::iter::once(&lines.len()))
+    {
+        chunks.push(lines[last..*start].join("\n"));
+        last = *start;
+    }
+
+    // Remove items one at a time, keeping the removal if the compiler still panics
+    let mut keep = vec![true; chunks.len()];
+
+    for i in (1..chunks.len()).rev()
+    {
+        keep[i] = false;
+
+        let candidate: Vec<String> = chunks.iter().zip(&keep).filter(|(_, k)| **k).map(|(c, _)| c.clone()).collect();
+
+        if !source_panics(&candidate.join("\n"), filename, options)
+        {
+            keep[i] = true;
+        }
+    }
+
+    let result: Vec<String> = chunks.iter().zip(&keep).filter(|(_, k)| **k).map(|(c, _)| c.clone()).collect();
+
+    format!("{}\n", result.join("\n").trim())
+}
+
+/// Get the directory to write a reproduction to, that of the output given by `-o` or
+/// `--split-output`, falling back to the temporary directory rather than cluttering the current one
+fn reproduction_directory(options: &Options) -> PathBuf
+{
+    let output = options.map.get("-o").and_then(|args| args.first()).and_then(|name| Path::new(name).parent());
+
+    match output
+    {
+        Some(directory) if !directory.as_os_str().is_empty() => directory.to_path_buf(),
+        _ => match options.map.get("--split-output").and_then(|args| args.first())
+        {
+            Some(directory) => PathBuf::from(directory),
+            None => std::env::temp_dir()
+        }
+    }
+}
+
+/// Report an internal compiler error which occured while compiling the given input
+pub fn report(input: Option<&InputFile>, options: &Options)
+{
+    let (phase, function, instruction, message) = with_state(|state|
+    {
+        state.quiet = true;
+        (state.phase.clone(), state.function.clone(), state.instruction.clone(), state.message.clone())
+    });
+
+    eprintln!("compiler: \x1b[1m\x1b[31minternal compiler error\x1b[0m: {}", message.unwrap_or_else(|| String::from("unknown panic")));
+    eprintln!("    phase:       {}", phase);
+
+    if let Some(name) = function
+    {
+        eprintln!("    function:    {}", name);
+    }
+
+    if let Some(inst) = instruction
+    {
+        eprintln!("    instruction: {}", inst.trim_end());
+    }
+
+    // Write out a reproduction of the failure
+    if let Some(input) = input
+    {
+        let reduced = minimize(&input.data, &input.filename, options);
+
+        let stem = Path::new(&input.filename).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_else(|| String::from("input"));
+        let repro_filename = reproduction_directory(options).join(format!("{}.ice.pc", stem));
+
+        if std::fs::write(&repro_filename, reduced).is_ok()
+        {
+            eprintln!("    reproduction written to '{}'", repro_filename.display());
+        }
+    }
+
+    eprintln!("\nThis is a bug in the compiler. Please file a report at {}", BUG_REPORT_URL);
+    eprintln!("including the following:");
+    eprintln!("    - the command line used: {}", std::env::args().collect::<Vec<String>>().join(" "));
+    eprintln!("    - the reproduction file above (or the original input)");
+    eprintln!("    - the output of 'compiler --version'");
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+use std::rc::Rc;
+
+use crate::io::InputFile;
+use crate::irgen::{Context, DataType, Function, declare_structs, type_from_parse_tree, arguments_from_parse_tree};
+use crate::parser::{ParseTreeNode, NodeArena};
+
+use super::{parse_source, position_of_offset, token_before, enclosing_function};
+
+/// What a name offered for completion refers to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompletionKind
+{
+    Argument,
+    Variable,
+    Global,
+    Function
+}
+
+/// A name in scope at a position in a source file, along with its type
+#[derive(Debug, Clone)]
+pub struct Completion
+{
+    pub name: String,
+    pub kind: CompletionKind,
+    pub detail: String
+}
+
+impl fmt::Display for Completion
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        let kind = match self.kind
+        {
+            CompletionKind::Argument => "argument",
+            CompletionKind::Variable => "variable",
+            CompletionKind::Global => "global",
+            CompletionKind::Function => "function"
+        };
+
+        write!(f, "{}\t{}\t{}", self.name, kind, self.detail)
+    }
+}
+
+/// Get every name in scope at a byte offset into a source file, which may have syntax errors.
+/// These are the arguments of the function the offset is in and the variables it declares before
+/// the offset, as a variable is in scope from its declaration to the end of the function, followed
+/// by the global variables of the file which they do not hide and every function of the file
+pub fn completions(input: &InputFile, offset: usize) -> Vec<Completion>
+{
+    let arena = NodeArena::new();
+    let library = parse_source(input, &arena);
+    let position = position_of_offset(&input.data, offset);
+
+    // The structs are declared as they are for a compile, so types naming them can be described,
+    // even if some of them cannot be
+    let mut context = Context::default();
+    let _ = declare_structs(library.children(), &mut context);
+    let context = Rc::new(context);
+
+    let mut result = vec![];
+
+    if let Some(ParseTreeNode::Function(children)) = enclosing_function(&library, position)
+    {
+        if let Ok(arguments) = arguments_from_parse_tree(&children[2], &context)
+        {
+            result.extend(arguments.into_iter().map(|(name, datatype)| Completion { name, kind: CompletionKind::Argument, detail: datatype.to_string() }));
+        }
+
+        if let Some(body) = children.get(3)
+        {
+            declarations_before(body, position, &context, &mut result);
+        }
+    }
+
+    for item in library.children()
+    {
+        if let ParseTreeNode::GlobalVariable(children) = item
+        {
+            if let (Some(token), Ok(datatype)) = (children[1].first_token(), type_from_parse_tree(&children[0], &context))
+            {
+                if !result.iter().any(|other| other.name == token.data)
+                {
+                    result.push(Completion { name: token.data.to_string(), kind: CompletionKind::Global, detail: datatype.to_string() });
+                }
+            }
+        }
+    }
+
+    let mut functions: Vec<Completion> = library.children().iter()
+        .filter(|item| matches!(item, ParseTreeNode::Function(_)))
+        .filter_map(|item| Function::prototype_from_parse_tree_node(item, &context).ok())
+        .map(|function| Completion { detail: function_type(&function), name: function.name, kind: CompletionKind::Function })
+        .collect();
+
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+    functions.dedup_by(|a, b| a.name == b.name);
+
+    result.extend(functions);
+
+    result
+}
+
+/// Gather the variables declared within a statement before a position, in the order they are
+/// declared
+fn declarations_before(node: &ParseTreeNode, position: (usize, usize), context: &Context, result: &mut Vec<Completion>)
+{
+    if let ParseTreeNode::AssignmentStatement(children) = node
+    {
+        let datatype: Option<DataType> = type_from_parse_tree(&children[0], context).ok();
+
+        for assignment in children[1].children()
+        {
+            if let (Some(ParseTreeNode::Identifier(token)), Some(datatype)) = (assignment.children().first(), &datatype)
+            {
+                if token_before(token, position) && !result.iter().any(|other| other.name == token.data)
+                {
+                    result.push(Completion { name: token.data.to_string(), kind: CompletionKind::Variable, detail: datatype.to_string() });
+                }
+            }
+        }
+    }
+
+    for child in node.children()
+    {
+        declarations_before(child, position, context, result);
+    }
+}
+
+/// Write the type of a function as its return type followed by the types of its arguments,
+/// `i32(u8*, i32)`
+fn function_type(function: &Function) -> String
+{
+    let arguments: Vec<String> = function.arguments.iter().map(|(_, datatype)| datatype.to_string()).collect();
+    format!("{}({})", function.return_type, arguments.join(", "))
+}
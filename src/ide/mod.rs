@@ -0,0 +1,53 @@
+mod complete;
+mod rename;
+mod xref;
+
+pub use complete::*;
+pub use rename::*;
+pub use xref::*;
+
+use crate::io::InputFile;
+use crate::parser::{ParseTreeNode, NodeArena, parse_recovering};
+use crate::tokenizer::{tokenize, Token};
+
+/// Parse a file as it is being edited, keeping as much of the tree as possible past any syntax
+/// errors, allocating it in an arena
+pub fn parse_source<'a>(input: &InputFile, arena: &'a NodeArena<'a>) -> ParseTreeNode<'a>
+{
+    parse_recovering(tokenize(input.data.clone(), input.filename.clone()), arena).0
+}
+
+/// Get the line and column of a byte offset into a source file, counted as the tokenizer counts
+/// them, an offset past the end of the file giving the end of the file
+pub fn position_of_offset(source: &str, offset: usize) -> (usize, usize)
+{
+    let mut position = (1, 1);
+
+    for (index, c) in source.char_indices()
+    {
+        if index >= offset
+        {
+            break;
+        }
+
+        position = if c == '\n' {(position.0 + 1, 1)} else {(position.0, position.1 + 1)};
+    }
+
+    position
+}
+
+/// Check if a token comes before a line and column
+pub fn token_before(token: &Token, position: (usize, usize)) -> bool
+{
+    (token.location.row, token.location.col) < position
+}
+
+/// Get the function a position is within, which is the last item of the library starting before
+/// it, if that is a function
+pub fn enclosing_function<'a>(library: &ParseTreeNode<'a>, position: (usize, usize)) -> Option<&'a ParseTreeNode<'a>>
+{
+    library.children().iter()
+        .take_while(|item| item.first_token().map(|token| token_before(token, position)).unwrap_or(true))
+        .last()
+        .filter(|item| matches!(item, ParseTreeNode::Function(_)))
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::cli::Error;
+use crate::import::canonical;
+use crate::io::{InputFile, read_from_file};
+use crate::parser::is_identifier;
+use crate::tokenizer::tokenize;
+
+use super::{CrossReference, SymbolKind, Span, cross_references, cross_references_with_edits};
+
+/// The new contents of each file changed by a rename, by path
+pub type Edits = Vec<(String, String)>;
+
+/// Generate an error about a rename
+fn rename_error<T>(code: &'static str, text: String) -> Result<T, Error>
+{
+    Err(Error::fatal_error(&text).with_code(code))
+}
+
+/// Write where a span starts, as errors give locations
+fn describe(span: &Span) -> String
+{
+    format!("Line {}:{} in file '{}'", span.line, span.column, span.file)
+}
+
+/// Get the byte offset of a line and column in a source file, counted as the tokenizer counts them
+fn offset_of(source: &str, line: usize, column: usize) -> Option<usize>
+{
+    let mut position = (1, 1);
+
+    for (index, c) in source.char_indices()
+    {
+        if position == (line, column)
+        {
+            return Some(index);
+        }
+
+        position = if c == '\n' {(position.0 + 1, 1)} else {(position.0, position.1 + 1)};
+    }
+
+    None
+}
+
+/// Get every file a function, argument or variable is named in
+fn files_of(reference: &CrossReference) -> Vec<&str>
+{
+    reference.definition.iter().chain(&reference.references).map(|span| span.file.as_str()).collect()
+}
+
+/// Check if giving a function, argument or variable a new name would make it share its name with
+/// another. Arguments and variables clash with those of the same function, and functions with
+/// those named in any of the same files
+fn clashes(target: &CrossReference, other: &CrossReference, new_name: &str) -> bool
+{
+    if other.name != new_name || (other.symbol == target.symbol && other.scope == target.scope)
+    {
+        return false;
+    }
+
+    match target.kind
+    {
+        SymbolKind::Function => other.kind == SymbolKind::Function && files_of(other).iter().any(|file| files_of(target).contains(file)),
+        SymbolKind::Argument | SymbolKind::Variable => other.kind != SymbolKind::Function && other.scope == target.scope
+    }
+}
+
+/// Replace the name at each of a set of spans within a file
+fn rewrite(source: &str, spans: &[&Span], old_name: &str, new_name: &str) -> Result<String, Error>
+{
+    let mut result = source.to_string();
+    let mut spans = spans.to_vec();
+
+    // Rewrite from the end of the file, so the offsets of the spans still to be rewritten hold
+    spans.sort_by_key(|span| std::cmp::Reverse((span.line, span.column)));
+    spans.dedup();
+
+    for span in spans
+    {
+        let offset = offset_of(source, span.line, span.column);
+        let raw = format!("r#{}", old_name);
+
+        match offset
+        {
+            Some(offset) if source[offset..].starts_with(old_name) => result.replace_range(offset..offset + old_name.len(), new_name),
+            // A raw identifier is renamed to a plain one, as the new name is never a keyword
+            Some(offset) if source[offset..].starts_with(&raw) => result.replace_range(offset..offset + raw.len(), new_name),
+            _ => {return rename_error("E0110", format!("Could not find '{}' at {}", old_name, describe(span)));}
+        }
+    }
+
+    Ok(result)
+}
+
+/// Check if two cross reference indexes of the same source, before and after a rename, name the
+/// same functions, arguments and variables in the same places
+fn same_references(before: &[CrossReference], after: &[CrossReference], target: usize, new_name: &str) -> bool
+{
+    before.len() == after.len() && before.iter().zip(after).enumerate().all(|(i, (a, b))|
+        a.kind == b.kind &&
+        a.definition.is_some() == b.definition.is_some() &&
+        a.references.len() == b.references.len() &&
+        b.name == if i == target {new_name} else {a.name.as_str()})
+}
+
+/// Gather every source file within a directory and the directories within it, leaving out hidden
+/// directories
+fn source_files(directory: &Path, result: &mut Vec<PathBuf>)
+{
+    let entries = match std::fs::read_dir(directory)
+    {
+        Ok(entries) => entries,
+        Err(_) => {return;}
+    };
+
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path())
+    {
+        let hidden = path.file_name().map(|name| name.to_string_lossy().starts_with('.')).unwrap_or(false);
+
+        if path.is_dir() && !hidden
+        {
+            source_files(&path, result);
+        }
+        else if path.extension().is_some_and(|extension| extension == "pc")
+        {
+            result.push(path);
+        }
+    }
+}
+
+/// Get the canonical paths of the files a source file imports, read from its tokens so a file which
+/// does not parse still gives its imports
+fn imports_of(path: &Path) -> Vec<PathBuf>
+{
+    let data = match std::fs::read_to_string(path)
+    {
+        Ok(data) => data,
+        Err(_) => {return vec![];}
+    };
+
+    let tokens = tokenize(data, path.display().to_string());
+    let directory = path.parent().unwrap_or_else(|| Path::new(""));
+
+    tokens.windows(2)
+        .filter(|pair| &*pair[0].data == "import" && pair[1].data.len() >= 2 && pair[1].data.starts_with('"') && pair[1].data.ends_with('"'))
+        .map(|pair| canonical(&directory.join(&pair[1].data[1..pair[1].data.len() - 1])))
+        .collect()
+}
+
+/// Find the source files under the current directory which import a file, directly or through
+/// other files, and are not themselves imported by one of them, the roots of the module trees the
+/// file is part of
+fn importing_roots(filename: &str) -> Vec<PathBuf>
+{
+    let mut files = vec![];
+    source_files(Path::new("."), &mut files);
+    files.sort();
+
+    let imports: Vec<(PathBuf, Vec<PathBuf>)> = files.iter().map(|file| (canonical(file), imports_of(file))).collect();
+
+    // Work outwards from the file through the files importing it
+    let mut importers: Vec<PathBuf> = vec![];
+    let mut reached = vec![canonical(Path::new(filename))];
+
+    while let Some(file) = reached.pop()
+    {
+        for (importer, imported) in &imports
+        {
+            if imported.contains(&file) && !importers.contains(importer)
+            {
+                importers.push(importer.clone());
+                reached.push(importer.clone());
+            }
+        }
+    }
+
+    files.into_iter().zip(&imports)
+        .filter(|(_, (key, _))| importers.contains(key) && !imports.iter().any(|(other, imported)| importers.contains(other) && imported.contains(key)))
+        .map(|(file, _)| file.strip_prefix(".").map(Path::to_path_buf).unwrap_or(file))
+        .collect()
+}
+
+/// Rename the function, argument or variable named at a line and column of an input file, giving
+/// the new contents of every file it is named in. The input file may be imported by others, as a
+/// module of a larger program, so the rename is made from each of the source files under the
+/// current directory at the root of a module tree it is part of, reaching the files which import
+/// it, and otherwise from the input file itself, reaching the files it imports. The new name must
+/// not clash with another in scope, and every name must refer to the same function, argument or
+/// variable after the rename as before it
+pub fn rename(input: &InputFile, line: usize, column: usize, new_name: &str) -> Result<Edits, Error>
+{
+    let roots = importing_roots(&input.filename);
+
+    if roots.is_empty()
+    {
+        return rename_in_tree(input, &input.filename, line, column, new_name);
+    }
+
+    // Each file is listed once, by the name the input file or first module tree gives it
+    let mut edits: Edits = vec![];
+    let mut keys: Vec<PathBuf> = vec![];
+
+    for root in roots
+    {
+        let filename = root.display().to_string();
+        let root_input = InputFile { data: read_from_file(filename.clone())?, filename };
+
+        for (file, data) in rename_in_tree(&root_input, &input.filename, line, column, new_name)?
+        {
+            let key = canonical(Path::new(&file));
+
+            match keys.iter().position(|other| other == &key)
+            {
+                Some(i) if edits[i].1 != data => {return rename_error("E0036", format!("Renaming to '{}' would change '{}' differently from '{}' than from another file importing it", new_name, file, root_input.filename));},
+                Some(_) => {},
+                None =>
+                {
+                    let name = if key == canonical(Path::new(&input.filename)) {input.filename.clone()} else {file};
+                    keys.push(key);
+                    edits.push((name, data));
+                }
+            }
+        }
+    }
+
+    Ok(edits)
+}
+
+/// Rename the function, argument or variable named at a line and column of a file of the module
+/// tree of an input file, giving the new contents of every file of the tree it is named in
+fn rename_in_tree(input: &InputFile, filename: &str, line: usize, column: usize, new_name: &str) -> Result<Edits, Error>
+{
+    if !is_identifier(new_name)
+    {
+        return rename_error("E0110", format!("Cannot rename to '{}', it is not an identifier or is a keyword or type", new_name));
+    }
+
+    let index = cross_references(input)?;
+
+    // The file may be named differently by the file importing it
+    let key = canonical(Path::new(filename));
+    let named = index.iter().flat_map(|reference| reference.definition.iter().chain(&reference.references))
+        .map(|span| span.file.as_str())
+        .find(|file| *file == filename || canonical(Path::new(file)) == key)
+        .unwrap_or(filename);
+
+    let target = match index.iter().position(|reference| reference.contains(named, line, column))
+    {
+        Some(target) => target,
+        None => {return rename_error("E0110", format!("No function, argument or variable is named at Line {}:{} in file '{}'", line, column, filename));}
+    };
+
+    let reference = &index[target];
+
+    let definition = match &reference.definition
+    {
+        Some(definition) => definition,
+        None => {return rename_error("E0110", format!("Cannot rename '{}', it is not defined in the source", reference.name));}
+    };
+
+    if reference.name == new_name
+    {
+        return Ok(vec![]);
+    }
+
+    if let Some(other) = index.iter().find(|other| clashes(reference, other, new_name))
+    {
+        let place = other.definition.as_ref().or(other.references.first()).map(describe).unwrap_or_default();
+        return rename_error("E0036", format!("Renaming '{}' to '{}' would clash with the {} '{}' at {}", reference.name, new_name, other.kind.name(), other.name, place));
+    }
+
+    // Rewrite each file the name appears in
+    let spans: Vec<&Span> = std::iter::once(definition).chain(&reference.references).collect();
+    let mut files: Vec<&str> = spans.iter().map(|span| span.file.as_str()).collect();
+    files.sort();
+    files.dedup();
+
+    let mut edits = vec![];
+
+    for file in files
+    {
+        let source = if file == input.filename {input.data.clone()} else {read_from_file(file.to_string())?};
+        let in_file: Vec<&Span> = spans.iter().copied().filter(|span| span.file == file).collect();
+
+        edits.push((file.to_string(), rewrite(&source, &in_file, &reference.name, new_name)?));
+    }
+
+    // Make sure every other name still refers to what it did
+    let edited: HashMap<String, String> = edits.iter().cloned().collect();
+    let renamed = InputFile { filename: input.filename.clone(), data: edited.get(&input.filename).cloned().unwrap_or_else(|| input.data.clone()) };
+
+    let unchanged = match cross_references_with_edits(&renamed, &edited)
+    {
+        Ok(after) => same_references(&index, &after, target, new_name),
+        Err(_) => false
+    };
+
+    if !unchanged
+    {
+        return rename_error("E0036", format!("Renaming '{}' to '{}' would change what other names refer to", reference.name, new_name));
+    }
+
+    Ok(edits)
+}
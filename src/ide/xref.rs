@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+
+use crate::cli::Error;
+use crate::import::resolve_with_imports;
+use crate::io::InputFile;
+use crate::parser::{ParseTreeNode, NodeArena, ExpressionType};
+use crate::tokenizer::Token;
+
+/// What a name in the cross reference index refers to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind
+{
+    Function,
+    Argument,
+    Variable
+}
+
+impl SymbolKind
+{
+    /// Get the name of the kind of symbol
+    pub fn name(&self) -> &'static str
+    {
+        match self
+        {
+            SymbolKind::Function => "function",
+            SymbolKind::Argument => "argument",
+            SymbolKind::Variable => "variable"
+        }
+    }
+}
+
+/// The place a name is written in a source file, by the line and column of its first character
+/// and its length
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span
+{
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub length: usize
+}
+
+impl Span
+{
+    /// Get the span of the name a token gives, which for a function named with its module,
+    /// `math::square`, is the name after the module
+    pub fn of(token: &Token) -> Self
+    {
+        let (skipped, name) = match token.data.rsplit_once("::")
+        {
+            Some((module, name)) => (module.chars().count() + 2, name),
+            None => (0, token.data.as_str())
+        };
+
+        Span { file: token.location.name().to_string(), line: token.location.row, column: token.location.col + skipped, length: name.chars().count() }
+    }
+}
+
+/// A function, argument or variable along with where it is defined and every place it is named.
+/// Functions are told apart by their name in the output, `math.square`, and arguments and
+/// variables by that of the function they belong to, their scope
+#[derive(Debug, Clone)]
+pub struct CrossReference
+{
+    pub name: String,
+    pub symbol: String,
+    pub kind: SymbolKind,
+    pub scope: Option<String>,
+    pub definition: Option<Span>,
+    pub references: Vec<Span>
+}
+
+impl CrossReference
+{
+    /// Check if a position in a file is within the definition or one of the references
+    pub fn contains(&self, file: &str, line: usize, column: usize) -> bool
+    {
+        self.definition.iter().chain(&self.references).any(|span| span.file == file && span.line == line && (span.column..span.column + span.length).contains(&column))
+    }
+}
+
+/// Get every function of an input file and every file it imports, and every argument and
+/// variable of those functions, along with where each is defined and named. Functions come first,
+/// in the order they are first named, followed by the arguments and variables of each function
+pub fn cross_references(input: &InputFile) -> Result<Vec<CrossReference>, Error>
+{
+    cross_references_with_edits(input, &HashMap::new())
+}
+
+/// Get the cross references of an input file as `cross_references` does, reading the imported
+/// files being edited from their edited contents, by path, rather than from disk
+pub fn cross_references_with_edits(input: &InputFile, edited: &HashMap<String, String>) -> Result<Vec<CrossReference>, Error>
+{
+    let arena = NodeArena::new();
+    let (items, names) = resolve_with_imports(input, edited, &arena)?;
+
+    let mut result: Vec<CrossReference> = vec![];
+
+    for name in names
+    {
+        let span = Span::of(&name.token);
+        let short = name.token.data.rsplit("::").next().unwrap_or_default().to_string();
+
+        let index = match result.iter().position(|other| other.symbol == name.symbol)
+        {
+            Some(index) => index,
+            None =>
+            {
+                result.push(CrossReference { name: short, symbol: name.symbol.clone(), kind: SymbolKind::Function, scope: None, definition: None, references: vec![] });
+                result.len() - 1
+            }
+        };
+
+        if name.definition && result[index].definition.is_none()
+        {
+            result[index].definition = Some(span);
+        }
+        else
+        {
+            result[index].references.push(span);
+        }
+    }
+
+    let mut variables = vec![];
+
+    for item in &items
+    {
+        if let ParseTreeNode::Function(children) = item
+        {
+            if children.get(3).map(|body| matches!(body, ParseTreeNode::Empty)).unwrap_or(true)
+            {
+                continue;
+            }
+
+            // The function is known by the name given at its definition
+            let span = Span::of(token_of(&children[1]));
+            let scope = match result.iter().find(|function| function.definition.as_ref() == Some(&span))
+            {
+                Some(function) => function.symbol.clone(),
+                None => continue
+            };
+
+            let mut locals = vec![];
+
+            for argument in children[2].children()
+            {
+                if let Some(ParseTreeNode::Identifier(token)) = argument.children().get(1)
+                {
+                    name_local(&mut locals, token, SymbolKind::Argument, &scope);
+                }
+            }
+
+            gather_locals(&children[3], &mut locals, &scope);
+            variables.extend(locals);
+        }
+    }
+
+    result.extend(variables);
+
+    for reference in &mut result
+    {
+        reference.references.sort_by(|a, b| (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)));
+    }
+
+    Ok(result)
+}
+
+/// Get the token of an identifier
+fn token_of<'b>(node: &'b ParseTreeNode) -> &'b Token
+{
+    match node
+    {
+        ParseTreeNode::Identifier(token) => token,
+        _ => unreachable!()
+    }
+}
+
+/// Record a declaration of an argument or variable, the first of which is its definition. As a
+/// function has a single scope, declaring a name again refers to the same variable
+fn name_local(locals: &mut Vec<CrossReference>, token: &Token, kind: SymbolKind, scope: &str)
+{
+    match locals.iter_mut().find(|local| local.name == token.data)
+    {
+        Some(local) => local.references.push(Span::of(token)),
+        None => locals.push(CrossReference
+        {
+            name: token.data.to_string(),
+            symbol: token.data.to_string(),
+            kind,
+            scope: Some(scope.to_string()),
+            definition: Some(Span::of(token)),
+            references: vec![]
+        })
+    }
+}
+
+/// Gather the declarations and uses of the arguments and variables of a function within a
+/// statement. Names which are not arguments or variables declared before them are left out
+fn gather_locals(node: &ParseTreeNode, locals: &mut Vec<CrossReference>, scope: &str)
+{
+    match node
+    {
+        ParseTreeNode::AssignmentStatement(children) =>
+        {
+            for assignment in children[1].children()
+            {
+                if let Some(ParseTreeNode::Identifier(token)) = assignment.children().first()
+                {
+                    name_local(locals, token, SymbolKind::Variable, scope);
+                }
+
+                for value in assignment.children().iter().skip(1)
+                {
+                    gather_locals(value, locals, scope);
+                }
+            }
+        },
+        ParseTreeNode::Expression(ExpressionType::FunctionCall, children) =>
+        {
+            for argument in children.iter().skip(1)
+            {
+                gather_locals(argument, locals, scope);
+            }
+        },
+        ParseTreeNode::Identifier(token) =>
+        {
+            if let Some(local) = locals.iter_mut().find(|local| local.name == token.data)
+            {
+                local.references.push(Span::of(token));
+            }
+        },
+        ParseTreeNode::Attributes(_) => {},
+        _ =>
+        {
+            for child in node.children()
+            {
+                gather_locals(child, locals, scope);
+            }
+        }
+    }
+}
@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use super::io::{InputFile, read_from_file};
+use super::cli::Error;
+use super::tokenizer::{tokenize, Token};
+use super::parser::{ParseTreeNode, NodeArena, ExpressionType, parse};
+use super::irgen::Function;
+use super::ice;
+
+/// A source file, named after the file for an imported one, along with the modules it imports
+struct Module<'a>
+{
+    name: Option<String>,
+    items: Vec<ParseTreeNode<'a>>,
+    imports: Vec<usize>,
+    uses: Vec<ParseTreeNode<'a>>
+}
+
+/// Loads a source file along with every file it imports, allocating their parse trees in an arena
+struct Importer<'e, 'a>
+{
+    modules: Vec<Module<'a>>,
+    order: Vec<usize>,
+    loaded: Vec<(PathBuf, usize)>,
+    stack: Vec<(PathBuf, String)>,
+    files: Vec<String>,
+    edited: &'e HashMap<String, String>,
+    arena: &'a NodeArena<'a>
+}
+
+/// Generate an error at a token naming a file, module or function
+fn located_error<T>(code: &'static str, token: &Token, text: String) -> Result<T, Error>
+{
+    Err(Error::error(&format!("{} at {}", text, token.location)).with_code(code))
+}
+
+/// Get the token of an identifier or raw token
+fn token_of<'b>(node: &'b ParseTreeNode) -> &'b Token
+{
+    match node
+    {
+        ParseTreeNode::Identifier(token) | ParseTreeNode::RawToken(token) => token,
+        _ => unreachable!()
+    }
+}
+
+/// Get the canonical form of a path, which is the same however the file is named, falling back on
+/// the path as given for a file which does not exist on disk
+pub fn canonical(path: &Path) -> PathBuf
+{
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl<'e, 'a> Importer<'e, 'a>
+{
+    /// Parse a file and everything it imports, giving the index of its module
+    fn load(&mut self, filename: &str, data: String, name: Option<String>) -> Result<usize, Error>
+    {
+        let index = self.modules.len();
+        self.modules.push(Module { name, items: vec![], imports: vec![], uses: vec![] });
+
+        ice::enter_phase("tokenization");
+        let tokens = tokenize(data, filename.to_string());
+
+        ice::enter_phase("parsing");
+        let library = parse(tokens, self.arena)?;
+
+        for item in library.children()
+        {
+            match item
+            {
+                ParseTreeNode::Import(children) =>
+                {
+                    let path = token_of(&children[1]);
+
+                    // Paths are relative to the file containing the import
+                    let relative = &path.data[1..path.data.len() - 1];
+                    let imported = Path::new(filename).parent().unwrap_or_else(|| Path::new("")).join(relative);
+                    let key = canonical(&imported);
+
+                    if let Some(start) = self.stack.iter().position(|(other, _)| other == &key)
+                    {
+                        let mut cycle: Vec<String> = self.stack[start..].iter().map(|(_, name)| name.clone()).collect();
+                        cycle.push(imported.display().to_string());
+
+                        return located_error("E0031", path, format!("Circular import, {}", cycle.join(" -> ")));
+                    }
+
+                    // A file is only ever loaded once, however many times it is imported
+                    if let Some((_, module)) = self.loaded.iter().find(|(other, _)| other == &key)
+                    {
+                        let module = *module;
+                        self.modules[index].imports.push(module);
+                        continue;
+                    }
+
+                    let name = imported.display().to_string();
+
+                    let edited = self.edited.get(&name).cloned().map(Ok);
+
+                    let data = match edited.unwrap_or_else(|| read_from_file(name.clone()))
+                    {
+                        Ok(data) => data,
+                        Err(_) => {return located_error("E0030", path, format!("Could not import '{}'", name));}
+                    };
+
+                    // The module is named after the file
+                    let module_name = imported.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+                    if self.modules.iter().any(|module| module.name.as_ref() == Some(&module_name))
+                    {
+                        return located_error("E0033", path, format!("Another imported file is already named '{}', the module names of imported files must differ", module_name));
+                    }
+
+                    self.files.push(name.clone());
+                    self.loaded.push((key.clone(), self.modules.len()));
+
+                    self.stack.push((key, name.clone()));
+                    let module = self.load(&name, data, Some(module_name))?;
+                    self.stack.pop();
+
+                    self.modules[index].imports.push(module);
+                },
+                ParseTreeNode::Use(_) => self.modules[index].uses.push(item.clone()),
+                _ => self.modules[index].items.push(item.clone())
+            }
+        }
+
+        // Files come after the files they import
+        self.order.push(index);
+
+        Ok(index)
+    }
+
+    /// Get the name each function of a module is known by in the output, and whether it can be
+    /// called from other modules. Functions defined in an imported module are qualified with the
+    /// name of the module so they never clash with functions of the same name in other modules, and
+    /// are private to it unless marked `pub`
+    fn symbols(&self, module: usize) -> HashMap<String, Symbol>
+    {
+        let module = &self.modules[module];
+
+        let functions: Vec<(&ParseTreeNode, String)> = module.items.iter()
+            .filter(|item| matches!(item, ParseTreeNode::Function(_)))
+            .map(|item| (item, token_of(&item.children()[1]).data.to_string()))
+            .collect();
+
+        let defined: Vec<&String> = functions.iter().filter(|(item, _)| !Function::is_prototype(item)).map(|(_, name)| name).collect();
+
+        // A function is public if any of its declarations is
+        let public: Vec<&String> = functions.iter()
+            .filter(|(item, _)| matches!(item.children().get(6), Some(ParseTreeNode::RawToken(_))))
+            .map(|(_, name)| name)
+            .collect();
+
+        functions.iter().map(|(_, name)| match &module.name
+        {
+            // Prototypes of functions defined elsewhere keep their names, to link against
+            Some(module_name) if defined.contains(&name) =>
+                (name.clone(), Symbol { name: format!("{}.{}", module_name, name), public: public.contains(&name) }),
+            Some(_) => (name.clone(), Symbol { name: name.clone(), public: public.contains(&name) }),
+            // Every function of the input file is public
+            None => (name.clone(), Symbol { name: name.clone(), public: true })
+        }).collect()
+    }
+}
+
+/// The name of a function in the output and whether it can be called from other modules
+#[derive(Clone)]
+struct Symbol
+{
+    name: String,
+    public: bool
+}
+
+/// The functions which may be called from within a module
+struct Scope<'s, 'a>
+{
+    importer: &'s Importer<'s, 'a>,
+    module: usize,
+    symbols: &'s [HashMap<String, Symbol>],
+    used: HashMap<String, (String, String)>
+}
+
+impl<'s, 'a> Scope<'s, 'a>
+{
+    /// Gather the functions a module may call, checking the functions it uses from other modules
+    fn new(importer: &'s Importer<'s, 'a>, module: usize, symbols: &'s [HashMap<String, Symbol>]) -> Result<Self, Error>
+    {
+        let mut scope = Self { importer, module, symbols, used: HashMap::new() };
+
+        for item in &importer.modules[module].uses
+        {
+            let (module_token, name) = (token_of(&item.children()[1]), token_of(&item.children()[2]));
+            let symbol = scope.qualified(module_token, name)?;
+
+            if symbols[module].contains_key(&*name.data)
+            {
+                return located_error("E0033", name, format!("'{}' is used from '{}' but is also defined here", name.data, module_token.data));
+            }
+
+            if let Some((other, _)) = scope.used.get(&*name.data).filter(|(_, other)| other != &symbol)
+            {
+                return located_error("E0033", name, format!("'{}' is used from both '{}' and '{}'", name.data, other, module_token.data));
+            }
+
+            scope.used.insert(name.data.to_string(), (module_token.data.to_string(), symbol));
+        }
+
+        Ok(scope)
+    }
+
+    /// Find a module imported by this module from its name
+    fn imported(&self, name: &str) -> Option<usize>
+    {
+        self.importer.modules[self.module].imports.iter().copied().find(|module| self.importer.modules[*module].name.as_deref() == Some(name))
+    }
+
+    /// Get the name in the output of a function named with its module, `math::square`
+    fn qualified(&self, module: &Token, name: &Token) -> Result<String, Error>
+    {
+        let index = if self.importer.modules[self.module].name.as_deref() == Some(module.data.as_str())
+        {
+            self.module
+        }
+        else
+        {
+            match self.imported(&module.data)
+            {
+                Some(index) => index,
+                None => {return located_error("E0032", module, format!("Module '{}' is not imported here", module.data));}
+            }
+        };
+
+        match self.symbols[index].get(&*name.data)
+        {
+            Some(symbol) if index != self.module && !symbol.public =>
+                located_error("E0034", name, format!("Function '{}' of module '{}' is private, it must be marked 'pub' to be called from other modules", name.data, module.data)),
+            Some(symbol) => Ok(symbol.name.clone()),
+            None => located_error("E0032", name, format!("Module '{}' has no function '{}'", module.data, name.data))
+        }
+    }
+
+    /// Get the name in the output of a function called from this module, looking first at the
+    /// functions it defines, then those it uses and then those of the modules it imports, leaving
+    /// the names of builtins and functions it does not know alone
+    fn resolve(&self, token: &Token) -> Result<String, Error>
+    {
+        if let Some((module, name)) = token.data.split_once("::")
+        {
+            return self.qualified(&Token::new(token.location.clone(), module.to_string()), &Token::new(token.location.clone(), name.to_string()));
+        }
+
+        if let Some(symbol) = self.symbols[self.module].get(&*token.data)
+        {
+            return Ok(symbol.name.clone());
+        }
+
+        if let Some((_, symbol)) = self.used.get(&*token.data)
+        {
+            return Ok(symbol.clone());
+        }
+
+        // The private functions of imported modules are not considered
+        let mut candidates: Vec<(&str, &String)> = vec![];
+        let mut private = None;
+
+        for module in &self.importer.modules[self.module].imports
+        {
+            let module_name = self.importer.modules[*module].name.as_deref().unwrap_or_default();
+
+            match self.symbols[*module].get(&*token.data)
+            {
+                Some(symbol) if !symbol.public => {private = private.or(Some(module_name));},
+                Some(symbol) if !candidates.iter().any(|(_, other)| *other == &symbol.name) => candidates.push((module_name, &symbol.name)),
+                _ => {}
+            }
+        }
+
+        match candidates.as_slice()
+        {
+            [] => match private
+            {
+                Some(module) => located_error("E0034", token, format!("Function '{}' of module '{}' is private, it must be marked 'pub' to be called from other modules", token.data, module)),
+                None => Ok(token.data.to_string())
+            },
+            [(_, symbol)] => Ok(symbol.to_string()),
+            [(first, _), (second, _), ..] => located_error("E0033", token,
+                format!("Call to '{}' is ambiguous, it could be '{}::{}' or '{}::{}'", token.data, first, token.data, second, token.data))
+        }
+    }
+
+    /// Give every function defined and called within a parse tree its name in the output, along
+    /// with every global variable private to an imported module, which is qualified with the name
+    /// of the module as a function is
+    fn rename(&self, node: &ParseTreeNode<'a>) -> Result<ParseTreeNode<'a>, Error>
+    {
+        let mut children = vec![];
+        let module_name = self.importer.modules[self.module].name.as_ref();
+
+        for (i, child) in node.children().iter().enumerate()
+        {
+            let named = match (node, child)
+            {
+                (ParseTreeNode::Function(_), ParseTreeNode::Identifier(token)) if i == 1 => Some(self.symbols[self.module][&*token.data].name.clone()),
+                (ParseTreeNode::GlobalVariable(nodes), ParseTreeNode::Identifier(token)) if i == 1 && !matches!(nodes.get(4), Some(ParseTreeNode::RawToken(_))) =>
+                    module_name.map(|module| format!("{}.{}", module, token.data)),
+                (ParseTreeNode::Expression(ExpressionType::FunctionCall, _), ParseTreeNode::Identifier(token)) if i == 0 => Some(self.resolve(token)?),
+                _ => None
+            };
+
+            match (named, child)
+            {
+                (Some(name), ParseTreeNode::Identifier(token)) => children.push(ParseTreeNode::Identifier(Token::new(token.location.clone(), name))),
+                _ => children.push(self.rename(child)?)
+            }
+        }
+
+        Ok(node.with_children(self.importer.arena, children))
+    }
+
+    /// Gather every function defined or called within a parse tree, or used from another module
+    fn references(&self, node: &ParseTreeNode, result: &mut Vec<NameReference>) -> Result<(), Error>
+    {
+        match node
+        {
+            ParseTreeNode::Function(children) =>
+            {
+                let token = token_of(&children[1]);
+                result.push(NameReference { token: token.clone(), symbol: self.symbols[self.module][&*token.data].name.clone(), definition: !Function::is_prototype(node) });
+            },
+            ParseTreeNode::Expression(ExpressionType::FunctionCall, children) =>
+            {
+                if let ParseTreeNode::Identifier(token) = &children[0]
+                {
+                    result.push(NameReference { token: token.clone(), symbol: self.resolve(token)?, definition: false });
+                }
+            },
+            ParseTreeNode::Use(children) =>
+            {
+                let (module, name) = (token_of(&children[1]), token_of(&children[2]));
+                result.push(NameReference { token: name.clone(), symbol: self.qualified(module, name)?, definition: false });
+            },
+            _ => {}
+        }
+
+        for child in node.children()
+        {
+            self.references(child, result)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A function named in a source file, by the token naming it as it was written (`square` or
+/// `math::square`), along with the name in the output of the function it refers to and whether
+/// it is where the function is defined, rather than a call, a prototype or a `use`
+#[derive(Debug, Clone)]
+pub struct NameReference
+{
+    pub token: Token,
+    pub symbol: String,
+    pub definition: bool
+}
+
+/// The items of an input file and every file it imports as they were written, along with every
+/// function named in them
+pub type ResolvedLibrary<'a> = (Vec<ParseTreeNode<'a>>, Vec<NameReference>);
+
+/// Parse an input file along with every file it imports, giving the items of all of them as they
+/// were written along with the function each function name in them refers to. Imported files
+/// being edited are read from their edited contents, by path, rather than from disk
+pub fn resolve_with_imports<'a>(input: &InputFile, edited: &HashMap<String, String>, arena: &'a NodeArena<'a>) -> Result<ResolvedLibrary<'a>, Error>
+{
+    let (importer, symbols) = load_with_imports(input, edited, arena)?;
+
+    let mut items = vec![];
+    let mut references = vec![];
+
+    for module in &importer.order
+    {
+        let scope = Scope::new(&importer, *module, &symbols)?;
+
+        for item in importer.modules[*module].items.iter().chain(&importer.modules[*module].uses)
+        {
+            scope.references(item, &mut references)?;
+            items.push(item.clone());
+        }
+    }
+
+    Ok((items, references))
+}
+
+/// Get the module a function was defined in from its name in the output, `math` for `math.square`,
+/// or None for a function of the input file
+pub fn module_of(symbol: &str) -> Option<&str>
+{
+    symbol.split_once('.').map(|(module, _)| module)
+}
+
+/// A library of the items of an input file and every file it imports, the paths of the imported
+/// files, and the names in the output of the functions private to their modules
+pub type ImportedLibrary<'a> = (ParseTreeNode<'a>, Vec<String>, Vec<String>);
+
+/// Load an input file along with every file it imports, directly or through other imports, along
+/// with the name in the output of every function of each file. Imported files being edited are
+/// read from their edited contents, by path, rather than from disk. The parse trees of the files are
+/// allocated in an arena
+fn load_with_imports<'e, 'a>(input: &InputFile, edited: &'e HashMap<String, String>, arena: &'a NodeArena<'a>) -> Result<(Importer<'e, 'a>, Vec<HashMap<String, Symbol>>), Error>
+{
+    let key = canonical(Path::new(&input.filename));
+
+    let mut importer = Importer
+    {
+        modules: vec![],
+        order: vec![],
+        loaded: vec![(key.clone(), 0)],
+        stack: vec![(key, input.filename.clone())],
+        files: vec![],
+        edited,
+        arena
+    };
+
+    importer.load(&input.filename, input.data.clone(), None)?;
+
+    let symbols = (0..importer.modules.len()).map(|module| importer.symbols(module)).collect();
+
+    Ok((importer, symbols))
+}
+
+/// Parse an input file along with every file it imports, directly or through other imports,
+/// giving a library of the items of all of them with the calls between them resolved, allocated in
+/// an arena
+pub fn parse_with_imports<'a>(input: &InputFile, arena: &'a NodeArena<'a>) -> Result<ImportedLibrary<'a>, Error>
+{
+    let edited = HashMap::new();
+    let (importer, symbols) = load_with_imports(input, &edited, arena)?;
+
+    // Name resolution
+    let mut items = vec![];
+
+    for module in &importer.order
+    {
+        let scope = Scope::new(&importer, *module, &symbols)?;
+
+        for item in &importer.modules[*module].items
+        {
+            items.push(scope.rename(item)?);
+        }
+    }
+
+    // Only functions defined in an imported module are renamed, prototypes keep the name they link against
+    let private = symbols.iter().flat_map(|symbols| symbols.iter())
+        .filter(|(name, symbol)| !symbol.public && &symbol.name != *name)
+        .map(|(_, symbol)| symbol.name.clone())
+        .collect();
+
+    Ok((ParseTreeNode::Library(arena.alloc_extend(items)), importer.files, private))
+}
@@ -0,0 +1,191 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+use serde::{Serialize, Serializer, Deserialize, Deserializer};
+
+/// Every string interned so far, by its symbol and the symbol of each string. The strings live for
+/// the rest of the run, so a symbol can give out its string without holding the lock
+struct Interner
+{
+    strings: Vec<&'static str>,
+    symbols: HashMap<&'static str, Symbol>
+}
+
+lazy_static!
+{
+    static ref INTERNER: RwLock<Interner> = RwLock::new(Interner { strings: vec![], symbols: HashMap::new() });
+}
+
+/// A handle to an interned string, such as the text of a token or the name of a file. Symbols of
+/// the same string are the same, so comparing two of them is comparing two integers, and copying
+/// one copies no text
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol
+{
+    /// Get the symbol of a string, interning it if it has not been seen before
+    pub fn intern(string: &str) -> Self
+    {
+        if let Some(symbol) = INTERNER.read().unwrap_or_else(|poisoned| poisoned.into_inner()).symbols.get(string)
+        {
+            return *symbol;
+        }
+
+        let mut interner = INTERNER.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        // Another thread may have interned the string in between the two locks
+        if let Some(symbol) = interner.symbols.get(string)
+        {
+            return *symbol;
+        }
+
+        let string: &'static str = Box::leak(string.to_string().into_boxed_str());
+        let symbol = Symbol(interner.strings.len() as u32);
+
+        interner.strings.push(string);
+        interner.symbols.insert(string, symbol);
+
+        symbol
+    }
+
+    /// Get the string the symbol was interned from
+    pub fn as_str(&self) -> &'static str
+    {
+        INTERNER.read().unwrap_or_else(|poisoned| poisoned.into_inner()).strings[self.0 as usize]
+    }
+}
+
+impl Deref for Symbol
+{
+    type Target = str;
+
+    fn deref(&self) -> &str
+    {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Symbol
+{
+    fn as_ref(&self) -> &str
+    {
+        self.as_str()
+    }
+}
+
+impl From<&str> for Symbol
+{
+    fn from(string: &str) -> Self
+    {
+        Symbol::intern(string)
+    }
+}
+
+impl From<String> for Symbol
+{
+    fn from(string: String) -> Self
+    {
+        Symbol::intern(&string)
+    }
+}
+
+impl PartialEq<str> for Symbol
+{
+    fn eq(&self, other: &str) -> bool
+    {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol
+{
+    fn eq(&self, other: &&str) -> bool
+    {
+        self.as_str() == *other
+    }
+}
+
+impl PartialEq<String> for Symbol
+{
+    fn eq(&self, other: &String) -> bool
+    {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<Symbol> for str
+{
+    fn eq(&self, other: &Symbol) -> bool
+    {
+        self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for &str
+{
+    fn eq(&self, other: &Symbol) -> bool
+    {
+        *self == other.as_str()
+    }
+}
+
+impl PartialEq<Symbol> for String
+{
+    fn eq(&self, other: &Symbol) -> bool
+    {
+        self == other.as_str()
+    }
+}
+
+impl Default for Symbol
+{
+    fn default() -> Self
+    {
+        Symbol::intern("")
+    }
+}
+
+impl From<Symbol> for String
+{
+    fn from(symbol: Symbol) -> Self
+    {
+        symbol.as_str().to_string()
+    }
+}
+
+impl fmt::Display for Symbol
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+// Symbols are written out as their strings, as the numbers they are given differ from run to run
+impl Serialize for Symbol
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    {
+        String::deserialize(deserializer).map(Symbol::from)
+    }
+}
@@ -38,6 +38,20 @@ pub fn read_from_file(filename: String) -> Result<String, cli::Error>
     match raw_read_from_file(filename.clone())
     {
         Ok(v) => Ok(v),
-        Err(_) => Err(cli::Error::error(&format!("{}: No such file or directory", filename.clone())))
+        Err(_) => Err(cli::Error::error(&format!("{}: No such file or directory", filename.clone())).with_code("E0100"))
     }
+}
+
+/// Express a path relative to the current directory if it lies within it
+pub fn relative_to_current_dir(path: &str) -> String
+{
+    if let Ok(current) = std::env::current_dir()
+    {
+        if let Ok(stripped) = std::path::Path::new(path).strip_prefix(&current)
+        {
+            return stripped.to_string_lossy().to_string();
+        }
+    }
+
+    String::from(path)
 }
\ No newline at end of file
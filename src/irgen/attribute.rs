@@ -0,0 +1,145 @@
+use std::fmt;
+
+use serde::{Serialize, Deserialize};
+
+use crate::parser::ParseTreeNode;
+use crate::cli::Error;
+
+use super::{compiler_error, expected_got_error};
+
+/// Attributes which may be placed on a function along with the number of arguments they take
+static FUNCTION_ATTRIBUTES: &[(&str, usize)] = &[("optimize", 1), ("noreturn", 0), ("const", 0), ("interrupt", 0), ("section", 1), ("used", 0), ("constructor", 0)];
+
+/// Attributes which may be placed on a loop along with the number of arguments they take
+static LOOP_ATTRIBUTES: &[(&str, usize)] = &[("vectorize", 0), ("unroll", 1)];
+
+/// Attribute attached to an item (for example #[optimize(0)])
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attribute
+{
+    pub name: String,
+    pub arguments: Vec<String>
+}
+
+impl Attribute
+{
+    /// Generate a new attribute
+    pub fn new(name: String, arguments: Vec<String>) -> Self
+    {
+        Self
+        {
+            name,
+            arguments
+        }
+    }
+}
+
+impl fmt::Display for Attribute
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "#[{}", self.name)?;
+
+        if !self.arguments.is_empty()
+        {
+            write!(f, "({})", self.arguments.join(", "))?;
+        }
+
+        write!(f, "]")
+    }
+}
+
+/// Extract the attributes placed on a function from a parse tree node
+pub fn function_attributes_from_parse_tree(node: &ParseTreeNode) -> Result<Vec<Attribute>, Error>
+{
+    let result = attributes_from_parse_tree(node, FUNCTION_ATTRIBUTES, "function")?;
+
+    for attribute in &result
+    {
+        if attribute.name == "section" && !is_section_name(&attribute.arguments[0])
+        {
+            return compiler_error("E0014", format!("Bad section name '{}' in attribute on function, expected a string such as \".init\"", attribute.arguments[0]));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Check if an attribute argument is a string naming a section, which is kept along with its
+/// quotes and written into the output as it is, so it cannot contain quotes or escapes of its own
+fn is_section_name(argument: &str) -> bool
+{
+    match argument.strip_prefix('"').and_then(|argument| argument.strip_suffix('"'))
+    {
+        Some(name) => !name.is_empty() && name.chars().all(|c| !c.is_control() && c != '"' && c != '\\'),
+        None => false
+    }
+}
+
+/// Extract the attributes placed on a loop from a parse tree node
+pub fn loop_attributes_from_parse_tree(node: &ParseTreeNode) -> Result<Vec<Attribute>, Error>
+{
+    let result = attributes_from_parse_tree(node, LOOP_ATTRIBUTES, "loop")?;
+
+    for attribute in &result
+    {
+        if attribute.name == "unroll" && !matches!(attribute.arguments[0].parse::<u32>(), Ok(count) if count > 0)
+        {
+            return compiler_error("E0014", format!("Bad unroll count '{}' in attribute on loop", attribute.arguments[0]));
+        }
+    }
+
+    Ok(result)
+}
+
+/// Extract attributes from a parse tree node, checking them against the attributes allowed on the item
+fn attributes_from_parse_tree(node: &ParseTreeNode, allowed: &[(&str, usize)], item: &str) -> Result<Vec<Attribute>, Error>
+{
+    match node
+    {
+        ParseTreeNode::Attributes(children) =>
+        {
+            let mut result = vec![];
+
+            for child in children.iter()
+            {
+                match child
+                {
+                    ParseTreeNode::Attribute(values) =>
+                    {
+                        let mut values = values.iter().map(|value| value.first_token().map(|token| token.data.to_string()).unwrap_or_default());
+
+                        let name = values.next().unwrap_or_default();
+                        let arguments: Vec<String> = values.collect();
+
+                        // Make sure the attribute exists and is given the right number of arguments
+                        match allowed.iter().find(|(attr, _)| *attr == name)
+                        {
+                            Some((_, count)) if *count != arguments.len() =>
+                            {
+                                return compiler_error("E0014", format!("Attribute '{}' expects {} argument{}, got {}", name, count, if *count == 1 {""} else {"s"}, arguments.len()));
+                            },
+                            Some(_) => {},
+                            None =>
+                            {
+                                return compiler_error("E0013", format!("Unknown {} attribute '{}'", item, name));
+                            }
+                        }
+
+                        result.push(Attribute::new(name, arguments));
+                    },
+                    default =>
+                    {
+                        return expected_got_error("Attribute", default);
+                    }
+                }
+            }
+
+            Ok(result)
+        },
+        default =>
+        {
+            expected_got_error("Attributes", default)
+        }
+    }
+}
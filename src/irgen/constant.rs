@@ -0,0 +1,223 @@
+use std::convert::TryFrom;
+
+use crate::parser::{ParseTreeNode, ExpressionType};
+use crate::cli::Error;
+
+use super::{Context, NonPtrType, type_from_parse_tree, compiler_error, compiler_error_loc};
+
+/// Get the number of bytes a type takes on the target of the library's context, as given by
+/// `sizeof`
+pub fn size_of_type(node: &ParseTreeNode, context: &Context) -> Result<i128, Error>
+{
+    let datatype = type_from_parse_tree(node, context)?;
+
+    if datatype.is_void()
+    {
+        return compiler_error_loc("E0016", String::from("Cannot take the size of 'void'"), &node.first_token().map(|token| token.location.clone()));
+    }
+
+    Ok(context.target.bytes_size_of(&datatype) as i128)
+}
+
+/// Get the number of bits in an integer type
+pub fn bits_of(raw_type: NonPtrType) -> Option<u32>
+{
+    match raw_type
+    {
+        NonPtrType::Bool => Some(1),
+        NonPtrType::I8 | NonPtrType::U8 => Some(8),
+        NonPtrType::I16 | NonPtrType::U16 => Some(16),
+        NonPtrType::I32 | NonPtrType::U32 => Some(32),
+        NonPtrType::I64 | NonPtrType::U64 => Some(64),
+        NonPtrType::Int(bits) | NonPtrType::UInt(bits) => Some(bits as u32),
+        NonPtrType::Vector(..) | NonPtrType::Struct(_) | NonPtrType::Void | NonPtrType::Unknown => None
+    }
+}
+
+/// Evaluate an expression from the parse tree at compile time, integers are evaluated without
+/// overflow and only wrap when cast to a type
+pub fn evaluate_constant(node: &ParseTreeNode, context: &Context) -> Result<i128, Error>
+{
+    match node
+    {
+        ParseTreeNode::IntegerLiteral(token) =>
+        {
+            match token.data.parse::<i128>()
+            {
+                Ok(value) => Ok(value),
+                Err(_) => compiler_error_loc("E0007", format!("Integer literal '{}' is too large", token.data), &Some(token.location.clone()))
+            }
+        },
+        ParseTreeNode::SizeOf(children) => size_of_type(&children[0], context),
+        ParseTreeNode::Expression(ExpressionType::Cast, children) =>
+        {
+            let value = evaluate_constant(&children[0], context)?;
+            let datatype = type_from_parse_tree(&children[1], context)?;
+
+            let bits = match bits_of(datatype.raw_type)
+            {
+                Some(bits) if datatype.num_ptr == 0 => bits,
+                _ => {return compiler_error("E0016", format!("Cannot cast to '{}' in a constant expression", datatype));}
+            };
+
+            Ok(wrap_constant(value, bits, datatype.is_signed()))
+        },
+        ParseTreeNode::Expression(ExpressionType::Ternary, children) =>
+        {
+            if evaluate_constant(&children[0], context)? != 0
+            {
+                evaluate_constant(&children[1], context)
+            }
+            else
+            {
+                evaluate_constant(&children[2], context)
+            }
+        },
+        ParseTreeNode::Expression(ExpressionType::LogicalAnd, children) =>
+        {
+            Ok((evaluate_constant(&children[0], context)? != 0 && evaluate_constant(&children[1], context)? != 0) as i128)
+        },
+        ParseTreeNode::Expression(ExpressionType::LogicalOr, children) =>
+        {
+            Ok((evaluate_constant(&children[0], context)? != 0 || evaluate_constant(&children[1], context)? != 0) as i128)
+        },
+        ParseTreeNode::Expression(expr_type, children) if children.len() == 1 =>
+        {
+            let value = evaluate_constant(&children[0], context)?;
+
+            match expr_type
+            {
+                ExpressionType::UnaryPlus => Ok(value),
+                ExpressionType::UnaryMinus => match value.checked_neg()
+                {
+                    Some(value) => Ok(value),
+                    None => compiler_error_loc("E0018", String::from("Overflow in a constant expression"), &node.first_token().map(|token| token.location.clone()))
+                },
+                ExpressionType::BitwiseNot => Ok(!value),
+                ExpressionType::LogicalNot => Ok((value == 0) as i128),
+                _ => not_constant(node)
+            }
+        },
+        ParseTreeNode::Expression(expr_type, children) if children.len() == 2 =>
+        {
+            let value0 = evaluate_constant(&children[0], context)?;
+            let value1 = evaluate_constant(&children[1], context)?;
+
+            let result = match expr_type
+            {
+                ExpressionType::Add => value0.checked_add(value1),
+                ExpressionType::Subtract => value0.checked_sub(value1),
+                ExpressionType::Multiply => value0.checked_mul(value1),
+                ExpressionType::Divide | ExpressionType::Modulus if value1 == 0 =>
+                {
+                    return compiler_error_loc("E0017", String::from("Division by zero in a constant expression"), &node.first_token().map(|token| token.location.clone()));
+                },
+                ExpressionType::Divide => value0.checked_div(value1),
+                ExpressionType::Modulus => value0.checked_rem(value1),
+                ExpressionType::ShiftLeft => u32::try_from(value1).ok().and_then(|shift| value0.checked_shl(shift)),
+                ExpressionType::ShiftRight => u32::try_from(value1).ok().and_then(|shift| value0.checked_shr(shift)),
+                ExpressionType::BitwiseAnd => Some(value0 & value1),
+                ExpressionType::BitwiseOr => Some(value0 | value1),
+                ExpressionType::BitwiseXor => Some(value0 ^ value1),
+                ExpressionType::LessThan => Some((value0 < value1) as i128),
+                ExpressionType::LessThanOrEqual => Some((value0 <= value1) as i128),
+                ExpressionType::GreaterThan => Some((value0 > value1) as i128),
+                ExpressionType::GreaterThanOrEqual => Some((value0 >= value1) as i128),
+                ExpressionType::Equal => Some((value0 == value1) as i128),
+                ExpressionType::NotEqual => Some((value0 != value1) as i128),
+                // Both sides of a comma are constants, so only the value of the right matters
+                ExpressionType::Comma => Some(value1),
+                _ => {return not_constant(node);}
+            };
+
+            match result
+            {
+                Some(value) => Ok(value),
+                None => compiler_error_loc("E0018", String::from("Overflow in a constant expression"), &node.first_token().map(|token| token.location.clone()))
+            }
+        },
+        _ => not_constant(node)
+    }
+}
+
+/// Wrap a constant to the given number of bits, then sign extend it if it is signed
+pub fn wrap_constant(value: i128, bits: u32, signed: bool) -> i128
+{
+    let mask = (1i128 << bits) - 1;
+    let wrapped = value & mask;
+
+    if signed && wrapped >> (bits - 1) != 0
+    {
+        wrapped - (1i128 << bits)
+    }
+    else
+    {
+        wrapped
+    }
+}
+
+/// Error for an expression which cannot be evaluated at compile time
+fn not_constant<T>(node: &ParseTreeNode) -> Result<T, Error>
+{
+    compiler_error_loc("E0016", String::from("Expression is not a compile time constant"), &node.first_token().map(|token| token.location.clone()))
+}
+
+/// Remove the quotes from a string token, replacing any escapes with the characters they stand for
+pub fn unescape_string(data: &str) -> String
+{
+    let inner = data.strip_prefix('"').unwrap_or(data);
+    let inner = inner.strip_suffix('"').unwrap_or(inner);
+
+    let mut result = String::new();
+    let mut chars = inner.chars();
+
+    while let Some(c) = chars.next()
+    {
+        if c == '\\'
+        {
+            match chars.next()
+            {
+                Some('n') => result.push('\n'),
+                Some('t') => result.push('\t'),
+                Some('0') => result.push('\0'),
+                Some(other) => result.push(other),
+                None => {}
+            }
+        }
+        else
+        {
+            result.push(c);
+        }
+    }
+
+    result
+}
+
+/// Check a static assertion, failing compilation with its message if the condition is false
+pub fn check_static_assert(node: &ParseTreeNode, context: &Context) -> Result<(), Error>
+{
+    match node
+    {
+        ParseTreeNode::StaticAssert(children) =>
+        {
+            let location = node.first_token().map(|token| token.location.clone());
+
+            if evaluate_constant(&children[1], context)? == 0
+            {
+                let message = match &children[2]
+                {
+                    ParseTreeNode::RawToken(token) => unescape_string(&token.data),
+                    _ => String::new()
+                };
+
+                return compiler_error_loc("E0019", format!("Static assertion failed: {}", message), &location);
+            }
+
+            Ok(())
+        },
+        default =>
+        {
+            super::expected_got_error("Static Assert", default)
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use super::{StructTable, GlobalVariable};
+
+use crate::codegen::TargetSpec;
+
+/// What the functions of a library share as they are lowered and generated, which belongs to the
+/// library being compiled alone so compiling another library alongside it cannot change it
+#[derive(Debug, Clone)]
+pub struct Context
+{
+    pub structs: StructTable, // Every struct the library declares
+    pub globals: Vec<GlobalVariable>, // Every global variable of the library, in the order they are declared
+    pub target: TargetSpec // Target the library is compiled for, which gives the sizes `sizeof` works out
+}
+
+impl Context
+{
+    /// Generate the context of a library compiled for the given target, before anything in it is
+    /// declared
+    pub fn new(target: TargetSpec) -> Self
+    {
+        Self
+        {
+            structs: StructTable::default(),
+            globals: vec![],
+            target
+        }
+    }
+}
+
+// A library is compiled for the host unless it is given a target
+impl Default for Context
+{
+    fn default() -> Self
+    {
+        Self::new(TargetSpec::host())
+    }
+}
@@ -3,27 +3,37 @@ use crate::parser::ParseTreeNode;
 use crate::tokenizer::FileLocation;
 
 /// Display a compiler error (from IR code gen)
-pub fn compiler_error<T>(text: String) -> Result<T, Error>
+pub fn compiler_error<T>(code: &'static str, text: String) -> Result<T, Error>
 {
-    Err(Error::error(&format!("Compilation Error: {}", text)))
+    Err(Error::error(&format!("Compilation Error: {}", text)).with_code(code))
 }
 
 /// Expected, got style error for IR code gen
-pub fn expected_got_error<T>(expected: &str, got: ParseTreeNode) -> Result<T, Error>
+pub fn expected_got_error<T>(expected: &str, got: &ParseTreeNode) -> Result<T, Error>
 {
     let raw_got_str = format!("{:?}", got);
     let got_str = raw_got_str.split("(").nth(0).unwrap();
 
-    compiler_error(format!("Expected {}, got {}", expected, got_str))
+    compiler_error("E0005", format!("Expected {}, got {}", expected, got_str))
 }
 
 /// Display the location if the location is known
-pub fn compiler_error_loc<T>(text: String, loc: &Option<FileLocation>) -> Result<T, Error>
+pub fn compiler_error_loc<T>(code: &'static str, text: String, loc: &Option<FileLocation>) -> Result<T, Error>
 {
     match loc
     {
-        None => Err(Error::error(&format!("Compilation Error: {}", text))),
-        Some(l) => Err(Error::error(&format!("Compilation Error: {}", format!("{} at {}", text, l))))
+        None => Err(Error::error(&format!("Compilation Error: {}", text)).with_code(code)),
+        Some(l) => Err(Error::error(&format!("Compilation Error: {}", format!("{} at {}", text, l))).with_code(code))
     }
     
-}
\ No newline at end of file
+}
+
+/// Generate a compiler warning, with the location if the location is known
+pub fn compiler_warning_loc(code: &'static str, text: String, loc: &Option<FileLocation>) -> Error
+{
+    match loc
+    {
+        None => Error::warning(&text).with_code(code),
+        Some(l) => Error::warning(&format!("{} at {}", text, l)).with_code(code)
+    }
+}
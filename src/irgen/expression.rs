@@ -1,16 +1,51 @@
-use super::{Function, Value, Literal, Symbol, Instruction, OpCode, attempt_mutate_type, has_unknown_type, get_value_type, correct_type_references, type_from_parse_tree};
+use super::{Function, Signature, Value, Literal, Symbol, Instruction, OpCode, GlobalVariable, attempt_mutate_type, has_unknown_type, get_value_type, correct_type_references, converts_implicitly, type_from_parse_tree};
 
-use crate::cli::Error;
+use crate::cli::{Error, suggest};
 
-use super::{expected_got_error, compiler_error_loc};
+use super::{expected_got_error, compiler_error_loc, compiler_warning_loc, evaluate_constant, size_of_type, unescape_string, bits_of, MAX_VECTOR_LANES};
 use super::{DataType, NonPtrType};
 
+use crate::import::module_of;
 use crate::parser::ParseTreeNode;
 use crate::parser::ExpressionType as ExpressionTypeP;
 
 use crate::tokenizer::{Token, FileLocation};
 
 use std::cell::RefCell;
+use std::rc::Rc;
+use std::iter::once;
+
+/// Builtin functions which map directly onto an operation, along with that operation and the
+/// number of arguments it takes
+static BUILTINS: &[(&str, OpCode, usize)] = &[
+    ("__builtin_clz", OpCode::Clz, 1),
+    ("__builtin_ctz", OpCode::Ctz, 1),
+    ("__builtin_popcount", OpCode::Popcnt, 1),
+    ("__builtin_bswap", OpCode::Bswap, 1),
+    ("__builtin_rotl", OpCode::Rotl, 2),
+    ("__builtin_rotr", OpCode::Rotr, 2)
+];
+
+/// Builtin functions which need the C library, so are only available when compiling for a hosted
+/// environment, along with the type (and number of pointers) they return and of their arguments
+static HOSTED_BUILTINS: &[(&str, (NonPtrType, usize), &[(NonPtrType, usize)])] = &[
+    ("__builtin_print", (NonPtrType::Void, 0), &[(NonPtrType::I64, 0)]),
+    ("__builtin_alloc", (NonPtrType::U8, 1), &[(NonPtrType::U64, 0)]),
+    ("__builtin_assert", (NonPtrType::Void, 0), &[(NonPtrType::Bool, 0)]),
+    ("__builtin_abort", (NonPtrType::Void, 0), &[])
+];
+
+/// Get the signatures of the hosted builtins, which are called like any other function
+pub fn hosted_signatures() -> impl Iterator<Item = (String, Signature)>
+{
+    HOSTED_BUILTINS.iter().map(|(name, (return_type, return_ptrs), arguments)| (String::from(*name), Signature
+    {
+        return_type: DataType::new(*return_type, *return_ptrs, false),
+        arguments: arguments.iter().map(|(datatype, ptrs)| DataType::new(*datatype, *ptrs, false)).collect(),
+        defaults: vec![None; arguments.len()],
+        interrupt: false
+    }))
+}
 
 /// Expression Types
 #[derive(Debug, Clone, PartialEq)]
@@ -29,11 +64,15 @@ pub enum ExpressionType
     Comma,
     UnaryMinus,
     IntegerLiteral,
+    StringLiteral,
     Identifier,
     PreExpression(OpCode),
     PostExpression(OpCode),
     BinaryExpression(OpCode),
     AssignmentExpression(Option<OpCode>),
+    Shuffle(Vec<i128>),
+    Member(String, bool, bool), // The field, whether it is reached through a pointer, and whether it is written to
+    Global(String, bool), // The global variable, and whether it is written to
 }
 
 /// Expression Struct
@@ -73,23 +112,90 @@ impl Expression
     }
 
 
-    pub fn from_parse_tree_node(node: ParseTreeNode, func: &RefCell<&mut Function>) -> Result<Self, Error>
+    /// Build a unary minus or bitwise not, folding it straight into a literal when it is applied to
+    /// a literal which has not been given a type yet, so `-1` is a constant wherever it is used
+    fn fold_unary_literal(mode: ExpressionType, child: Expression) -> Self
+    {
+        if let (ExpressionType::IntegerLiteral, Some(Value::Literal(literal))) = (&child.mode, &child.value)
+        {
+            let folded = match mode
+            {
+                ExpressionType::UnaryMinus => literal.value.checked_neg(),
+                ExpressionType::BitwiseNot => Some(!literal.value),
+                _ => None
+            };
+
+            if let (Some(value), NonPtrType::Unknown, 0) = (folded, literal.datatype.raw_type, literal.datatype.num_ptr)
+            {
+                let mut result = Expression::new(ExpressionType::IntegerLiteral, Some(Value::Literal(Literal::new(value, literal.datatype))), vec![]);
+                result.pos = child.pos.clone();
+
+                return result;
+            }
+        }
+
+        Expression::new(mode, None, vec![child])
+    }
+
+    /// Mark a field or global variable as being written to, so it is reached through a reference
+    /// to it rather than loaded. Any other expression is left as it is
+    fn into_target(mut self) -> Self
+    {
+        match self.mode
+        {
+            ExpressionType::Member(field, arrow, _) => {self.mode = ExpressionType::Member(field, arrow, true);},
+            ExpressionType::Global(name, _) => {self.mode = ExpressionType::Global(name, true);},
+            _ => {}
+        }
+
+        self
+    }
+
+    pub fn from_parse_tree_node(node: &ParseTreeNode, func: &RefCell<&mut Function>) -> Result<Self, Error>
     {
         match &node
         {
             ParseTreeNode::IntegerLiteral(token) =>
             {
                 Ok(Expression::new_with_token(ExpressionType::IntegerLiteral, 
-                    Some(Value::Literal(Literal::new(i128::from_str_radix(token.data.as_str(), 10).unwrap(),
+                    Some(Value::Literal(Literal::new(evaluate_constant(&node, &func.borrow().context)?,
+                        DataType::new(NonPtrType::Unknown, 0, false)))), vec![], token))
+            },
+            // A size is an integer literal, taking the type it is used as
+            ParseTreeNode::SizeOf(children) =>
+            {
+                let token = children[0].first_token().unwrap();
+
+                Ok(Expression::new_with_token(ExpressionType::IntegerLiteral,
+                    Some(Value::Literal(Literal::new(size_of_type(&children[0], &func.borrow().context)?,
                         DataType::new(NonPtrType::Unknown, 0, false)))), vec![], token))
             },
+            // Null is a pointer of unknown type, which becomes whichever pointer type it is used as
+            ParseTreeNode::NullLiteral(token) =>
+            {
+                Ok(Expression::new_with_token(ExpressionType::IntegerLiteral,
+                    Some(Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 1, false)))), vec![], token))
+            },
+            // A string is a pointer to its bytes, which are placed in the output as constant data
+            ParseTreeNode::StringLiteral(token) =>
+            {
+                Ok(Expression::new_with_token(ExpressionType::StringLiteral,
+                    Some(Value::Data(unescape_string(&token.data).into_bytes())), vec![], token))
+            },
             ParseTreeNode::Identifier(token) =>
             {
-                let val = Value::Symbol(match func.borrow_mut().symbol_table.get(&token.data)
+                // A name which is not a variable of the function may be a global variable
+                if !func.borrow().symbol_table.contains_key(&*token.data)
                 {
-                    Some(v) => v.clone(),
-                    None => {compiler_error_loc(format!("Symbol {} not found in symbol table", token.data), &Some(token.location.clone()))?;unreachable!()}
-                });
+                    let context = Rc::clone(&func.borrow().context);
+
+                    if let Some(global) = GlobalVariable::resolve(&context.globals, token, module_of(&func.borrow().name))?
+                    {
+                        return Ok(Expression::new_with_token(ExpressionType::Global(global.name.clone(), false), None, vec![], token));
+                    }
+                }
+
+                let val = Value::Symbol(func.borrow().lookup_symbol(token)?.clone());
 
                 Ok(Expression::new(ExpressionType::Identifier,
                     Some(val), vec![]))
@@ -99,46 +205,50 @@ impl Expression
                 match expr_type
                 {
                     // The Unary Plus Does basically Nothing
-                    ExpressionTypeP::UnaryPlus => {Expression::from_parse_tree_node(children[0].clone(), func)},
+                    ExpressionTypeP::UnaryPlus => {Expression::from_parse_tree_node(&children[0], func)},
                     ExpressionTypeP::UnaryMinus => 
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
 
-                        Ok(Expression::new(ExpressionType::UnaryMinus, None, vec![child0]))
+                        Ok(Expression::fold_unary_literal(ExpressionType::UnaryMinus, child0))
                     },
                     ExpressionTypeP::BitwiseNot => 
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
 
-                        Ok(Expression::new(ExpressionType::UnaryMinus, None, vec![child0]))
+                        Ok(Expression::fold_unary_literal(ExpressionType::BitwiseNot, child0))
                     },
                     ExpressionTypeP::LogicalNot => 
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
+
+                        let mut result = Expression::new(ExpressionType::LogicalNot, None, vec![child0]);
 
-                        Ok(Expression::new(ExpressionType::LogicalNot, None, vec![child0]))
+                        result.pos = node.first_token().map(|token| token.location.clone());
+
+                        Ok(result)
                     },
                     ExpressionTypeP::PreIncrement => 
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&step_target(&children[0]), func)?.into_target();
 
                         Ok(Expression::new(ExpressionType::PreExpression(OpCode::Add), None, vec![child0]))
                     },
                     ExpressionTypeP::PreDecrement => 
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&step_target(&children[0]), func)?.into_target();
 
                         Ok(Expression::new(ExpressionType::PreExpression(OpCode::Sub), None, vec![child0]))
                     },
                     ExpressionTypeP::PostIncrement => 
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&step_target(&children[0]), func)?.into_target();
 
                         Ok(Expression::new(ExpressionType::PostExpression(OpCode::Add), None, vec![child0]))
                     },
                     ExpressionTypeP::PostDecrement => 
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&step_target(&children[0]), func)?.into_target();
 
                         Ok(Expression::new(ExpressionType::PostExpression(OpCode::Sub), None, vec![child0]))
                     },
@@ -148,10 +258,10 @@ impl Expression
                     ExpressionTypeP::Equal | ExpressionTypeP::NotEqual | ExpressionTypeP::BitwiseAnd | ExpressionTypeP::BitwiseOr |
                     ExpressionTypeP::BitwiseXor => 
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
-                        let child1 = Expression::from_parse_tree_node(children[1].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
+                        let child1 = Expression::from_parse_tree_node(&children[1], func)?;
 
-                        Ok(Expression::new(ExpressionType::BinaryExpression(
+                        let mut result = Expression::new(ExpressionType::BinaryExpression(
                             match expr_type
                             {
                                 ExpressionTypeP::Add => OpCode::Add,
@@ -172,21 +282,37 @@ impl Expression
                                 ExpressionTypeP::BitwiseXor => OpCode::Xor,
                                 _ => {unreachable!();}
                             }
-                        ), None, vec![child0, child1]))
+                        ), None, vec![child0, child1]);
+
+                        result.pos = node.first_token().map(|token| token.location.clone());
+
+                        Ok(result)
                     },
                     ExpressionTypeP::ArrayAccess =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
-                        let child1 = Expression::from_parse_tree_node(children[1].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
+                        let child1 = Expression::from_parse_tree_node(&children[1], func)?;
 
                         Ok(Expression::new(ExpressionType::ArrayAccess, None, vec![child0, child1]))
                     },
-                    ExpressionTypeP::AddAssign =>
+                    ExpressionTypeP::Member | ExpressionTypeP::PointerMember =>
+                    {
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
+                        let field = children[1].first_token().unwrap();
+
+                        let arrow = *expr_type == ExpressionTypeP::PointerMember;
+
+                        Ok(Expression::new_with_token(ExpressionType::Member(field.data.to_string(), arrow, false), None, vec![child0], field))
+                    },
+                    ExpressionTypeP::AddAssign | ExpressionTypeP::SubtractAssign | ExpressionTypeP::MultiplyAssign |
+                    ExpressionTypeP::DivideAssign | ExpressionTypeP::ModulusAssign | ExpressionTypeP::ShiftLeftAssign |
+                    ExpressionTypeP::ShiftRightAssign | ExpressionTypeP::BitwiseAndAssign | ExpressionTypeP::BitwiseOrAssign |
+                    ExpressionTypeP::BitwiseXorAssign =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
-                        let child1 = Expression::from_parse_tree_node(children[1].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?.into_target();
+                        let child1 = Expression::from_parse_tree_node(&children[1], func)?;
 
-                        Ok(Expression::new(ExpressionType::AssignmentExpression(Some(
+                        let mut result = Expression::new(ExpressionType::AssignmentExpression(Some(
                             match expr_type
                             {
                                 ExpressionTypeP::AddAssign => OpCode::Add,
@@ -201,12 +327,16 @@ impl Expression
                                 ExpressionTypeP::BitwiseXorAssign => OpCode::Xor,
                                 _ => {unreachable!();}
                             })
-                        ), None, vec![child0, child1]))
+                        ), None, vec![child0, child1]);
+
+                        result.pos = node.first_token().map(|token| token.location.clone());
+
+                        Ok(result)
                     }
                     ExpressionTypeP::Assignment => 
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
-                        let child1 = Expression::from_parse_tree_node(children[1].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?.into_target();
+                        let child1 = Expression::from_parse_tree_node(&children[1], func)?;
 
                         Ok(Expression::new(ExpressionType::AssignmentExpression(None), None, vec![
                             child0,
@@ -215,8 +345,8 @@ impl Expression
                     },
                     ExpressionTypeP::Comma =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
-                        let child1 = Expression::from_parse_tree_node(children[1].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
+                        let child1 = Expression::from_parse_tree_node(&children[1], func)?;
 
                         Ok(Expression::new(ExpressionType::Comma, None, vec![
                             child0,
@@ -225,8 +355,13 @@ impl Expression
                     },
                     ExpressionTypeP::Cast =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
-                        let datatype = type_from_parse_tree(children[1].clone())?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
+                        let datatype = type_from_parse_tree(&children[1], &func.borrow().context)?;
+
+                        if datatype.align.is_some()
+                        {
+                            return compiler_error_loc("E0011", String::from("An alignment can only be given for a variable"), &children[1].first_token().map(|token| token.location.clone()));
+                        }
 
                         Ok(Expression::new(ExpressionType::Cast(datatype), None, vec![
                             child0
@@ -234,19 +369,23 @@ impl Expression
                     },
                     ExpressionTypeP::Ternary =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
-                        let child1 = Expression::from_parse_tree_node(children[1].clone(), func)?;
-                        let child2 = Expression::from_parse_tree_node(children[2].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
+                        let child1 = Expression::from_parse_tree_node(&children[1], func)?;
+                        let child2 = Expression::from_parse_tree_node(&children[2], func)?;
 
-                        Ok(Expression::new(ExpressionType::Ternary, None, vec![
+                        let mut result = Expression::new(ExpressionType::Ternary, None, vec![
                             child0,
                             child1,
                             child2
-                        ]))
+                        ]);
+
+                        result.pos = node.first_token().map(|token| token.location.clone());
+
+                        Ok(result)
                     },
                     ExpressionTypeP::Reference =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
 
                         Ok(Expression::new(ExpressionType::UnaryOperation(OpCode::Ref, 1), None, vec![
                             child0
@@ -254,7 +393,7 @@ impl Expression
                     },
                     ExpressionTypeP::Dereference =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
 
                         Ok(Expression::new(ExpressionType::UnaryOperation(OpCode::Deref, -1), None, vec![
                             child0
@@ -262,7 +401,7 @@ impl Expression
                     },
                     ExpressionTypeP::DereferenceLeft =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
 
                         Ok(Expression::new(ExpressionType::DereferenceLeft, None, vec![
                             child0
@@ -272,45 +411,178 @@ impl Expression
                     {
                         let func_name = match &children[0]
                         {
-                            ParseTreeNode::Identifier(token) => token.data.clone(),
+                            ParseTreeNode::Identifier(token) => token.data.to_string(),
                             _ => {panic!("")}
                         };
 
+                        let hosted = HOSTED_BUILTINS.iter().any(|(name, ..)| *name == func_name);
+
+                        // The hosted builtins only have a signature when compiling for a hosted environment
+                        if hosted && !func.borrow().signatures.contains_key(&func_name)
+                        {
+                            let location = children[0].first_token().map(|token| token.location.clone());
+                            return compiler_error_loc("E0035", format!("'{}' needs the C library, so is not available with --freestanding", func_name), &location);
+                        }
+
+                        // Builtins take integers and give a result of the same type as the first
+                        if func_name.starts_with("__builtin_") && !hosted
+                        {
+                            let location = children[0].first_token().map(|token| token.location.clone());
+
+                            // Shuffles take two vectors followed by the constant lanes to pick from them
+                            if func_name == "__builtin_shuffle"
+                            {
+                                if children.len() < 4
+                                {
+                                    return compiler_error_loc("E0003", format!("'{}' takes two vectors and at least one lane, got {} arguments", func_name, children.len() - 1), &location);
+                                }
+
+                                let mut lanes = vec![];
+
+                                for child in &children[3..]
+                                {
+                                    lanes.push(evaluate_constant(child, &func.borrow().context)?);
+                                }
+
+                                let vectors = vec![
+                                    Expression::from_parse_tree_node(&children[1], func)?,
+                                    Expression::from_parse_tree_node(&children[2], func)?
+                                ];
+
+                                let mut result = Expression::new(ExpressionType::Shuffle(lanes), None, vectors);
+                                result.pos = location;
+
+                                return Ok(result);
+                            }
+
+                            let (opcode, arity) = match BUILTINS.iter().find(|(name, ..)| *name == func_name)
+                            {
+                                Some((_, opcode, arity)) => (*opcode, *arity),
+                                None =>
+                                {
+                                    let error = compiler_error_loc("E0002", format!("Unknown builtin '{}'", func_name), &location);
+                                    let known = BUILTINS.iter().map(|(name, ..)| *name).chain(HOSTED_BUILTINS.iter().map(|(name, ..)| *name));
+                                    return suggest(error, &func_name, known.chain(once("__builtin_shuffle")));
+                                }
+                            };
+
+                            if children.len() - 1 != arity
+                            {
+                                return compiler_error_loc("E0003", format!("'{}' takes {} argument{}, got {}", func_name, arity, if arity == 1 {""} else {"s"}, children.len() - 1), &location);
+                            }
+
+                            let mut arguments = vec![];
+
+                            for child in &children[1..]
+                            {
+                                arguments.push(Expression::from_parse_tree_node(child, func)?);
+                            }
+
+                            let mode = if arity == 1 {ExpressionType::UnaryOperation(opcode, 0)} else {ExpressionType::BinaryExpression(opcode)};
+
+                            let mut result = Expression::new(mode, None, arguments);
+                            result.pos = location;
+
+                            return Ok(result);
+                        }
+
                         let mut new_children = vec![];
 
                         for child in &children[1..children.len()]
                         {
-                            new_children.push(Expression::from_parse_tree_node(child.clone(), func)?);
+                            new_children.push(Expression::from_parse_tree_node(child, func)?);
+                        }
+
+                        // An assertion tests its argument as a condition, so it may be any integer or pointer
+                        if func_name == "__builtin_assert" && new_children.len() == 1
+                        {
+                            let condition = new_children.remove(0);
+                            let pos = condition.pos.clone();
+
+                            let mut not = Expression::new(ExpressionType::LogicalNot, None, vec![condition]);
+                            not.pos = pos.clone();
+
+                            let mut test = Expression::new(ExpressionType::LogicalNot, None, vec![not]);
+                            test.pos = pos;
+
+                            new_children.push(test);
+                        }
+
+                        let location = children[0].first_token().map(|token| token.location.clone());
+
+                        // Functions in the library are checked against their signature, wherever they are defined
+                        let signature = func.borrow().signatures.get(&func_name).cloned();
+
+                        if let Some(signature) = signature
+                        {
+                            if signature.interrupt
+                            {
+                                return compiler_error_loc("E0038", format!("'{}' is an interrupt handler, which cannot be called", func_name), &location);
+                            }
+
+                            let arity = signature.arguments.len();
+                            let required = signature.defaults.iter().filter(|default| default.is_none()).count();
+
+                            if new_children.len() < required || new_children.len() > arity
+                            {
+                                let expected = if required == arity {format!("{}", arity)} else {format!("{} to {}", required, arity)};
+                                return compiler_error_loc("E0003", format!("'{}' takes {} argument{}, got {}", func_name, expected, if arity == 1 {""} else {"s"}, new_children.len()), &location);
+                            }
+
+                            // Arguments left out of the call take their default values
+                            for i in new_children.len()..arity
+                            {
+                                let default = Literal::new(signature.defaults[i].unwrap(), signature.arguments[i]);
+                                new_children.push(Expression::new(ExpressionType::IntegerLiteral, Some(Value::Literal(default)), vec![]));
+                            }
+                        }
+                        else
+                        {
+                            let mut names: Vec<String> = func.borrow().signatures.keys().cloned().collect();
+                            names.sort();
+
+                            let error = compiler_error_loc("E0043", format!("Call to undeclared function '{}'", func_name), &location);
+                            return suggest(error, &func_name, names.iter().map(|name| name.as_str()));
                         }
 
-                        Ok(Expression::new(ExpressionType::FunctionCall, Some(Value::Label(func_name)), new_children))
+                        let mut result = Expression::new(ExpressionType::FunctionCall, Some(Value::Label(func_name)), new_children);
+                        result.pos = location;
+
+                        Ok(result)
                     },
                     ExpressionTypeP::LogicalAnd =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
-                        let child1 = Expression::from_parse_tree_node(children[1].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
+                        let child1 = Expression::from_parse_tree_node(&children[1], func)?;
 
-                        Ok(Expression::new(ExpressionType::LogicalAnd, None, vec![
+                        let mut result = Expression::new(ExpressionType::LogicalAnd, None, vec![
                             child0,
                             child1
-                        ]))
+                        ]);
+
+                        result.pos = node.first_token().map(|token| token.location.clone());
+
+                        Ok(result)
                     },
                     ExpressionTypeP::LogicalOr =>
                     {
-                        let child0 = Expression::from_parse_tree_node(children[0].clone(), func)?;
-                        let child1 = Expression::from_parse_tree_node(children[1].clone(), func)?;
+                        let child0 = Expression::from_parse_tree_node(&children[0], func)?;
+                        let child1 = Expression::from_parse_tree_node(&children[1], func)?;
 
-                        Ok(Expression::new(ExpressionType::LogicalOr, None, vec![
+                        let mut result = Expression::new(ExpressionType::LogicalOr, None, vec![
                             child0,
                             child1
-                        ]))
+                        ]);
+
+                        result.pos = node.first_token().map(|token| token.location.clone());
+
+                        Ok(result)
                     },
-                    default => {panic!("{:?}", default);}
                 }
             },
             default =>
             {
-                expected_got_error("an expression", default.clone())
+                expected_got_error("an expression", default)
             }
         }
     }
@@ -320,15 +592,17 @@ impl Expression
     {
         match self.mode.clone()
         {
-            ExpressionType::IntegerLiteral | ExpressionType::Identifier=> {},
+            ExpressionType::IntegerLiteral | ExpressionType::StringLiteral | ExpressionType::Identifier=> {},
             ExpressionType::UnaryMinus =>
             {
                 self.children[0].render(func)?;
                 let val0 = self.children[0].value(func)?;
+                check_not_struct(&val0, "in arithmetic", &self.pos)?;
+                let val0 = promote_bool(func, val0, DataType::new(NonPtrType::I32, 0, false));
 
                 let datatype = get_value_type(&val0).unwrap();
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), correct_type_references(datatype.clone())));
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), correct_type_references(datatype.clone())));
                 self.value = Some(value.clone());
 
                 func.borrow_mut().add_instruction(Instruction::new(OpCode::Sub, vec![
@@ -341,10 +615,12 @@ impl Expression
             {
                 self.children[0].render(func)?;
                 let val0 = self.children[0].value(func)?;
+                check_not_struct(&val0, "in arithmetic", &self.pos)?;
+                let val0 = promote_bool(func, val0, DataType::new(NonPtrType::I32, 0, false));
 
                 let datatype = get_value_type(&val0).unwrap();
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), correct_type_references(datatype.clone())));
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), correct_type_references(datatype.clone())));
                 self.value = Some(value.clone());
 
                 func.borrow_mut().add_instruction(Instruction::new(OpCode::Xor, vec![
@@ -361,116 +637,129 @@ impl Expression
                 let mut val0 = self.children[0].value(func)?;
                 let mut val1 = self.children[1].value(func)?;
 
-                let datatype = if !has_unknown_type(&val0) && has_unknown_type(&val1) // First is known
-                {
-                    get_value_type(&val0).unwrap()
-                }
-                else if has_unknown_type(&val0) && !has_unknown_type(&val1) // Second is known
+                check_not_struct(&val0, "in arithmetic or a comparison", &self.pos)?;
+                check_not_struct(&val1, "in arithmetic or a comparison", &self.pos)?;
+
+                let is_pointer_pair = self.check_pointer_operands(opcode, &val0, &val1)?;
+                self.check_vector_operands(opcode, &val0, &val1)?;
+
+                (val0, val1) = promote_bool_operands(func, opcode, val0, val1);
+
+                if branch_for_compare(opcode).is_some()
                 {
-                    get_value_type(&val1).unwrap()
+                    (val0, val1) = self.compare_operands(func, val0, val1);
                 }
-                else if !has_unknown_type(&val0) && !has_unknown_type(&val1) // Both Known
+
+                let mut datatype = common_type(&val0, &val1);
+
+                // Rotates give a value of the type being rotated, with the amount converted to it
+                if opcode == OpCode::Rotl || opcode == OpCode::Rotr
                 {
-                    if get_value_type(&val0).unwrap() == get_value_type(&val1).unwrap()
+                    datatype = correct_type_references(get_value_type(&val0).unwrap());
+
+                    if datatype.num_ptr > 0 || get_value_type(&val1).unwrap().num_ptr > 0
                     {
-                        get_value_type(&val0).unwrap()
+                        return builtin_type_error(opcode, if datatype.num_ptr > 0 {datatype} else {get_value_type(&val1).unwrap()}, &self.pos);
                     }
-                    else
+
+                    let amount_type = get_value_type(&val1).unwrap();
+
+                    if datatype.raw_type != NonPtrType::Unknown && amount_type.raw_type != NonPtrType::Unknown && correct_type_references(amount_type) != datatype
                     {
-                        DataType::new(NonPtrType::Unknown, 0, false)
+                        let amount = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), datatype));
+
+                        func.borrow_mut().add_instruction(Instruction::new(OpCode::Cast, vec![
+                            amount.clone(),
+                            val1
+                            ]));
+
+                        val1 = amount;
                     }
                 }
-                else
-                {
-                    DataType::new(NonPtrType::Unknown, 0, false)
-                };
 
                 val0 = attempt_mutate_type(val0, datatype.clone());
                 val1 = attempt_mutate_type(val1, datatype.clone());
+
+                // Subtracting pointers gives an element count, and comparing anything but vectors gives a bool
+                let result_type = if is_pointer_pair && opcode == OpCode::Sub
+                {
+                    DataType::new(NonPtrType::I64, 0, false)
+                }
+                else if !datatype.is_vector() && branch_for_compare(opcode).is_some()
+                {
+                    DataType::new(NonPtrType::Bool, 0, false)
+                }
+                else
+                {
+                    correct_type_references(datatype)
+                };
                 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), correct_type_references(datatype)));
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), result_type));
                 self.value = Some(value.clone());
 
-                func.borrow_mut().add_instruction(Instruction::new(opcode, vec![
-                    value,
-                    val0,
-                    val1,
-                    ]));
+                let instruction = checked_operation(func, opcode, value, val0, val1, &self.pos);
+                func.borrow_mut().add_instruction(instruction);
             },
             ExpressionType::ArrayAccess =>
             {
                 self.children[0].render(func)?;
-                self.children[1].render(func)?;
 
                 let val0 = self.children[0].value(func)?;
-                let mut val1 = self.children[1].value(func)?;
-
-                val1 = attempt_mutate_type(val1, DataType::new(NonPtrType::U64, 0, false));
-
-                let mut dt = get_value_type(&val0).unwrap();
-                dt.num_ptr -= 1;
-                
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), correct_type_references(dt)));
-                self.value = Some(value.clone());
 
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Array, vec![
-                    value,
-                    val0,
-                    val1,
-                    ]));
+                self.render_element(func, val0)?;
             },
             ExpressionType::AssignmentExpression(operation) =>
             {
-                self.children[0].render(func)?;
-                self.children[1].render(func)?;
+                // Assigning to a lane of a vector replaces that lane of the vector
+                if self.children[0].mode == ExpressionType::ArrayAccess
+                {
+                    self.children[0].children[0].render(func)?;
 
-                let mut val0 = self.children[0].value(func)?;
-                let mut val1 = self.children[1].value(func)?;
+                    let base = self.children[0].children[0].value(func)?;
 
-                let datatype = if !has_unknown_type(&val0) && has_unknown_type(&val1) // First is known
-                {
-                    get_value_type(&val0).unwrap()
+                    if correct_type_references(get_value_type(&base).unwrap()).is_vector()
+                    {
+                        return self.render_lane_assignment(func, base, operation);
+                    }
+
+                    self.children[0].render_element(func, base)?;
                 }
-                else if has_unknown_type(&val0) && !has_unknown_type(&val1) // Second is known
+                else
                 {
-                    get_value_type(&val1).unwrap()
+                    self.children[0].render(func)?;
                 }
-                else if !has_unknown_type(&val0) && !has_unknown_type(&val1) // Both Known
+
+                self.children[1].render(func)?;
+
+                let mut val0 = self.children[0].value(func)?;
+                let mut val1 = self.children[1].value(func)?;
+
+                val1 = promote_bool(func, val1, correct_type_references(get_value_type(&val0).unwrap()));
+
+                match operation
                 {
-                    if get_value_type(&val0).unwrap() == get_value_type(&val1).unwrap()
+                    Some(_) =>
                     {
-                        get_value_type(&val0).unwrap()
-                    }
-                    else
-                    {
-                        DataType::new(NonPtrType::Unknown, 0, false)
-                    }
+                        check_not_struct(&val0, "in arithmetic", &self.pos)?;
+                        check_not_struct(&val1, "in arithmetic", &self.pos)?;
+                    },
+                    None => {val1 = implicit_conversion(func, val1, get_value_type(&val0).unwrap(), "in an assignment", &self.pos)?;}
                 }
-                else
-                {
-                    DataType::new(NonPtrType::Unknown, 0, false)
-                };
+
+                let datatype = common_type(&val0, &val1);
 
                 val0 = attempt_mutate_type(val0, datatype.clone());
                 val1 = attempt_mutate_type(val1, datatype.clone());
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), correct_type_references(datatype)));
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), correct_type_references(datatype)));
                 self.value = Some(value.clone());
 
                 match operation
                 {
                     Some(opcode) =>
                     {
-                        func.borrow_mut().add_instruction(Instruction::new(opcode, vec![
-                            val0.clone(),
-                            val0.clone(),
-                            val1,
-                            ]));
-                        
-                        func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
-                            value.clone(),
-                            val0.clone()
-                            ]));
+                        update_in_place(func, opcode, &val0, val1, &self.pos);
+                        copy_value(func, &value, &val0);
                     },
                     None =>
                     {
@@ -493,33 +782,28 @@ impl Expression
                 self.children[0].render(func)?;
 
                 let val0 = self.children[0].value(func)?;
+                check_not_struct(&val0, "in arithmetic", &self.pos)?;
 
-                func.borrow_mut().add_instruction(Instruction::new(opcode, vec![
-                    val0.clone(),
-                    val0.clone(),
-                    Value::Literal(Literal::new(1, get_value_type(&val0).unwrap())),
-                    ]));
+                step_value(func, opcode, &val0, &self.pos);
+
+                // Copy the new value out so later changes to the variable in the same expression do
+                // not change it
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), correct_type_references(get_value_type(&val0).unwrap())));
+                copy_value(func, &value, &val0);
 
-                self.value = Some(val0)
+                self.value = Some(value)
             },
             ExpressionType::PostExpression(opcode) =>
             {
                 self.children[0].render(func)?;
 
                 let val0 = self.children[0].value(func)?;
+                check_not_struct(&val0, "in arithmetic", &self.pos)?;
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), correct_type_references(get_value_type(&val0).unwrap())));
-
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
-                    value.clone(),
-                    val0.clone(),
-                    ]));
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), correct_type_references(get_value_type(&val0).unwrap())));
+                copy_value(func, &value, &val0);
 
-                func.borrow_mut().add_instruction(Instruction::new(opcode, vec![
-                    val0.clone(),
-                    val0.clone(),
-                    Value::Literal(Literal::new(1, get_value_type(&val0).unwrap())),
-                    ]));
+                step_value(func, opcode, &val0, &self.pos);
 
                 self.value = Some(value)
             },
@@ -537,7 +821,23 @@ impl Expression
 
                 let corrected_type = correct_type_references(datatype.clone());
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), corrected_type.clone()));
+                // Vectors can only be converted to vectors with as many lanes or of the same size, a
+                // literal converted to a vector fills every lane
+                let source_type = correct_type_references(get_value_type(&val0).unwrap());
+
+                if source_type.is_struct() || corrected_type.is_struct()
+                {
+                    return compiler_error_loc("E0040", format!("Cannot cast a '{}' to a '{}'", source_type, corrected_type), &self.pos);
+                }
+
+                if (source_type.is_vector() || corrected_type.is_vector()) && source_type.raw_type != NonPtrType::Unknown &&
+                    !(source_type.is_vector() && corrected_type.is_vector() &&
+                        (source_type.lanes() == corrected_type.lanes() || vector_bits(source_type) == vector_bits(corrected_type)))
+                {
+                    return compiler_error_loc("E0020", format!("Cannot cast a '{}' to a '{}'", source_type, corrected_type), &self.pos);
+                }
+
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), corrected_type.clone()));
 
                 val0 = attempt_mutate_type(val0, corrected_type);
 
@@ -550,21 +850,15 @@ impl Expression
             },
             ExpressionType::Ternary =>
             {
-                let body = func.borrow_mut().get_label();
-                let clause = func.borrow_mut().get_label();
-                let exit = func.borrow_mut().get_label();
+                let body = func.borrow_mut().get_label_at("ternary.true", &self.pos);
+                let clause = func.borrow_mut().get_label_at("ternary.false", &self.pos);
+                let exit = func.borrow_mut().get_label_at("ternary.end", &self.pos);
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), DataType::new(NonPtrType::Unknown, 0, false)));
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), DataType::new(NonPtrType::Unknown, 0, false)));
                 self.value = Some(value.clone());
 
-                self.children[0].render(func)?;
-
-                // Perform the comparison
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Bne, vec![
-                    self.children[0].value(func)?, 
-                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 0, false))),
-                    Value::Label(body.clone()),
-                    Value::Label(clause.clone())]));
+                // Test the condition
+                self.children[0].render_condition(func, &body, &clause)?;
 
                 // Place the body label
                 func.borrow_mut().place_label_here(body.clone());
@@ -592,30 +886,25 @@ impl Expression
                 // Place the exit label
                 func.borrow_mut().place_label_here(exit.clone());
             },
-            ExpressionType::LogicalNot =>
+            ExpressionType::LogicalNot | ExpressionType::LogicalAnd | ExpressionType::LogicalOr =>
             {
-                let body = func.borrow_mut().get_label();
-                let clause = func.borrow_mut().get_label();
-                let exit = func.borrow_mut().get_label();
+                let body = func.borrow_mut().get_label_at("logical.true", &self.pos);
+                let clause = func.borrow_mut().get_label_at("logical.false", &self.pos);
+                let exit = func.borrow_mut().get_label_at("logical.end", &self.pos);
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), DataType::new(NonPtrType::Unknown, 0, false)));
-                self.value = Some(value.clone());
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), DataType::new(NonPtrType::Bool, 0, false)));
 
-                self.children[0].render(func)?;
+                // Test the whole expression as a condition
+                self.render_condition(func, &body, &clause)?;
 
-                // Perform the comparison
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Bne, vec![
-                    self.children[0].value(func)?, 
-                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 0, false))),
-                    Value::Label(body.clone()),
-                    Value::Label(clause.clone())]));
+                self.value = Some(value.clone());
 
                 // Place the body label
                 func.borrow_mut().place_label_here(body.clone());
 
                 func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
                     value.clone(),
-                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 0, false))),
+                    Value::Literal(Literal::new(1, DataType::new(NonPtrType::Bool, 0, false))),
                     ]));
 
                 // Add a jump statement to skip the clause
@@ -626,22 +915,74 @@ impl Expression
 
                 func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
                     value,
-                    Value::Literal(Literal::new(1, DataType::new(NonPtrType::Unknown, 0, false))),
+                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Bool, 0, false))),
                     ]));
 
                 // Place the exit label
                 func.borrow_mut().place_label_here(exit.clone());
             },
+            // Taking the address of a field computes the address without loading it
+            ExpressionType::UnaryOperation(OpCode::Ref, _) if matches!(self.children[0].mode, ExpressionType::Member(..)) =>
+            {
+                self.value = Some(self.children[0].render_member_address(func)?);
+            },
+            ExpressionType::UnaryOperation(OpCode::Ref, _) if matches!(self.children[0].mode, ExpressionType::Global(..)) =>
+            {
+                self.value = Some(self.children[0].render_struct_address(func, &self.pos)?);
+            },
+            // Taking the address of an array element computes the address without loading it
+            ExpressionType::UnaryOperation(OpCode::Ref, _) if self.children[0].mode == ExpressionType::ArrayAccess =>
+            {
+                let element = &mut self.children[0];
+
+                element.children[0].render(func)?;
+                element.children[1].render(func)?;
+
+                let val0 = element.children[0].value(func)?;
+                let mut val1 = element.children[1].value(func)?;
+
+                if correct_type_references(get_value_type(&val0).unwrap()).is_vector()
+                {
+                    return compiler_error_loc("E0022", String::from("Cannot take the address of a lane of a vector"), &self.pos);
+                }
+
+                val1 = attempt_mutate_type(val1, DataType::new(NonPtrType::U64, 0, false));
+
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), correct_type_references(get_value_type(&val0).unwrap())));
+                self.value = Some(value.clone());
+
+                func.borrow_mut().add_instruction(Instruction::new(OpCode::Index, vec![
+                    value,
+                    val0,
+                    val1,
+                    ]));
+            },
             ExpressionType::UnaryOperation(opcode, delta) =>
             {
                 self.children[0].render(func)?;
 
                 let val0 = self.children[0].value(func)?;
 
+                if delta == 0
+                {
+                    check_not_struct(&val0, "in arithmetic", &self.pos)?;
+                }
+
+                if delta == 0 && get_value_type(&val0).unwrap().num_ptr > 0
+                {
+                    return builtin_type_error(opcode, get_value_type(&val0).unwrap(), &self.pos);
+                }
+
                 let mut datatype = get_value_type(&val0).unwrap();
                 datatype.num_ptr = (datatype.num_ptr as isize + delta) as usize;
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), datatype.clone()));
+                // The result of a builtin is a new value, even when its argument is a reference
+                if delta == 0
+                {
+                    datatype = correct_type_references(datatype);
+                }
+
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), datatype.clone()));
 
                 func.borrow_mut().add_instruction(Instruction::new(opcode, vec![
                     value.clone(),
@@ -650,140 +991,901 @@ impl Expression
 
                 self.value = Some(value);
             },
-            ExpressionType::DereferenceLeft =>
+            ExpressionType::Shuffle(lanes) =>
             {
                 self.children[0].render(func)?;
+                self.children[1].render(func)?;
+
                 let mut val0 = self.children[0].value(func)?;
+                let mut val1 = self.children[1].value(func)?;
 
-                let mut datatype = get_value_type(&val0).unwrap().clone();
-                datatype.is_ref = true;
-                datatype.num_ptr -= 1;
+                let datatype = correct_type_references(common_type(&val0, &val1));
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), datatype.clone()));
+                let (bits, signed, count) = match datatype.raw_type
+                {
+                    NonPtrType::Vector(bits, signed, count) if datatype.num_ptr == 0 => (bits, signed, count as i128),
+                    _ =>
+                    {
+                        return compiler_error_loc("E0023", format!("'__builtin_shuffle' takes two vectors of the same type, got '{}' and '{}'", get_value_type(&val0).unwrap(), get_value_type(&val1).unwrap()), &self.pos);
+                    }
+                };
 
-                val0 = attempt_mutate_type(val0, datatype);
+                if !lanes.len().is_power_of_two() || lanes.len() > MAX_VECTOR_LANES
+                {
+                    return compiler_error_loc("E0022", format!("'__builtin_shuffle' must pick a power of two lanes, up to {}, got {}", MAX_VECTOR_LANES, lanes.len()), &self.pos);
+                }
 
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Cast, vec![
-                    value.clone(),
-                    val0.clone()
-                    ]));
+                // Lanes are numbered through the first vector and then the second
+                if let Some(lane) = lanes.iter().find(|lane| **lane < 0 || **lane >= 2 * count)
+                {
+                    return compiler_error_loc("E0022", format!("Lane {} is out of range for shuffling two '{}' vectors", lane, datatype), &self.pos);
+                }
 
-                self.value = Some(value);
-            },
-            ExpressionType::FunctionCall =>
-            {
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), DataType::new(NonPtrType::Unknown, 0, false)));
+                val0 = attempt_mutate_type(val0, datatype);
+                val1 = attempt_mutate_type(val1, datatype);
 
-                let l = self.children.len();
-                for arg in &mut self.children[0..l]
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), DataType::new(NonPtrType::Vector(bits, signed, lanes.len() as u16), 0, false)));
+                self.value = Some(value.clone());
+
+                let mut arguments = vec![value, val0, val1];
+
+                for lane in lanes
+                {
+                    arguments.push(Value::Literal(Literal::new(lane, DataType::new(NonPtrType::I32, 0, false))));
+                }
+
+                func.borrow_mut().add_instruction(Instruction::new(OpCode::Shuffle, arguments));
+            },
+            // A field or global variable is read by loading it from its address, or written through
+            // a reference to it
+            ExpressionType::Member(_, _, target) | ExpressionType::Global(_, target) =>
+            {
+                let pos = self.pos.clone();
+                let address = self.render_struct_address(func, &pos)?;
+
+                let mut datatype = get_value_type(&address).unwrap();
+                datatype.num_ptr -= 1;
+                datatype.is_ref = target;
+
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), datatype));
+
+                func.borrow_mut().add_instruction(Instruction::new(if target {OpCode::Cast} else {OpCode::Deref}, vec![
+                    value.clone(),
+                    address
+                    ]));
+
+                self.value = Some(value);
+            },
+            ExpressionType::DereferenceLeft =>
+            {
+                self.children[0].render(func)?;
+                let mut val0 = self.children[0].value(func)?;
+
+                let mut datatype = get_value_type(&val0).unwrap().clone();
+                datatype.is_ref = true;
+                datatype.num_ptr -= 1;
+
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), datatype.clone()));
+
+                val0 = attempt_mutate_type(val0, datatype);
+
+                func.borrow_mut().add_instruction(Instruction::new(OpCode::Cast, vec![
+                    value.clone(),
+                    val0.clone()
+                    ]));
+
+                self.value = Some(value);
+            },
+            ExpressionType::FunctionCall =>
+            {
+                // Without a signature, for a function outside the library, the types are worked
+                // out from how the call is used
+                let signature = match &self.value
+                {
+                    Some(Value::Label(name)) => func.borrow().signatures.get(name).cloned(),
+                    _ => None
+                };
+
+                let return_type = signature.as_ref().map(|signature| correct_type_references(signature.return_type))
+                    .unwrap_or_else(|| DataType::new(NonPtrType::Unknown, 0, false));
+
+                let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), return_type));
+                let call_pos = self.pos.clone();
+
+                let mut arguments = vec![];
+
+                for (i, arg) in self.children.iter_mut().enumerate()
                 {
                     arg.render(func)?;
 
-                    // Push an argument
+                    let mut argument = arg.value(func)?;
+
+                    if let (Some(signature), Some(Value::Label(name))) = (&signature, &self.value)
+                    {
+                        argument = implicit_conversion(func, argument, signature.arguments[i], &format!("for argument {} of '{}'", i + 1, name), &arg.pos.clone().or_else(|| call_pos.clone()))?;
+                    }
+
+                    arguments.push(argument);
+                }
+
+                // Push the arguments only once every one has been evaluated, so the arguments of a
+                // call made by an argument are not mixed in with them
+                for argument in arguments
+                {
                     func.borrow_mut().add_instruction(Instruction::new(OpCode::Push, vec![
-                        arg.value(func)?]));
+                        argument]));
                 }
 
-                // Call the function
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Call, vec![
-                    value.clone(),
-                    self.value.clone().unwrap()]));
+                // Call the function, a void function gives no value to store
+                let call_arguments = if return_type.is_void() {vec![self.value.clone().unwrap()]} else {vec![value.clone(), self.value.clone().unwrap()]};
+                func.borrow_mut().add_instruction(Instruction::new(OpCode::Call, call_arguments));
 
                 self.value = Some(value.clone());
 
             },
+        }
+
+        Ok(())
+    }
+
+    /// Render the expression as a condition, branching to the first label if it is true and to
+    /// the second if it is false
+    ///
+    /// Integers are true when nonzero and pointers are true when not null, comparisons branch
+    /// directly on the compared values and logical operators short circuit, so no 0 or 1 value
+    /// is ever produced just to be tested
+    pub fn render_condition(&mut self, func: &RefCell<&mut Function>, if_true: &str, if_false: &str) -> Result<(), Error>
+    {
+        match self.mode.clone()
+        {
+            ExpressionType::LogicalNot =>
+            {
+                self.children[0].render_condition(func, if_false, if_true)?;
+            },
             ExpressionType::LogicalAnd =>
             {
-                let body = func.borrow_mut().get_label();
-                let clause = func.borrow_mut().get_label();
-                let exit = func.borrow_mut().get_label();
+                let next = func.borrow_mut().get_label_at("and.rhs", &self.pos);
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), DataType::new(NonPtrType::Unknown, 0, false)));
-                self.value = Some(value.clone());
+                self.children[0].render_condition(func, &next, if_false)?;
+                func.borrow_mut().place_label_here(next);
+                self.children[1].render_condition(func, if_true, if_false)?;
+            },
+            ExpressionType::LogicalOr =>
+            {
+                let next = func.borrow_mut().get_label_at("or.rhs", &self.pos);
 
+                self.children[0].render_condition(func, if_true, &next)?;
+                func.borrow_mut().place_label_here(next);
+                self.children[1].render_condition(func, if_true, if_false)?;
+            },
+            ExpressionType::BinaryExpression(opcode) if branch_for_compare(opcode).is_some() =>
+            {
                 self.children[0].render(func)?;
+                self.children[1].render(func)?;
 
-                // Perform the comparison
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Bne, vec![
-                    self.children[0].value(func)?, 
-                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 0, false))),
-                    Value::Label(body.clone()),
-                    Value::Label(clause.clone())]));
+                let val0 = self.children[0].value(func)?;
+                let val1 = self.children[1].value(func)?;
 
-                // Place the body label
-                func.borrow_mut().place_label_here(body.clone());
+                check_not_struct(&val0, "in a comparison", &self.pos)?;
+                check_not_struct(&val1, "in a comparison", &self.pos)?;
 
-                self.children[1].render(func)?;
+                self.check_pointer_operands(opcode, &val0, &val1)?;
+                self.check_vector_operands(opcode, &val0, &val1)?;
 
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
-                    value.clone(),
-                    self.children[1].value(func)?,
-                    ]));
+                let (val0, val1) = promote_bool_operands(func, opcode, val0, val1);
+                let (val0, val1) = self.compare_operands(func, val0, val1);
 
-                // Add a jump statement to skip the clause
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(exit.clone())]));
+                let datatype = common_type(&val0, &val1);
 
-                // Place the clause label
-                func.borrow_mut().place_label_here(clause.clone());
+                func.borrow_mut().add_instruction(Instruction::new(branch_for_compare(opcode).unwrap(), vec![
+                    attempt_mutate_type(val0, datatype),
+                    attempt_mutate_type(val1, datatype),
+                    Value::Label(String::from(if_true)),
+                    Value::Label(String::from(if_false))]));
+            },
+            _ =>
+            {
+                self.render(func)?;
 
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
+                let value = self.value(func)?;
+                let datatype = get_value_type(&value).unwrap_or(DataType::new(NonPtrType::Unknown, 0, false));
+
+                if correct_type_references(datatype).is_vector()
+                {
+                    return compiler_error_loc("E0021", format!("Cannot use a vector ('{}') as a condition", correct_type_references(datatype)), &self.pos);
+                }
+
+                check_not_struct(&value, "as a condition", &self.pos)?;
+
+                if datatype.raw_type == NonPtrType::Void && datatype.num_ptr == 0
+                {
+                    return compiler_error_loc("E0021", String::from("Cannot use a void expression as a condition"), &self.pos);
+                }
+
+                // Compare against zero (or null for pointers) of the same type
+                let zero = if datatype.raw_type == NonPtrType::Unknown
+                {
+                    DataType::new(NonPtrType::Unknown, 0, false)
+                }
+                else
+                {
+                    correct_type_references(datatype)
+                };
+
+                func.borrow_mut().add_instruction(Instruction::new(OpCode::Bne, vec![
                     value,
-                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 0, false))),
-                    ]));
+                    Value::Literal(Literal::new(0, zero)),
+                    Value::Label(String::from(if_true)),
+                    Value::Label(String::from(if_false))]));
+            }
+        }
 
-                // Place the exit label
-                func.borrow_mut().place_label_here(exit.clone());
-            },
-            ExpressionType::LogicalOr =>
+        Ok(())
+    }
+
+    /// Get the value of the expression if it is an integer constant
+    pub fn constant_value(&self) -> Option<i128>
+    {
+        match (&self.mode, &self.value)
+        {
+            (ExpressionType::IntegerLiteral, Some(Value::Literal(literal))) if literal.datatype.num_ptr == 0 => Some(literal.value),
+            _ => None
+        }
+    }
+
+    /// Render an element access on a base which has already been rendered, reading a lane of a
+    /// vector or the element a pointer points to
+    fn render_element(&mut self, func: &RefCell<&mut Function>, val0: Value) -> Result<(), Error>
+    {
+        self.children[1].render(func)?;
+
+        let mut val1 = self.children[1].value(func)?;
+
+        val1 = attempt_mutate_type(val1, DataType::new(NonPtrType::U64, 0, false));
+
+        let mut dt = get_value_type(&val0).unwrap();
+
+        if let Some(element_type) = correct_type_references(dt).element_type()
+        {
+            self.check_lane(correct_type_references(dt), &val1)?;
+
+            let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), element_type));
+            self.value = Some(value.clone());
+
+            func.borrow_mut().add_instruction(Instruction::new(OpCode::Extract, vec![
+                value,
+                val0,
+                val1,
+                ]));
+
+            return Ok(());
+        }
+
+        if dt.num_ptr == 0
+        {
+            return compiler_error_loc("E0022", format!("Cannot index into a value of type '{}'", correct_type_references(dt)), &self.pos);
+        }
+
+        dt.num_ptr -= 1;
+
+        let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), correct_type_references(dt)));
+        self.value = Some(value.clone());
+
+        func.borrow_mut().add_instruction(Instruction::new(OpCode::Array, vec![
+            value,
+            val0,
+            val1,
+            ]));
+
+        Ok(())
+    }
+
+    /// Render an assignment to a lane of a vector, the vector itself has already been rendered
+    fn render_lane_assignment(&mut self, func: &RefCell<&mut Function>, base: Value, operation: Option<OpCode>) -> Result<(), Error>
+    {
+        let vector_type = correct_type_references(get_value_type(&base).unwrap());
+        let element_type = vector_type.element_type().unwrap();
+
+        self.children[0].children[1].render(func)?;
+        let index = attempt_mutate_type(self.children[0].children[1].value(func)?, DataType::new(NonPtrType::U64, 0, false));
+
+        self.check_lane(vector_type, &index)?;
+
+        self.children[1].render(func)?;
+        let mut val1 = attempt_mutate_type(self.children[1].value(func)?, element_type);
+
+        let value_type = correct_type_references(get_value_type(&val1).unwrap());
+
+        if value_type.raw_type != NonPtrType::Unknown && value_type != element_type
+        {
+            return compiler_error_loc("E0023", format!("Cannot assign a '{}' to a lane of a '{}'", value_type, vector_type), &self.pos);
+        }
+
+        // Compound assignments operate on the current value of the lane
+        if let Some(opcode) = operation
+        {
+            let lane = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), element_type));
+
+            func.borrow_mut().add_instruction(Instruction::new(OpCode::Extract, vec![
+                lane.clone(),
+                base.clone(),
+                index.clone(),
+                ]));
+
+            func.borrow_mut().add_instruction(Instruction::new(opcode, vec![
+                lane.clone(),
+                lane.clone(),
+                val1,
+                ]));
+
+            val1 = lane;
+        }
+
+        func.borrow_mut().add_instruction(Instruction::new(OpCode::Insert, vec![
+            base.clone(),
+            base,
+            val1.clone(),
+            index,
+            ]));
+
+        let value = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), element_type));
+        self.value = Some(value.clone());
+
+        func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
+            value,
+            val1,
+            ]));
+
+        Ok(())
+    }
+
+    /// Render the address of the field a member access reads, from the address of the struct it
+    /// is a field of
+    fn render_member_address(&mut self, func: &RefCell<&mut Function>) -> Result<Value, Error>
+    {
+        let (field, arrow) = match &self.mode
+        {
+            ExpressionType::Member(field, arrow, _) => (field.clone(), *arrow),
+            _ => unreachable!()
+        };
+
+        // Through a pointer the struct is wherever it points, otherwise it is the struct itself
+        let base = if arrow
+        {
+            self.children[0].render(func)?;
+            self.children[0].value(func)?
+        }
+        else
+        {
+            self.children[0].render_struct_address(func, &self.pos)?
+        };
+
+        let pointer_type = correct_type_references(get_value_type(&base).unwrap());
+
+        let structure = match pointer_type.raw_type
+        {
+            NonPtrType::Struct(structure) if pointer_type.num_ptr == 1 => structure,
+            _ if arrow => {return compiler_error_loc("E0040", format!("'->' needs a pointer to a struct, got a '{}'", pointer_type), &self.pos);},
+            _ =>
             {
-                let body = func.borrow_mut().get_label();
-                let clause = func.borrow_mut().get_label();
-                let exit = func.borrow_mut().get_label();
+                let mut datatype = pointer_type;
+                datatype.num_ptr = datatype.num_ptr.saturating_sub(1);
 
-                let value = Value::Symbol(Symbol::new(func.borrow_mut().get_register(), DataType::new(NonPtrType::Unknown, 0, false)));
-                self.value = Some(value.clone());
+                return compiler_error_loc("E0040", format!("'.' needs a struct, got a '{}'", datatype), &self.pos);
+            }
+        };
 
-                self.children[0].render(func)?;
+        let (index, mut datatype) = match structure.field(&field)
+        {
+            Some(found) => found,
+            None =>
+            {
+                let error = compiler_error_loc("E0040", format!("'{}' has no field named '{}'", structure, field), &self.pos);
+                let fields = structure.fields();
 
-                // Perform the comparison
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Bne, vec![
-                    self.children[0].value(func)?, 
-                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 0, false))),
-                    Value::Label(body.clone()),
-                    Value::Label(clause.clone())]));
+                return suggest(error, &field, fields.iter().map(|(name, _)| name.as_str()));
+            }
+        };
 
-                // Place the body label
-                func.borrow_mut().place_label_here(body.clone());
+        datatype.num_ptr += 1;
 
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
-                    value.clone(),
-                    Value::Literal(Literal::new(1, DataType::new(NonPtrType::Unknown, 0, false))),
-                    ]));
+        let address = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), datatype));
 
-                // Add a jump statement to skip the clause
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(exit.clone())]));
+        func.borrow_mut().add_instruction(Instruction::new(OpCode::Member, vec![
+            address.clone(),
+            base,
+            Value::Literal(Literal::new(index as i128, DataType::new(NonPtrType::U32, 0, false)))
+            ]));
 
-                // Place the clause label
-                func.borrow_mut().place_label_here(clause.clone());
+        Ok(address)
+    }
+
+    /// Render the address of a struct whose field is read with '.', which must be held in a
+    /// variable, a global variable, a field, an element or the memory a pointer points to
+    fn render_struct_address(&mut self, func: &RefCell<&mut Function>, pos: &Option<FileLocation>) -> Result<Value, Error>
+    {
+        match self.mode
+        {
+            ExpressionType::Identifier =>
+            {
+                let variable = self.value(func)?;
 
+                let mut datatype = correct_type_references(get_value_type(&variable).unwrap());
+                datatype.num_ptr += 1;
+
+                let address = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), datatype));
+
+                func.borrow_mut().add_instruction(Instruction::new(OpCode::Ref, vec![
+                    address.clone(),
+                    variable
+                    ]));
+
+                Ok(address)
+            },
+            ExpressionType::Member(..) => self.render_member_address(func),
+            ExpressionType::Global(ref name, _) => Ok(GlobalVariable::lookup(&func.borrow().context.globals, name).unwrap().address()),
+            ExpressionType::UnaryOperation(OpCode::Deref, -1) | ExpressionType::DereferenceLeft =>
+            {
+                self.children[0].render(func)?;
+                self.children[0].value(func)
+            },
+            ExpressionType::ArrayAccess =>
+            {
+                self.children[0].render(func)?;
                 self.children[1].render(func)?;
 
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
-                    value.clone(),
-                    self.children[1].value(func)?,
+                let val0 = self.children[0].value(func)?;
+                let val1 = attempt_mutate_type(self.children[1].value(func)?, DataType::new(NonPtrType::U64, 0, false));
+
+                let address = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), correct_type_references(get_value_type(&val0).unwrap())));
+
+                func.borrow_mut().add_instruction(Instruction::new(OpCode::Index, vec![
+                    address.clone(),
+                    val0,
+                    val1
                     ]));
 
-                // Place the exit label
-                func.borrow_mut().place_label_here(exit.clone());
+                Ok(address)
             },
+            _ => compiler_error_loc("E0040", String::from("A field can only be read with '.' from a variable, a field, an element or a dereferenced pointer"), pos)
+        }
+    }
+
+    /// Check a constant lane index is within a vector
+    fn check_lane(&self, vector_type: DataType, index: &Value) -> Result<(), Error>
+    {
+        if let Value::Literal(lit) = index
+        {
+            if lit.value < 0 || lit.value >= vector_type.lanes() as i128
+            {
+                return compiler_error_loc("E0022", format!("Lane {} is out of range for a '{}'", lit.value, vector_type), &self.pos);
+            }
         }
 
         Ok(())
     }
 
+    /// Check the operands of a binary operation when either is a vector, both must be vectors of
+    /// the same type (or a literal, which fills every lane), and cannot be compared
+    fn check_vector_operands(&self, opcode: OpCode, val0: &Value, val1: &Value) -> Result<(), Error>
+    {
+        let type0 = correct_type_references(get_value_type(val0).unwrap());
+        let type1 = correct_type_references(get_value_type(val1).unwrap());
+
+        if !type0.is_vector() && !type1.is_vector()
+        {
+            return Ok(());
+        }
+
+        if branch_for_compare(opcode).is_some()
+        {
+            return compiler_error_loc("E0023", format!("Vectors cannot be compared ('{}' and '{}')", type0, type1), &self.pos);
+        }
+
+        if type0.raw_type != NonPtrType::Unknown && type1.raw_type != NonPtrType::Unknown && type0 != type1
+        {
+            return compiler_error_loc("E0023", format!("Mismatched types in a vector operation ('{}' and '{}')", type0, type1), &self.pos);
+        }
+
+        Ok(())
+    }
+
+    /// Check the operands of a binary operation when both are pointers, pointers may only be
+    /// subtracted from or compared with pointers to the same type, returns if both are pointers
+    fn check_pointer_operands(&self, opcode: OpCode, val0: &Value, val1: &Value) -> Result<bool, Error>
+    {
+        let (type0, type1) = match (get_value_type(val0), get_value_type(val1))
+        {
+            (Some(type0), Some(type1)) if type0.num_ptr > 0 && type1.num_ptr > 0 => (correct_type_references(type0), correct_type_references(type1)),
+            _ => {return Ok(false);}
+        };
+
+        // A null pointer takes the type of the other pointer
+        if type0.raw_type == NonPtrType::Unknown || type1.raw_type == NonPtrType::Unknown
+        {
+            return Ok(true);
+        }
+
+        let operation = if opcode == OpCode::Sub
+        {
+            "subtraction"
+        }
+        else if branch_for_compare(opcode).is_some()
+        {
+            "comparison"
+        }
+        else
+        {
+            return Ok(true);
+        };
+
+        if type0 != type1
+        {
+            return compiler_error_loc("E0023", format!("Mismatched pointee types in pointer {} ('{}' and '{}')", operation, type0, type1), &self.pos);
+        }
+
+        Ok(true)
+    }
+
+    /// Convert the operands of a comparison between integers of different types to a type which
+    /// holds every value of both, so a negative value is always less than an unsigned one, warning
+    /// when their signedness differs
+    fn compare_operands(&self, func: &RefCell<&mut Function>, val0: Value, val1: Value) -> (Value, Value)
+    {
+        let type0 = correct_type_references(get_value_type(&val0).unwrap());
+        let type1 = correct_type_references(get_value_type(&val1).unwrap());
+
+        let (bits0, bits1) = match (bits_of(type0.raw_type), bits_of(type1.raw_type))
+        {
+            (Some(bits0), Some(bits1)) if type0.num_ptr == 0 && type1.num_ptr == 0 && type0 != type1 => (bits0, bits1),
+            _ => {return (val0, val1);}
+        };
+
+        let datatype = if type0.is_signed() == type1.is_signed()
+        {
+            if bits0 >= bits1 {type0} else {type1}
+        }
+        else
+        {
+            // An unsigned value needs one more bit to be held as a signed value
+            let (signed_bits, unsigned_bits) = if type0.is_signed() {(bits0, bits1)} else {(bits1, bits0)};
+            let needed = signed_bits.max(unsigned_bits + 1);
+            let bits = [8, 16, 32, 64].iter().copied().find(|bits| *bits >= needed).unwrap_or(needed);
+
+            let datatype = DataType::new(NonPtrType::integer(bits as u16, true), 0, false);
+
+            func.borrow_mut().warnings.push(compiler_warning_loc("W0006",
+                format!("Comparison of a '{}' with a '{}' of different signedness, both are compared as a '{}'", type0, type1, datatype), &self.pos));
+
+            datatype
+        };
+
+        let convert = |value: Value, source_type: DataType|
+        {
+            if source_type == datatype
+            {
+                return value;
+            }
+
+            let result = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), datatype));
+
+            func.borrow_mut().add_instruction(Instruction::new(OpCode::Cast, vec![
+                result.clone(),
+                value
+                ]));
+
+            result
+        };
+
+        (convert(val0, type0), convert(val1, type1))
+    }
+
     /// Get the return value from an expression
     pub fn value(&self, _func: &RefCell<&mut Function>) -> Result<Value, Error>
     {
+        // A call to a void function can only be made as a statement of its own
+        if let (ExpressionType::FunctionCall, Some(datatype)) = (&self.mode, self.value.as_ref().and_then(get_value_type))
+        {
+            if datatype.is_void()
+            {
+                return compiler_error_loc("E0023", String::from("Cannot use the result of a call to a void function as a value"), &self.pos);
+            }
+        }
+
         Ok(self.value.clone().unwrap())
     }
-}
\ No newline at end of file
+}
+
+/// Get the variable an increment or decrement changes, writing through a pointer which is
+/// dereferenced rather than changing a copy of the value it points to
+fn step_target<'a>(node: &ParseTreeNode<'a>) -> ParseTreeNode<'a>
+{
+    match node
+    {
+        ParseTreeNode::Expression(ExpressionTypeP::Dereference, children) => ParseTreeNode::Expression(ExpressionTypeP::DereferenceLeft, children),
+        node => node.clone()
+    }
+}
+
+/// Widen a bool, such as the result of a comparison, to an integer type it is used as, leaving any
+/// other value as it is
+pub fn promote_bool(func: &RefCell<&mut Function>, value: Value, datatype: DataType) -> Value
+{
+    let source_type = correct_type_references(get_value_type(&value).unwrap());
+
+    if source_type.raw_type != NonPtrType::Bool || source_type.num_ptr > 0 || datatype.raw_type == NonPtrType::Bool
+    {
+        return value;
+    }
+
+    let result = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), datatype));
+
+    func.borrow_mut().add_instruction(Instruction::new(OpCode::Cast, vec![
+        result.clone(),
+        value
+        ]));
+
+    result
+}
+
+/// Widen any bool operand of a binary operation to the type of the other operand, or to an 'i32'
+/// when that is not an integer, so arithmetic on the results of comparisons counts as in C. Two
+/// bools compared with each other are left as they are
+fn promote_bool_operands(func: &RefCell<&mut Function>, opcode: OpCode, val0: Value, val1: Value) -> (Value, Value)
+{
+    let is_bool = |value: &Value| get_value_type(value).map(|datatype| datatype.raw_type == NonPtrType::Bool && datatype.num_ptr == 0).unwrap_or(false);
+
+    if branch_for_compare(opcode).is_some() && is_bool(&val0) && is_bool(&val1)
+    {
+        return (val0, val1);
+    }
+
+    let operand_type = |other: &Value|
+    {
+        let datatype = correct_type_references(get_value_type(other).unwrap());
+
+        match bits_of(datatype.raw_type)
+        {
+            Some(_) if datatype.num_ptr == 0 && datatype.raw_type != NonPtrType::Bool => datatype,
+            _ => DataType::new(NonPtrType::I32, 0, false)
+        }
+    };
+
+    let val0 = promote_bool(func, val0, operand_type(&val1));
+    let val1 = promote_bool(func, val1, operand_type(&val0));
+
+    (val0, val1)
+}
+
+/// Convert a value to the type it is used as without a cast, such as the return type of a function,
+/// giving an error naming where it is used when the conversion could lose the value
+pub fn implicit_conversion(func: &RefCell<&mut Function>, value: Value, datatype: DataType, usage: &str, pos: &Option<FileLocation>) -> Result<Value, Error>
+{
+    check_struct_copy(&value, datatype, usage, pos)?;
+
+    let value = attempt_mutate_type(value, datatype);
+    let source_type = correct_type_references(get_value_type(&value).unwrap());
+    let datatype = correct_type_references(datatype);
+
+    if source_type == datatype
+    {
+        return Ok(value);
+    }
+
+    // A value without a known type, such as a negated literal, is moved into one of the type it is
+    // used as so the type is carried back to it when types are corrected
+    let known = source_type.raw_type != NonPtrType::Unknown;
+
+    if known && !converts_implicitly(source_type, datatype)
+    {
+        return compiler_error_loc("E0023", format!("Cannot implicitly convert a '{}' to a '{}' {}, cast it with 'as'", source_type, datatype, usage), pos);
+    }
+
+    let result = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), datatype));
+
+    func.borrow_mut().add_instruction(Instruction::new(if known {OpCode::Cast} else {OpCode::Mov}, vec![
+        result.clone(),
+        value
+        ]));
+
+    Ok(result)
+}
+
+/// Get the type both operands of a binary operation should take
+fn common_type(val0: &Value, val1: &Value) -> DataType
+{
+    if !has_unknown_type(val0) && has_unknown_type(val1) // First is known
+    {
+        get_value_type(val0).unwrap()
+    }
+    else if has_unknown_type(val0) && !has_unknown_type(val1) // Second is known
+    {
+        get_value_type(val1).unwrap()
+    }
+    else if !has_unknown_type(val0) && !has_unknown_type(val1) // Both Known
+    {
+        if get_value_type(val0).unwrap() == get_value_type(val1).unwrap()
+        {
+            get_value_type(val0).unwrap()
+        }
+        else
+        {
+            DataType::new(NonPtrType::Unknown, 0, false)
+        }
+    }
+    else
+    {
+        DataType::new(NonPtrType::Unknown, 0, false)
+    }
+}
+
+/// Add or subtract one from a variable in place for an increment or decrement, stepping a pointer
+/// by one element rather than one byte
+fn step_value(func: &RefCell<&mut Function>, opcode: OpCode, value: &Value, pos: &Option<FileLocation>)
+{
+    let datatype = correct_type_references(get_value_type(value).unwrap());
+
+    if datatype.num_ptr > 0
+    {
+        let offset = if opcode == OpCode::Sub {-1} else {1};
+
+        update_in_place(func, OpCode::Index, value, Value::Literal(Literal::new(offset, DataType::new(NonPtrType::I64, 0, false))), pos);
+    }
+    else
+    {
+        update_in_place(func, opcode, value, Value::Literal(Literal::new(1, datatype)), pos);
+    }
+}
+
+/// Copy the value of a variable into a register, loading it for a variable reached through a pointer
+fn copy_value(func: &RefCell<&mut Function>, dest: &Value, src: &Value)
+{
+    let opcode = if get_value_type(src).unwrap().is_ref {OpCode::Deref} else {OpCode::Mov};
+
+    func.borrow_mut().add_instruction(Instruction::new(opcode, vec![
+        dest.clone(),
+        src.clone(),
+        ]));
+}
+
+/// Apply an operation to a variable in place, loading and storing the value for a variable reached
+/// through a pointer
+fn update_in_place(func: &RefCell<&mut Function>, opcode: OpCode, target: &Value, operand: Value, pos: &Option<FileLocation>)
+{
+    let datatype = get_value_type(target).unwrap();
+
+    if datatype.is_ref
+    {
+        let current = Value::Symbol(Symbol::register(func.borrow_mut().get_register(), correct_type_references(datatype)));
+
+        copy_value(func, &current, target);
+
+        update_in_place(func, opcode, &current, operand, pos);
+
+        func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![
+            target.clone(),
+            current,
+            ]));
+    }
+    else
+    {
+        let instruction = checked_operation(func, opcode, target.clone(), target.clone(), operand, pos);
+        func.borrow_mut().add_instruction(instruction);
+    }
+}
+
+/// Build the instruction for an arithmetic operation, warning about a shift by a constant amount
+/// which is out of range or a division by a constant zero, and giving such an operation a fixed
+/// result in place of one LLVM leaves undefined
+fn checked_operation(func: &RefCell<&mut Function>, opcode: OpCode, dest: Value, val0: Value, val1: Value, pos: &Option<FileLocation>) -> Instruction
+{
+    let amount = match &val1
+    {
+        Value::Literal(literal) => literal.value,
+        _ => {return Instruction::new(opcode, vec![dest, val0, val1]);}
+    };
+
+    let datatype = correct_type_references(get_value_type(&dest).unwrap());
+    let element_type = datatype.element_type().unwrap_or(datatype);
+
+    match opcode
+    {
+        OpCode::Shl | OpCode::Shr if element_type.num_ptr == 0 =>
+        {
+            let bits = match bits_of(element_type.raw_type)
+            {
+                Some(bits) if amount < 0 || amount >= bits as i128 => bits,
+                _ => {return Instruction::new(opcode, vec![dest, val0, val1]);}
+            };
+
+            func.borrow_mut().warnings.push(compiler_warning_loc("W0004",
+                format!("Shift by {} is out of range for a '{}', which is {} bits wide", amount, element_type, bits), pos));
+
+            // Every bit is shifted out, leaving copies of the sign bit for a signed right shift
+            if opcode == OpCode::Shr && element_type.is_signed()
+            {
+                Instruction::new(opcode, vec![dest, val0, Value::Literal(Literal::new(bits as i128 - 1, datatype))])
+            }
+            else
+            {
+                Instruction::new(OpCode::Mov, vec![dest, Value::Literal(Literal::new(0, datatype))])
+            }
+        },
+        OpCode::Div | OpCode::Mod if amount == 0 =>
+        {
+            func.borrow_mut().warnings.push(compiler_warning_loc("W0005", String::from("Division by zero"), pos));
+
+            // Dividing by zero gives zero, leaving the whole of the value as the remainder
+            if opcode == OpCode::Div
+            {
+                Instruction::new(OpCode::Mov, vec![dest, Value::Literal(Literal::new(0, datatype))])
+            }
+            else
+            {
+                Instruction::new(OpCode::Mov, vec![dest, val0])
+            }
+        },
+        _ => Instruction::new(opcode, vec![dest, val0, val1])
+    }
+}
+
+/// Get the branch opcode matching a comparison opcode
+fn branch_for_compare(opcode: OpCode) -> Option<OpCode>
+{
+    match opcode
+    {
+        OpCode::Ceq => Some(OpCode::Beq),
+        OpCode::Cne => Some(OpCode::Bne),
+        OpCode::Clt => Some(OpCode::Blt),
+        OpCode::Cle => Some(OpCode::Ble),
+        OpCode::Cgt => Some(OpCode::Bgt),
+        OpCode::Cge => Some(OpCode::Bge),
+        _ => None
+    }
+}
+
+/// Error for a builtin given a value which is not an integer
+fn builtin_type_error<T>(opcode: OpCode, datatype: DataType, location: &Option<FileLocation>) -> Result<T, Error>
+{
+    let name = BUILTINS.iter().find(|(_, op, _)| *op == opcode).map(|(name, ..)| *name).unwrap_or_default();
+    compiler_error_loc("E0023", format!("'{}' requires integer arguments, got '{}'", name, datatype), location)
+}
+
+/// Check a value is not a struct, which cannot be used in the given way
+fn check_not_struct(value: &Value, usage: &str, pos: &Option<FileLocation>) -> Result<(), Error>
+{
+    let datatype = correct_type_references(get_value_type(value).unwrap());
+
+    if datatype.is_struct()
+    {
+        return compiler_error_loc("E0040", format!("Cannot use a '{}' {}", datatype, usage), pos);
+    }
+
+    Ok(())
+}
+
+/// Check a value copied into a place of the given type is a struct of the same type if either is a
+/// struct, as a struct is only ever copied whole
+pub fn check_struct_copy(value: &Value, datatype: DataType, usage: &str, pos: &Option<FileLocation>) -> Result<(), Error>
+{
+    let source_type = correct_type_references(get_value_type(value).unwrap());
+    let datatype = correct_type_references(datatype);
+
+    if (source_type.is_struct() || datatype.is_struct()) && source_type != datatype
+    {
+        let source = if source_type.raw_type == NonPtrType::Unknown {String::from("an integer")} else {format!("a '{}'", source_type)};
+        return compiler_error_loc("E0040", format!("Cannot use {} as a '{}' {}", source, datatype, usage), pos);
+    }
+
+    Ok(())
+}
+
+/// Get the total number of bits in a vector
+fn vector_bits(datatype: DataType) -> usize
+{
+    match datatype.raw_type
+    {
+        NonPtrType::Vector(bits, _, lanes) => bits as usize * lanes as usize,
+        _ => 0
+    }
+}
@@ -0,0 +1,154 @@
+use serde::{Serialize, Deserialize};
+
+use super::{Context, DataType, NonPtrType, Symbol, Value, type_from_parse_tree, evaluate_constant, wrap_constant, bits_of, compiler_error_loc};
+
+use crate::cli::Error;
+use crate::import::module_of;
+use crate::parser::{ParseTreeNode, ExpressionType};
+use crate::tokenizer::{FileLocation, Token};
+
+/// A variable declared at the top level of a library, which lives for the whole program and can be
+/// used by every function which does not have a variable of its own with the same name
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GlobalVariable
+{
+    pub name: String,
+    pub datatype: DataType,
+    pub value: i128, // Value the variable starts with, given to every lane of a vector, a struct always starting zeroed
+    pub pos: Option<FileLocation>, // Location of the name of the variable in the source
+    pub doc: Option<String>, // Text of the doc comment on the variable
+    pub internal: bool // Only visible within the module defining it
+}
+
+impl GlobalVariable
+{
+    /// Get the global variable of a library with the given name
+    pub fn lookup<'g>(globals: &'g [Self], name: &str) -> Option<&'g Self>
+    {
+        globals.iter().find(|global| global.name == name)
+    }
+
+    /// Get the global variable of a library a function of the given module reaches by a name, one
+    /// private to the module before a public one, giving an error at the name if it is only that of
+    /// variables private to other modules
+    pub fn resolve<'g>(globals: &'g [Self], name: &Token, module: Option<&str>) -> Result<Option<&'g Self>, Error>
+    {
+        let own = module.and_then(|module| Self::lookup(globals, &format!("{}.{}", module, name.data)));
+
+        if let Some(global) = own.or_else(|| Self::lookup(globals, &name.data))
+        {
+            return Ok(Some(global));
+        }
+
+        let private = globals.iter().find(|global| global.internal && global.name.split_once('.').map(|(_, short)| short) == Some(&*name.data));
+
+        match private
+        {
+            Some(global) => compiler_error_loc("E0034", format!("Global variable '{}' of module '{}' is private, it must be marked 'pub' to be used from other modules",
+                name.data, module_of(&global.name).unwrap_or_default()), &Some(name.location.clone())),
+            None => Ok(None)
+        }
+    }
+
+    /// Get the address of the variable, which is how the IR reaches it
+    pub fn address(&self) -> Value
+    {
+        let mut datatype = self.datatype;
+        datatype.num_ptr += 1;
+        datatype.align = None;
+
+        Value::Global(Symbol::new(self.name.clone(), datatype))
+    }
+}
+
+/// Declare every global variable of a library in its context, working out the value each starts
+/// with, which must be a constant expression. A global variable cannot share its name with a function, as both are
+/// symbols of the output. Those private to an imported module have been qualified with the name of
+/// the module, and are internal to it
+pub fn declare_globals(items: &[ParseTreeNode], context: &mut Context) -> Result<(), Error>
+{
+    let functions: Vec<&str> = items.iter().filter_map(|item| match item
+    {
+        ParseTreeNode::Function(children) => children[1].first_token().map(|token| &*token.data),
+        _ => None
+    }).collect();
+
+    let mut globals: Vec<GlobalVariable> = vec![];
+
+    for item in items
+    {
+        let children = match item
+        {
+            ParseTreeNode::GlobalVariable(children) => children,
+            _ => continue
+        };
+
+        let name = children[1].first_token().unwrap();
+        let location = Some(name.location.clone());
+        let datatype = type_from_parse_tree(&children[0], context)?;
+
+        if datatype.is_void()
+        {
+            return compiler_error_loc("E0041", format!("Global variable '{}' cannot be void", name.data), &location);
+        }
+
+        if globals.iter().any(|other| other.name == *name.data)
+        {
+            return compiler_error_loc("E0041", format!("Global variable '{}' is declared more than once", name.data), &location);
+        }
+
+        if functions.contains(&&*name.data)
+        {
+            return compiler_error_loc("E0041", format!("Global variable '{}' has the same name as a function", name.data), &location);
+        }
+
+        let doc = match &children[3]
+        {
+            ParseTreeNode::DocComment(token) => Some(token.data.to_string()),
+            _ => None
+        };
+
+        globals.push(GlobalVariable
+        {
+            name: name.data.to_string(),
+            datatype,
+            value: initial_value(&children[2], datatype, &name.data, context)?,
+            pos: location,
+            doc,
+            internal: module_of(&name.data).is_some()
+        });
+    }
+
+    context.globals = globals;
+
+    Ok(())
+}
+
+/// Work out the value a global variable starts with, wrapped to its type. A pointer can only start
+/// as `null` or a constant cast to a pointer, and a struct only zeroed
+fn initial_value(node: &ParseTreeNode, datatype: DataType, name: &str, context: &Context) -> Result<i128, Error>
+{
+    let location = node.first_token().map(|token| token.location.clone());
+
+    if let ParseTreeNode::Empty = node
+    {
+        return Ok(0);
+    }
+
+    if datatype.num_ptr > 0
+    {
+        return match node
+        {
+            ParseTreeNode::NullLiteral(_) => Ok(0),
+            ParseTreeNode::Expression(ExpressionType::Cast, children) if type_from_parse_tree(&children[1], context)?.num_ptr > 0 => evaluate_constant(&children[0], context),
+            _ => compiler_error_loc("E0041", format!("Global pointer '{}' can only start as null or as a constant cast to a pointer", name), &location)
+        };
+    }
+
+    match datatype.raw_type
+    {
+        NonPtrType::Struct(_) => compiler_error_loc("E0041", format!("Global variable '{}' of type '{}' cannot be given a value, it starts zeroed", name, datatype), &location),
+        NonPtrType::Vector(bits, signed, _) => Ok(wrap_constant(evaluate_constant(node, context)?, bits as u32, signed)),
+        raw_type => Ok(wrap_constant(evaluate_constant(node, context)?, bits_of(raw_type).unwrap_or(64), datatype.is_signed()))
+    }
+}
@@ -1,19 +1,25 @@
 use std::fmt;
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeMap};
 use std::cell::RefCell;
+use std::rc::Rc;
 
-use super::{DataType, NonPtrType};
+use serde::{Serialize, Serializer, Deserialize};
 
+use super::{Context, DataType, NonPtrType};
+
+use crate::import::module_of;
 use crate::parser::ParseTreeNode;
 
 use super::{expected_got_error};
 
-use super::{Statement, get_value_type, identifier_from_parse_tree, type_from_parse_tree, arguments_from_parse_tree};
+use super::{Statement, get_value_type, identifier_from_parse_tree, type_from_parse_tree, arguments_from_parse_tree, argument_defaults_from_parse_tree};
+use super::{Attribute, function_attributes_from_parse_tree, compiler_error, compiler_error_loc};
 
-use crate::cli::Error;
+use crate::cli::{Error, suggest};
+use crate::tokenizer::{Token, FileLocation};
 
 /// Intermediate Representation OpCode
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum OpCode
 {
     Alloc,
@@ -47,16 +53,29 @@ pub enum OpCode
     Deref,
     Ref,
     Array,
+    Index, // Address of an array element
+    Member, // Address of a field of a struct
     Push,
-    Call
+    Call,
+    Clz, // Count Leading Zeros
+    Ctz, // Count Trailing Zeros
+    Popcnt, // Count the bits set
+    Bswap, // Byte Swap
+    Rotl, // Rotate Left
+    Rotr, // Rotate Right
+    Extract, // Read a lane of a vector
+    Insert, // Replace a lane of a vector
+    Shuffle // Build a vector from the lanes of two others
 }
 
 /// Symbol with type
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Symbol
 {
     pub title: String,
-    pub datatype: DataType
+    pub datatype: DataType,
+    /// Set for a register made by the IR generator, which may share its title with a variable
+    pub register: bool
 }
 
 impl Symbol
@@ -67,7 +86,40 @@ impl Symbol
         Symbol
         {
             title,
-            datatype
+            datatype,
+            register: false
+        }
+    }
+
+    /// Generate a new register, titled by `Function::get_register`
+    pub fn register(title: String, datatype: DataType) -> Self
+    {
+        Symbol
+        {
+            title,
+            datatype,
+            register: true
+        }
+    }
+
+    /// Is the symbol a register made by the IR generator rather than a variable from the symbol
+    /// table
+    pub fn is_register(&self) -> bool
+    {
+        self.register
+    }
+
+    /// Get the name the symbol is known by within its function, which keeps a register apart from
+    /// a variable of the same title by a character no identifier can hold
+    pub fn key(&self) -> String
+    {
+        if self.register
+        {
+            format!("%{}", self.title)
+        }
+        else
+        {
+            self.title.clone()
         }
     }
 }
@@ -81,7 +133,7 @@ impl fmt::Display for Symbol
 }
 
 /// Literal with type
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub struct Literal
 {
     pub value: i128,
@@ -109,13 +161,18 @@ impl fmt::Display for Literal
     }
 }
 
+/// Type of constant data, a pointer to its first byte
+pub const DATA_TYPE: DataType = DataType::new(NonPtrType::U8, 1, false);
+
 /// Wrapper for all values viewable by IR
-#[derive(Debug, Clone, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum Value
 {
     Symbol(Symbol),
     Label(String),
-    Literal(Literal)
+    Literal(Literal),
+    Data(Vec<u8>), // Constant bytes, such as those of a string literal, used as a u8* to the first
+    Global(Symbol) // The address of a global variable, named by the symbol with the type of a pointer to it
 }
 
 impl fmt::Display for Value
@@ -126,13 +183,15 @@ impl fmt::Display for Value
         {
             Value::Symbol(symb) => write!(f, "{}", symb),
             Value::Label(s) => write!(f, "{}", s),
-            Value::Literal(lit) => write!(f, "{}", lit)
+            Value::Literal(lit) => write!(f, "{}", lit),
+            Value::Data(bytes) => write!(f, "{:?} ({})", String::from_utf8_lossy(bytes), DATA_TYPE),
+            Value::Global(symb) => write!(f, "@{} ({})", symb.title, symb.datatype)
         }
     }
 }
 
 /// Intermedaite Representation Instruction
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Instruction
 {
     pub opcode: OpCode,
@@ -150,6 +209,12 @@ impl Instruction
             arguments
         }
     }
+
+    /// Check if the instruction ends a block, so control never goes on to the instruction after it
+    pub fn is_terminator(&self) -> bool
+    {
+        matches!(self.opcode, OpCode::Jmp | OpCode::Ret | OpCode::Beq | OpCode::Bne | OpCode::Blt | OpCode::Bgt | OpCode::Ble | OpCode::Bge)
+    }
 }
 
 impl fmt::Display for Instruction
@@ -167,21 +232,72 @@ impl fmt::Display for Instruction
     }
 }
 
-/// Function implementation in Intermediate representation
+/// Labels making up the skeleton of a loop
+///
+/// The header tests the condition (for loops which test before the body), the body holds the
+/// statements of the loop, the latch tests the condition (for loops which test after the body)
+/// or jumps back to the header, and the exit follows the loop
 #[derive(Debug, Clone)]
+pub struct LoopLabels
+{
+    pub header: String,
+    pub body: String,
+    pub latch: String,
+    pub exit: String
+}
+
+/// Return and argument types of a function, gathered before any function body is generated so a
+/// function can be called before its definition
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Signature
+{
+    pub return_type: DataType,
+    pub arguments: Vec<DataType>,
+    pub defaults: Vec<Option<i128>>,
+    pub interrupt: bool // Interrupt handlers are only entered by the hardware, never called
+}
+
+/// Serialize a map in the order of its keys, so serializing the same function always gives the
+/// same bytes
+fn sorted<K: Ord + Serialize, V: Serialize, S: Serializer>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+{
+    map.iter().collect::<BTreeMap<&K, &V>>().serialize(serializer)
+}
+
+/// Function implementation in Intermediate representation
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Function
 {
+    #[serde(serialize_with = "sorted")]
     pub instructions: HashMap<usize, Instruction>,
+    #[serde(serialize_with = "sorted")]
     pub labels: HashMap<usize, Vec<String>>,
+    #[serde(serialize_with = "sorted")]
     pub labels_reverse: HashMap<String, usize>,
 
+    #[serde(serialize_with = "sorted")]
     pub symbol_table: HashMap<String, Symbol>,
 
     pub return_type: DataType,
     pub name: String,
+    pub pos: Option<FileLocation>, // Location of the name of the function in the source
     pub arguments: Vec<(String, DataType)>,
+    pub defaults: Vec<Option<i128>>,
+    pub attributes: Vec<Attribute>,
+    pub doc: Option<String>, // Text of the doc comment on the function
+    pub internal: bool, // Only visible within the module defining it
+    #[serde(serialize_with = "sorted")]
+    pub loop_hints: HashMap<String, Vec<Attribute>>,
+    #[serde(skip)]
+    pub warnings: Vec<Error>,
+    #[serde(skip)]
+    pub signatures: Rc<HashMap<String, Signature>>, // Shared by every function of a library, so written to an IR file once
+    #[serde(skip)]
+    pub context: Rc<Context>, // Structs and global variables of the library the function was lowered in, shared by each of its functions
 
     next_label: usize,
+    #[serde(serialize_with = "sorted")]
+    label_counts: HashMap<String, usize>,
     next_register: usize,
     next_index: usize,
 
@@ -206,16 +322,26 @@ impl Function
 
             return_type: DataType::new(NonPtrType::Void, 0, false),
             name: String::from("[UNKNOWN]"),
+            pos: None,
             arguments: vec![],
+            defaults: vec![],
+            attributes: vec![],
+            doc: None,
+            internal: false,
+            loop_hints: HashMap::new(),
+            warnings: vec![],
+            signatures: Rc::default(),
+            context: Rc::default(),
 
             next_label: 0,
+            label_counts: HashMap::new(),
             next_register: 1,
             next_index: 0,
 
             continue_stack: vec![],
             break_stack: vec![],
 
-            return_value: Value::Symbol(Symbol::new(String::from("R0"), DataType::new(NonPtrType::Void, 0, false)))
+            return_value: Value::Symbol(Symbol::register(String::from("R0"), DataType::new(NonPtrType::Void, 0, false)))
         }
     }
 
@@ -238,37 +364,122 @@ impl Function
         self.labels_reverse = new_reverse;
     }
 
-    /// Generate a new function object from a parse tree node
-    pub fn from_parse_tree_node(node: ParseTreeNode) -> Result<Self, Error>
+    /// Check if a function parse tree node is a prototype, ending in a ';' rather than a body
+    pub fn is_prototype(node: &ParseTreeNode) -> bool
+    {
+        matches!(node, ParseTreeNode::Function(children) if matches!(children.get(3), Some(ParseTreeNode::Empty)))
+    }
+
+    /// Generate a function object holding only the signature and attributes from a parse tree
+    /// node, for a prototype or for gathering the signatures of every function in a library, given
+    /// the context of the library it belongs to
+    pub fn prototype_from_parse_tree_node(node: &ParseTreeNode, context: &Rc<Context>) -> Result<Self, Error>
     {
         match node
         {
             ParseTreeNode::Function(children) =>
             {
                 let mut result = Self::new();
+                result.context = Rc::clone(context);
 
                 // Get the function signature
-                let name = identifier_from_parse_tree(children[1].clone())?;
-                let return_type = type_from_parse_tree(children[0].clone())?;
-                let arguments = arguments_from_parse_tree(children[2].clone())?;
+                let name = identifier_from_parse_tree(&children[1])?;
+                let return_type = type_from_parse_tree(&children[0], context)?;
+                let arguments = arguments_from_parse_tree(&children[2], context)?;
+
+                if return_type.align.is_some()
+                {
+                    return compiler_error("E0011", format!("An alignment can only be given for a variable, not the return type of '{}'", name));
+                }
 
                 result.set_function_signature(return_type, name, arguments);
+                result.defaults = argument_defaults_from_parse_tree(&children[2], context)?;
+                result.pos = children[1].first_token().map(|token| token.location.clone());
+
+                // Get any attributes placed on the function
+                if let Some(attributes) = children.get(4)
+                {
+                    result.attributes = function_attributes_from_parse_tree(attributes)?;
+                }
+
+                if let Some(ParseTreeNode::DocComment(token)) = children.get(5)
+                {
+                    result.doc = Some(token.data.to_string());
+                }
+
+                if let Some(attribute) = result.attribute("optimize")
+                {
+                    if attribute.arguments[0].parse::<usize>().is_err()
+                    {
+                        return compiler_error("E0014", format!("Bad optimization level '{}' in attribute on function '{}'", attribute.arguments[0], result.name));
+                    }
+                }
+
+                if result.attribute("interrupt").is_some()
+                {
+                    result.check_interrupt_handler()?;
+                }
+
+                if result.attribute("constructor").is_some()
+                {
+                    result.check_constructor()?;
+                }
+
+                Ok(result)
+            },
+            default =>
+            {
+                expected_got_error("Function", default)
+            }
+        }
+    }
+
+    /// Generate a new function object from a parse tree node, given the signatures of every
+    /// function it may call and the context of the library, which are shared with the other
+    /// functions of the library
+    pub fn from_parse_tree_node(node: &ParseTreeNode, signatures: &Rc<HashMap<String, Signature>>, context: &Rc<Context>) -> Result<Self, Error>
+    {
+        match &node
+        {
+            ParseTreeNode::Function(children) =>
+            {
+                let mut result = Self::prototype_from_parse_tree_node(node, context)?;
+                let return_type = result.return_type;
+                result.signatures = Rc::clone(signatures);
 
                 // Generate the code for the function
                 let refcell = RefCell::new(&mut result);
 
-                refcell.borrow_mut().return_value = Value::Symbol(Symbol::new(String::from("R0"), return_type.clone()));
+                refcell.borrow_mut().return_value = Value::Symbol(Symbol::register(String::from("R0"), return_type.clone()));
 
-                let statement = Statement::from_parse_tree_node(children[3].clone(), &refcell)?;
+                let statement = Statement::from_parse_tree_node(&children[3], &refcell)?;
 
                 statement.render(&refcell)?;
 
                 // Add the exit label
                 refcell.borrow_mut().place_label_here(String::from("exit"));
-                let ret_val = refcell.borrow().return_value.clone();
-                refcell.borrow_mut().add_instruction(Instruction::new(OpCode::Ret, vec![ret_val]));
+                // A void function returns without a value
+                let ret_vals = if refcell.borrow().return_type.is_void() {vec![]} else {vec![refcell.borrow().return_value.clone()]};
+                refcell.borrow_mut().add_instruction(Instruction::new(OpCode::Ret, ret_vals));
+
+                let mut finalresult = refcell.borrow_mut().clone();
+
+                finalresult.check_control_flow();
+
+                statement.check_unreachable(&mut finalresult.warnings);
+
+                // A function where every path ends in an infinite loop never returns
+                let never_returns = statement.never_completes() && !statement.contains_return();
+
+                if never_returns && finalresult.attribute("noreturn").is_none()
+                {
+                    finalresult.attributes.push(Attribute::new(String::from("noreturn"), vec![]));
+                }
+                else if !never_returns && finalresult.attribute("noreturn").is_some()
+                {
+                    return compiler_error("E0015", format!("Function '{}' is marked #[noreturn] but can return", finalresult.name));
+                }
 
-                let finalresult = refcell.borrow_mut().clone();
                 Ok(finalresult)
 
             },
@@ -279,6 +490,110 @@ impl Function
         }
     }
 
+    /// Check every loop entered while generating the function was left again, and every branch goes
+    /// to a label which is placed, break and continue outside of a loop being reported before this
+    /// so a failure here is a bug in the IR generator
+    fn check_control_flow(&self)
+    {
+        if !self.break_stack.is_empty() || !self.continue_stack.is_empty()
+        {
+            panic!("Function '{}' ends inside of {} loops", self.name, self.break_stack.len().max(self.continue_stack.len()));
+        }
+
+        for (i, instruction) in self.ordered_instructions()
+        {
+            // Calls name a function rather than a label
+            if instruction.opcode == OpCode::Call
+            {
+                continue;
+            }
+
+            for argument in &instruction.arguments
+            {
+                if let Value::Label(label) = argument
+                {
+                    if !self.labels_reverse.contains_key(label)
+                    {
+                        panic!("Instruction {} of '{}' branches to the label '{}' which is never placed", i, self.name, label);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Make sure a function marked #[interrupt] can be entered by the hardware, returning nothing
+    /// and taking at most a pointer to the interrupt frame followed by the error code
+    fn check_interrupt_handler(&self) -> Result<(), Error>
+    {
+        let bad = |text: &str| compiler_error_loc("E0038", format!("Interrupt handler '{}' {}", self.name, text), &self.pos);
+
+        if !self.return_type.is_void()
+        {
+            return bad("must return void");
+        }
+
+        if self.attribute("const").is_some()
+        {
+            return bad("cannot be const");
+        }
+
+        match self.arguments.as_slice()
+        {
+            [] => Ok(()),
+            [(_, frame), rest @ ..] if rest.len() <= 1 =>
+            {
+                if frame.num_ptr == 0
+                {
+                    return bad("must take a pointer to the interrupt frame as its first argument");
+                }
+
+                if rest.iter().any(|(_, code)| code.num_ptr > 0 || code.is_ref || !matches!(code.raw_type, NonPtrType::U8 | NonPtrType::U16 | NonPtrType::U32 | NonPtrType::U64 | NonPtrType::I8 | NonPtrType::I16 | NonPtrType::I32 | NonPtrType::I64))
+                {
+                    return bad("must take an integer error code as its second argument");
+                }
+
+                Ok(())
+            },
+            _ => bad("takes at most two arguments, the interrupt frame and the error code")
+        }
+    }
+
+    /// Make sure a function marked #[constructor] can be run before main, returning nothing and
+    /// taking no arguments
+    fn check_constructor(&self) -> Result<(), Error>
+    {
+        let bad = |text: &str| compiler_error_loc("E0039", format!("Constructor '{}' {}", self.name, text), &self.pos);
+
+        if !self.return_type.is_void()
+        {
+            return bad("must return void");
+        }
+
+        if !self.arguments.is_empty()
+        {
+            return bad("cannot take arguments");
+        }
+
+        if self.attribute("interrupt").is_some()
+        {
+            return bad("cannot also be an interrupt handler");
+        }
+
+        Ok(())
+    }
+
+    /// Get the signature of the function, which calls to it are checked against
+    pub fn signature(&self) -> Signature
+    {
+        Signature
+        {
+            return_type: self.return_type,
+            arguments: self.arguments.iter().map(|(_, datatype)| *datatype).collect(),
+            defaults: self.defaults.clone(),
+            interrupt: self.attribute("interrupt").is_some()
+        }
+    }
+
     /// Set the function signature
     pub fn set_function_signature(&mut self, return_type: DataType, name: String, arguments: Vec<(String, DataType)>)
     {
@@ -293,6 +608,53 @@ impl Function
         }
     }
 
+    /// Look up a variable by the token naming it, giving an error at the use if it was never
+    /// declared, with a hint if it is close to a variable or global variable which is declared, or
+    /// a keyword which can appear where an identifier can
+    pub fn lookup_symbol(&self, name: &Token) -> Result<&Symbol, Error>
+    {
+        if let Some(symbol) = self.symbol_table.get(&*name.data)
+        {
+            return Ok(symbol);
+        }
+
+        // Global variables private to other modules cannot be reached from here
+        let module = module_of(&self.name);
+        let globals: Vec<String> = self.context.globals.iter().filter_map(|global| match global.name.split_once('.')
+        {
+            Some((owner, name)) => (Some(owner) == module).then(|| name.to_string()),
+            None => Some(global.name.clone())
+        }).collect();
+
+        let mut names: Vec<&str> = self.symbol_table.keys().map(|name| name.as_str()).chain(globals.iter().map(|name| name.as_str())).collect();
+        names.sort();
+
+        let error = compiler_error_loc("E0001", format!("Use of undeclared variable '{}'", name.data), &Some(name.location.clone()));
+        suggest(error, &name.data, names.into_iter().chain(["break", "continue", "null"].iter().copied()))
+    }
+
+    /// Get an attribute placed on the function by name
+    pub fn attribute(&self, name: &str) -> Option<&Attribute>
+    {
+        self.attributes.iter().find(|attribute| attribute.name == name)
+    }
+
+    /// Get the optimization level requested by an #[optimize(..)] attribute on the function
+    pub fn optimization_level(&self) -> Option<usize>
+    {
+        self.attribute("optimize").and_then(|attribute| attribute.arguments[0].parse::<usize>().ok())
+    }
+
+    /// Get a copy of the instructions sorted by index, so passes visit them in a stable order
+    pub fn ordered_instructions(&self) -> Vec<(usize, Instruction)>
+    {
+        let mut result: Vec<(usize, Instruction)> = self.instructions.iter().map(|(i, inst)| (*i, inst.clone())).collect();
+
+        result.sort_by_key(|(i, _)| *i);
+
+        result
+    }
+
     /// Get the jump values for an instruction at the given index
     pub fn get_jump_values(&self, index: usize) -> Option<Vec<usize>>
     {
@@ -332,6 +694,31 @@ impl Function
         }
     }
 
+    /// Check every instruction following the end of a block starts a new block with a label, as
+    /// nothing else can reach it, so the code generators never have to leave out an instruction
+    pub fn check_blocks(&self) -> Result<(), Error>
+    {
+        let mut terminated = false;
+
+        for index in 0..self.instructions.len()
+        {
+            let inst = &self.instructions[&index];
+
+            if self.labels.contains_key(&index)
+            {
+                terminated = false;
+            }
+            else if terminated && inst.opcode != OpCode::Nop
+            {
+                return compiler_error("E0114", format!("Instruction {} of '{}', '{}', follows the end of a block without a label", index, self.name, inst.to_string().trim_end()));
+            }
+
+            terminated = terminated || inst.is_terminator();
+        }
+
+        Ok(())
+    }
+
     /// Change an instruction at the given index to a nop
     pub fn change_to_nop(&mut self, index: usize)
     {
@@ -390,6 +777,30 @@ impl Function
         format!("L{}", self.next_label - 1)
     }
 
+    /// Get a new label named after the construct it belongs to and the line of that construct, so
+    /// the blocks in the output can be traced back to the source (`while.cond.l12`), a number is
+    /// added for the second and later labels with the same name
+    pub fn get_label_at(&mut self, construct: &str, pos: &Option<FileLocation>) -> String
+    {
+        let name = match pos
+        {
+            Some(pos) => format!("{}.l{}", construct, pos.row),
+            None => String::from(construct)
+        };
+
+        let count = self.label_counts.entry(name.clone()).or_insert(0);
+        *count += 1;
+
+        if *count == 1
+        {
+            name
+        }
+        else
+        {
+            format!("{}.{}", name, *count - 1)
+        }
+    }
+
     /// Get a new label and place it here
     pub fn get_label_and_place(&mut self) -> String
     {
@@ -414,16 +825,22 @@ impl Function
         self.next_index += 1;
     }
 
-    /// Enter a loop (push to the loop stack)
-    pub fn enter_loop(&mut self) -> (String, String)
+    /// Enter a loop (push to the loop stack), continue statements jump to the latch and break
+    /// statements jump to the exit, the labels are named after the kind of loop
+    pub fn enter_loop(&mut self, construct: &str, pos: &Option<FileLocation>) -> LoopLabels
     {
-        let entry = self.get_label();
-        let exit = self.get_label();
+        let labels = LoopLabels
+        {
+            header: self.get_label_at(&format!("{}.cond", construct), pos),
+            body: self.get_label_at(&format!("{}.body", construct), pos),
+            latch: self.get_label_at(&format!("{}.latch", construct), pos),
+            exit: self.get_label_at(&format!("{}.end", construct), pos)
+        };
 
-        self.continue_stack.push(entry.clone());
-        self.break_stack.push(exit.clone());
+        self.continue_stack.push(labels.latch.clone());
+        self.break_stack.push(labels.exit.clone());
 
-        (entry, exit)
+        labels
     }
 
     /// Exit a loop (pop from the loop stack)
@@ -433,30 +850,16 @@ impl Function
         self.break_stack.pop();
     }
 
-    /// Get the current continue value
-    pub fn get_continue(&mut self) -> Option<String>
+    /// Get the label a continue in the innermost loop jumps to, or None outside of a loop
+    pub fn get_continue(&self) -> Option<String>
     {
-        if self.continue_stack.len() > 0
-        {
-            Some(self.continue_stack[self.continue_stack.len() - 1].clone())
-        }
-        else
-        {
-            None
-        }
+        self.continue_stack.last().cloned()
     }
 
-    /// Get the current break value
-    pub fn get_break(&mut self) -> Option<String>
+    /// Get the label a break in the innermost loop jumps to, or None outside of a loop
+    pub fn get_break(&self) -> Option<String>
     {
-        if self.break_stack.len() > 0
-        {
-            Some(self.break_stack[self.continue_stack.len() - 1].clone())
-        }
-        else
-        {
-            None
-        }
+        self.break_stack.last().cloned()
     }
 
     /// Get all instructions which can be reached from a given index
@@ -508,9 +911,15 @@ impl Function
         let mut reads = vec![];
         let mut writes = vec![];
 
-        // Iterate over all instructions
-        for (index, inst) in &self.instructions
+        // Iterate over all instructions (in order)
+        for index in 0..self.instructions.len()
         {
+            let inst = match self.instructions.get(&index)
+            {
+                Some(inst) => inst,
+                None => continue
+            };
+
             if inst.arguments.len() > 0
             {
                 match inst.opcode
@@ -520,7 +929,7 @@ impl Function
                     {
                         if inst.arguments.contains(&value)
                         {
-                            reads.push(*index);
+                            reads.push(index);
                         }
                     },
                     _ => 
@@ -530,11 +939,11 @@ impl Function
                         {
                             if !get_value_type(&value).unwrap().is_ref || inst.opcode == OpCode::Cast
                             {
-                                writes.push(*index);
+                                writes.push(index);
                             }
                             else
                             {
-                                reads.push(*index);
+                                reads.push(index);
                             }
                             
                         }
@@ -544,7 +953,7 @@ impl Function
                         {
                             if inst.arguments[1..inst.arguments.len()].contains(&value)
                             {
-                                reads.push(*index);
+                                reads.push(index);
                             }
                         }
                     }
@@ -639,47 +1048,35 @@ impl Function
         self.labels_reverse.remove(&label);
     }
 
-    /// Get the domain of a register
+    /// Get the domain of a register, every instruction it is live at along with every instruction
+    /// which writes to it
     pub fn get_register_domain(&self, register: Value) -> Vec<usize>
     {
-        let mut result = vec![];
+        let (reads, writes) = self.get_reads_writes_for(register);
+        let length = self.instructions.len();
 
-        let (reads, writes) = self.get_reads_writes_for(register.clone());
-        
-        // Go through all instructions
-        for i in 0..self.instructions.len()
-        {
-            // Any write must be in the domain
-            if writes.contains(&i)
-            {
-                result.push(i);
-                break;
-            }
+        // A register is live at an instruction which reads it, or which does not write it and can
+        // be followed by an instruction it is live at, so work backwards until nothing changes
+        let mut live = vec![false; length];
+        let mut changed = true;
 
-            let explored = self.get_explored_from(i);
+        while changed
+        {
+            changed = false;
 
-            // Any instruction that can reach a read before another write
-            for e in explored
+            for i in (0..length).rev()
             {
-                if reads.contains(&e)
-                {
-                    if !result.contains(&i)
-                    {
-                        result.push(i);
-                    }
-                    break;
-                }
-                else if writes.contains(&e)
+                if !live[i] && (reads.contains(&i) || (!writes.contains(&i) && self.get_next_branches(i).iter().any(|next| *next < length && live[*next])))
                 {
-                    break;
+                    live[i] = true;
+                    changed = true;
                 }
-            } 
+            }
         }
 
-        result
+        (0..length).filter(|i| live[*i] || writes.contains(i)).collect()
     }
 
-
     pub fn get_paths_from(&self, index: usize) -> Vec<Vec<usize>>
     {
         let mut results = vec![];
@@ -727,6 +1124,11 @@ impl fmt::Display for Function
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
     {
+        for attribute in &self.attributes
+        {
+            writeln!(f, "{}", attribute)?;
+        }
+
         write!(f, "{} {}(", self.return_type, self.name)?;
 
         for (i, (t, n)) in (&self.arguments).iter().enumerate()
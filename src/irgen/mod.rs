@@ -5,6 +5,11 @@ mod utils;
 mod optimize;
 mod errors;
 mod types;
+mod attribute;
+mod constant;
+mod structs;
+mod globals;
+mod context;
 
 pub use instruction::*;
 pub use expression::*;
@@ -12,4 +17,9 @@ pub use statement::*;
 pub use utils::*;
 pub use optimize::*;
 pub use errors::*;
-pub use types::*;
\ No newline at end of file
+pub use types::*;
+pub use attribute::*;
+pub use constant::*;
+pub use structs::*;
+pub use globals::*;
+pub use context::*;
\ No newline at end of file
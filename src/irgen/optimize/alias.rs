@@ -0,0 +1,178 @@
+use crate::irgen::{Function, Value, OpCode, DataType, NonPtrType};
+use crate::irgen::{get_value_type, bits_of};
+
+/// How two pointers relate to each other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alias
+{
+    No,
+    May,
+    Must
+}
+
+/// Where the memory a pointer points into came from
+#[derive(Debug, Clone, PartialEq)]
+enum Origin
+{
+    Local(String), // The address of a local variable
+    Global(String), // The address of a global variable
+    Argument(String, bool), // An argument which is never changed, and whether it is restrict
+    Unknown
+}
+
+/// Most moves and casts followed back when looking for where a pointer came from
+const MAX_ORIGIN_DEPTH: usize = 16;
+
+/// Simple alias analysis over a function, based on where pointers were made and the types they
+/// point to
+pub struct AliasAnalysis<'a>
+{
+    func: &'a Function
+}
+
+impl<'a> AliasAnalysis<'a>
+{
+    /// Generate a new alias analysis for a function
+    pub fn new(func: &'a Function) -> Self
+    {
+        Self
+        {
+            func
+        }
+    }
+
+    /// Check if two pointers may point to the same memory
+    pub fn alias(&self, a: &Value, b: &Value) -> Alias
+    {
+        if a == b
+        {
+            return Alias::Must;
+        }
+
+        if !self.compatible_types(a, b)
+        {
+            return Alias::No;
+        }
+
+        match (self.origin(a, 0), self.origin(b, 0))
+        {
+            // Different locals never overlap, but two pointers into the same one may be offset
+            (Origin::Local(x), Origin::Local(y)) if x != y => Alias::No,
+
+            // Neither do different global variables, or a global variable and a local
+            (Origin::Global(x), Origin::Global(y)) if x != y => Alias::No,
+            (Origin::Global(_), Origin::Local(_)) | (Origin::Local(_), Origin::Global(_)) => Alias::No,
+
+            // The locals of a function did not exist when its arguments were passed
+            (Origin::Local(_), Origin::Argument(..)) | (Origin::Argument(..), Origin::Local(_)) => Alias::No,
+
+            // A restrict argument is the only way to reach its memory
+            (Origin::Argument(x, true), Origin::Argument(y, _)) | (Origin::Argument(y, _), Origin::Argument(x, true)) if x != y => Alias::No,
+
+            _ => Alias::May
+        }
+    }
+
+    /// Get the pointer a reference register was made from, if it is only made in one place
+    pub fn reference_base(&self, reference: &Value) -> Option<Value>
+    {
+        let mut bases = self.func.instructions.values().filter(|inst| inst.opcode == OpCode::Cast && inst.arguments[0] == *reference);
+
+        match (bases.next(), bases.next())
+        {
+            (Some(inst), None) => Some(inst.arguments[1].clone()),
+            _ => None
+        }
+    }
+
+    /// Get the local variable a pointer points into, if it is known
+    pub fn local_of(&self, pointer: &Value) -> Option<String>
+    {
+        match self.origin(pointer, 0)
+        {
+            Origin::Local(name) => Some(name),
+            _ => None
+        }
+    }
+
+    /// Follow a pointer back to where it was made
+    fn origin(&self, value: &Value, depth: usize) -> Origin
+    {
+        let symbol = match value
+        {
+            Value::Global(global) => {return Origin::Global(global.title.clone());},
+            Value::Symbol(symbol) if depth < MAX_ORIGIN_DEPTH => symbol,
+            _ => {return Origin::Unknown;}
+        };
+
+        let writes = self.func.get_reads_writes_for(value.clone()).1;
+
+        if let Some((_, datatype)) = self.func.arguments.iter().find(|(name, _)| !symbol.is_register() && *name == symbol.title)
+        {
+            return if writes.is_empty() {Origin::Argument(symbol.title.clone(), datatype.restrict)} else {Origin::Unknown};
+        }
+
+        if writes.len() != 1
+        {
+            return Origin::Unknown;
+        }
+
+        let inst = &self.func.instructions[&writes[0]];
+
+        match inst.opcode
+        {
+            OpCode::Ref => match &inst.arguments[1]
+            {
+                Value::Symbol(local) => Origin::Local(local.key()),
+                _ => Origin::Unknown
+            },
+
+            // Copies, casts between pointers, pointer arithmetic and fields keep pointing into the same memory
+            OpCode::Mov | OpCode::Alloc | OpCode::Cast | OpCode::Index | OpCode::Member => self.origin(&inst.arguments[1], depth + 1),
+            OpCode::Add | OpCode::Sub => match get_value_type(&inst.arguments[1])
+            {
+                Some(datatype) if datatype.num_ptr > 0 => self.origin(&inst.arguments[1], depth + 1),
+                _ => self.origin(&inst.arguments[2], depth + 1)
+            },
+            _ => Origin::Unknown
+        }
+    }
+
+    /// Check if memory of the types the two pointers point to may overlap, bytes may overlap
+    /// anything, and otherwise only values of the same size (or vectors of them) may overlap
+    fn compatible_types(&self, a: &Value, b: &Value) -> bool
+    {
+        match (pointee_class(a), pointee_class(b))
+        {
+            (Some(x), Some(y)) => x == y,
+            _ => true
+        }
+    }
+}
+
+/// Get the size of the values a pointer points to (the lanes for a vector, zero for a pointer),
+/// giving nothing for bytes and unknown types which may overlap anything
+fn pointee_class(pointer: &Value) -> Option<u32>
+{
+    let datatype = match get_value_type(pointer)
+    {
+        Some(datatype) if datatype.num_ptr > 0 => datatype,
+        _ => {return None;}
+    };
+
+    if datatype.num_ptr > 1
+    {
+        return Some(0);
+    }
+
+    let pointee = DataType::new(datatype.raw_type, 0, false);
+
+    let bits = match pointee.element_type()
+    {
+        Some(element) => bits_of(element.raw_type),
+        None if pointee.raw_type == NonPtrType::Bool => None,
+        None => bits_of(pointee.raw_type)
+    };
+
+    bits.filter(|bits| *bits > 8)
+}
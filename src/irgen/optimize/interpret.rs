@@ -0,0 +1,308 @@
+use std::collections::HashMap;
+
+use crate::irgen::{Function, Instruction, OpCode, Value, Literal, DataType, bits_of};
+
+use super::optimizations::{fold_bit_operation, fold_rotate};
+
+/// The most instructions a single call to a const function may execute before it is left to run
+/// when the program runs instead
+const STEP_LIMIT: usize = 1_000_000;
+
+/// The deepest calls between const functions may nest while being evaluated
+const DEPTH_LIMIT: usize = 256;
+
+/// Evaluate a call to a const function with the given arguments, giving None if the call cannot be
+/// worked out while compiling (it uses memory, runs for too long or divides by zero)
+pub fn interpret(func: &Function, arguments: &[i128], functions: &HashMap<String, Function>) -> Option<i128>
+{
+    let mut steps = STEP_LIMIT;
+
+    interpret_call(func, arguments, functions, &mut steps, 0)
+}
+
+/// Replace calls to const functions whose arguments are all constants with the value the call
+/// returns, leaving any call which cannot be evaluated to run as normal
+pub fn fold_const_calls(func: &mut Function, functions: &HashMap<String, Function>)
+{
+    // Indexes and values of the arguments pushed for the next call, None once one is not constant
+    let mut pushed: Option<Vec<(usize, i128)>> = Some(vec![]);
+
+    for (i, instruction) in func.ordered_instructions()
+    {
+        match (instruction.opcode, instruction.arguments.as_slice())
+        {
+            (OpCode::Push, [Value::Literal(lit)]) =>
+            {
+                if let Some(pushed) = &mut pushed
+                {
+                    pushed.push((i, lit.value));
+                }
+            },
+            (OpCode::Push, _) =>
+            {
+                pushed = None;
+            },
+            (OpCode::Call, [Value::Symbol(result), Value::Label(name)]) =>
+            {
+                let callee = functions.get(name);
+
+                if let (Some(callee), Some(arguments)) = (callee, &pushed)
+                {
+                    let values: Vec<i128> = arguments.iter().map(|(_, value)| *value).collect();
+
+                    if let Some(value) = interpret(callee, &values, functions).and_then(|value| wrap(value, result.datatype))
+                    {
+                        for (index, _) in arguments
+                        {
+                            func.change_to_nop(*index);
+                        }
+
+                        func.instructions.insert(i, Instruction::new(OpCode::Mov, vec![
+                            Value::Symbol(result.clone()),
+                            Value::Literal(Literal::new(value, result.datatype))]));
+                    }
+                }
+
+                pushed = Some(vec![]);
+            },
+            (OpCode::Call, _) =>
+            {
+                pushed = Some(vec![]);
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Run a function with the given arguments, giving its return value
+fn interpret_call(func: &Function, arguments: &[i128], functions: &HashMap<String, Function>, steps: &mut usize, depth: usize) -> Option<i128>
+{
+    if depth > DEPTH_LIMIT || arguments.len() != func.arguments.len()
+    {
+        return None;
+    }
+
+    let mut values: HashMap<String, i128> = HashMap::new();
+
+    for ((name, datatype), value) in func.arguments.iter().zip(arguments)
+    {
+        values.insert(name.clone(), wrap(*value, *datatype)?);
+    }
+
+    let mut pushed = vec![];
+    let mut index = 0;
+
+    loop
+    {
+        *steps = steps.checked_sub(1)?;
+
+        let instruction = func.instructions.get(&index)?;
+        let args = &instruction.arguments;
+
+        let mut next = index + 1;
+
+        match instruction.opcode
+        {
+            OpCode::Nop => {},
+            OpCode::Alloc | OpCode::Mov | OpCode::Cast if args.len() == 2 =>
+            {
+                let (name, datatype) = destination(&args[0])?;
+                let value = wrap(read(&values, &args[1])?, datatype)?;
+
+                values.insert(name, value);
+            },
+            OpCode::Add | OpCode::Sub | OpCode::Mul | OpCode::Div | OpCode::Mod |
+            OpCode::Shl | OpCode::Shr | OpCode::And | OpCode::Or | OpCode::Xor if args.len() == 3 =>
+            {
+                let (name, datatype) = destination(&args[0])?;
+                let value = arithmetic(instruction.opcode, read(&values, &args[1])?, read(&values, &args[2])?, datatype)?;
+
+                values.insert(name, wrap(value, datatype)?);
+            },
+            OpCode::Cne | OpCode::Ceq | OpCode::Clt | OpCode::Cgt | OpCode::Cle | OpCode::Cge if args.len() == 3 =>
+            {
+                let (name, _) = destination(&args[0])?;
+                let value = compare(instruction.opcode, read(&values, &args[1])?, read(&values, &args[2])?)?;
+
+                values.insert(name, value as i128);
+            },
+            OpCode::Clz | OpCode::Ctz | OpCode::Popcnt | OpCode::Bswap if args.len() == 2 =>
+            {
+                let (name, datatype) = destination(&args[0])?;
+                let value = fold_bit_operation(instruction.opcode, Literal::new(read(&values, &args[1])?, datatype))?;
+
+                values.insert(name, value);
+            },
+            OpCode::Rotl | OpCode::Rotr if args.len() == 3 =>
+            {
+                let (name, datatype) = destination(&args[0])?;
+                let value = fold_rotate(instruction.opcode,
+                    Literal::new(read(&values, &args[1])?, datatype),
+                    Literal::new(read(&values, &args[2])?, datatype))?;
+
+                values.insert(name, value);
+            },
+            OpCode::Bne | OpCode::Beq | OpCode::Blt | OpCode::Bgt | OpCode::Ble | OpCode::Bge if args.len() == 4 =>
+            {
+                let compare_opcode = match instruction.opcode
+                {
+                    OpCode::Bne => OpCode::Cne,
+                    OpCode::Beq => OpCode::Ceq,
+                    OpCode::Blt => OpCode::Clt,
+                    OpCode::Bgt => OpCode::Cgt,
+                    OpCode::Ble => OpCode::Cle,
+                    _ => OpCode::Cge
+                };
+
+                let taken = compare(compare_opcode, read(&values, &args[0])?, read(&values, &args[1])?)?;
+
+                next = jump_target(func, &args[if taken {2} else {3}])?;
+            },
+            OpCode::Jmp if args.len() == 1 =>
+            {
+                next = jump_target(func, &args[0])?;
+            },
+            OpCode::Push if args.len() == 1 =>
+            {
+                pushed.push(read(&values, &args[0])?);
+            },
+            OpCode::Call if args.len() == 2 =>
+            {
+                let (name, datatype) = destination(&args[0])?;
+
+                let callee = match &args[1]
+                {
+                    Value::Label(callee) => functions.get(callee)?,
+                    _ => {return None;}
+                };
+
+                let value = interpret_call(callee, &pushed, functions, steps, depth + 1)?;
+                pushed.clear();
+
+                values.insert(name, wrap(value, datatype)?);
+            },
+            OpCode::Call if args.len() == 1 =>
+            {
+                let callee = match &args[0]
+                {
+                    Value::Label(callee) => functions.get(callee)?,
+                    _ => {return None;}
+                };
+
+                interpret_call(callee, &pushed, functions, steps, depth + 1)?;
+                pushed.clear();
+            },
+            OpCode::Ret if args.len() == 1 =>
+            {
+                return wrap(read(&values, &args[0])?, func.return_type);
+            },
+            // A void function gives no value, so any will do
+            OpCode::Ret if args.is_empty() =>
+            {
+                return Some(0);
+            },
+            // Anything touching memory, vectors or functions which are not const
+            _ => {return None;}
+        }
+
+        index = next;
+    }
+}
+
+/// Get the name and type of the register or variable an instruction writes
+fn destination(value: &Value) -> Option<(String, DataType)>
+{
+    match value
+    {
+        Value::Symbol(symbol) => Some((symbol.key(), symbol.datatype)),
+        _ => None
+    }
+}
+
+/// Read the value of a literal, register or variable
+fn read(values: &HashMap<String, i128>, value: &Value) -> Option<i128>
+{
+    match value
+    {
+        Value::Literal(lit) if lit.datatype.num_ptr == 0 => Some(lit.value),
+        Value::Symbol(symbol) if symbol.datatype.num_ptr == 0 => values.get(&symbol.key()).copied(),
+        _ => None
+    }
+}
+
+/// Get the index of the instruction a label is placed on
+fn jump_target(func: &Function, label: &Value) -> Option<usize>
+{
+    match label
+    {
+        Value::Label(label) => func.labels_reverse.get(label).copied(),
+        _ => None
+    }
+}
+
+/// Perform an arithmetic operation on two values of the given type, giving None for a division by
+/// zero or a shift by the width of the type or more
+fn arithmetic(opcode: OpCode, value0: i128, value1: i128, datatype: DataType) -> Option<i128>
+{
+    let bits = bits_of(datatype.raw_type)?;
+
+    match opcode
+    {
+        OpCode::Add => Some(value0.wrapping_add(value1)),
+        OpCode::Sub => Some(value0.wrapping_sub(value1)),
+        OpCode::Mul => Some(value0.wrapping_mul(value1)),
+        OpCode::Div | OpCode::Mod if value1 == 0 => None,
+        OpCode::Div => Some(value0.wrapping_div(value1)),
+        OpCode::Mod => Some(value0.wrapping_rem(value1)),
+        OpCode::Shl | OpCode::Shr if value1 < 0 || value1 >= bits as i128 => None,
+        OpCode::Shl => Some(value0.wrapping_shl(value1 as u32)),
+        // Unsigned values are held as non-negative numbers, so the shift fills with zeros
+        OpCode::Shr => Some(value0 >> value1),
+        OpCode::And => Some(value0 & value1),
+        OpCode::Or => Some(value0 | value1),
+        OpCode::Xor => Some(value0 ^ value1),
+        _ => None
+    }
+}
+
+/// Compare two values of the same type
+fn compare(opcode: OpCode, value0: i128, value1: i128) -> Option<bool>
+{
+    match opcode
+    {
+        OpCode::Cne => Some(value0 != value1),
+        OpCode::Ceq => Some(value0 == value1),
+        OpCode::Clt => Some(value0 < value1),
+        OpCode::Cgt => Some(value0 > value1),
+        OpCode::Cle => Some(value0 <= value1),
+        OpCode::Cge => Some(value0 >= value1),
+        _ => None
+    }
+}
+
+/// Wrap a value to the width of an integer type, sign extending it if the type is signed
+fn wrap(value: i128, datatype: DataType) -> Option<i128>
+{
+    let bits = bits_of(datatype.raw_type)?;
+
+    if datatype.num_ptr > 0
+    {
+        return None;
+    }
+
+    if bits >= 128
+    {
+        return Some(value);
+    }
+
+    let wrapped = value & ((1i128 << bits) - 1);
+
+    if datatype.is_signed() && wrapped >> (bits - 1) != 0
+    {
+        Some(wrapped - (1i128 << bits))
+    }
+    else
+    {
+        Some(wrapped)
+    }
+}
@@ -1,5 +1,11 @@
 mod optimizations;
 mod typecorrect;
+mod passes;
+mod alias;
+mod interpret;
+mod propagate;
 
-pub use optimizations::*;
 pub use typecorrect::*;
+pub use passes::*;
+pub use interpret::*;
+pub use propagate::*;
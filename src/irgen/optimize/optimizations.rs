@@ -1,79 +1,11 @@
-use crate::irgen::{Function, Value, OpCode, Literal};
-use crate::irgen::get_value_type;
+use crate::irgen::{Function, Value, OpCode, Literal, Instruction, DataType, Symbol};
+use crate::irgen::{get_value_type, bits_of};
 
-pub fn optimize_function(f: Function, level: usize, combine: bool) -> Function
-{
-    let mut func = f.clone();
-
-    // func = optimization_clean_branches(func);
-    func = optimization_remove_nop(func);
-
-    let mut last_loop = false;
-
-    loop 
-    {
-        let last = func.clone();
-
-        // Level 2 Optimizations (Clean Register Usage)
-        if level >= 2
-        {
-            // func = optimization_multiple_clean_registers(func);
-            func = optimization_clean_registers(func);
-            
-            func = optimization_remove_nop(func);
-        }
-
-        // Level 1 Optimizations (Remove Casts and Jump Chaining)
-        if level >= 1
-        {
-            func = optimization_remove_casts(func);
-            func = optimization_remove_nop(func);
-            func = optimization_jump_chaining(func);
-            func = optimization_remove_nop(func);
-        }
-
-        // Level 0 Optimizations (Constant Folding, Clean Branches, Remove Unused Registers, Remove Dead Code, Remove Unused Labels, Remove Nop's)
-        func = optimization_arithmatic_constants(func);
-        func = optimization_remove_unused_registers(func);
-        func = optimization_remove_nop(func);
-        func = optimization_redundant_moves(func);
-        func = optimization_remove_nop(func);
-        func = optimization_dead_code(func);
-        func = optimization_remove_nop(func);
-        func = optimization_redundant_labels(func);
-        func = optimization_remove_unused_labels(func);
-        func = optimization_remove_nop(func);
-
-        // If the code has changed length, keep going
-        if func.instructions.len() == last.instructions.len()
-        {
-            if last_loop
-            {
-                break;
-            }
-            
-            last_loop = true;
-        }
-        else
-        {
-            last_loop = false;
-        }
-    }
-
-    // If the combine register flag is set, combine the domains of registers
-    if combine
-    {
-        func = optimization_combine_domains(func);
-        func = optimize_function(func, level, false);
-    }
-
-    func.clone()
-}
+use super::alias::{AliasAnalysis, Alias};
 
 /// Remove nop instructions
-pub fn optimization_remove_nop(f: Function) -> Function
+pub fn optimization_remove_nop(func: &mut Function)
 {
-    let mut func = f.clone();
 
     let mut indexes_to_remove = vec![];
 
@@ -88,9 +20,10 @@ pub fn optimization_remove_nop(f: Function) -> Function
 
     // Keep a record of how much to shift everything by
     let mut amt_to_shift = 0;
+    let length = func.instructions.len();
 
     // Go over all instructions
-    for i in 0..func.instructions.len()
+    for i in 0..length
     {
         // Move any labels on nop's
         if indexes_to_remove.contains(&i)
@@ -137,18 +70,21 @@ pub fn optimization_remove_nop(f: Function) -> Function
         }
     }
 
-    func.clean_reverse_labels();
+    // A nop at the end has nothing shifted over it
+    for i in length - amt_to_shift..length
+    {
+        func.instructions.remove(&i);
+    }
 
-    func.clone()
+    func.clean_reverse_labels();
 }
 
 /// Connect the ends of a chain of jumps
-pub fn optimization_jump_chaining(f: Function) -> Function
+pub fn optimization_jump_chaining(func: &mut Function)
 {
-    let mut func = f.clone();
 
-    // Iterate over all instructions
-    for (index, inst) in (&func.instructions).clone()
+    // Iterate over all instructions (in order, as each chain depends on the last)
+    for (index, inst) in func.ordered_instructions()
     {
         // If the instruction is a jump
         if inst.opcode == OpCode::Jmp
@@ -206,14 +142,11 @@ pub fn optimization_jump_chaining(f: Function) -> Function
             }
         }
     }
-
-    func
 }
 
 /// Remove unused labels
-pub fn optimization_remove_unused_labels(f: Function) -> Function
+pub fn optimization_remove_unused_labels(func: &mut Function)
 {
-    let mut func = f.clone();
     let mut labels = vec![];
 
     // Iterate over all instructions, and determine all labels referenced in commands
@@ -228,6 +161,9 @@ pub fn optimization_remove_unused_labels(f: Function) -> Function
         }
     }
 
+    // The headers of loops with hints are kept so the back edges can still be found
+    labels.extend(func.loop_hints.keys().cloned());
+
     // Go over all labels stored and if they weren't used, mark them for removal
     let mut labels_to_remove = vec![];
     
@@ -244,14 +180,11 @@ pub fn optimization_remove_unused_labels(f: Function) -> Function
     {
         func.remove_label(l.clone());
     }
-
-    func
 }
 
 /// Remove dead code
-pub fn optimization_dead_code(f: Function) -> Function
+pub fn optimization_dead_code(func: &mut Function)
 {
-    let mut func = f.clone();
 
     // Get all instructions which can be reached from the start of the function
     let explored = func.get_explored_from(0);
@@ -261,27 +194,14 @@ pub fn optimization_dead_code(f: Function) -> Function
     {
         if !explored.contains(&index)
         {
-            // Do not remove return, instead replace it with Ret 0
-            if let Some(inst) = func.instructions.get(&index)
-            {
-                if inst.opcode == OpCode::Ret
-                {
-                    func.instructions.get_mut(&index).unwrap().arguments = vec![Value::Literal(Literal::new(0, func.return_type))];
-                    continue;
-                }
-            }
-
             func.change_to_nop(index);
         }
     }
-
-    func
 }
 
 /// Remove casts
-pub fn optimization_remove_casts(f: Function) -> Function
+pub fn optimization_remove_casts(func: &mut Function)
 {
-    let mut func = f.clone();
 
     let symbols = func.get_all_symbols();
 
@@ -289,7 +209,7 @@ pub fn optimization_remove_casts(f: Function) -> Function
     for symbol in symbols
     {
         // Skip if the symbol is an argument
-        if func.arguments.contains(&(symbol.title.clone(), symbol.datatype.clone()))
+        if !symbol.is_register() && func.arguments.contains(&(symbol.title.clone(), symbol.datatype.clone()))
         {
             continue;
         }
@@ -305,6 +225,12 @@ pub fn optimization_remove_casts(f: Function) -> Function
                 {
                     if let Value::Literal(lit) = inst.arguments[1]
                     {
+                        // Converting a vector can rearrange its bits, so the literal cannot just be retyped
+                        if lit.datatype.is_vector() || symbol.datatype.is_vector()
+                        {
+                            continue;
+                        }
+
                         let mut val = lit.clone();
 
                         val.datatype = symbol.datatype;
@@ -336,14 +262,11 @@ pub fn optimization_remove_casts(f: Function) -> Function
             }
         }
     }
-
-    func
 }
 
 /// Replace constants and symbols on registers with only one write
-pub fn optimization_clean_registers(f: Function) -> Function
+pub fn optimization_clean_registers(func: &mut Function)
 {
-    let mut func = f.clone();
 
     let symbols = func.get_all_symbols();
 
@@ -356,12 +279,18 @@ pub fn optimization_clean_registers(f: Function) -> Function
             continue;
         }
 
+        // Skip the register if its address is taken, as it must keep living in memory
+        if func.instructions.values().any(|inst| inst.opcode == OpCode::Ref && inst.arguments[1] == Value::Symbol(symbol.clone()))
+        {
+            continue;
+        }
+
         let (reads, writes) = func.get_reads_writes_for(Value::Symbol(symbol.clone()));
 
         // Replace Constants
         if writes.len() == 1
         {
-            if !func.arguments.contains(&(symbol.title.clone(), symbol.datatype.clone()))
+            if symbol.is_register() || !func.arguments.contains(&(symbol.title.clone(), symbol.datatype.clone()))
             {
                 if let Some(write_inst) = func.instructions.get(&writes[0])
                 {
@@ -474,14 +403,11 @@ pub fn optimization_clean_registers(f: Function) -> Function
             }
         }
     }
-
-    func
 }
 
 /// Remove any unused registers
-pub fn optimization_remove_unused_registers(f: Function) -> Function
+pub fn optimization_remove_unused_registers(func: &mut Function)
 {
-    let mut func = f.clone();
 
     let symbols = func.get_all_symbols();
 
@@ -538,14 +464,11 @@ pub fn optimization_remove_unused_registers(f: Function) -> Function
             }
         }
     }
-
-    func
 }
 
 /// Clean up branches (change a compare and a branch to just a compare)
-pub fn optimization_clean_branches(f: Function) -> Function
+pub fn optimization_clean_branches(func: &mut Function)
 {
-    let mut func = f.clone();
 
     let symbols = func.get_all_symbols();
 
@@ -599,14 +522,11 @@ pub fn optimization_clean_branches(f: Function) -> Function
             }
         }
     }
-
-    func
 }
 
 /// Combine registers which domains which do not overlap
-pub fn optimization_combine_domains(f: Function) -> Function
+pub fn optimization_combine_domains(func: &mut Function)
 {
-    let mut func = f.clone();
     let symbols = func.get_all_symbols();
 
     let mut domains: Vec<(usize, Value, Vec<usize>)> = vec![];
@@ -616,6 +536,13 @@ pub fn optimization_combine_domains(f: Function) -> Function
     // Find all domains
     for symbol in symbols
     {
+        // Arguments are given their value on entry, and a variable whose address is taken can be
+        // used through a pointer, so neither can share with another register
+        if (!symbol.is_register() && func.arguments.iter().any(|(name, _)| name == &symbol.title)) || is_address_taken(&func, &symbol)
+        {
+            continue;
+        }
+
         let domain = func.get_register_domain(Value::Symbol(symbol.clone()));
 
         domains.push((domain.len(), Value::Symbol(symbol.clone()), domain.clone()));
@@ -712,30 +639,203 @@ pub fn optimization_combine_domains(f: Function) -> Function
             }
         }
     }
+}
 
-    func
+/// Check if the address of a symbol is taken anywhere in a function
+fn is_address_taken(func: &Function, symbol: &Symbol) -> bool
+{
+    func.instructions.values().any(|inst| matches!(inst.opcode, OpCode::Ref | OpCode::Array | OpCode::Index | OpCode::Member) &&
+        inst.arguments.iter().any(|arg| matches!(arg, Value::Symbol(other) if other.key() == symbol.key())))
 }
 
 /// Remove redundant moves
-pub fn optimization_redundant_moves(f: Function) -> Function
+pub fn optimization_redundant_moves(func: &mut Function)
 {
-    let mut func = f.clone();
 
-    for (i, instruction) in &func.instructions.clone()
+    for (i, instruction) in &func.ordered_instructions()
     {
         if instruction.opcode == OpCode::Mov && instruction.arguments[0] == instruction.arguments[1] 
         {
             func.change_to_nop(*i);
         }
     }  
+}
+
+/// Reuse a value loaded through a pointer for later loads through the same pointer in the same
+/// block, as long as nothing in between may have changed the memory it points to
+pub fn optimization_redundant_loads(func: &mut Function)
+{
+    let mut replacements = vec![];
+
+    {
+        let analysis = AliasAnalysis::new(func);
+
+        for i in 0..func.instructions.len()
+        {
+            let (loaded, pointer) = match func.instructions.get(&i)
+            {
+                Some(inst) if inst.opcode == OpCode::Deref => (inst.arguments[0].clone(), inst.arguments[1].clone()),
+                _ => continue
+            };
+
+            for j in (i + 1)..func.instructions.len()
+            {
+                // A label starts a new block, which may be reached from somewhere else
+                if func.labels.contains_key(&j)
+                {
+                    break;
+                }
+
+                let inst = match func.instructions.get(&j)
+                {
+                    Some(inst) => inst,
+                    None => break
+                };
 
-    func
+                if inst.opcode == OpCode::Deref && inst.arguments[1] == pointer
+                {
+                    replacements.push((j, Instruction::new(OpCode::Mov, vec![inst.arguments[0].clone(), loaded.clone()])));
+                }
+                else if ends_block(inst) || writes_value(inst, &pointer) || writes_value(inst, &loaded) || may_write_memory(&analysis, inst, &pointer)
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    for (index, inst) in replacements
+    {
+        func.instructions.insert(index, inst);
+    }
+}
+
+/// Remove stores through a pointer which are overwritten by a later store through the same pointer
+/// in the same block, before anything may read the memory
+pub fn optimization_dead_stores(func: &mut Function)
+{
+    let mut dead = vec![];
+
+    {
+        let analysis = AliasAnalysis::new(func);
+
+        for i in 0..func.instructions.len()
+        {
+            let (pointer, datatype) = match func.instructions.get(&i).and_then(|inst| stored_through(&analysis, inst))
+            {
+                Some(store) => store,
+                None => continue
+            };
+
+            for j in (i + 1)..func.instructions.len()
+            {
+                if func.labels.contains_key(&j)
+                {
+                    break;
+                }
+
+                let inst = match func.instructions.get(&j)
+                {
+                    Some(inst) => inst,
+                    None => break
+                };
+
+                if ends_block(inst) || writes_value(inst, &pointer) || may_read_memory(&analysis, inst, &pointer)
+                {
+                    break;
+                }
+
+                if let Some((next_pointer, next_datatype)) = stored_through(&analysis, inst)
+                {
+                    if analysis.alias(&pointer, &next_pointer) == Alias::Must && next_datatype == datatype
+                    {
+                        dead.push(i);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    for index in dead
+    {
+        func.change_to_nop(index);
+    }
+}
+
+/// Check if an instruction leaves the block, or calls a function which may do anything to memory
+fn ends_block(inst: &Instruction) -> bool
+{
+    matches!(inst.opcode, OpCode::Jmp | OpCode::Ret | OpCode::Call |
+        OpCode::Beq | OpCode::Bne | OpCode::Blt | OpCode::Bgt | OpCode::Ble | OpCode::Bge)
+}
+
+/// Check if an instruction writes to a register or variable
+fn writes_value(inst: &Instruction, value: &Value) -> bool
+{
+    !matches!(inst.opcode, OpCode::Push | OpCode::Nop) && inst.arguments.first() == Some(value)
+}
+
+/// Get the pointer an instruction stores through, and the type stored, writing to a reference
+/// stores through the pointer it was made from
+fn stored_through(analysis: &AliasAnalysis, inst: &Instruction) -> Option<(Value, DataType)>
+{
+    match inst.arguments.first().and_then(get_value_type)
+    {
+        Some(datatype) if datatype.is_ref && !matches!(inst.opcode, OpCode::Cast | OpCode::Push | OpCode::Nop) =>
+        {
+            let base = analysis.reference_base(&inst.arguments[0]).unwrap_or_else(|| inst.arguments[0].clone());
+            Some((base, datatype))
+        },
+        _ => None
+    }
+}
+
+/// Check if an instruction may change the memory a pointer points to
+fn may_write_memory(analysis: &AliasAnalysis, inst: &Instruction, pointer: &Value) -> bool
+{
+    match stored_through(analysis, inst)
+    {
+        Some((base, _)) => analysis.alias(pointer, &base) != Alias::No,
+        None => false
+    }
+}
+
+/// Check if an instruction may read the memory a pointer points to, either through a pointer or by
+/// reading the local variable it points into
+fn may_read_memory(analysis: &AliasAnalysis, inst: &Instruction, pointer: &Value) -> bool
+{
+    let reads = match inst.opcode
+    {
+        OpCode::Push => &inst.arguments[..],
+        _ => inst.arguments.get(1..).unwrap_or_default()
+    };
+
+    if matches!(inst.opcode, OpCode::Deref | OpCode::Array) && analysis.alias(pointer, &inst.arguments[1]) != Alias::No
+    {
+        return true;
+    }
+
+    let local = analysis.local_of(pointer);
+
+    reads.iter().any(|value| match value
+    {
+        Value::Symbol(symbol) if local.as_ref() == Some(&symbol.key()) => true,
+        Value::Symbol(symbol) if symbol.datatype.is_ref =>
+        {
+            match analysis.reference_base(value)
+            {
+                Some(base) => analysis.alias(pointer, &base) != Alias::No,
+                None => true
+            }
+        },
+        _ => false
+    })
 }
 
 /// Remove redundant labels
-pub fn optimization_redundant_labels(f: Function) -> Function
+pub fn optimization_redundant_labels(func: &mut Function)
 {
-    let mut func = f.clone();
 
     // Loop over every instruction
     for i in 0..func.instructions.len()
@@ -775,16 +875,13 @@ pub fn optimization_redundant_labels(f: Function) -> Function
             }
         }
     }
-
-    func
 }
 
 /// Perform arithmatic operations on constants
-pub fn optimization_arithmatic_constants(f: Function) -> Function
+pub fn optimization_arithmatic_constants(func: &mut Function)
 {
-    let mut func = f.clone();
 
-    for (i, instruction) in &func.instructions.clone()
+    for (i, instruction) in &func.ordered_instructions()
     {
         if instruction.arguments.len() > 2
         {
@@ -816,12 +913,105 @@ pub fn optimization_arithmatic_constants(f: Function) -> Function
                                 func.instructions.insert(*i, new_inst);
                             }
                         },
+                        OpCode::Rotl | OpCode::Rotr =>
+                        {
+                            if let Some(value) = fold_rotate(instruction.opcode, lit0, lit1)
+                            {
+                                let mut new_inst = instruction.clone();
+
+                                new_inst.opcode = OpCode::Mov;
+                                new_inst.arguments = vec![new_inst.arguments[0].clone(), Value::Literal(Literal::new(value, lit0.datatype))];
+
+                                func.instructions.insert(*i, new_inst);
+                            }
+                        },
                         _ => {}
                     }
                 }
             }
         }
+        else if instruction.arguments.len() == 2
+        {
+            if let Value::Literal(lit) = instruction.arguments[1]
+            {
+                if let Some(value) = fold_bit_operation(instruction.opcode, lit)
+                {
+                    let mut new_inst = instruction.clone();
+
+                    new_inst.opcode = OpCode::Mov;
+                    new_inst.arguments[1] = Value::Literal(Literal::new(value, lit.datatype));
+
+                    func.instructions.insert(*i, new_inst);
+                }
+            }
+        }
     }  
+}
+
+/// Evaluate a bit manipulation builtin on a constant, giving None for any other operation
+pub(super) fn fold_bit_operation(opcode: OpCode, lit: Literal) -> Option<i128>
+{
+    let bits = bits_of(lit.datatype.raw_type)?;
+
+    if lit.datatype.num_ptr > 0
+    {
+        return None;
+    }
+
+    let value = unsigned_bits(lit.value, bits);
 
-    func
+    let result = match opcode
+    {
+        OpCode::Clz => (value.leading_zeros() - (128 - bits)) as u128,
+        OpCode::Ctz => value.trailing_zeros().min(bits) as u128,
+        OpCode::Popcnt => value.count_ones() as u128,
+        OpCode::Bswap if bits < 16 => value,
+        OpCode::Bswap => value.swap_bytes() >> (128 - bits),
+        _ => {return None;}
+    };
+
+    Some(from_unsigned_bits(result, bits, lit.datatype.is_signed()))
+}
+
+/// Evaluate a rotate of a constant by a constant, the amount is taken modulo the width of the type
+pub(super) fn fold_rotate(opcode: OpCode, lit0: Literal, lit1: Literal) -> Option<i128>
+{
+    let bits = bits_of(lit0.datatype.raw_type)?;
+
+    if lit0.datatype.num_ptr > 0
+    {
+        return None;
+    }
+
+    let value = unsigned_bits(lit0.value, bits);
+    let amount = (unsigned_bits(lit1.value, bits) % bits as u128) as u32;
+
+    if amount == 0
+    {
+        return Some(from_unsigned_bits(value, bits, lit0.datatype.is_signed()));
+    }
+
+    let (left, right) = if opcode == OpCode::Rotl {(amount, bits - amount)} else {(bits - amount, amount)};
+    let result = ((value << left) | (value >> right)) & ((1u128 << bits) - 1);
+
+    Some(from_unsigned_bits(result, bits, lit0.datatype.is_signed()))
+}
+
+/// Get the bits of a value as the unsigned integer the width of its type
+fn unsigned_bits(value: i128, bits: u32) -> u128
+{
+    (value as u128) & ((1u128 << bits) - 1)
+}
+
+/// Reinterpret the bits of an unsigned integer in the signedness of its type
+fn from_unsigned_bits(value: u128, bits: u32, signed: bool) -> i128
+{
+    if signed && value >> (bits - 1) != 0
+    {
+        value as i128 - (1i128 << bits)
+    }
+    else
+    {
+        value as i128
+    }
 }
\ No newline at end of file
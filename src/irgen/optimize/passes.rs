@@ -0,0 +1,227 @@
+use crate::irgen::Function;
+use crate::cli::{Error, Options};
+use crate::plugin::{PassStage, plugin_passes};
+
+use super::optimizations::*;
+
+/// A named optimization pass
+pub struct Pass
+{
+    pub name: &'static str,
+    pub run: fn(&mut Function)
+}
+
+/// Every pass the pass manager can run, in no particular order
+pub static PASSES: &[Pass] = &[
+    Pass {name: "remove-nop", run: optimization_remove_nop},
+    Pass {name: "clean-registers", run: optimization_clean_registers},
+    Pass {name: "remove-casts", run: optimization_remove_casts},
+    Pass {name: "jump-chaining", run: optimization_jump_chaining},
+    Pass {name: "arithmatic-constants", run: optimization_arithmatic_constants},
+    Pass {name: "remove-unused-registers", run: optimization_remove_unused_registers},
+    Pass {name: "redundant-moves", run: optimization_redundant_moves},
+    Pass {name: "redundant-loads", run: optimization_redundant_loads},
+    Pass {name: "dead-stores", run: optimization_dead_stores},
+    Pass {name: "dead-code", run: optimization_dead_code},
+    Pass {name: "redundant-labels", run: optimization_redundant_labels},
+    Pass {name: "remove-unused-labels", run: optimization_remove_unused_labels},
+    Pass {name: "combine-domains", run: optimization_combine_domains}
+];
+
+/// Look up a pass by name, among the built in passes and those added by plugins
+fn find_pass(name: &str) -> Option<&'static Pass>
+{
+    PASSES.iter().find(|pass| pass.name == name).or_else(|| plugin_passes().into_iter().map(|plugin| plugin.pass).find(|pass| pass.name == name))
+}
+
+/// Get the name of every pass, those built in in alphabetical order followed by those added by
+/// plugins
+pub fn pass_names() -> Vec<&'static str>
+{
+    let mut names: Vec<&'static str> = PASSES.iter().map(|pass| pass.name).collect();
+    names.sort_unstable();
+
+    names.extend(plugin_passes().into_iter().map(|plugin| plugin.pass.name));
+    names
+}
+
+/// Runs the optimization pipeline over functions, optionally dumping the IR around passes
+#[derive(Debug, Clone)]
+pub struct PassManager
+{
+    level: usize,
+    combine: bool,
+    dump_before: Vec<String>,
+    dump_after: Vec<String>,
+    function_filter: Vec<String>
+}
+
+impl PassManager
+{
+    /// Generate a new pass manager for the given optimization level
+    pub fn new(level: usize, combine: bool) -> Self
+    {
+        Self
+        {
+            level,
+            combine,
+            dump_before: vec![],
+            dump_after: vec![],
+            function_filter: vec![]
+        }
+    }
+
+    /// Generate a new pass manager, reading the IR dump flags from the command line options
+    pub fn from_options(level: usize, combine: bool, options: &Options) -> Result<Self, Error>
+    {
+        let mut manager = Self::new(level, combine);
+
+        manager.dump_before = Self::pass_names(options, "--dump-ir-before")?;
+        manager.dump_after = Self::pass_names(options, "--dump-ir-after")?;
+
+        if let Some(names) = options.map.get("--function")
+        {
+            manager.function_filter = names.clone();
+        }
+
+        Ok(manager)
+    }
+
+    /// Read and validate the pass names passed to a flag
+    fn pass_names(options: &Options, flag: &str) -> Result<Vec<String>, Error>
+    {
+        let names = match options.map.get(flag)
+        {
+            Some(names) => names.clone(),
+            None => {return Ok(vec![]);}
+        };
+
+        for name in &names
+        {
+            if name != "all" && find_pass(name).is_none()
+            {
+                return Err(Error::fatal_error(&format!("Unknown pass '{}' passed to {} (expected 'all' or one of {})", name, flag, pass_names().join(", "))));
+            }
+        }
+
+        Ok(names)
+    }
+
+    /// Check if the IR should be dumped for the given pass list and function
+    fn should_dump(&self, list: &[String], pass: &str, func: &Function) -> bool
+    {
+        (self.function_filter.is_empty() || self.function_filter.contains(&func.name)) &&
+            list.iter().any(|name| name == "all" || name == pass)
+    }
+
+    /// Run a single pass by name over a function, dumping the IR around it if requested
+    pub fn run_pass(&self, name: &str, func: &mut Function)
+    {
+        let pass = find_pass(name).unwrap();
+        let _span = tracing::trace_span!("pass", pass = name).entered();
+        let before = func.instructions.len();
+
+        if self.should_dump(&self.dump_before, name, func)
+        {
+            eprintln!("*** IR Dump Before {} ({}) ***\n{}", name, func.name, func);
+        }
+
+        (pass.run)(func);
+        tracing::trace!(before, after = func.instructions.len(), "instructions");
+
+        if self.should_dump(&self.dump_after, name, func)
+        {
+            eprintln!("*** IR Dump After {} ({}) ***\n{}", name, func.name, func);
+        }
+    }
+
+    /// Run the optimization pipeline over a function, at the level given by an #[optimize(..)]
+    /// attribute on the function if there is one, otherwise at the global level
+    pub fn run(&self, func: &mut Function)
+    {
+        let level = func.optimization_level().unwrap_or(self.level);
+
+        self.run_plugin_passes(PassStage::Start, func, level);
+        self.run_at_level(func, level, self.combine);
+        self.run_plugin_passes(PassStage::End, func, level);
+    }
+
+    /// Run the passes added by plugins for a stage of the pipeline over a function, in the order
+    /// they were registered, clearing away the nops each leaves behind
+    fn run_plugin_passes(&self, stage: PassStage, func: &mut Function, level: usize)
+    {
+        for plugin in plugin_passes().into_iter().filter(|plugin| plugin.stage == stage && plugin.level <= level)
+        {
+            self.run_pass(plugin.pass.name, func);
+            self.run_pass("remove-nop", func);
+        }
+    }
+
+    /// Run the optimization pipeline over a function at the given level
+    fn run_at_level(&self, func: &mut Function, level: usize, combine: bool)
+    {
+        self.run_pass("remove-nop", func);
+
+        let mut last_loop = false;
+
+        loop
+        {
+            let last_length = func.instructions.len();
+
+            // Level 2 Optimizations (Clean Register Usage, Reuse Loads and Remove Dead Stores)
+            if level >= 2
+            {
+                self.run_pass("clean-registers", func);
+                self.run_pass("remove-nop", func);
+                self.run_pass("redundant-loads", func);
+                self.run_pass("dead-stores", func);
+                self.run_pass("remove-nop", func);
+            }
+
+            // Level 1 Optimizations (Remove Casts and Jump Chaining)
+            if level >= 1
+            {
+                self.run_pass("remove-casts", func);
+                self.run_pass("remove-nop", func);
+                self.run_pass("jump-chaining", func);
+                self.run_pass("remove-nop", func);
+            }
+
+            // Level 0 Optimizations (Constant Folding, Clean Branches, Remove Unused Registers, Remove Dead Code, Remove Unused Labels, Remove Nop's)
+            self.run_pass("arithmatic-constants", func);
+            self.run_pass("remove-unused-registers", func);
+            self.run_pass("remove-nop", func);
+            self.run_pass("redundant-moves", func);
+            self.run_pass("remove-nop", func);
+            self.run_pass("dead-code", func);
+            self.run_pass("remove-nop", func);
+            self.run_pass("redundant-labels", func);
+            self.run_pass("remove-unused-labels", func);
+            self.run_pass("remove-nop", func);
+
+            self.run_plugin_passes(PassStage::Loop, func, level);
+
+            // If the code has changed length, keep going
+            if func.instructions.len() == last_length
+            {
+                if last_loop
+                {
+                    break;
+                }
+
+                last_loop = true;
+            }
+            else
+            {
+                last_loop = false;
+            }
+        }
+
+        // If the combine register flag is set, combine the domains of registers
+        if combine
+        {
+            self.run_pass("combine-domains", func);
+            self.run_at_level(func, level, false);
+        }
+    }
+}
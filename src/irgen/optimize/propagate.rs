@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+
+use crate::irgen::{Function, GlobalVariable, Instruction, Literal, OpCode, Symbol, Value, bits_of, wrap_constant};
+
+/// How a function reaches a global variable, by the index of the instruction
+enum Access
+{
+    /// Loaded into a register
+    Load(usize),
+    /// Made into a reference register
+    Reference(usize),
+    /// Stored to with the value it starts with through a reference register
+    Store(usize),
+    /// Loaded through a reference register
+    LoadThrough(usize)
+}
+
+/// Replace every load of a global variable which only ever holds the value it starts with by that
+/// value, and drop the variable from the global variables of the library, along with the stores
+/// giving it the value it already has. This only applies to a whole program, one defining `main`,
+/// as the C code a library is linked with could change its variables, and only to integers and
+/// pointers. A variable whose address is kept anywhere, such as in a variable or passed to a
+/// function, is left alone, as it could be changed through that pointer
+pub fn propagate_constant_globals(functions: &mut [Function], globals: &mut Vec<GlobalVariable>)
+{
+    if !functions.iter().any(|function| function.name == "main")
+    {
+        return;
+    }
+
+    let mut dropped = vec![];
+
+    for global in globals.iter()
+    {
+        if global.datatype.is_struct() || global.datatype.element_type().is_some()
+        {
+            continue;
+        }
+
+        let accesses: Option<Vec<Vec<Access>>> = functions.iter().map(|function| accesses(function, global)).collect();
+
+        let accesses = match accesses
+        {
+            Some(accesses) => accesses,
+            None => continue
+        };
+
+        for (function, accesses) in functions.iter_mut().zip(accesses)
+        {
+            replace_accesses(function, &accesses, global.value);
+        }
+
+        dropped.push(global.name.clone());
+    }
+
+    globals.retain(|global| !dropped.contains(&global.name));
+}
+
+/// Find every access of a function to a global variable, giving None if any of them could change
+/// it to something other than the value it starts with or lets its address escape
+fn accesses(function: &Function, global: &GlobalVariable) -> Option<Vec<Access>>
+{
+    let is_global = |value: &Value| matches!(value, Value::Global(symbol) if symbol.title == global.name);
+
+    // The reference registers made from the address of the variable, and where each is made
+    let mut references: HashMap<String, usize> = HashMap::new();
+    let mut result = vec![];
+
+    for (i, instruction) in &function.instructions
+    {
+        match (instruction.opcode, instruction.arguments.as_slice())
+        {
+            (OpCode::Deref, [Value::Symbol(_), address]) if is_global(address) => result.push(Access::Load(*i)),
+            (OpCode::Cast, [Value::Symbol(reference), address]) if is_global(address) && reference.datatype.is_ref =>
+            {
+                // A register made a reference more than once could reach something else
+                if references.contains_key(&reference.key())
+                {
+                    return None;
+                }
+
+                references.insert(reference.key(), *i);
+            },
+            (_, arguments) if arguments.iter().any(is_global) => return None,
+            _ => {}
+        }
+    }
+
+    for (i, instruction) in &function.instructions
+    {
+        let made = |symbol: &Symbol| references.get(&symbol.key()).copied();
+
+        match (instruction.opcode, instruction.arguments.as_slice())
+        {
+            (OpCode::Cast, [Value::Symbol(reference), address]) if is_global(address) && made(reference) == Some(*i) => result.push(Access::Reference(*i)),
+            (OpCode::Mov, [Value::Symbol(reference), Value::Literal(literal)]) if made(reference).is_some() =>
+            {
+                if starting_value(literal, global) != global.value
+                {
+                    return None;
+                }
+
+                result.push(Access::Store(*i));
+            },
+            (OpCode::Deref, [Value::Symbol(_), Value::Symbol(reference)]) if made(reference).is_some() => result.push(Access::LoadThrough(*i)),
+            (_, arguments) if arguments.iter().any(|argument| matches!(argument, Value::Symbol(symbol) if made(symbol).is_some())) => return None,
+            _ => {}
+        }
+    }
+
+    Some(result)
+}
+
+/// Get the value a literal stored to a global variable gives it, wrapped to the type of the variable
+fn starting_value(literal: &Literal, global: &GlobalVariable) -> i128
+{
+    if global.datatype.num_ptr > 0
+    {
+        return literal.value;
+    }
+
+    wrap_constant(literal.value, bits_of(global.datatype.raw_type).unwrap_or(64), global.datatype.is_signed())
+}
+
+/// Load the value a global variable starts with in place of each access of a function to it, and
+/// remove the stores to it along with the references they were made through
+fn replace_accesses(function: &mut Function, accesses: &[Access], value: i128)
+{
+    for access in accesses
+    {
+        match *access
+        {
+            Access::Load(i) | Access::LoadThrough(i) =>
+            {
+                let destination = match &function.instructions[&i].arguments[0]
+                {
+                    Value::Symbol(symbol) => symbol.clone(),
+                    _ => unreachable!()
+                };
+
+                let datatype = destination.datatype;
+                function.instructions.insert(i, Instruction::new(OpCode::Mov, vec![Value::Symbol(destination), Value::Literal(Literal::new(value, datatype))]));
+            },
+            Access::Reference(i) | Access::Store(i) => function.change_to_nop(i)
+        }
+    }
+}
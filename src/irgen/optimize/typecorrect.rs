@@ -1,13 +1,12 @@
-use crate::irgen::{Function, Value, DataType, NonPtrType, OpCode};
-use crate::irgen::{force_mutate_type};
+use crate::irgen::{Function, Value, DataType, NonPtrType, OpCode, DATA_TYPE};
+use crate::irgen::{force_mutate_type, compiler_error};
+use crate::cli::Error;
 
 use std::collections::HashMap;
 
 /// Correct the types within the instructions in an IR Function
-pub fn correct_types(f: Function) -> Function
+pub fn correct_types(func: &mut Function) -> Result<(), Error>
 {
-    let mut func = f.clone();
-
     let mut symbol_map: HashMap<String, DataType> = HashMap::new();
 
     loop
@@ -18,7 +17,7 @@ pub fn correct_types(f: Function) -> Function
         {
             if let Some(inst) = func.instructions.get_mut(&i)
             {
-                if inst.opcode == OpCode::Array {continue;}
+                if matches!(inst.opcode, OpCode::Array | OpCode::Index | OpCode::Member | OpCode::Extract | OpCode::Insert | OpCode::Shuffle) {continue;}
 
                 let mut datatype = DataType::new(NonPtrType::Unknown, 0, false);
 
@@ -27,8 +26,9 @@ pub fn correct_types(f: Function) -> Function
                     if match arg
                     {
                         Value::Label(_) => {false},
+                        Value::Data(_) => {if datatype.raw_type == NonPtrType::Unknown {datatype = DATA_TYPE; true} else {false}},
                         Value::Literal(lit) => {if datatype.raw_type == NonPtrType::Unknown && lit.datatype.raw_type != NonPtrType::Unknown {datatype = lit.datatype; true} else {false}},
-                        Value::Symbol(symb) => {if datatype.raw_type == NonPtrType::Unknown && symb.datatype.raw_type != NonPtrType::Unknown {datatype = symb.datatype; true} else {false}},
+                        Value::Symbol(symb) | Value::Global(symb) => {if datatype.raw_type == NonPtrType::Unknown && symb.datatype.raw_type != NonPtrType::Unknown {datatype = symb.datatype; true} else {false}},
                     }
                     {
                         break;
@@ -46,9 +46,9 @@ pub fn correct_types(f: Function) -> Function
 
                     if let Value::Symbol(symb) = v.clone()
                     {
-                        if !symbol_map.contains_key(&symb.title) && symb.datatype.raw_type != NonPtrType::Unknown
+                        if !symbol_map.contains_key(&symb.key()) && symb.datatype.raw_type != NonPtrType::Unknown
                         {
-                            symbol_map.insert(symb.title, symb.datatype);
+                            symbol_map.insert(symb.key(), symb.datatype);
                         }
                     }
 
@@ -67,9 +67,9 @@ pub fn correct_types(f: Function) -> Function
 
                     if let Value::Symbol(mut symb) = v.clone()
                     {
-                        if symb.datatype.raw_type == NonPtrType::Unknown && symbol_map.contains_key(&symb.title)
+                        if symb.datatype.raw_type == NonPtrType::Unknown && symbol_map.contains_key(&symb.key())
                         {
-                            symb.datatype = symbol_map.get(&symb.title).unwrap().clone();
+                            symb.datatype = symbol_map.get(&symb.key()).unwrap().clone();
                             inst.arguments[i] = Value::Symbol(symb);
                             changed = true;
                         }
@@ -104,6 +104,12 @@ pub fn correct_types(f: Function) -> Function
 
                 if let Value::Literal(mut lit) = v.clone()
                 {
+                    // A null which never found a pointer type to take was used as something else
+                    if lit.datatype.raw_type == NonPtrType::Unknown && lit.datatype.num_ptr > 0
+                    {
+                        return compiler_error("E0023", format!("'null' can only be used as a pointer (in function '{}')", func.name));
+                    }
+
                     if lit.datatype.raw_type == NonPtrType::Unknown
                     {
                         lit.datatype = DataType::new(NonPtrType::I32, 0, false);
@@ -113,5 +119,5 @@ pub fn correct_types(f: Function) -> Function
             }
         }
     }
-    func
+    Ok(())
 }
\ No newline at end of file
@@ -1,11 +1,12 @@
-use super::{Function, Value, Literal, Expression, Instruction, OpCode, Symbol, attempt_mutate_type, type_from_parse_tree, identifier_from_parse_tree};
+use super::{Function, Value, Literal, Expression, Instruction, OpCode, Symbol, correct_type_references, implicit_conversion, promote_bool, type_from_parse_tree, identifier_from_parse_tree, check_static_assert};
 
 use crate::cli::Error;
 
-use super::{expected_got_error, compiler_error, compiler_error_loc};
-use super::{DataType, NonPtrType};
+use super::{expected_got_error, compiler_error, compiler_error_loc, compiler_warning_loc};
+use super::{Attribute, loop_attributes_from_parse_tree};
+use super::DataType;
 
-use crate::parser::ParseTreeNode;
+use crate::parser::{ParseTreeNode, ExpressionType};
 
 use crate::tokenizer::{Token, FileLocation};
 
@@ -24,6 +25,7 @@ pub enum StatementType
     WhileStatement,
     DoWhileStatement,
     LoopStatement,
+    ForStatement,
     ReturnStatement,
     ExpressionStatement
 }
@@ -34,9 +36,11 @@ pub struct Statement
 {
     mode: StatementType,
     pub expr: Option<Expression>,
+    conditions: Vec<Expression>,
     children: Vec<Statement>,
     pos: Option<FileLocation>,
-    init_data: Option<(DataType, String)>
+    init_data: Option<(DataType, String)>,
+    attributes: Vec<Attribute>
 }
 
 impl Statement
@@ -48,9 +52,11 @@ impl Statement
         {
             mode,
             expr: None,
+            conditions: vec![],
             children: vec![],
             pos: None,
-            init_data: None
+            init_data: None,
+            attributes: vec![]
         }
     }
 
@@ -61,9 +67,11 @@ impl Statement
         {
             mode,
             expr: None,
+            conditions: vec![],
             children: vec![],
             pos: Some(token.location.clone()),
-            init_data: None
+            init_data: None,
+            attributes: vec![]
         }
     }
 
@@ -74,9 +82,9 @@ impl Statement
     }
 
     /// Generate a new IR Statement from a parse tree node
-    pub fn from_parse_tree_node(node: ParseTreeNode, func: &RefCell<&mut Function>) -> Result<Self, Error>
+    pub fn from_parse_tree_node(node: &ParseTreeNode, func: &RefCell<&mut Function>) -> Result<Self, Error>
     {
-        match &node
+        let result: Result<Self, Error> = match &node
         {
             ParseTreeNode::Empty =>
             {
@@ -105,21 +113,22 @@ impl Statement
                             }
                             else
                             {
-                                compiler_error(format!("Expected 'continue' or 'break', got '{}'", token.data))?;
+                                compiler_error("E0005", format!("Expected 'continue' or 'break', got '{}'", token.data))?;
                                 unreachable!();
                             }
                         },
-                        ParseTreeNode::Expression(_, _) =>
+                        // A lone identifier is also an expression, and is where a misspelled 'break' ends up
+                        ParseTreeNode::Expression(_, _) | ParseTreeNode::Identifier(_) =>
                         {
                             let mut result = Statement::new(StatementType::ExpressionStatement);
 
-                            result.expr = Some(Expression::from_parse_tree_node(children[0].clone(), func)?);
+                            result.expr = Some(Expression::from_parse_tree_node(&children[0], func)?);
 
                             Ok(result)
                         }
                         default =>
                         {
-                            expected_got_error("RawToken or Expression", default.clone())?;
+                            expected_got_error("RawToken or Expression", default)?;
                             unreachable!();
                         }
                     }
@@ -130,19 +139,33 @@ impl Statement
             {
                 let mut result = Statement::new(StatementType::CompoundStatement);
 
-                for child in children
+                for child in children.iter()
                 {
-                    result.add_child(Statement::from_parse_tree_node(child.clone(), func)?)
+                    result.add_child(Statement::from_parse_tree_node(child, func)?)
                 }
 
                 Ok(result)
             },
+            // Static assertions are checked as soon as they are seen and generate no code
+            ParseTreeNode::StaticAssert(_) =>
+            {
+                check_static_assert(&node, &func.borrow().context)?;
+
+                Ok(Statement::new(StatementType::Empty))
+            },
             // Return Statement
             ParseTreeNode::ReturnStatement(children) =>
             {
-                let mut result = Statement::new(StatementType::ReturnStatement);
+                let mut result = match children.get(1)
+                {
+                    Some(ParseTreeNode::RawToken(token)) => Statement::new_with_token(StatementType::ReturnStatement, token),
+                    _ => Statement::new(StatementType::ReturnStatement)
+                };
 
-                result.expr = Some(Expression::from_parse_tree_node(children[0].clone(), func)?);
+                if !matches!(children[0], ParseTreeNode::Empty)
+                {
+                    result.expr = Some(Expression::from_parse_tree_node(&children[0], func)?);
+                }
 
                 Ok(result)
             },
@@ -151,7 +174,13 @@ impl Statement
             {
                 let mut result = Statement::new(StatementType::LoopStatement);
 
-                result.add_child(Statement::from_parse_tree_node(children[0].clone(), func)?);
+                result.add_child(Statement::from_parse_tree_node(&children[0], func)?);
+
+                // Get any attributes placed on the loop
+                if let Some(attributes) = children.get(1)
+                {
+                    result.attributes = loop_attributes_from_parse_tree(attributes)?;
+                }
 
                 Ok(result)
             },
@@ -159,15 +188,36 @@ impl Statement
             ParseTreeNode::IfStatement(children) =>
             {
                 let mut result = Statement::new(StatementType::IfStatement);
+                let mut current = *children;
 
-                // Condition
-                result.expr = Some(Expression::from_parse_tree_node(children[0].clone(), func)?);
+                // Flatten any else if chain into a single list of conditions, each guarding a body
+                loop
+                {
+                    if let Some(warning) = dangling_else_warning(&current)
+                    {
+                        func.borrow_mut().warnings.push(warning);
+                    }
 
-                // Body
-                result.add_child(Statement::from_parse_tree_node(children[1].clone(), func)?);
-                
-                // Clause
-                result.add_child(Statement::from_parse_tree_node(children[2].clone(), func)?);
+                    // Condition
+                    result.conditions.push(condition_from_parse_tree_node(&current[0], func)?);
+
+                    // Body
+                    result.add_child(Statement::from_parse_tree_node(&current[1], func)?);
+
+                    match &current[2]
+                    {
+                        ParseTreeNode::IfStatement(next) =>
+                        {
+                            current = next;
+                        },
+                        clause =>
+                        {
+                            // Clause
+                            result.add_child(Statement::from_parse_tree_node(clause, func)?);
+                            break;
+                        }
+                    }
+                }
 
                 Ok(result)
             },
@@ -177,10 +227,46 @@ impl Statement
                 let mut result = Statement::new(StatementType::WhileStatement);
 
                 // Condition
-                result.expr = Some(Expression::from_parse_tree_node(children[0].clone(), func)?);
+                result.expr = Some(condition_from_parse_tree_node(&children[0], func)?);
+
+                // Body
+                result.add_child(Statement::from_parse_tree_node(&children[1], func)?);
+
+                // Get any attributes placed on the loop
+                if let Some(attributes) = children.get(2)
+                {
+                    result.attributes = loop_attributes_from_parse_tree(attributes)?;
+                }
+
+                Ok(result)
+            },
+            // For Statement
+            ParseTreeNode::ForLoop(children) =>
+            {
+                let mut result = Statement::new(StatementType::ForStatement);
+
+                // Initialization, converted first as it may declare the variables used after it
+                let init = Statement::from_parse_tree_node(&children[0], func)?;
+
+                // Condition, without which the loop runs until it is broken out of
+                if !matches!(children[1], ParseTreeNode::Empty)
+                {
+                    result.expr = Some(condition_from_parse_tree_node(&children[1], func)?);
+                }
 
                 // Body
-                result.add_child(Statement::from_parse_tree_node(children[1].clone(), func)?);
+                result.add_child(Statement::from_parse_tree_node(&children[3], func)?);
+
+                // Step
+                result.add_child(Statement::from_parse_tree_node(&children[2], func)?);
+
+                result.add_child(init);
+
+                // Get any attributes placed on the loop
+                if let Some(attributes) = children.get(4)
+                {
+                    result.attributes = loop_attributes_from_parse_tree(attributes)?;
+                }
 
                 Ok(result)
             },
@@ -190,11 +276,17 @@ impl Statement
                 let mut result = Statement::new(StatementType::DoWhileStatement);
 
                 // Condition
-                result.expr = Some(Expression::from_parse_tree_node(children[0].clone(), func)?);
+                result.expr = Some(condition_from_parse_tree_node(&children[0], func)?);
 
                 // Body
-                result.add_child(Statement::from_parse_tree_node(children[1].clone(), func)?);
+                result.add_child(Statement::from_parse_tree_node(&children[1], func)?);
                 
+                // Get any attributes placed on the loop
+                if let Some(attributes) = children.get(2)
+                {
+                    result.attributes = loop_attributes_from_parse_tree(attributes)?;
+                }
+
                 Ok(result)
             },
             // Initialization
@@ -202,13 +294,13 @@ impl Statement
             {
                 let mut result = Statement::new(StatementType::CompoundStatement);
 
-                let datatype = type_from_parse_tree(children[0].clone())?;
+                let datatype = type_from_parse_tree(&children[0], &func.borrow().context)?;
 
                 match &children[1]
                 {
                     ParseTreeNode::Assignments(assignment_children) =>
                     {
-                        for child in assignment_children
+                        for child in assignment_children.iter()
                         {
                             match child
                             {
@@ -216,10 +308,17 @@ impl Statement
                                 {
                                     let mut temp = Statement::new(StatementType::InitializationStatement);
 
-                                    let s = identifier_from_parse_tree(assignment_data[0].clone())?;
+                                    let s = identifier_from_parse_tree(&assignment_data[0])?;
 
                                     temp.init_data = Some((datatype, s.clone()));
-                                    temp.expr = Some(Expression::from_parse_tree_node(assignment_data[1].clone(), func)?);
+                                    temp.pos = assignment_data[0].first_token().map(|token| token.location.clone());
+
+                                    // A struct declared without a value starts zeroed
+                                    temp.expr = Some(match assignment_data.get(1)
+                                    {
+                                        Some(value) => Expression::from_parse_tree_node(value, func)?,
+                                        None => Expression::new(super::ExpressionType::IntegerLiteral, Some(Value::Literal(Literal::new(0, correct_type_references(datatype)))), vec![])
+                                    });
 
                                     func.borrow_mut().symbol_table.insert(s.clone(), Symbol::new(s.clone(), datatype.clone()));
 
@@ -227,7 +326,7 @@ impl Statement
                                 },
                                 default =>
                                 {
-                                    expected_got_error("an assignment", default.clone())?;
+                                    expected_got_error("an assignment", default)?;
                                     unreachable!();
                                 }
                             }
@@ -235,7 +334,7 @@ impl Statement
                     },
                     default =>
                     {
-                        expected_got_error("assignments", default.clone())?;
+                        expected_got_error("assignments", default)?;
                         unreachable!();
                     }
                 }
@@ -244,8 +343,150 @@ impl Statement
             }
             default =>
             {
-                expected_got_error("a statement", default.clone())
+                expected_got_error("a statement", default)
             }
+        };
+
+        // Statements without a more specific location are placed at their first token
+        result.map(|mut statement|
+        {
+            if statement.pos.is_none()
+            {
+                statement.pos = node.first_token().map(|token| token.location.clone());
+            }
+
+            statement
+        })
+    }
+
+    /// Check if the statement contains a return statement
+    pub fn contains_return(&self) -> bool
+    {
+        match self.mode
+        {
+            StatementType::ReturnStatement => true,
+            _ => self.children.iter().any(|child| child.contains_return())
+        }
+    }
+
+    /// Check if the statement, when inside the body of a loop, can leave that loop, either by a
+    /// break out of it or by a return
+    fn leaves_loop(&self) -> bool
+    {
+        match self.mode
+        {
+            StatementType::BreakStatement | StatementType::ReturnStatement => true,
+            // A break within a nested loop only leaves the nested loop
+            StatementType::WhileStatement | StatementType::DoWhileStatement | StatementType::LoopStatement |
+            StatementType::ForStatement => self.children[0].contains_return(),
+            _ => self.children.iter().any(|child| child.leaves_loop())
+        }
+    }
+
+    /// Check if the statement is a loop which can never be left, that is a `loop` or a loop with a
+    /// constant nonzero condition, whose body contains no break or return
+    pub fn is_infinite_loop(&self) -> bool
+    {
+        self.always_loops() && !self.children[0].leaves_loop()
+    }
+
+    /// Check if every path through the statement ends in an infinite loop
+    pub fn never_completes(&self) -> bool
+    {
+        match self.mode
+        {
+            StatementType::CompoundStatement => self.children.iter().any(|child| child.never_completes()),
+            StatementType::IfStatement => self.children.iter().all(|child| child.never_completes()),
+            _ => self.is_infinite_loop()
+        }
+    }
+
+    /// Check if the statement contains a break which leaves the loop it is in
+    fn breaks_loop(&self) -> bool
+    {
+        match self.mode
+        {
+            StatementType::BreakStatement => true,
+            StatementType::WhileStatement | StatementType::DoWhileStatement | StatementType::LoopStatement |
+            StatementType::ForStatement => false,
+            _ => self.children.iter().any(|child| child.breaks_loop())
+        }
+    }
+
+    /// Check if a loop's condition always holds, so the loop can only be left from its body
+    fn always_loops(&self) -> bool
+    {
+        match self.mode
+        {
+            StatementType::LoopStatement => true,
+            StatementType::ForStatement => self.expr.as_ref().map_or(true, |e| matches!(e.constant_value(), Some(value) if value != 0)),
+            StatementType::WhileStatement | StatementType::DoWhileStatement =>
+            {
+                matches!(self.expr.as_ref().and_then(|e| e.constant_value()), Some(value) if value != 0)
+            },
+            _ => false
+        }
+    }
+
+    /// Check if control can reach the end of the statement and carry on to the one after it
+    pub fn falls_through(&self) -> bool
+    {
+        match self.mode
+        {
+            StatementType::ReturnStatement | StatementType::BreakStatement | StatementType::ContinueStatement => false,
+            StatementType::CompoundStatement => self.children.iter().all(|child| child.falls_through()),
+            StatementType::IfStatement => self.children.iter().any(|child| child.falls_through()),
+            StatementType::WhileStatement | StatementType::DoWhileStatement | StatementType::LoopStatement |
+            StatementType::ForStatement =>
+            {
+                !self.always_loops() || self.children[0].breaks_loop()
+            },
+            _ => true
+        }
+    }
+
+    /// Describe why control does not continue on past a statement
+    fn describe_exit(&self) -> &'static str
+    {
+        match self.mode
+        {
+            StatementType::ReturnStatement => "a return statement",
+            StatementType::BreakStatement => "a break statement",
+            StatementType::ContinueStatement => "a continue statement",
+            _ if self.is_infinite_loop() => "an infinite loop",
+            StatementType::WhileStatement | StatementType::DoWhileStatement | StatementType::LoopStatement |
+            StatementType::ForStatement => "a loop which can only be left by returning",
+            StatementType::IfStatement => "an if statement where no branch continues on",
+            _ => "a block which never completes"
+        }
+    }
+
+    /// Warn about any code placed after a statement which control never continues on from, such as
+    /// a return, break or continue, or an infinite loop, as it can never run
+    pub fn check_unreachable(&self, warnings: &mut Vec<Error>)
+    {
+        // Find the first statement in a block which is never reached
+        let mut unreachable = None;
+
+        if let StatementType::CompoundStatement = self.mode
+        {
+            if let Some(index) = self.children.iter().position(|child| !child.falls_through())
+            {
+                unreachable = (index + 1..self.children.len())
+                    .find(|i| !matches!(self.children[*i].mode, StatementType::Empty))
+                    .map(|i| (i, self.children[index].describe_exit()));
+            }
+        }
+
+        // Report the warnings in the order they appear in the source
+        for (i, child) in self.children.iter().enumerate()
+        {
+            if let Some((_, reason)) = unreachable.filter(|(index, _)| *index == i)
+            {
+                warnings.push(compiler_warning_loc("W0001", format!("Unreachable code after {}", reason), &child.pos));
+            }
+
+            child.check_unreachable(warnings);
         }
     }
 
@@ -262,38 +503,24 @@ impl Statement
                 // Loop over all children and render those statements
                 for child in &self.children
                 {
-                    child.render(func.clone())?;
+                    child.render(func)?;
                 }
             },
-            StatementType::ContinueStatement =>
+            StatementType::ContinueStatement | StatementType::BreakStatement =>
             {
-                let mut f = func.borrow_mut();
+                let is_break = matches!(self.mode, StatementType::BreakStatement);
 
-                match f.get_continue()
-                {
-                    Some(v) =>
-                    {
-                        f.add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(v)]))
-                    },
-                    None => 
-                    {
-                        compiler_error_loc(format!("Cannot use continue statement outside of loop"), &self.pos)?
-                    }
-                }
-            },
-            StatementType::BreakStatement =>
-            {
-                let mut f = func.borrow_mut();
+                let target = if is_break {func.borrow().get_break()} else {func.borrow().get_continue()};
 
-                match f.get_break()
+                match target
                 {
-                    Some(v) =>
+                    Some(label) =>
                     {
-                        f.add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(v)]))
+                        func.borrow_mut().add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(label)]))
                     },
-                    None => 
+                    None =>
                     {
-                        compiler_error_loc(format!("Cannot use break statement outside of loop"), &self.pos)?
+                        compiler_error_loc("E0004", format!("Cannot use '{}' outside of a loop", if is_break {"break"} else {"continue"}), &self.pos)?
                     }
                 }
             },
@@ -303,9 +530,10 @@ impl Statement
                 let symbol = func.borrow_mut().symbol_table.get(&self.init_data.clone().unwrap().1).unwrap().clone();
 
                 // Render the expression
-                e.render(func.clone())?;
+                e.render(func)?;
 
-                let value = attempt_mutate_type(e.value(func)?, symbol.datatype);
+                let value = promote_bool(func, e.value(func)?, correct_type_references(symbol.datatype));
+                let value = implicit_conversion(func, value, symbol.datatype, &format!("to initialize '{}'", symbol.title), &self.pos)?;
 
                 func.borrow_mut().add_instruction(Instruction::new(OpCode::Alloc, vec![
                     Value::Symbol(symbol),
@@ -314,128 +542,81 @@ impl Statement
             },
             StatementType::IfStatement =>
             {
-                let body = func.borrow_mut().get_label();
-                let clause = func.borrow_mut().get_label();
-                let exit = func.borrow_mut().get_label();
+                let exit = func.borrow_mut().get_label_at("if.end", &self.pos);
 
-                let mut e = self.expr.clone().unwrap();
+                // Test each condition in turn, running the body of the first which holds
+                for (condition, body) in self.conditions.iter().zip(&self.children)
+                {
+                    let body_label = func.borrow_mut().get_label_at("if.then", &body.pos);
+                    let next = func.borrow_mut().get_label_at("if.else", &body.pos);
 
-                // Render the expression
-                e.render(func.clone())?;
-
-                // Perform the comparison
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Bne, vec![
-                    e.value(func)?, 
-                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 0, false))),
-                    Value::Label(body.clone()),
-                    Value::Label(clause.clone())]));
-                
-                // Place the body label
-                func.borrow_mut().place_label_here(body.clone());
+                    // Test the condition
+                    condition.clone().render_condition(func, &body_label, &next)?;
+
+                    // Place the body label
+                    func.borrow_mut().place_label_here(body_label);
 
-                // Render the body
-                self.children[0].render(func)?;
+                    // Render the body
+                    body.render(func)?;
 
-                // Add a jump statement to skip the clause
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(exit.clone())]));
+                    // Add a jump statement to skip the remaining tests
+                    func.borrow_mut().add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(exit.clone())]));
 
-                // Place the clause label
-                func.borrow_mut().place_label_here(clause.clone());
+                    // Place the label for the next test
+                    func.borrow_mut().place_label_here(next);
+                }
 
                 // Render the clause
-                self.children[1].render(func)?;
+                self.children[self.conditions.len()].render(func)?;
 
                 // Place the exit label
-                func.borrow_mut().place_label_here(exit.clone());
+                func.borrow_mut().place_label_here(exit);
             },
             StatementType::WhileStatement =>
             {
-                let (start, end) = func.borrow_mut().enter_loop();
-                let allow = func.borrow_mut().get_label();
-
-                let mut e = self.expr.clone().unwrap();
-
-                // Add a label to the start of the loop
-                func.borrow_mut().place_label_here(start.clone());
-
-                // Render the expression
-                e.render(func.clone())?;
-
-                // Perform the comparison
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Bne, vec![
-                    e.value(func)?, 
-                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 0, false))),
-                    Value::Label(allow.clone()),
-                    Value::Label(end.clone())]));
-                func.borrow_mut().place_label_here(allow.clone());
-
-                // Render the statement within the loop
-                self.children[0].render(func)?;
-                
-                // Add a jump statement to loop back to the top
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(start)]));
-
-                // Add a label to the end of the loop
-                func.borrow_mut().place_label_here(end);
-
-                func.borrow_mut().exit_loop();
+                self.render_loop(func, "while", self.expr.clone(), true)?;
             },
             StatementType::DoWhileStatement =>
             {
-                let (start, end) = func.borrow_mut().enter_loop();
-
-                let mut e = self.expr.clone().unwrap();
-
-                // Add a label to the start of the loop
-                func.borrow_mut().place_label_here(start.clone());
-
-                // Render the statement within the loop
-                self.children[0].render(func)?;
-
-                // Render the expression
-                e.render(func.clone())?;
-
-                // Perform the comparison
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Bne, vec![
-                    e.value(func)?, 
-                    Value::Literal(Literal::new(0, DataType::new(NonPtrType::Unknown, 0, false))),
-                    Value::Label(start.clone()),
-                    Value::Label(end.clone())]));
-            
-                // Add a label to the end of the loop
-                func.borrow_mut().place_label_here(end);
-
-                func.borrow_mut().exit_loop();
+                self.render_loop(func, "do", self.expr.clone(), false)?;
             },
             StatementType::LoopStatement =>
             {
-                let (start, end) = func.borrow_mut().enter_loop();
-
-                // Add a label to the start of the loop
-                func.borrow_mut().place_label_here(start.clone());
-
-                // Render the statement within the loop
-                self.children[0].render(func)?;
-
-                // Add a jump statement to loop back to the top
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(start)]));
-
-                // Add a label to the end of the loop
-                func.borrow_mut().place_label_here(end);
+                self.render_loop(func, "loop", None, true)?;
+            },
+            StatementType::ForStatement =>
+            {
+                // The initialization runs once, ahead of the loop
+                self.children[2].render(func)?;
 
-                func.borrow_mut().exit_loop();
+                self.render_loop(func, "for", self.expr.clone(), true)?;
             },
             StatementType::ReturnStatement =>
             {
-                let mut e = self.expr.clone().unwrap();
+                let (name, return_type) = {let f = func.borrow(); (f.name.clone(), f.return_type)};
 
-                // Render the expression
-                e.render(func.clone())?;
+                match (self.expr.clone(), return_type.is_void())
+                {
+                    (Some(mut e), false) =>
+                    {
+                        // Render the expression
+                        e.render(func)?;
 
-                // Then add the return statement
-                let val = e.value(func)?;
-                let ret_val = func.borrow().return_value.clone();
-                func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![ret_val, val]));
+                        // Then add the return statement, converting the value to the return type
+                        let val = implicit_conversion(func, e.value(func)?, return_type, &format!("when returning from '{}'", name), &self.pos)?;
+                        let ret_val = func.borrow().return_value.clone();
+                        func.borrow_mut().add_instruction(Instruction::new(OpCode::Mov, vec![ret_val, val]));
+                    },
+                    (Some(_), true) =>
+                    {
+                        return compiler_error_loc("E0023", format!("Cannot return a value from the void function '{}'", name), &self.pos);
+                    },
+                    (None, false) =>
+                    {
+                        return compiler_error_loc("E0023", format!("Function '{}' must return a value of type '{}'", name, return_type), &self.pos);
+                    },
+                    (None, true) => {}
+                }
 
                 // Then jump to the exit
                 func.borrow_mut().add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(String::from("exit"))]))
@@ -444,10 +625,130 @@ impl Statement
             StatementType::ExpressionStatement =>
             {
                 // Just render the expression
-                self.expr.clone().unwrap().render(func.clone())?;
+                self.expr.clone().unwrap().render(func)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Render a loop around the first child of the statement, shared by every kind of loop so
+    /// break and continue behave the same in each
+    ///
+    /// A loop without a condition runs forever, otherwise the condition is tested either in the
+    /// header before each run of the body or in the latch after it. The step of a for loop, its
+    /// second child, is rendered at the start of the latch so a continue runs it too
+    fn render_loop(&self, func: &RefCell<&mut Function>, construct: &str, condition: Option<Expression>, test_first: bool) -> Result<(), Error>
+    {
+        let labels = func.borrow_mut().enter_loop(construct, &self.pos);
+
+        // Hints for LLVM are attached to the branches back to the header
+        if !self.attributes.is_empty()
+        {
+            func.borrow_mut().loop_hints.insert(labels.header.clone(), self.attributes.clone());
+        }
+
+        // Header
+        func.borrow_mut().place_label_here(labels.header.clone());
+
+        if let (Some(e), true) = (&condition, test_first)
+        {
+            e.clone().render_condition(func, &labels.body, &labels.exit)?;
+        }
+
+        // Body
+        func.borrow_mut().place_label_here(labels.body.clone());
+
+        self.children[0].render(func)?;
+
+        // Latch
+        func.borrow_mut().place_label_here(labels.latch.clone());
+
+        if let Some(step) = self.children.get(1)
+        {
+            step.render(func)?;
+        }
+
+        match (&condition, test_first)
+        {
+            (Some(e), false) =>
+            {
+                e.clone().render_condition(func, &labels.header, &labels.exit)?;
+            },
+            _ =>
+            {
+                func.borrow_mut().add_instruction(Instruction::new(OpCode::Jmp, vec![Value::Label(labels.header.clone())]));
             }
         }
 
+        // Exit
+        func.borrow_mut().place_label_here(labels.exit.clone());
+
+        func.borrow_mut().exit_loop();
+
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Convert the condition of an if statement or loop, warning if the condition is an assignment as
+/// it is most likely meant to be a comparison
+fn condition_from_parse_tree_node(node: &ParseTreeNode, func: &RefCell<&mut Function>) -> Result<Expression, Error>
+{
+    if let Some(assignment) = assignment_in_condition(node)
+    {
+        let warning = compiler_warning_loc("W0003", String::from("Assignment used as a condition"), &assignment.first_token().map(|token| token.location.clone()));
+        func.borrow_mut().warnings.push(warning.with_hint("did you mean `==`?"));
+    }
+
+    Expression::from_parse_tree_node(node, func)
+}
+
+/// Find an assignment whose value is used as the truth value of a condition, looking through the
+/// logical operators
+fn assignment_in_condition<'a, 'b>(node: &'b ParseTreeNode<'a>) -> Option<&'b ParseTreeNode<'a>>
+{
+    match node
+    {
+        ParseTreeNode::Expression(ExpressionType::Assignment, _) => Some(node),
+        ParseTreeNode::Expression(ExpressionType::LogicalAnd, children) | ParseTreeNode::Expression(ExpressionType::LogicalOr, children) |
+        ParseTreeNode::Expression(ExpressionType::LogicalNot, children) =>
+        {
+            children.iter().find_map(assignment_in_condition)
+        },
+        _ => None
+    }
+}
+
+/// Warn when the else of an if placed without braces inside another if is lined up with the outer
+/// if, as the else belongs to the inner if however it is indented
+fn dangling_else_warning(children: &[ParseTreeNode]) -> Option<Error>
+{
+    let keyword = |node: Option<&ParseTreeNode>| match node
+    {
+        Some(ParseTreeNode::RawToken(token)) => Some(token.location.clone()),
+        _ => None
+    };
+
+    // Only an if without an else of its own leaves one for the inner if to take
+    if !matches!(children.get(2), Some(ParseTreeNode::Empty))
+    {
+        return None;
+    }
+
+    let outer_if = keyword(children.get(3))?;
+
+    let (inner_if, inner_else) = match children.get(1)
+    {
+        Some(ParseTreeNode::IfStatement(inner)) => (keyword(inner.get(3))?, keyword(inner.get(4))?),
+        _ => {return None;}
+    };
+
+    if inner_else.row != inner_if.row && inner_else.col != inner_if.col && inner_else.col == outer_if.col
+    {
+        Some(compiler_warning_loc("W0002", format!("This 'else' belongs to the 'if' on line {}, not the 'if' on line {} it is lined up with, add braces to make the meaning clear", inner_if.row, outer_if.row), &Some(inner_else)))
+    }
+    else
+    {
+        None
+    }
+}
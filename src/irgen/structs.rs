@@ -0,0 +1,295 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::OnceLock;
+
+use serde::{Serialize, Serializer, Deserialize, Deserializer, de::Error as _};
+
+use super::{Context, DataType, NonPtrType, type_from_parse_tree, compiler_error_loc};
+
+use crate::cli::Error;
+use crate::intern::Symbol;
+use crate::parser::ParseTreeNode;
+
+/// A struct as it was declared, by its name and, once they are known, the name and type of each
+/// of its fields in order
+struct Definition
+{
+    name: Symbol,
+    fields: OnceLock<Vec<(String, DataType)>>
+}
+
+/// A handle to a struct declared in a library, which holds its definition so a type holding one can
+/// still be copied, and compared as the address of the definition. Definitions live for the rest
+/// of the run like interned strings, but only the library declaring a struct can find it by name
+#[derive(Clone, Copy)]
+pub struct StructType(&'static Definition);
+
+impl StructType
+{
+    /// Give the fields of the struct, which are only given once
+    pub fn define(&self, fields: Vec<(String, DataType)>)
+    {
+        let _ = self.0.fields.set(fields);
+    }
+
+    /// Get the name the struct was declared with
+    pub fn name(&self) -> &'static str
+    {
+        self.0.name.as_str()
+    }
+
+    /// Get the name and type of each field of the struct, which are empty until it is defined
+    pub fn fields(&self) -> Vec<(String, DataType)>
+    {
+        self.0.fields.get().cloned().unwrap_or_default()
+    }
+
+    /// Get the position and type of the field of the struct with the given name
+    pub fn field(&self, name: &str) -> Option<(usize, DataType)>
+    {
+        self.fields().into_iter().enumerate().find(|(_, (field, _))| field == name).map(|(index, (_, datatype))| (index, datatype))
+    }
+}
+
+impl PartialEq for StructType
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for StructType {}
+
+impl Hash for StructType
+{
+    fn hash<H: Hasher>(&self, state: &mut H)
+    {
+        std::ptr::hash(self.0, state);
+    }
+}
+
+// Structs are ordered by name, which only two structs of different libraries can share
+impl PartialOrd for StructType
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering>
+    {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StructType
+{
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering
+    {
+        self.name().cmp(other.name()).then_with(|| (self.0 as *const Definition).cmp(&(other.0 as *const Definition)))
+    }
+}
+
+// The fields are left out, as a struct may point to itself through them
+impl fmt::Debug for StructType
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "StructType({})", self.name())
+    }
+}
+
+impl fmt::Display for StructType
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
+    {
+        write!(f, "struct {}", self.name())
+    }
+}
+
+/// The structs declared by a library, by their names, which is kept with the library being
+/// compiled so no other library sees them
+#[derive(Debug, Clone, Default)]
+pub struct StructTable
+{
+    declared: Vec<StructType>,
+    scope: HashMap<Symbol, StructType>
+}
+
+thread_local!
+{
+    /// The structs named in the IR file being read on this thread, which a struct is read back as
+    static READING: RefCell<Option<StructTable>> = const { RefCell::new(None) };
+}
+
+impl StructTable
+{
+    /// Declare a struct with the given name, whose fields are given once every struct is declared
+    pub fn declare(&mut self, name: &str) -> StructType
+    {
+        let definition: &'static Definition = Box::leak(Box::new(Definition { name: Symbol::intern(name), fields: OnceLock::new() }));
+        let result = StructType(definition);
+
+        self.declared.push(result);
+        self.scope.insert(Symbol::intern(name), result);
+
+        result
+    }
+
+    /// Get the struct declared with the given name
+    pub fn lookup(&self, name: &str) -> Option<StructType>
+    {
+        self.scope.get(&Symbol::intern(name)).copied()
+    }
+
+    /// Get the struct declared with the given name, declaring it if it has not been
+    pub fn named(&mut self, name: &str) -> StructType
+    {
+        self.lookup(name).unwrap_or_else(|| self.declare(name))
+    }
+
+    /// Get every struct of the library, in the order they were declared
+    pub fn in_scope(&self) -> &[StructType]
+    {
+        &self.declared
+    }
+
+    /// Run a closure reading values which name structs, such as the functions of an IR file, giving
+    /// back what it read along with the table of the structs named, each name standing for one
+    /// struct of the table
+    pub fn reading<T>(read: impl FnOnce() -> T) -> (T, StructTable)
+    {
+        let outer = READING.with(|reading| reading.replace(Some(StructTable::default())));
+        let result = read();
+        let table = READING.with(|reading| reading.replace(outer)).unwrap_or_default();
+
+        (result, table)
+    }
+}
+
+// A struct is written by its name, and read back as the struct of that name in the table of the
+// values being read
+impl Serialize for StructType
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    {
+        serializer.serialize_str(self.name())
+    }
+}
+
+impl<'de> Deserialize<'de> for StructType
+{
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error>
+    {
+        let name = String::deserialize(deserializer)?;
+
+        READING.with(|reading| match reading.borrow_mut().as_mut()
+        {
+            Some(table) => Ok(table.named(&name)),
+            None => Err(D::Error::custom(format!("struct '{}' is not read as part of a library", name)))
+        })
+    }
+}
+
+/// Declare every struct of a library, then give each its fields, so the fields of a struct may
+/// point to any struct of the library wherever it is declared. A struct cannot hold itself, other
+/// than through a pointer, as it would never end
+pub fn declare_structs(items: &[ParseTreeNode], context: &mut Context) -> Result<(), Error>
+{
+    let structs: Vec<&[ParseTreeNode]> = items.iter().filter_map(|item| match item
+    {
+        ParseTreeNode::Struct(children) => Some(*children),
+        _ => None
+    }).collect();
+
+    for children in &structs
+    {
+        let name = match &children[0]
+        {
+            ParseTreeNode::Identifier(token) => token,
+            _ => unreachable!()
+        };
+
+        if context.structs.lookup(&name.data).is_some()
+        {
+            return compiler_error_loc("E0040", format!("Struct '{}' is declared more than once", name.data), &Some(name.location.clone()));
+        }
+
+        context.structs.declare(&name.data);
+    }
+
+    for children in &structs
+    {
+        let name = children[0].first_token().unwrap();
+        let mut fields: Vec<(String, DataType)> = vec![];
+
+        for field in children.iter().filter(|child| matches!(child, ParseTreeNode::Field(_)))
+        {
+            let field_name = field.children()[1].first_token().unwrap();
+            let datatype = type_from_parse_tree(&field.children()[0], context)?;
+            let location = Some(field_name.location.clone());
+
+            if datatype.is_void()
+            {
+                return compiler_error_loc("E0040", format!("Field '{}' of struct '{}' cannot be void", field_name.data, name.data), &location);
+            }
+
+            if datatype.align.is_some()
+            {
+                return compiler_error_loc("E0011", String::from("An alignment can only be given for a variable"), &location);
+            }
+
+            if fields.iter().any(|(other, _)| field_name.data == *other)
+            {
+                return compiler_error_loc("E0040", format!("Struct '{}' has more than one field named '{}'", name.data, field_name.data), &location);
+            }
+
+            fields.push((field_name.data.to_string(), datatype));
+        }
+
+        context.structs.lookup(&name.data).unwrap().define(fields);
+    }
+
+    for children in &structs
+    {
+        let name = children[0].first_token().unwrap();
+        let start = context.structs.lookup(&name.data).unwrap();
+
+        if let Some(path) = containing_path(start, start, &mut vec![])
+        {
+            return compiler_error_loc("E0040", format!("Struct '{}' contains itself through '{}', which can only be done through a pointer", name.data, path.join(".")), &Some(name.location.clone()));
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the fields leading from a struct to a struct held within it by value, following the
+/// structs it holds which have not been visited yet
+fn containing_path(from: StructType, target: StructType, visited: &mut Vec<StructType>) -> Option<Vec<String>>
+{
+    for (name, datatype) in from.fields()
+    {
+        let inner = match datatype.raw_type
+        {
+            NonPtrType::Struct(inner) if datatype.num_ptr == 0 => inner,
+            _ => continue
+        };
+
+        if inner == target
+        {
+            return Some(vec![name]);
+        }
+
+        if !visited.contains(&inner)
+        {
+            visited.push(inner);
+
+            if let Some(mut path) = containing_path(inner, target, visited)
+            {
+                path.insert(0, name);
+                return Some(path);
+            }
+        }
+    }
+
+    None
+}
@@ -1,7 +1,11 @@
 use std::fmt;
 
+use serde::{Serialize, Deserialize};
+
+use super::StructType;
+
 /// Non Pointer Type, a raw type
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Serialize, Deserialize)]
 pub enum NonPtrType
 {
     I8,
@@ -12,30 +16,86 @@ pub enum NonPtrType
     U32,
     I64,
     U64,
+    Int(u16), // Signed integer of any other width, in bits
+    UInt(u16), // Unsigned integer of any other width, in bits
+    Vector(u16, bool, u16), // Vector of integers, with the width of each lane in bits, their signedness and the number of lanes
+    Struct(StructType),
     Void,
     Bool,
     Unknown
 }
 
+/// Widest integer type which can be declared, values are held in 128 bits while compiling
+pub const MAX_INTEGER_BITS: usize = 64;
+
+/// Most lanes a vector type can have
+pub const MAX_VECTOR_LANES: usize = 256;
+
+/// Largest alignment which can be requested for a variable, in bytes
+pub const MAX_ALIGNMENT: u32 = 4096;
+
+impl NonPtrType
+{
+    /// Get the integer type with the given width, using the named types where they exist
+    pub fn integer(bits: u16, signed: bool) -> Self
+    {
+        match (bits, signed)
+        {
+            (8, true) => NonPtrType::I8,
+            (8, false) => NonPtrType::U8,
+            (16, true) => NonPtrType::I16,
+            (16, false) => NonPtrType::U16,
+            (32, true) => NonPtrType::I32,
+            (32, false) => NonPtrType::U32,
+            (64, true) => NonPtrType::I64,
+            (64, false) => NonPtrType::U64,
+            (bits, true) => NonPtrType::Int(bits),
+            (bits, false) => NonPtrType::UInt(bits)
+        }
+    }
+}
+
 /// A datatype with the possibility of being a pointer and a reference
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
 pub struct DataType
 {
     pub raw_type: NonPtrType,
     pub num_ptr: usize,
-    pub is_ref: bool
+    pub is_ref: bool,
+    pub align: Option<u32>, // Alignment requested for the storage of a variable, in bytes
+    pub restrict: bool // Pointer argument which is the only way the function reaches the memory it points to
+}
+
+// The alignment and restrict qualifier only affect how a variable is placed and optimized, so they
+// are ignored when comparing types
+impl PartialEq for DataType
+{
+    fn eq(&self, other: &Self) -> bool
+    {
+        (self.raw_type, self.num_ptr, self.is_ref) == (other.raw_type, other.num_ptr, other.is_ref)
+    }
+}
+
+impl PartialOrd for DataType
+{
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering>
+    {
+        (self.raw_type, self.num_ptr, self.is_ref).partial_cmp(&(other.raw_type, other.num_ptr, other.is_ref))
+    }
 }
 
 impl DataType
 {
     /// Generate a new datatype object
-    pub fn new(raw: NonPtrType, ptrs: usize, is_ref: bool) -> Self
+    pub const fn new(raw: NonPtrType, ptrs: usize, is_ref: bool) -> Self
     {
         Self
         {
             raw_type: raw,
             num_ptr: ptrs,
-            is_ref: is_ref
+            is_ref: is_ref,
+            align: None,
+            restrict: false
         }
     }
 
@@ -50,11 +110,50 @@ impl DataType
         {
             match self.raw_type
             {
-                NonPtrType::I8 | NonPtrType::I16 | NonPtrType::I32 | NonPtrType::I64 => true,
+                NonPtrType::I8 | NonPtrType::I16 | NonPtrType::I32 | NonPtrType::I64 | NonPtrType::Int(_) => true,
+                NonPtrType::Vector(_, signed, _) => signed,
                 _ => false
             }
         }   
     }
+
+    /// Is the datatype void (rather than a pointer to void)
+    pub fn is_void(&self) -> bool
+    {
+        self.num_ptr == 0 && self.raw_type == NonPtrType::Void
+    }
+
+    /// Is the datatype a vector value (rather than a pointer to one)
+    pub fn is_vector(&self) -> bool
+    {
+        self.num_ptr == 0 && matches!(self.raw_type, NonPtrType::Vector(..))
+    }
+
+    /// Is the datatype a struct value (rather than a pointer to one)
+    pub fn is_struct(&self) -> bool
+    {
+        self.num_ptr == 0 && matches!(self.raw_type, NonPtrType::Struct(_))
+    }
+
+    /// Get the type of each lane of a vector
+    pub fn element_type(&self) -> Option<DataType>
+    {
+        match self.raw_type
+        {
+            NonPtrType::Vector(bits, signed, _) if self.num_ptr == 0 => Some(DataType::new(NonPtrType::integer(bits, signed), 0, false)),
+            _ => None
+        }
+    }
+
+    /// Get the number of lanes in a vector, or zero for any other type
+    pub fn lanes(&self) -> usize
+    {
+        match self.raw_type
+        {
+            NonPtrType::Vector(_, _, lanes) if self.num_ptr == 0 => lanes as usize,
+            _ => 0
+        }
+    }
 }
 
 impl fmt::Display for DataType
@@ -66,20 +165,27 @@ impl fmt::Display for DataType
             write!(f, "&")?;
         }
         
-        write!(f, "{}", match self.raw_type
+        match self.raw_type
         {
-            NonPtrType::Bool => "i1",
-            NonPtrType::I8 => "i8",
-            NonPtrType::U8 => "u8",
-            NonPtrType::I16 => "i16",
-            NonPtrType::U16 => "u16",
-            NonPtrType::I32 => "i32",
-            NonPtrType::U32 => "u32",
-            NonPtrType::I64 => "i64",
-            NonPtrType::U64 => "u64",
-            NonPtrType::Void => "void",
-            NonPtrType::Unknown => "Unk"
-        })?;
+            NonPtrType::Int(bits) => write!(f, "i{}", bits)?,
+            NonPtrType::UInt(bits) => write!(f, "u{}", bits)?,
+            NonPtrType::Vector(bits, signed, lanes) => write!(f, "{}{}x{}", if signed {"i"} else {"u"}, bits, lanes)?,
+            NonPtrType::Struct(structure) => write!(f, "{}", structure)?,
+            raw => write!(f, "{}", match raw
+            {
+                NonPtrType::Bool => "bool",
+                NonPtrType::I8 => "i8",
+                NonPtrType::U8 => "u8",
+                NonPtrType::I16 => "i16",
+                NonPtrType::U16 => "u16",
+                NonPtrType::I32 => "i32",
+                NonPtrType::U32 => "u32",
+                NonPtrType::I64 => "i64",
+                NonPtrType::U64 => "u64",
+                NonPtrType::Void => "void",
+                _ => "Unk"
+            })?
+        }
 
         for _ in 0..self.num_ptr
         {
@@ -1,7 +1,7 @@
-use super::{NonPtrType, DataType};
-use super::Value;
+use super::{NonPtrType, DataType, MAX_INTEGER_BITS, MAX_VECTOR_LANES, MAX_ALIGNMENT};
+use super::{Value, DATA_TYPE};
 
-use super::{compiler_error, expected_got_error};
+use super::{Context, compiler_error, compiler_error_loc, expected_got_error, evaluate_constant, bits_of};
 
 use crate::parser::ParseTreeNode;
 
@@ -16,7 +16,7 @@ pub fn attempt_mutate_type(value: Value, new_type: DataType) -> Value
         {
             let mut lit = literal.clone();
 
-            if lit.datatype.raw_type == NonPtrType::Unknown
+            if lit.datatype.raw_type == NonPtrType::Unknown && (lit.datatype.num_ptr == 0 || new_type.num_ptr > 0)
             {
                 lit.datatype = correct_type_references(new_type);
             }
@@ -36,7 +36,7 @@ pub fn force_mutate_type(value: Value, new_type: DataType) -> Value
         {
             let mut lit = literal.clone();
 
-            if lit.datatype.raw_type == NonPtrType::Unknown
+            if lit.datatype.raw_type == NonPtrType::Unknown && (lit.datatype.num_ptr == 0 || new_type.num_ptr > 0)
             {
                 lit.datatype = correct_type_references(new_type);
             }
@@ -65,6 +65,8 @@ pub fn get_value_type(value: &Value) -> Option<DataType>
     {
         Value::Literal(literal) => Some(literal.datatype),
         Value::Symbol(symbol) => Some(symbol.datatype),
+        Value::Data(_) => Some(DATA_TYPE),
+        Value::Global(symbol) => Some(symbol.datatype),
         Value::Label(_) => None
     }
 }
@@ -79,6 +81,32 @@ pub fn has_unknown_type(value: &Value) -> bool
     }
 }
 
+/// Check if a value of one type can be converted to another without a cast, which is only allowed
+/// when every value of the first type can be held by the second, or between a pointer and a void
+/// pointer
+pub fn converts_implicitly(from: DataType, to: DataType) -> bool
+{
+    let (from, to) = (correct_type_references(from), correct_type_references(to));
+
+    if from == to
+    {
+        return true;
+    }
+
+    if from.num_ptr > 0 || to.num_ptr > 0
+    {
+        return from.num_ptr > 0 && to.num_ptr > 0 && (from.raw_type == NonPtrType::Void || to.raw_type == NonPtrType::Void);
+    }
+
+    match (bits_of(from.raw_type), bits_of(to.raw_type))
+    {
+        (Some(from_bits), Some(to_bits)) if from.is_signed() == to.is_signed() => to_bits >= from_bits,
+        // An unsigned value needs a bit more to be held by a signed type
+        (Some(from_bits), Some(to_bits)) if to.is_signed() => to_bits > from_bits,
+        _ => false
+    }
+}
+
 /// Remove any references
 pub fn correct_type_references(datatype: DataType) -> DataType
 {
@@ -89,8 +117,8 @@ pub fn correct_type_references(datatype: DataType) -> DataType
     result
 }
 
-/// Extract a datatype from a parse tree node
-pub fn type_from_parse_tree(node: ParseTreeNode) -> Result<DataType, Error>
+/// Extract a datatype from a parse tree node, naming a struct of the library being compiled
+pub fn type_from_parse_tree(node: &ParseTreeNode, context: &Context) -> Result<DataType, Error>
 {
     match node
     {
@@ -112,21 +140,78 @@ pub fn type_from_parse_tree(node: ParseTreeNode) -> Result<DataType, Error>
                         "u64" => NonPtrType::U64,
                         "void" => NonPtrType::Void,
 
+                        // Integers of any other width, and vectors of them
+                        name if name.len() > 1 && name[1..].chars().all(|c| c.is_ascii_digit() || c == 'x') =>
+                        {
+                            let location = Some(token.location.clone());
+                            let mut parts = name[1..].splitn(2, 'x');
+
+                            let bits = match parts.next().unwrap_or_default().parse::<u16>()
+                            {
+                                Ok(bits) if bits > 0 && bits as usize <= MAX_INTEGER_BITS => bits,
+                                _ => {return compiler_error_loc("E0008", format!("Integer types must be between 1 and {} bits wide, got '{}'", MAX_INTEGER_BITS, name), &location);}
+                            };
+
+                            // Vectors are made of the named integer types, with a power of two lanes
+                            match parts.next()
+                            {
+                                None => NonPtrType::integer(bits, name.starts_with('i')),
+                                Some(_) if ![8, 16, 32, 64].contains(&bits) =>
+                                {
+                                    return compiler_error_loc("E0009", format!("Vector lanes must be 8, 16, 32 or 64 bits wide, got '{}'", name), &location);
+                                },
+                                Some(lanes) => match lanes.parse::<u16>()
+                                {
+                                    Ok(lanes) if lanes.is_power_of_two() && lanes as usize <= MAX_VECTOR_LANES => NonPtrType::Vector(bits, name.starts_with('i'), lanes),
+                                    _ => {return compiler_error_loc("E0009", format!("Vector types must have a power of two lanes, up to {}, got '{}'", MAX_VECTOR_LANES, name), &location);}
+                                }
+                            }
+                        },
+
                         default => 
                         {
-                            compiler_error(format!("Bad type, '{}'", default))?;
+                            compiler_error("E0024", format!("Bad type, '{}'", default))?;
                             unreachable!();
                         }
                     }
                 },
+                ParseTreeNode::StructType(token) => match context.structs.lookup(&token.data)
+                {
+                    Some(structure) => NonPtrType::Struct(structure),
+                    None => {return compiler_error_loc("E0040", format!("Unknown struct '{}'", token.data), &Some(token.location.clone()));}
+                },
                 default =>
                 {
-                    expected_got_error("Type", default.clone())?;
+                    expected_got_error("Type", default)?;
                     unreachable!();
                 }
             };
 
-            Ok(DataType::new(non_ptr, children.len() - 1, false))
+            let mut result = DataType::new(non_ptr, 0, false);
+
+            for child in &children[1..]
+            {
+                match child
+                {
+                    ParseTreeNode::Alignment(values) =>
+                    {
+                        let token = match values.first()
+                        {
+                            Some(ParseTreeNode::IntegerLiteral(token)) => token,
+                            _ => {return compiler_error("E0010", String::from("Expected an integer alignment"));}
+                        };
+
+                        match token.data.parse::<u32>()
+                        {
+                            Ok(align) if align.is_power_of_two() && align <= MAX_ALIGNMENT => {result.align = Some(align);},
+                            _ => {return compiler_error_loc("E0010", format!("Alignment must be a power of two, up to {}, got '{}'", MAX_ALIGNMENT, token.data), &Some(token.location.clone()));}
+                        }
+                    },
+                    _ => {result.num_ptr += 1;}
+                }
+            }
+
+            Ok(result)
         },
         default =>
         {
@@ -136,7 +221,7 @@ pub fn type_from_parse_tree(node: ParseTreeNode) -> Result<DataType, Error>
 }
 
 /// Extract an identifier from a parse tree node
-pub fn identifier_from_parse_tree(node: ParseTreeNode) -> Result<String, Error>
+pub fn identifier_from_parse_tree(node: &ParseTreeNode) -> Result<String, Error>
 {
     match node
     {
@@ -152,7 +237,7 @@ pub fn identifier_from_parse_tree(node: ParseTreeNode) -> Result<String, Error>
 }
 
 /// Extract arguments from a parse tree node
-pub fn arguments_from_parse_tree(node: ParseTreeNode) -> Result<Vec<(String, DataType)>, Error>
+pub fn arguments_from_parse_tree(node: &ParseTreeNode, context: &Context) -> Result<Vec<(String, DataType)>, Error>
 {
     match node
     {
@@ -164,18 +249,30 @@ pub fn arguments_from_parse_tree(node: ParseTreeNode) -> Result<Vec<(String, Dat
         {
             let mut result = vec![];
 
-            for child in children
+            for child in children.iter()
             {
                 match &child
                 {
                     ParseTreeNode::Argument(arg_vals) =>
                     {
-                        result.push((identifier_from_parse_tree(arg_vals[1].clone())?,
-                                     type_from_parse_tree(arg_vals[0].clone())?))
+                        let name = identifier_from_parse_tree(&arg_vals[1])?;
+                        let mut datatype = type_from_parse_tree(&arg_vals[0], context)?;
+
+                        if let Some(restrict) = arg_vals.iter().skip(2).find(|node| matches!(node, ParseTreeNode::RawToken(_)))
+                        {
+                            if datatype.num_ptr == 0
+                            {
+                                return compiler_error_loc("E0012", format!("Only pointer arguments can be restrict, '{}' is a '{}'", name, datatype), &restrict.first_token().map(|token| token.location.clone()));
+                            }
+
+                            datatype.restrict = true;
+                        }
+
+                        result.push((name, datatype))
                     }
                     default =>
                     {
-                        expected_got_error("Argument", default.clone())?;
+                        expected_got_error("Argument", default)?;
                     }
                 }
             }
@@ -187,4 +284,31 @@ pub fn arguments_from_parse_tree(node: ParseTreeNode) -> Result<Vec<(String, Dat
             expected_got_error("Arguments or Empty", default)
         }
     }
+}
+
+/// Extract the default values of the arguments from a parse tree node, which must be constant, and
+/// once one argument has a default every argument after it must have one too
+pub fn argument_defaults_from_parse_tree(node: &ParseTreeNode, context: &Context) -> Result<Vec<Option<i128>>, Error>
+{
+    let mut result = vec![];
+
+    for child in node.children()
+    {
+        let default = child.children().iter().find(|node| matches!(node, ParseTreeNode::DefaultValue(_)));
+
+        match default
+        {
+            Some(default) => result.push(Some(evaluate_constant(&default.children()[0], context)?)),
+            None if result.iter().any(|value: &Option<i128>| value.is_some()) =>
+            {
+                let name = child.children().get(1).and_then(|node| node.first_token());
+
+                return compiler_error_loc("E0027", format!("Argument '{}' follows an argument with a default value, so must have one too",
+                    name.map(|token| token.data.as_str()).unwrap_or_default()), &name.map(|token| token.location.clone()));
+            },
+            None => result.push(None)
+        }
+    }
+
+    Ok(result)
 }
\ No newline at end of file
@@ -0,0 +1,16 @@
+#![allow(dead_code)]
+
+pub mod tokenizer;
+pub mod io;
+pub mod cli;
+pub mod compile;
+pub mod import;
+pub mod intern;
+pub mod lint;
+pub mod parser;
+pub mod irgen;
+pub mod codegen;
+pub mod emit;
+pub mod ide;
+pub mod plugin;
+pub mod ice;
@@ -0,0 +1,116 @@
+use crate::cli::Error;
+use crate::irgen::{Context, compiler_warning_loc, evaluate_constant};
+use crate::parser::ParseTreeNode;
+use crate::tokenizer::Token;
+
+/// Warnings given by the lint pass, which are on unless turned off with --allow, along with the
+/// name they are turned off or made errors by
+pub static LINTS: &[(&str, &str)] = &[
+    ("W0007", "shadowing"),
+    ("W0008", "constant-condition"),
+    ("W0009", "empty-body")
+];
+
+/// Check the parse tree of a library for code which is valid but likely a mistake, giving a
+/// warning for each, given the context of the library its constants are worked out in
+pub fn lint(library: &ParseTreeNode, context: &Context) -> Vec<Error>
+{
+    let mut warnings = vec![];
+
+    for item in library.children()
+    {
+        if let ParseTreeNode::Function(children) = item
+        {
+            let mut declared: Vec<(&Token, &str)> = vec![];
+
+            for argument in children[2].children()
+            {
+                if let Some(ParseTreeNode::Identifier(token)) = argument.children().get(1)
+                {
+                    declared.push((token, "argument"));
+                }
+            }
+
+            lint_statement(&children[3], &mut declared, context, &mut warnings);
+        }
+    }
+
+    warnings
+}
+
+/// Check a statement and everything within it, given the arguments and variables declared before it
+fn lint_statement<'a>(node: &'a ParseTreeNode, declared: &mut Vec<(&'a Token, &'static str)>, context: &Context, warnings: &mut Vec<Error>)
+{
+    match node
+    {
+        // A function has a single scope, so a second declaration of a name is the same variable
+        ParseTreeNode::AssignmentStatement(children) =>
+        {
+            for assignment in children[1].children()
+            {
+                if let Some(ParseTreeNode::Identifier(token)) = assignment.children().first()
+                {
+                    match declared.iter().find(|(other, _)| other.data == token.data)
+                    {
+                        Some((other, kind)) => warnings.push(compiler_warning_loc("W0007",
+                            format!("'{}' is declared again, reusing the {} from line {}", token.data, kind, other.location.row), &Some(token.location.clone()))
+                            .with_hint("a function has a single scope, so this is the same variable, give it another name if it is meant to be a new one")),
+                        None => declared.push((token, "variable"))
+                    }
+                }
+            }
+        },
+        ParseTreeNode::IfStatement(children) =>
+        {
+            lint_condition(&children[0], "if", context, warnings);
+            lint_body(&children[0], &children[1], "if", warnings);
+        },
+        ParseTreeNode::WhileLoop(children) =>
+        {
+            lint_condition(&children[0], "while", context, warnings);
+            lint_body(&children[0], &children[1], "while", warnings);
+        },
+        ParseTreeNode::DoWhileLoop(children) =>
+        {
+            lint_condition(&children[0], "do while", context, warnings);
+        },
+        ParseTreeNode::ForLoop(children) =>
+        {
+            lint_condition(&children[1], "for", context, warnings);
+            // A for loop need not have a condition to place the warning at
+            let condition = if matches!(children[1], ParseTreeNode::Empty) {node} else {&children[1]};
+            lint_body(condition, &children[3], "for", warnings);
+        },
+        _ => {}
+    }
+
+    for child in node.children()
+    {
+        lint_statement(child, declared, context, warnings);
+    }
+}
+
+/// Warn about the condition of an `if` or loop which is a constant, so always or never holds
+fn lint_condition(condition: &ParseTreeNode, statement: &str, context: &Context, warnings: &mut Vec<Error>)
+{
+    if let Ok(value) = evaluate_constant(condition, context)
+    {
+        let warning = compiler_warning_loc("W0008", format!("Condition of '{}' is always {}", statement, value != 0), &condition.first_token().map(|token| token.location.clone()));
+
+        warnings.push(match statement
+        {
+            "while" | "do while" | "for" if value != 0 => warning.with_hint("use 'loop' for a loop which runs until it is broken out of"),
+            _ => warning
+        });
+    }
+}
+
+/// Warn about the body of an `if`, `while` or `for` which is only a `;`, which is usually a stray `;`
+/// after the condition
+fn lint_body(condition: &ParseTreeNode, body: &ParseTreeNode, statement: &str, warnings: &mut Vec<Error>)
+{
+    if matches!(body, ParseTreeNode::Statement(children) if children.is_empty())
+    {
+        warnings.push(compiler_warning_loc("W0009", format!("Body of '{}' is empty, use '{{}}' if this is intended", statement), &condition.first_token().map(|token| token.location.clone())));
+    }
+}
@@ -1,18 +1,23 @@
-#![allow(dead_code)]
-
-mod tokenizer;
-mod io;
-mod cli;
-mod compile;
-mod parser;
-mod irgen;
-mod codegen;
+use compiler::{cli, ice};
 
 fn main()
 {
-    match cli::run(&cli::Options::new(std::env::args().collect()))
+    ice::install_panic_hook();
+
+    let options = cli::Options::new(std::env::args().collect());
+
+    match std::panic::catch_unwind(|| cli::run(&options))
     {
-        Ok(()) => {},
-        Err(error) => {eprintln!("{}\nCompilation Terminated", error);}
+        Ok(Ok(())) => {},
+        Ok(Err(error)) =>
+        {
+            eprintln!("{}\nCompilation Terminated", error);
+            std::process::exit(1);
+        },
+        Err(_) =>
+        {
+            ice::report(None, &options);
+            std::process::exit(101);
+        }
     }
 }
\ No newline at end of file
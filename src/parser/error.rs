@@ -1,22 +1,60 @@
 use crate::cli::Error;
 use crate::tokenizer::{Token, FileLocation};
 
+use super::MAX_NESTING;
+
 /// Generate a new parse error
-pub fn parse_error<T>(location: FileLocation, text: String) -> Result<T, Error>
+pub fn parse_error<T>(code: &'static str, location: FileLocation, text: String) -> Result<T, Error>
 {
-    Err(Error::error(&format!("Parse Error: {} at {}", text, location)))
+    Err(Error::error(&format!("Parse Error: {} at {}", text, location)).with_code(code))
 }
 
 /// Generate a parse error with an expected, got format
 pub fn expected_got_error<T>(expected: &str, got: &Token) -> Result<T, Error>
 {
-    parse_error(got.clone().location, format!("Expected {}, got '{}'", expected, got.data))
+    parse_error("E0005", got.clone().location, format!("Expected {}, got '{}'", expected, got.data))
+}
+
+/// Generate an error when reaching an unexpected EOF, placed after the last token of the file if
+/// there is one
+pub fn unexpected_eof_error<T>(expected: &str, last: Option<&Token>) -> Result<T, Error>
+{
+    match last
+    {
+        Some(last) =>
+        {
+            let mut loc = last.clone().location;
+            loc.col += last.data.len();
+            parse_error("E0006", loc, format!("Unexpected EOF while parsing, expected {}", expected))
+        },
+        None => Err(Error::error(&format!("Parse Error: Unexpected EOF while parsing, expected {}", expected)).with_code("E0006"))
+    }
 }
 
-/// Generate an error when reaching an unexpected EOF
-pub fn unexpected_eof_error<T>(expected: &str, last: &Token) -> Result<T, Error>
+/// Generate an error for a semicolon missing after the last token of a statement
+pub fn missing_semicolon_error<T>(after: &str, last: &Token) -> Result<T, Error>
 {
     let mut loc = last.clone().location;
     loc.col += last.data.len();
-    parse_error(loc, format!("Unexpected EOF while parsing, expected {}", expected))
-}
\ No newline at end of file
+    parse_error("E0025", loc, format!("Missing ';' after {}", after))
+}
+
+/// Generate an error for expressions or statements nested more deeply than the parser allows,
+/// placed at the token which goes too deep
+pub fn nested_too_deeply_error<T>(at: Option<&Token>) -> Result<T, Error>
+{
+    let text = format!("Expressions and statements can be nested at most {} deep", MAX_NESTING);
+
+    match at
+    {
+        Some(token) => parse_error("E0037", token.clone().location, text),
+        None => Err(Error::error(&format!("Parse Error: {}", text)).with_code("E0037"))
+    }
+}
+
+/// Generate an error for the condition of an if statement or while loop which is not in
+/// parentheses while the body after it is not in braces, placed at the start of the condition
+pub fn unparenthesized_condition_error<T>(construct: &str, first: &Token) -> Result<T, Error>
+{
+    parse_error("E0028", first.clone().location, format!("The condition of {} must be in parentheses when its body is not in braces", construct))
+}
@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use crate::cli::Error;
+use crate::intern::Symbol;
+use crate::tokenizer::Token;
+use super::{ParseTreeNode, NodeArena, ExpressionType, convert_to_left};
+use super::error::parse_error;
+
+/// How deeply macros may expand into uses of other macros, past which a macro is taken to use itself
+static MAX_EXPANSION_DEPTH: usize = 64;
+
+/// A macro, its parameters and the block it expands to
+struct Macro<'a>
+{
+    parameters: Vec<Symbol>,
+    body: ParseTreeNode<'a>
+}
+
+/// Expands the uses of macros within a library, allocating the expanded tree in the arena of the
+/// library
+struct Expander<'a>
+{
+    macros: HashMap<Symbol, Macro<'a>>,
+    expansions: usize,
+    arena: &'a NodeArena<'a>
+}
+
+/// Get the name and arguments of a use of a macro, `name(a, b)`
+fn macro_call<'a, 'b>(node: &'b ParseTreeNode<'a>, macros: &HashMap<Symbol, Macro>) -> Option<(&'b Token, &'a [ParseTreeNode<'a>])>
+{
+    match node
+    {
+        ParseTreeNode::Expression(ExpressionType::FunctionCall, children) => match children.first()
+        {
+            Some(ParseTreeNode::Identifier(name)) if macros.contains_key(&name.data) => Some((name, &children[1..])),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// Get the names of the variables declared anywhere within a parse tree
+fn declared_names(node: &ParseTreeNode, names: &mut Vec<Symbol>)
+{
+    if let ParseTreeNode::Assignment(children) = node
+    {
+        if let Some(ParseTreeNode::Identifier(name)) = children.first()
+        {
+            names.push(name.data);
+        }
+    }
+
+    for child in node.children()
+    {
+        declared_names(child, names);
+    }
+}
+
+/// Whether an expression assigns to its first child
+fn is_assignment(exprtype: ExpressionType) -> bool
+{
+    matches!(exprtype, ExpressionType::Assignment | ExpressionType::AddAssign | ExpressionType::SubtractAssign |
+        ExpressionType::MultiplyAssign | ExpressionType::DivideAssign | ExpressionType::ModulusAssign |
+        ExpressionType::ShiftLeftAssign | ExpressionType::ShiftRightAssign | ExpressionType::BitwiseAndAssign |
+        ExpressionType::BitwiseXorAssign | ExpressionType::BitwiseOrAssign)
+}
+
+/// Replace the parameters of a macro with the arguments it was given, and rename the variables
+/// declared in it
+fn substitute<'a>(node: &ParseTreeNode<'a>, arguments: &HashMap<Symbol, ParseTreeNode<'a>>, renames: &HashMap<Symbol, String>, arena: &'a NodeArena<'a>) -> Result<ParseTreeNode<'a>, Error>
+{
+    match node
+    {
+        ParseTreeNode::Identifier(token) =>
+        {
+            if let Some(argument) = arguments.get(&token.data)
+            {
+                return Ok(argument.clone());
+            }
+
+            match renames.get(&token.data)
+            {
+                Some(name) => Ok(ParseTreeNode::Identifier(Token::new(token.location.clone(), name.as_str()))),
+                None => Ok(node.clone())
+            }
+        },
+        ParseTreeNode::Expression(exprtype, children) =>
+        {
+            let mut new_children = vec![];
+
+            for (i, child) in children.iter().enumerate()
+            {
+                // The name of a called function is left alone
+                if i == 0 && *exprtype == ExpressionType::FunctionCall
+                {
+                    new_children.push(child.clone());
+                }
+                // An argument assigned to is on the left hand side, as it would be if it were
+                // written there
+                else if i == 0 && is_assignment(*exprtype)
+                {
+                    new_children.push(convert_to_left(substitute(child, arguments, renames, arena)?, arena)?);
+                }
+                else
+                {
+                    new_children.push(substitute(child, arguments, renames, arena)?);
+                }
+            }
+
+            Ok(ParseTreeNode::Expression(*exprtype, arena.alloc_extend(new_children)))
+        },
+        _ =>
+        {
+            let mut new_children = vec![];
+
+            for child in node.children()
+            {
+                new_children.push(substitute(child, arguments, renames, arena)?);
+            }
+
+            Ok(node.with_children(arena, new_children))
+        }
+    }
+}
+
+impl<'a> Expander<'a>
+{
+    /// Get the block a use of a macro expands to
+    fn expand_call(&mut self, name: &Token, arguments: &[ParseTreeNode<'a>]) -> Result<ParseTreeNode<'a>, Error>
+    {
+        self.expansions += 1;
+
+        let mac = &self.macros[&name.data];
+
+        if arguments.len() != mac.parameters.len()
+        {
+            return parse_error("E0003", name.location.clone(),
+                format!("Macro '{}' takes {} argument{}, got {}", name.data, mac.parameters.len(), if mac.parameters.len() == 1 {""} else {"s"}, arguments.len()));
+        }
+
+        // Variables declared in the macro are given names of their own in each expansion, so they
+        // cannot hide the variables named in the arguments
+        let mut declared = vec![];
+        declared_names(&mac.body, &mut declared);
+
+        let renames = declared.into_iter()
+            .filter(|declared| !mac.parameters.contains(declared))
+            .map(|declared| (declared, format!("{}.{}.{}", declared, name.data, self.expansions)))
+            .collect();
+
+        let arguments = mac.parameters.iter().cloned().zip(arguments.iter().cloned()).collect();
+
+        substitute(&mac.body, &arguments, &renames, self.arena)
+    }
+
+    /// Expand every use of a macro within a parse tree
+    fn expand(&mut self, node: &ParseTreeNode<'a>, depth: usize) -> Result<ParseTreeNode<'a>, Error>
+    {
+        if let ParseTreeNode::Statement(children) = node
+        {
+            if let Some((name, arguments)) = children.first().and_then(|child| macro_call(child, &self.macros))
+            {
+                if depth >= MAX_EXPANSION_DEPTH
+                {
+                    return parse_error("E0029", name.location.clone(),
+                        format!("Macro '{}' is expanded more than {} deep, a macro cannot use itself", name.data, MAX_EXPANSION_DEPTH));
+                }
+
+                let expansion = self.expand_call(name, arguments)?;
+                return self.expand(&expansion, depth + 1);
+            }
+        }
+
+        if let Some((name, _)) = macro_call(node, &self.macros)
+        {
+            return parse_error("E0029", name.location.clone(), format!("Macro '{}' can only be used as a statement, not within an expression", name.data));
+        }
+
+        let mut new_children = vec![];
+
+        for child in node.children()
+        {
+            new_children.push(self.expand(child, depth)?);
+        }
+
+        Ok(node.with_children(self.arena, new_children))
+    }
+}
+
+/// Expand the macros defined in a library, replacing each statement which uses one with the block
+/// of the macro, with the arguments given in place of its parameters
+pub fn expand_macros<'a>(library: ParseTreeNode<'a>, arena: &'a NodeArena<'a>) -> Result<ParseTreeNode<'a>, Error>
+{
+    let mut expander = Expander { macros: HashMap::new(), expansions: 0, arena };
+    let mut items = vec![];
+
+    for item in library.children()
+    {
+        match item
+        {
+            ParseTreeNode::Macro(children) =>
+            {
+                let name = match &children[0]
+                {
+                    ParseTreeNode::Identifier(name) => name,
+                    _ => unreachable!()
+                };
+
+                let parameters = children[1].children().iter().filter_map(|parameter| parameter.first_token().map(|token| token.data)).collect();
+
+                if expander.macros.insert(name.data, Macro { parameters, body: children[2].clone() }).is_some()
+                {
+                    return parse_error("E0029", name.location.clone(), format!("Macro '{}' is defined more than once", name.data));
+                }
+            },
+            _ => items.push(item.clone())
+        }
+    }
+
+    // Without any macros there is nothing to expand, and the library is kept as it is
+    if expander.macros.is_empty()
+    {
+        return Ok(library);
+    }
+
+    let mut expanded = vec![];
+
+    for item in items
+    {
+        expanded.push(expander.expand(&item, 0)?);
+    }
+
+    Ok(ParseTreeNode::Library(arena.alloc_extend(expanded)))
+}
@@ -1,6 +1,7 @@
 mod node;
 mod parser;
 mod error;
+mod macros;
 
 pub use node::*;
-pub use parser::*;
\ No newline at end of file
+pub use parser::*;
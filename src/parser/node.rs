@@ -1,3 +1,5 @@
+use typed_arena::Arena;
+
 use crate::tokenizer::Token;
 
 /// Expression Types
@@ -48,38 +50,144 @@ pub enum ExpressionType
     BitwiseXorAssign,
     BitwiseOrAssign,
     Cast,
-    Comma
+    Comma,
+    Member,
+    PointerMember
 }
 
-/// Parse Tree Node
+/// Parse Tree Node, whose children are allocated in a `NodeArena` and shared between copies of
+/// the node, so copying a node never copies the tree below it
 #[derive(Debug, Clone)]
-pub enum ParseTreeNode
+pub enum ParseTreeNode<'a>
 {
-    Library(Vec<ParseTreeNode>),
-    Function(Vec<ParseTreeNode>),
-    Arguments(Vec<ParseTreeNode>),
-    Argument(Vec<ParseTreeNode>),
-    Type(Vec<ParseTreeNode>),
+    Library(&'a [ParseTreeNode<'a>]),
+    Function(&'a [ParseTreeNode<'a>]),
+    Arguments(&'a [ParseTreeNode<'a>]),
+    Argument(&'a [ParseTreeNode<'a>]),
+    Type(&'a [ParseTreeNode<'a>]),
     Identifier(Token),
     RawType(Token),
-    Statement(Vec<ParseTreeNode>),
-    Statements(Vec<ParseTreeNode>),
-    Assignments(Vec<ParseTreeNode>),
-    Assignment(Vec<ParseTreeNode>),
-    Expression(ExpressionType, Vec<ParseTreeNode>),
+    StructType(Token), // A struct named in a type, by the token of its name
+    Statement(&'a [ParseTreeNode<'a>]),
+    Statements(&'a [ParseTreeNode<'a>]),
+    Assignments(&'a [ParseTreeNode<'a>]),
+    Assignment(&'a [ParseTreeNode<'a>]),
+    Expression(ExpressionType, &'a [ParseTreeNode<'a>]),
     RawToken(Token),
     IntegerLiteral(Token),
-    AssignmentStatement(Vec<ParseTreeNode>),
-    IfStatement(Vec<ParseTreeNode>),
-    ReturnStatement(Vec<ParseTreeNode>),
-    WhileLoop(Vec<ParseTreeNode>),
-    DoWhileLoop(Vec<ParseTreeNode>),
-    Loop(Vec<ParseTreeNode>),
+    NullLiteral(Token),
+    StringLiteral(Token),
+    AssignmentStatement(&'a [ParseTreeNode<'a>]),
+    IfStatement(&'a [ParseTreeNode<'a>]),
+    ReturnStatement(&'a [ParseTreeNode<'a>]),
+    WhileLoop(&'a [ParseTreeNode<'a>]),
+    DoWhileLoop(&'a [ParseTreeNode<'a>]),
+    Loop(&'a [ParseTreeNode<'a>]),
+    ForLoop(&'a [ParseTreeNode<'a>]),
+    Attributes(&'a [ParseTreeNode<'a>]),
+    Attribute(&'a [ParseTreeNode<'a>]),
+    Alignment(&'a [ParseTreeNode<'a>]),
+    StaticAssert(&'a [ParseTreeNode<'a>]),
+    DefaultValue(&'a [ParseTreeNode<'a>]),
+    Macro(&'a [ParseTreeNode<'a>]),
+    Import(&'a [ParseTreeNode<'a>]),
+    Use(&'a [ParseTreeNode<'a>]),
+    Struct(&'a [ParseTreeNode<'a>]),
+    Field(&'a [ParseTreeNode<'a>]),
+    GlobalVariable(&'a [ParseTreeNode<'a>]),
+    SizeOf(&'a [ParseTreeNode<'a>]), // The size of the type given as its only child
+    DocComment(Token),
+    Error(Token), // Placeholder for something which could not be parsed, at the token it started at
     Empty
 }
 
+/// The arena every node of a parse tree is allocated in, which lives as long as the tree
+pub type NodeArena<'a> = Arena<ParseTreeNode<'a>>;
+
+impl<'a> ParseTreeNode<'a>
+{
+    /// Get the children of a parse tree node
+    pub fn children(&self) -> &'a [ParseTreeNode<'a>]
+    {
+        match self
+        {
+            ParseTreeNode::Library(nodes) | ParseTreeNode::Function(nodes) | ParseTreeNode::Arguments(nodes) |
+            ParseTreeNode::Argument(nodes) | ParseTreeNode::Type(nodes) | ParseTreeNode::Statement(nodes) |
+            ParseTreeNode::Statements(nodes) | ParseTreeNode::Assignments(nodes) | ParseTreeNode::Assignment(nodes) |
+            ParseTreeNode::Expression(_, nodes) | ParseTreeNode::AssignmentStatement(nodes) | ParseTreeNode::IfStatement(nodes) |
+            ParseTreeNode::ReturnStatement(nodes) | ParseTreeNode::WhileLoop(nodes) | ParseTreeNode::DoWhileLoop(nodes) |
+            ParseTreeNode::Loop(nodes) | ParseTreeNode::ForLoop(nodes) | ParseTreeNode::Attributes(nodes) | ParseTreeNode::Attribute(nodes) |
+            ParseTreeNode::Alignment(nodes) | ParseTreeNode::StaticAssert(nodes) | ParseTreeNode::DefaultValue(nodes) |
+            ParseTreeNode::Macro(nodes) | ParseTreeNode::Import(nodes) | ParseTreeNode::Use(nodes) | ParseTreeNode::Struct(nodes) |
+            ParseTreeNode::Field(nodes) | ParseTreeNode::GlobalVariable(nodes) | ParseTreeNode::SizeOf(nodes) => *nodes,
+            ParseTreeNode::Identifier(_) | ParseTreeNode::RawType(_) | ParseTreeNode::StructType(_) | ParseTreeNode::RawToken(_) |
+            ParseTreeNode::IntegerLiteral(_) | ParseTreeNode::NullLiteral(_) | ParseTreeNode::StringLiteral(_) |
+            ParseTreeNode::DocComment(_) | ParseTreeNode::Error(_) | ParseTreeNode::Empty => &[]
+        }
+    }
+
+    /// Get a copy of a parse tree node with its children replaced, allocating them in an arena
+    pub fn with_children(&self, arena: &'a NodeArena<'a>, nodes: Vec<ParseTreeNode<'a>>) -> ParseTreeNode<'a>
+    {
+        let nodes = arena.alloc_extend(nodes);
+
+        match self
+        {
+            ParseTreeNode::Library(_) => ParseTreeNode::Library(nodes),
+            ParseTreeNode::Function(_) => ParseTreeNode::Function(nodes),
+            ParseTreeNode::Arguments(_) => ParseTreeNode::Arguments(nodes),
+            ParseTreeNode::Argument(_) => ParseTreeNode::Argument(nodes),
+            ParseTreeNode::Type(_) => ParseTreeNode::Type(nodes),
+            ParseTreeNode::Statement(_) => ParseTreeNode::Statement(nodes),
+            ParseTreeNode::Statements(_) => ParseTreeNode::Statements(nodes),
+            ParseTreeNode::Assignments(_) => ParseTreeNode::Assignments(nodes),
+            ParseTreeNode::Assignment(_) => ParseTreeNode::Assignment(nodes),
+            ParseTreeNode::Expression(exprtype, _) => ParseTreeNode::Expression(*exprtype, nodes),
+            ParseTreeNode::AssignmentStatement(_) => ParseTreeNode::AssignmentStatement(nodes),
+            ParseTreeNode::IfStatement(_) => ParseTreeNode::IfStatement(nodes),
+            ParseTreeNode::ReturnStatement(_) => ParseTreeNode::ReturnStatement(nodes),
+            ParseTreeNode::WhileLoop(_) => ParseTreeNode::WhileLoop(nodes),
+            ParseTreeNode::DoWhileLoop(_) => ParseTreeNode::DoWhileLoop(nodes),
+            ParseTreeNode::Loop(_) => ParseTreeNode::Loop(nodes),
+            ParseTreeNode::ForLoop(_) => ParseTreeNode::ForLoop(nodes),
+            ParseTreeNode::Attributes(_) => ParseTreeNode::Attributes(nodes),
+            ParseTreeNode::Attribute(_) => ParseTreeNode::Attribute(nodes),
+            ParseTreeNode::Alignment(_) => ParseTreeNode::Alignment(nodes),
+            ParseTreeNode::StaticAssert(_) => ParseTreeNode::StaticAssert(nodes),
+            ParseTreeNode::DefaultValue(_) => ParseTreeNode::DefaultValue(nodes),
+            ParseTreeNode::Macro(_) => ParseTreeNode::Macro(nodes),
+            ParseTreeNode::Import(_) => ParseTreeNode::Import(nodes),
+            ParseTreeNode::Use(_) => ParseTreeNode::Use(nodes),
+            ParseTreeNode::Struct(_) => ParseTreeNode::Struct(nodes),
+            ParseTreeNode::Field(_) => ParseTreeNode::Field(nodes),
+            ParseTreeNode::GlobalVariable(_) => ParseTreeNode::GlobalVariable(nodes),
+            ParseTreeNode::SizeOf(_) => ParseTreeNode::SizeOf(nodes),
+            leaf => leaf.clone()
+        }
+    }
+
+    /// Get the earliest token in the source within a parse tree node
+    pub fn first_token(&self) -> Option<&Token>
+    {
+        match self
+        {
+            ParseTreeNode::Identifier(token) | ParseTreeNode::RawType(token) | ParseTreeNode::StructType(token) |
+            ParseTreeNode::RawToken(token) | ParseTreeNode::IntegerLiteral(token) | ParseTreeNode::NullLiteral(token) |
+            ParseTreeNode::StringLiteral(token) | ParseTreeNode::Error(token) => Some(token),
+            _ => self.children().iter().filter_map(|child| child.first_token()).min_by_key(|token| (token.location.row, token.location.col))
+        }
+    }
+
+    /// Check if a parse tree node has a placeholder for something which could not be parsed
+    /// anywhere within it
+    pub fn has_error(&self) -> bool
+    {
+        matches!(self, ParseTreeNode::Error(_)) || self.children().iter().any(|child| child.has_error())
+    }
+}
+
 /// Render a parse tree node
-fn render_node(node: ParseTreeNode) -> (String, Vec<ParseTreeNode>)
+fn render_node<'a>(node: &ParseTreeNode<'a>) -> (String, &'a [ParseTreeNode<'a>])
 {
     match node
     {
@@ -88,27 +196,45 @@ fn render_node(node: ParseTreeNode) -> (String, Vec<ParseTreeNode>)
         ParseTreeNode::Arguments(nodes) => (format!("Arguments"), nodes),
         ParseTreeNode::Argument(nodes) => (format!("Argument"), nodes),
         ParseTreeNode::Type(nodes) => (format!("Type"), nodes),
-        ParseTreeNode::Identifier(token) => (format!("Identifier ({})", token.data), vec![]),
-        ParseTreeNode::RawType(token) => (format!("Raw Type ({})", token.data), vec![]),
+        ParseTreeNode::Identifier(token) => (format!("Identifier ({})", token.data), &[]),
+        ParseTreeNode::RawType(token) => (format!("Raw Type ({})", token.data), &[]),
+        ParseTreeNode::StructType(token) => (format!("Struct Type ({})", token.data), &[]),
         ParseTreeNode::Statement(nodes) => (format!("Statement"), nodes),
         ParseTreeNode::Statements(nodes) => (format!("Statements"), nodes),
         ParseTreeNode::Assignments(nodes) => (format!("Assignments"), nodes),
         ParseTreeNode::Assignment(nodes) => (format!("Assignment"), nodes),
         ParseTreeNode::Expression(exprtype, nodes) => (format!("Expression ({:?})", exprtype), nodes),
-        ParseTreeNode::RawToken(token) => (format!("Raw Token ({})", token.data), vec![]),
-        ParseTreeNode::IntegerLiteral(token) => (format!("Integer ({})", token.data), vec![]),
+        ParseTreeNode::RawToken(token) => (format!("Raw Token ({})", token.data), &[]),
+        ParseTreeNode::IntegerLiteral(token) => (format!("Integer ({})", token.data), &[]),
+        ParseTreeNode::NullLiteral(_) => (String::from("Null"), &[]),
+        ParseTreeNode::StringLiteral(token) => (format!("String ({})", token.data), &[]),
         ParseTreeNode::AssignmentStatement(nodes) => (format!("Assignment Statement"), nodes),
         ParseTreeNode::IfStatement(nodes) => (format!("If Statement"), nodes),
         ParseTreeNode::ReturnStatement(nodes) => (format!("Return Statement"), nodes),
         ParseTreeNode::WhileLoop(nodes) => (format!("While Loop"), nodes),
         ParseTreeNode::DoWhileLoop(nodes) => (format!("Do While Loop"), nodes),
         ParseTreeNode::Loop(nodes) => (format!("Loop"), nodes),
-        ParseTreeNode::Empty => (format!("Empty"), vec![]),
+        ParseTreeNode::ForLoop(nodes) => ("For Loop".to_string(), nodes),
+        ParseTreeNode::Attributes(nodes) => (String::from("Attributes"), nodes),
+        ParseTreeNode::Attribute(nodes) => (String::from("Attribute"), nodes),
+        ParseTreeNode::Alignment(nodes) => (String::from("Alignment"), nodes),
+        ParseTreeNode::StaticAssert(nodes) => (String::from("Static Assert"), nodes),
+        ParseTreeNode::DefaultValue(nodes) => (String::from("Default Value"), nodes),
+        ParseTreeNode::Macro(nodes) => (String::from("Macro"), nodes),
+        ParseTreeNode::Import(nodes) => (String::from("Import"), nodes),
+        ParseTreeNode::Use(nodes) => (String::from("Use"), nodes),
+        ParseTreeNode::Struct(nodes) => (String::from("Struct"), nodes),
+        ParseTreeNode::Field(nodes) => (String::from("Field"), nodes),
+        ParseTreeNode::GlobalVariable(nodes) => (String::from("Global Variable"), nodes),
+        ParseTreeNode::SizeOf(nodes) => (String::from("Size Of"), nodes),
+        ParseTreeNode::DocComment(token) => (format!("Doc Comment ({:?})", token.data), &[]),
+        ParseTreeNode::Error(token) => (format!("Error ({})", token.data), &[]),
+        ParseTreeNode::Empty => (format!("Empty"), &[]),
     }
 }
 
 /// Display a parse tree
-pub fn display_parse_tree(node: ParseTreeNode, prev: String, is_last: bool)
+pub fn display_parse_tree(node: &ParseTreeNode, prev: String, is_last: bool)
 {
     let mut mprev = prev.clone();
     let (text, nodes) = render_node(node);
@@ -130,6 +256,6 @@ pub fn display_parse_tree(node: ParseTreeNode, prev: String, is_last: bool)
     for (i, n) in nodes.iter().enumerate()
     {
         let new_prev = format!("{}{}", mprev, "│");
-        display_parse_tree(n.clone(), new_prev, i == nodes.len() - 1);
+        display_parse_tree(n, new_prev, i == nodes.len() - 1);
     }
 }
\ No newline at end of file
@@ -1,41 +1,67 @@
+use std::rc::Rc;
+
 use regex::Regex;
 use lazy_static::lazy_static;
 
-use crate::tokenizer::Token;
-use super::{ParseTreeNode, ExpressionType};
-use crate::cli::Error;
-use super::error::{expected_got_error, unexpected_eof_error};
+use crate::tokenizer::{Token, FileLocation};
+use super::{ParseTreeNode, NodeArena, ExpressionType};
+use super::macros::expand_macros;
+use crate::cli::{Error, suggest};
+use super::error::{expected_got_error, unexpected_eof_error, missing_semicolon_error, unparenthesized_condition_error, nested_too_deeply_error};
 
 static TYPES: &[&str] = &["i8", "u8", "i16", "u16", "i32", "u32", "i64", "u64", "void"];
-static KEYWORDS: &[&str] = &["loop", "while", "if", "break", "continue", "else", "do", "as"];
+static KEYWORDS: &[&str] = &["loop", "while", "for", "if", "break", "continue", "else", "do", "as", "null", "static_assert", "macro", "import", "use", "pub", "struct", "sizeof"];
+
+/// Keywords which begin a statement, a misspelling of one of these is parsed as an expression
+pub static STATEMENT_KEYWORDS: &[&str] = &["while", "for", "if", "do", "loop", "break", "continue", "return", "static_assert"];
+
 static MAX_EXPRESSION: usize = 17;
 
+/// How deeply expressions and statements may be nested within each other. A level takes up to
+/// ~60KB of stack through the parser and the phases after it in a debug build, so this keeps a
+/// compile within the 8MB stack of a main thread
+pub static MAX_NESTING: usize = 100;
+
 lazy_static!
 {
     static ref IDENTIFIER_REGEX: Regex = Regex::new(r"\A[a-zA-Z|_][a-zA-Z0-9|_]*").unwrap();
     static ref INTEGER_REGEX: Regex = Regex::new(r"\A[0-9]+").unwrap();
 }
 
-/// Token Stream
-#[derive(Debug, Clone)]
-pub struct Stream
+/// Token Stream, along with the arena the nodes parsed from it are allocated in
+#[derive(Clone)]
+pub struct Stream<'a>
 {
-    tokens: Vec<Token>,
-    index: usize
+    tokens: Rc<[Token]>,
+    index: usize,
+    recover: bool,
+    errors: Vec<Error>,
+    nesting: usize,
+    arena: &'a NodeArena<'a>
 }
 
-impl Stream
+impl<'a> Stream<'a>
 {
     /// Generate a new token stream object
-    pub fn new(tokens: Vec<Token>) -> Self
+    pub fn new(tokens: Vec<Token>, arena: &'a NodeArena<'a>) -> Self
     {
         Self
         {
-            tokens,
-            index: 0
+            tokens: tokens.into(),
+            index: 0,
+            recover: false,
+            errors: vec![],
+            nesting: 0,
+            arena
         }
     }
 
+    /// Allocate the children of a parse tree node in the arena
+    fn nodes(&self, nodes: Vec<ParseTreeNode<'a>>) -> &'a [ParseTreeNode<'a>]
+    {
+        self.arena.alloc_extend(nodes)
+    }
+
     /// Peek at the next token
     pub fn peek(&self) -> Option<Token>
     {
@@ -98,7 +124,7 @@ impl Stream
         }
         else
         {
-            unexpected_eof_error(&format!("'{}'", data), &self.tokens[index - 1])
+            unexpected_eof_error(&format!("'{}'", data), self.tokens.last())
         }
     }
 
@@ -118,6 +144,22 @@ impl Stream
         }
     }
     
+    /// Expect and consume the semicolon ending a statement, if it is missing and the token in its
+    /// place is on a later line or starts another statement, the error is placed just after the
+    /// previous token, where the semicolon should go
+    pub fn expect_semicolon(&mut self, after: &str) -> Result<(), Error>
+    {
+        match (self.index.checked_sub(1).and_then(|i| self.tokens.get(i)), self.current())
+        {
+            (Some(last), Some(next)) if next.data != ";" &&
+                (next.location.row > last.location.row || starts_statement(&next.data)) =>
+            {
+                missing_semicolon_error(after, last)
+            },
+            _ => self.expect_and_consume(String::from(";"))
+        }
+    }
+
     /// Expect the next token
     pub fn expect_next(&self, data: String) -> Result<(), Error>
     {
@@ -129,7 +171,7 @@ impl Stream
     {
         if self.current().is_none()
         {
-            unexpected_eof_error(s, &self.tokens[self.index - 1])
+            unexpected_eof_error(s, self.tokens.last())
         }
         else
         {
@@ -137,232 +179,150 @@ impl Stream
         }
     }
 
+    /// Go a level deeper into the expressions and statements nested within each other, which fails
+    /// once they are nested too deeply for the parser, and the phases after it, to handle without
+    /// running out of stack. The level is kept by the stream of each parse function, so it is
+    /// left behind when the caller accepts the stream
+    fn nest(&mut self) -> Result<(), Error>
+    {
+        self.nesting += 1;
+
+        if self.nesting > MAX_NESTING
+        {
+            return nested_too_deeply_error(self.current().as_ref().or(self.tokens.last()));
+        }
+
+        Ok(())
+    }
+
     /// Accept a stream
-    pub fn accept_stream(&mut self, result: Result<(Stream, ParseTreeNode), Error>) -> Result<ParseTreeNode, Error>
+    pub fn accept_stream(&mut self, result: Result<(Stream<'a>, ParseTreeNode<'a>), Error>) -> Result<ParseTreeNode<'a>, Error>
     {
         let val = result?;
         self.tokens = val.0.tokens;
         self.index = val.0.index;
+        self.errors = val.0.errors;
 
         Ok(val.1)
     }
-}
-
-/// Convert a parse tree to be on the left hand side
-pub fn convert_to_left(node: ParseTreeNode)  -> Result<ParseTreeNode, Error>
-{
-    let mut tree = node.clone();
-    
-    tree = match &tree
-    {
-        ParseTreeNode::Expression(expr_type, children) =>
-        {
-            if expr_type == &ExpressionType::Dereference
-            {
-                ParseTreeNode::Expression(ExpressionType::DereferenceLeft, children.clone())
-            }
-            else
-            {
-                tree
-            }
-        },
-        _ => tree
-    };
 
-    tree = match tree
+    /// Record a syntax error when recovering from them, and skip the rest of the statement or
+    /// item it was found in, giving a placeholder for it. Skipping stops after the next ';' or
+    /// block, or before a '}' closing the enclosing block, which is skipped over at the top level
+    fn recover_from(&mut self, error: Error, top_level: bool) -> ParseTreeNode<'a>
     {
-        ParseTreeNode::Library(c) =>
-        {
-            let mut new_children = vec![];
-
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
-
-            ParseTreeNode::Library(new_children)
-        },
-        ParseTreeNode::Function(c) =>
-        {
-            let mut new_children = vec![];
-
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
-
-            ParseTreeNode::Function(new_children)
-        },
-        ParseTreeNode::Arguments(c) =>
-        {
-            let mut new_children = vec![];
-
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
-
-            ParseTreeNode::Arguments(new_children)
-        },
-        ParseTreeNode::Argument(c) =>
-        {
-            let mut new_children = vec![];
-
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
-
-            ParseTreeNode::Argument(new_children)
-        },
-        ParseTreeNode::Type(c) =>
-        {
-            let mut new_children = vec![];
-
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
-
-            ParseTreeNode::Type(new_children)
-        },
-        ParseTreeNode::Statement(c) =>
-        {
-            let mut new_children = vec![];
-
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
+        let start = self.current().or_else(|| self.tokens.last().cloned()).unwrap_or_else(|| Token::new(FileLocation::new(), String::new()));
+        self.errors.push(error);
 
-            ParseTreeNode::Statement(new_children)
-        },
-        ParseTreeNode::Statements(c) =>
-        {
-            let mut new_children = vec![];
-
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
+        let mut depth = 0;
 
-            ParseTreeNode::Statements(new_children)
-        },
-        ParseTreeNode::Assignments(c) =>
+        while let Some(token) = self.current()
         {
-            let mut new_children = vec![];
-
-            for child in c
+            match token.data.as_str()
             {
-                new_children.push(convert_to_left(child)?);
-            }
-
-            ParseTreeNode::Assignments(new_children)
-        },
-        ParseTreeNode::Assignment(c) =>
-        {
-            let mut new_children = vec![];
+                "{" => {depth += 1;},
+                "}" if depth == 0 =>
+                {
+                    if top_level
+                    {
+                        self.consume();
+                    }
 
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
+                    break;
+                },
+                "}" if depth == 1 =>
+                {
+                    self.consume();
+                    break;
+                },
+                "}" => {depth -= 1;},
+                ";" if depth == 0 =>
+                {
+                    self.consume();
+                    break;
+                },
+                _ => {}
             }
 
-            ParseTreeNode::Assignment(new_children)
-        },
-        ParseTreeNode::Expression(t, c) =>
-        {
-            let mut new_children = vec![];
-
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
+            self.consume();
+        }
 
-            ParseTreeNode::Expression(t, new_children)
-        },
-        ParseTreeNode::AssignmentStatement(c) =>
-        {
-            let mut new_children = vec![];
+        ParseTreeNode::Error(start)
+    }
+}
 
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
+/// Convert a parse tree to be on the left hand side
+pub fn convert_to_left<'a>(node: ParseTreeNode<'a>, arena: &'a NodeArena<'a>) -> Result<ParseTreeNode<'a>, Error>
+{
+    Ok(converted_to_left(&node, arena).unwrap_or(node))
+}
 
-            ParseTreeNode::AssignmentStatement(new_children)
-        },
+/// Get a parse tree converted to be on the left hand side, or None if converting it changes
+/// nothing. Only the nodes above a dereference are copied, the rest of the tree is shared
+fn converted_to_left<'a>(node: &ParseTreeNode<'a>, arena: &'a NodeArena<'a>) -> Option<ParseTreeNode<'a>>
+{
+    let (tree, converted) = match node
+    {
+        ParseTreeNode::Expression(ExpressionType::Dereference, children) => (ParseTreeNode::Expression(ExpressionType::DereferenceLeft, children), true),
+        ParseTreeNode::Library(_) | ParseTreeNode::Function(_) | ParseTreeNode::Arguments(_) | ParseTreeNode::Argument(_) |
+        ParseTreeNode::Type(_) | ParseTreeNode::Statement(_) | ParseTreeNode::Statements(_) | ParseTreeNode::Assignments(_) |
+        ParseTreeNode::Assignment(_) | ParseTreeNode::Expression(_, _) | ParseTreeNode::AssignmentStatement(_) |
+        ParseTreeNode::IfStatement(_) | ParseTreeNode::ReturnStatement(_) | ParseTreeNode::WhileLoop(_) |
+        ParseTreeNode::DoWhileLoop(_) | ParseTreeNode::Loop(_) | ParseTreeNode::ForLoop(_) => (node.clone(), false),
+        _ => {return None;}
+    };
 
-        ParseTreeNode::IfStatement(c) =>
-        {
-            let mut new_children = vec![];
+    let children: Vec<Option<ParseTreeNode>> = tree.children().iter().map(|child| converted_to_left(child, arena)).collect();
 
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
+    if children.iter().all(Option::is_none)
+    {
+        return if converted {Some(tree)} else {None};
+    }
 
-            ParseTreeNode::IfStatement(new_children)
-        },
-        ParseTreeNode::ReturnStatement(c) =>
-        {
-            let mut new_children = vec![];
+    let children = children.into_iter().zip(tree.children()).map(|(new, old)| new.unwrap_or_else(|| old.clone())).collect();
 
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
+    Some(tree.with_children(arena, children))
+}
 
-            ParseTreeNode::ReturnStatement(new_children)
-        },
-        ParseTreeNode::WhileLoop(c) =>
-        {
-            let mut new_children = vec![];
+/// Get the parse tree for a translation unit, allocating its nodes in an arena
+pub fn parse<'a>(tokens: Vec<Token>, arena: &'a NodeArena<'a>) -> Result<ParseTreeNode<'a>, Error>
+{
+    expand_macros(parse_library(&Stream::new(tokens, arena))?.1, arena)
+}
 
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
+/// Get the parse tree for a translation unit which may have syntax errors, as it is being edited,
+/// along with every syntax error found. Each statement or item which could not be parsed is
+/// replaced by an `Error` node, so the rest of the tree is kept
+pub fn parse_recovering<'a>(tokens: Vec<Token>, arena: &'a NodeArena<'a>) -> (ParseTreeNode<'a>, Vec<Error>)
+{
+    let mut stream = Stream::new(tokens, arena);
+    stream.recover = true;
 
-            ParseTreeNode::WhileLoop(new_children)
-        },
-        ParseTreeNode::DoWhileLoop(c) =>
-        {
-            let mut new_children = vec![];
+    let (stream, library) = match parse_library(&stream)
+    {
+        Ok(result) => result,
+        Err(error) => {return (ParseTreeNode::Library(&[]), vec![error]);}
+    };
 
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
+    let mut errors = stream.errors;
 
-            ParseTreeNode::DoWhileLoop(new_children)
-        },
-        ParseTreeNode::Loop(c) =>
+    // Macros are expanded where they can be, leaving the tree as it was if they cannot
+    let tree = match expand_macros(library.clone(), arena)
+    {
+        Ok(tree) => tree,
+        Err(error) =>
         {
-            let mut new_children = vec![];
-
-            for child in c
-            {
-                new_children.push(convert_to_left(child)?);
-            }
-
-            ParseTreeNode::Loop(new_children)
-        },
-        _ => tree
+            errors.push(error);
+            library
+        }
     };
 
-    Ok(tree)
-}
-
-/// Get the parse tree for a translation unit
-pub fn parse(tokens: Vec<Token>) -> Result<ParseTreeNode, Error>
-{
-    Ok(parse_library(&Stream::new(tokens))?.1)
+    (tree, errors)
 }
 
 /// Parse out a raw type
 /// (for example i8, but not i8*)
-fn parse_raw_type(orig_stream: &Stream) -> Result<(Stream, ParseTreeNode), Error>
+fn parse_raw_type<'a>(orig_stream: &Stream<'a>) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -371,19 +331,65 @@ fn parse_raw_type(orig_stream: &Stream) -> Result<(Stream, ParseTreeNode), Error
 
     let val = stream.current().unwrap();
 
-    if TYPES.contains(&val.data.as_str())
+    if is_type_name(&val.data)
     {
         stream.consume();
         Ok((stream, ParseTreeNode::RawType(val.clone())))
     }
+    // A struct is named after the `struct` keyword, as in `struct Point`
+    else if val.data == "struct"
+    {
+        stream.consume();
+
+        match stream.accept_stream(parse_identifier(&stream))?
+        {
+            ParseTreeNode::Identifier(name) => Ok((stream, ParseTreeNode::StructType(name))),
+            _ => unreachable!()
+        }
+    }
     else
     {
-        expected_got_error("raw type", &val)
+        suggest(expected_got_error("raw type", &val), &val.data, TYPES.iter().copied())
     }
 }
 
+/// Check if a name is a type, either one of the named types, an integer of any width (`i24`) or a
+/// vector of integers (`i32x4`)
+fn is_type_name(name: &str) -> bool
+{
+    if TYPES.contains(&name)
+    {
+        return true;
+    }
+
+    if !name.starts_with('i') && !name.starts_with('u')
+    {
+        return false;
+    }
+
+    let mut parts = name[1..].splitn(2, 'x');
+    let is_number = |part: &str| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit());
+
+    parts.all(is_number)
+}
+
+/// Check if a name can be written as an identifier without the `r#` of a raw identifier, so is not
+/// a keyword or a type
+pub fn is_identifier(name: &str) -> bool
+{
+    let whole = IDENTIFIER_REGEX.find(name).map(|found| found.end() == name.len()).unwrap_or(false);
+
+    whole && !name.contains('|') && !KEYWORDS.contains(&name) && !is_type_name(name)
+}
+
+/// Check if a token can only begin a statement or declaration, or close the block around it
+fn starts_statement(data: &str) -> bool
+{
+    data == "}" || data == "#" || data == "struct" || STATEMENT_KEYWORDS.contains(&data) || is_type_name(data)
+}
+
 /// Parse out an identifier
-fn parse_identifier(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+fn parse_identifier<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -392,9 +398,21 @@ fn parse_identifier(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Er
 
     let val = stream.current().unwrap();
 
-    
+    // A raw identifier, `r#loop`, can be named after a keyword or a type, and is known by the name
+    // after the `r#`
+    if let Some(name) = val.data.strip_prefix("r#")
+    {
+        if !IDENTIFIER_REGEX.is_match(name)
+        {
+            return expected_got_error("identifier", &val);
+        }
+
+        stream.consume();
+        return Ok((stream, ParseTreeNode::Identifier(Token::new(val.location.clone(), String::from(name)))));
+    }
+
         // A type instead
-    if  TYPES.contains(&val.data.as_str()) ||  
+    if  is_type_name(&val.data) ||  
 
         // A keyword
         KEYWORDS.contains(&val.data.as_str()) ||
@@ -408,8 +426,35 @@ fn parse_identifier(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Er
     Ok((stream, ParseTreeNode::Identifier(val.clone())))
 }
 
+/// Parse out an identifier which may be qualified with the module it belongs to, as in
+/// `math::square`, giving the whole name as one identifier
+fn parse_qualified_identifier<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let module = match stream.accept_stream(parse_identifier(&stream))?
+    {
+        ParseTreeNode::Identifier(token) => token,
+        _ => unreachable!()
+    };
+
+    if !(stream.check_current(String::from(":")) && stream.check_next(String::from(":")))
+    {
+        return Ok((stream, ParseTreeNode::Identifier(module)));
+    }
+
+    stream.consume();
+    stream.consume();
+
+    match stream.accept_stream(parse_identifier(&stream))?
+    {
+        ParseTreeNode::Identifier(name) => Ok((stream, ParseTreeNode::Identifier(Token::new(module.location, format!("{}::{}", module.data, name.data))))),
+        _ => unreachable!()
+    }
+}
+
 /// Parse out an integer
-fn parse_integer(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+fn parse_integer<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -428,7 +473,7 @@ fn parse_integer(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error
 }
 
 /// Parse out a single token
-fn parse_token(orig_stream: &Stream, what: &str) -> Result<(Stream, ParseTreeNode), Error>
+fn parse_token<'a>(orig_stream: &Stream<'a>, what: &str) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -442,15 +487,32 @@ fn parse_token(orig_stream: &Stream, what: &str) -> Result<(Stream, ParseTreeNod
 }
 
 /// Parse out a type
-/// Either just a raw type or a raw type folloed by multiple '*'s
-/// Parse out an identifier
-fn parse_type(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+/// Either just a raw type or a raw type folloed by multiple '*'s, optionally preceded by an
+/// alignment (for example align(16) i32x4)
+fn parse_type<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
     // Make sure the stream isn't exhausted
     stream.expect_current_exists("type")?;
 
+    // An alignment may come before the type
+    let alignment = if stream.check_current(String::from("align"))
+    {
+        stream.consume();
+        stream.expect_and_consume(String::from("("))?;
+
+        let value = stream.accept_stream(parse_integer(&stream))?;
+
+        stream.expect_and_consume(String::from(")"))?;
+
+        Some(ParseTreeNode::Alignment(stream.nodes(vec![value])))
+    }
+    else
+    {
+        None
+    };
+
     // First is getting the raw type
     let raw_type = stream.accept_stream(parse_raw_type(&stream))?;
     let mut items = vec![raw_type];
@@ -461,363 +523,494 @@ fn parse_type(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
         stream.consume();
     }
 
-    Ok((stream, ParseTreeNode::Type(items)))
+    // The alignment is kept after the pointers so the raw type stays the first child
+    if let Some(alignment) = alignment
+    {
+        items.push(alignment);
+    }
+
+    let node = ParseTreeNode::Type(stream.nodes(items));
+    Ok((stream, node))
+}
+
+/// Recursive Parsing of expressions, each depth parsing the operators of one precedence and
+/// taking its operands from the depth below. Each depth has a function of its own, so parsing a
+/// nested expression only takes the stack each depth needs
+fn recursive_expression<'a>(orig_stream: &Stream<'a>, depth: usize) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    match depth
+    {
+        0 => primary_expression(orig_stream),
+        1 => access_expression(orig_stream),
+        2 => postfix_expression(orig_stream),
+        3 => prefix_expression(orig_stream),
+        4..=13 => binary_expression(orig_stream, depth),
+        14 => ternary_expression(orig_stream),
+        15 => assignment_expression(orig_stream),
+        16 => cast_expression(orig_stream),
+        17 => comma_expression(orig_stream),
+        default => panic!("Unexpected depth value of {}", default)
+    }
 }
 
-/// Recursive Parsing of expressions
-fn recursive_expression(orig_stream: &Stream, depth: usize) -> Result<(Stream, ParseTreeNode), Error>
+/// Parse out a number, string, identifier or expression in parentheses
+fn primary_expression<'a>(orig_stream: &Stream<'a>) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
-    match depth
+    if stream.check_current(String::from("("))
     {
-        // Number, Identifier, (expr)
-        0 => 
-        {
-            if stream.check_current(String::from("("))
-            {
-                // Open Paren
-                stream.expect_and_consume(String::from("("))?;
+        // Open Paren
+        stream.nest()?;
+        stream.expect_and_consume(String::from("("))?;
 
-                let val = stream.accept_stream(recursive_expression(&stream, MAX_EXPRESSION))?;
+        let val = stream.accept_stream(recursive_expression(&stream, MAX_EXPRESSION))?;
 
-                // Close Paren
-                stream.expect_and_consume(String::from(")"))?;
+        // Close Paren
+        stream.expect_and_consume(String::from(")"))?;
 
-                Ok((stream, val))
-            }
-            else if let Ok(val) = parse_integer(&stream)
-            {
-                Ok(val)
-            }
-            else if let Ok(val) = parse_identifier(&stream)
-            {
-                Ok(val)
-            }
-            else
-            {
-                expected_got_error("expression", &stream.current().unwrap())
-            }
-        },
-        // Array Access or Function Call
-        1 =>
-        {
-            // Start with a previous expression
-            let mut current = stream.accept_stream(recursive_expression(&stream, depth - 1))?;
+        Ok((stream, val))
+    }
+    else if let Ok(val) = parse_integer(&stream)
+    {
+        Ok(val)
+    }
+    else if stream.check_current(String::from("null"))
+    {
+        let token = stream.current().unwrap();
+        stream.consume();
 
-            // Loop because this operation is left associative
-            while stream.check_current(String::from("[")) || stream.check_current(String::from("("))
-            {
-                // Array access
-                if stream.check_current(String::from("["))
-                {
-                    // Open bracket
-                    stream.expect_and_consume(String::from("["))?;
+        Ok((stream, ParseTreeNode::NullLiteral(token)))
+    }
+    // The size of a type, which is only known once the target is
+    else if stream.check_current(String::from("sizeof"))
+    {
+        stream.consume();
+        stream.expect_and_consume(String::from("("))?;
 
-                    // Get the internal expression
-                    let expr = stream.accept_stream(parse_expression(&stream))?;
+        let datatype = stream.accept_stream(parse_type(&stream))?;
 
-                    // Close bracket
-                    stream.expect_and_consume(String::from("]"))?;
+        stream.expect_and_consume(String::from(")"))?;
 
-                    current = ParseTreeNode::Expression(ExpressionType::ArrayAccess, vec![current, expr]);
-                }
-                // Function Call
-                else
-                {
-                    // Open bracket
-                    stream.expect_and_consume(String::from("("))?;
+        let node = ParseTreeNode::SizeOf(stream.nodes(vec![datatype]));
+        Ok((stream, node))
+    }
+    // Strings are kept as the token, along with their quotes and escapes
+    else if let Some(string) = stream.current().filter(|token| token.data.starts_with('"'))
+    {
+        if string.data.len() < 2 || !string.data.ends_with('"')
+        {
+            return unexpected_eof_error("'\"'", Some(&string));
+        }
 
-                    // Array starts with the current expression
-                    let mut items = vec![current];
+        stream.consume();
 
-                    while !stream.check_current(String::from(")"))
-                    {
-                        items.push(stream.accept_stream(recursive_expression(&stream, MAX_EXPRESSION - 1))?);
-
-                        if stream.check_current(String::from(","))
-                        {
-                            stream.expect_and_consume(String::from(","))?;
-                        }
-                        else
-                        {
-                            break;
-                        }
-                    }
+        Ok((stream, ParseTreeNode::StringLiteral(string)))
+    }
+    else if let Ok(val) = parse_qualified_identifier(&stream)
+    {
+        Ok(val)
+    }
+    else
+    {
+        stream.expect_current_exists("expression")?;
+        expected_got_error("expression", &stream.current().unwrap())
+    }
+}
+
+/// Parse out an array access, function call or access of a member of a struct
+fn access_expression<'a>(orig_stream: &Stream<'a>) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
 
-                    // Close bracket
-                    stream.expect_and_consume(String::from(")"))?;
+    // Start with a previous expression
+    let mut current = stream.accept_stream(recursive_expression(&stream, 0))?;
 
-                    current = ParseTreeNode::Expression(ExpressionType::FunctionCall, items);
-                }
-            }
+    // Loop because this operation is left associative
+    while stream.check_current(String::from("[")) || stream.check_current(String::from("(")) ||
+        stream.check_current(String::from(".")) || stream.check_current(String::from("->"))
+    {
+        stream.nest()?;
 
-            Ok((stream, current))
-        },
-        // Post Increment and Post Decrement
-        2 =>
+        // Member access, of a struct or of the struct a pointer points to
+        if stream.check_current(String::from(".")) || stream.check_current(String::from("->"))
         {
-            // Start with a previous expression
-            let mut current = stream.accept_stream(recursive_expression(&stream, depth - 1))?;
+            let kind = if stream.check_current(String::from(".")) {ExpressionType::Member} else {ExpressionType::PointerMember};
+            stream.consume();
 
-            // Loop because this operation is left associative
-            while stream.check_current(String::from("++")) || stream.check_current(String::from("--"))
+            let field = match stream.accept_stream(parse_identifier(&stream))?
             {
-                // Post Increment
-                if stream.check_current(String::from("++"))
-                {
-                    // Opperation
-                    stream.expect_and_consume(String::from("++"))?;
-                    
-                    current = ParseTreeNode::Expression(ExpressionType::PostIncrement, vec![current]);
-                }
-                // Post Decrement
-                else
+                ParseTreeNode::Identifier(token) => token,
+                _ => unreachable!()
+            };
+
+            current = ParseTreeNode::Expression(kind, stream.nodes(vec![current, ParseTreeNode::RawToken(field)]));
+        }
+        // Array access
+        else if stream.check_current(String::from("["))
+        {
+            // Open bracket
+            stream.expect_and_consume(String::from("["))?;
+
+            // Get the internal expression
+            let expr = stream.accept_stream(parse_expression(&stream))?;
+
+            // Close bracket
+            stream.expect_and_consume(String::from("]"))?;
+
+            current = ParseTreeNode::Expression(ExpressionType::ArrayAccess, stream.nodes(vec![current, expr]));
+        }
+        // Function Call
+        else
+        {
+            // Open bracket
+            stream.expect_and_consume(String::from("("))?;
+
+            // Array starts with the current expression
+            let mut items = vec![current];
+
+            // Arguments are separated by commas, and a trailing comma may be left before
+            // the ')'
+            while !stream.check_current(String::from(")"))
+            {
+                items.push(stream.accept_stream(recursive_expression(&stream, MAX_EXPRESSION - 1))?);
+
+                if stream.check_current(String::from(","))
+                {
+                    stream.expect_and_consume(String::from(","))?;
+                }
+                else
                 {
-                    // Opperation
-                    stream.expect_and_consume(String::from("--"))?;
-                    
-                    current = ParseTreeNode::Expression(ExpressionType::PostDecrement, vec![current]);
+                    break;
                 }
             }
 
-            Ok((stream, current))
+            // Close bracket
+            stream.expect_and_consume(String::from(")"))?;
+
+            current = ParseTreeNode::Expression(ExpressionType::FunctionCall, stream.nodes(items));
+        }
+    }
+
+    Ok((stream, current))
+}
+
+/// Parse out a post increment or post decrement
+fn postfix_expression<'a>(orig_stream: &Stream<'a>) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    // Start with a previous expression
+    let mut current = stream.accept_stream(recursive_expression(&stream, 1))?;
+
+    // Loop because this operation is left associative
+    while stream.check_current(String::from("++")) || stream.check_current(String::from("--"))
+    {
+        stream.nest()?;
+
+        // Post Increment
+        if stream.check_current(String::from("++"))
+        {
+            // Opperation
+            stream.expect_and_consume(String::from("++"))?;
+            
+            current = ParseTreeNode::Expression(ExpressionType::PostIncrement, stream.nodes(vec![current]));
+        }
+        // Post Decrement
+        else
+        {
+            // Opperation
+            stream.expect_and_consume(String::from("--"))?;
+            
+            current = ParseTreeNode::Expression(ExpressionType::PostDecrement, stream.nodes(vec![current]));
+        }
+    }
+
+    Ok((stream, current))
+}
+
+/// Parse out a prefix operator
+fn prefix_expression<'a>(orig_stream: &Stream<'a>) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    stream.expect_current_exists("expression")?;
+
+    let op = match stream.current().unwrap().data.as_str()
+    {
+        "++" => Some(ExpressionType::PreIncrement),
+        "--" => Some(ExpressionType::PreDecrement),
+        "+" => Some(ExpressionType::UnaryPlus),
+        "-" => Some(ExpressionType::UnaryMinus),
+        "!" => Some(ExpressionType::LogicalNot),
+        "~" => Some(ExpressionType::BitwiseNot),
+        "*" => Some(ExpressionType::Dereference),
+        "&" => Some(ExpressionType::Reference),
+        _ => None
+    };
+
+    if op.is_none()
+    {
+        recursive_expression(&stream, 2)
+    }
+    else
+    {
+        stream.nest()?;
+        stream.consume();
+
+        let post = stream.accept_stream(recursive_expression(&stream, 3))?;
+        let node = ParseTreeNode::Expression(op.unwrap(), stream.nodes(vec![post]));
+        Ok((stream, node))
+    }
+}
+
+/// Parse out a ternary operator
+fn ternary_expression<'a>(orig_stream: &Stream<'a>) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let prev = stream.accept_stream(recursive_expression(&stream, 13))?;
+
+    if stream.check_current(String::from("?"))
+    {
+        stream.nest()?;
+        stream.expect_and_consume(String::from("?"))?;
+        let inner = stream.accept_stream(parse_expression(&stream))?;
+        stream.expect_and_consume(String::from(":"))?;
+        let last = stream.accept_stream(recursive_expression(&stream, 14))?;
+
+        let node = ParseTreeNode::Expression(ExpressionType::Ternary, stream.nodes(vec![prev, inner, last]));
+        Ok((stream, node))
+    }
+    else
+    {
+        Ok((stream, prev))
+    }
+}
+
+/// Parse out an assignment operator, which is right associative
+fn assignment_expression<'a>(orig_stream: &Stream<'a>) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let prev = stream.accept_stream(recursive_expression(&stream, 14))?;
+
+    if let Some(op) = stream.current().and_then(|current| binary_operator(15, &current.data))
+    {
+        stream.nest()?;
+        stream.consume();
+
+        // We know the expression is an assignment
+        let prev = convert_to_left(prev, stream.arena)?;
+
+        let post = stream.accept_stream(recursive_expression(&stream, 15))?;
+        let node = ParseTreeNode::Expression(op, stream.nodes(vec![prev, post]));
+        Ok((stream, node))
+    }
+    else
+    {
+        Ok((stream, prev))
+    }
+}
+
+/// Parse out a cast
+fn cast_expression<'a>(orig_stream: &Stream<'a>) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let prev = stream.accept_stream(recursive_expression(&stream, 15))?;
+
+    if stream.check_current(String::from("as"))
+    {
+        stream.expect_and_consume(String::from("as"))?;
+        let datatype = stream.accept_stream(parse_type(&stream))?;
+
+        let node = ParseTreeNode::Expression(ExpressionType::Cast, stream.nodes(vec![prev, datatype]));
+        Ok((stream, node))
+    }
+    else
+    {
+        Ok((stream, prev))
+    }
+}
+
+/// Parse out comma expressions, which are left associative
+fn comma_expression<'a>(orig_stream: &Stream<'a>) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let mut prev = stream.accept_stream(recursive_expression(&stream, 16))?;
+
+    while let Some(op) = stream.current().and_then(|current| binary_operator(17, &current.data))
+    {
+        stream.nest()?;
+        stream.consume();
+
+        let post = stream.accept_stream(recursive_expression(&stream, 16))?;
+        prev = ParseTreeNode::Expression(op, stream.nodes(vec![prev, post]));
+    }
+
+    Ok((stream, prev))
+}
+
+/// Parse out the binary operators of up to the given depth by precedence climbing, rather than
+/// recursing once for each depth. The operand on the right of an operator is parsed from the
+/// operators below its depth, so operators of the same depth are left associative
+fn binary_expression<'a>(orig_stream: &Stream<'a>, max_depth: usize) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let mut prev = stream.accept_stream(recursive_expression(&stream, 3))?;
+
+    while let Some((depth, op)) = stream.current().and_then(|current| (4..=max_depth).find_map(|depth| binary_operator(depth, &current.data).map(|op| (depth, op))))
+    {
+        stream.nest()?;
+        stream.consume();
+
+        let post = stream.accept_stream(binary_expression(&stream, depth - 1))?;
+        prev = ParseTreeNode::Expression(op, stream.nodes(vec![prev, post]));
+    }
+
+    Ok((stream, prev))
+}
+
+/// Get the binary operator a token stands for at the given depth of an expression, if any
+fn binary_operator(depth: usize, data: &str) -> Option<ExpressionType>
+{
+    match depth
+    {
+        // Multiplicative Operations
+        4 =>
+        {
+            match data
+            {
+                "*" => Some(ExpressionType::Multiply),
+                "/" => Some(ExpressionType::Divide),
+                "%" => Some(ExpressionType::Modulus),
+                _ => None
+            }
         },
-        // Prefix Operators
-        3 =>
+        // Additive Operations
+        5 =>
         {
-            let op = match stream.current().unwrap().data.as_str()
+            match data
             {
-                "++" => Some(ExpressionType::PreIncrement),
-                "--" => Some(ExpressionType::PreDecrement),
-                "+" => Some(ExpressionType::UnaryPlus),
-                "-" => Some(ExpressionType::UnaryMinus),
-                "!" => Some(ExpressionType::LogicalNot),
-                "~" => Some(ExpressionType::BitwiseNot),
-                "*" => Some(ExpressionType::Dereference),
-                "&" => Some(ExpressionType::Reference),
+                "+" => Some(ExpressionType::Add),
+                "-" => Some(ExpressionType::Subtract),
                 _ => None
-            };
-
-            if op.is_none()
+            }
+        },
+        // Shift Operations
+        6 =>
+        {
+            match data
             {
-                recursive_expression(&stream, depth - 1)
+                "<<" => Some(ExpressionType::ShiftLeft),
+                ">>" => Some(ExpressionType::ShiftRight),
+                _ => None
             }
-            else
+        },
+        // Comparison Operations
+        7 =>
+        {
+            match data
             {
-                stream.consume();
-
-                let post = stream.accept_stream(recursive_expression(&stream, depth))?;
-                Ok((stream, ParseTreeNode::Expression(op.unwrap(), vec![post])))
+                "<" => Some(ExpressionType::LessThan),
+                "<=" => Some(ExpressionType::LessThanOrEqual),
+                ">" => Some(ExpressionType::GreaterThan),
+                ">=" => Some(ExpressionType::GreaterThanOrEqual),
+                _ => None
             }
         },
-        // Binary Operators
-        4..=13 | 15 | 17 =>
+        // Equality Operations
+        8 =>
         {
-            let mut prev = stream.accept_stream(recursive_expression(&stream, depth - 1))?;
-            
-            // Extract the operator
-            let op = 
-            if let Some(current) = stream.current()
+            match data
             {
-                match depth
-                {
-                    // Multiplicative Operations
-                    4 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "*" => Some(ExpressionType::Multiply),
-                            "/" => Some(ExpressionType::Divide),
-                            "%" => Some(ExpressionType::Modulus),
-                            _ => None
-                        }
-                    },
-                    // Additive Operations
-                    5 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "+" => Some(ExpressionType::Add),
-                            "-" => Some(ExpressionType::Subtract),
-                            _ => None
-                        }
-                    },
-                    // Shift Operations
-                    6 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "<<" => Some(ExpressionType::ShiftLeft),
-                            ">>" => Some(ExpressionType::ShiftRight),
-                            _ => None
-                        }
-                    },
-                    // Comparison Operations
-                    7 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "<" => Some(ExpressionType::LessThan),
-                            "<=" => Some(ExpressionType::LessThanOrEqual),
-                            ">" => Some(ExpressionType::GreaterThan),
-                            ">=" => Some(ExpressionType::GreaterThanOrEqual),
-                            _ => None
-                        }
-                    },
-                    // Equality Operations
-                    8 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "==" => Some(ExpressionType::Equal),
-                            "!=" => Some(ExpressionType::NotEqual),
-                            _ => None
-                        }
-                    },
-                    // Bitwise And
-                    9 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "&" => Some(ExpressionType::BitwiseAnd),
-                            _ => None
-                        }
-                    },
-                    // Bitwise Xor
-                    10 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "^" => Some(ExpressionType::BitwiseXor),
-                            _ => None
-                        }
-                    },
-                    // Bitwise Or
-                    11 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "|" => Some(ExpressionType::BitwiseOr),
-                            _ => None
-                        }
-                    },
-                    // Logical And
-                    12 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "&&" => Some(ExpressionType::LogicalAnd),
-                            _ => None
-                        }
-                    },
-                    // Logical Or
-                    13 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "||" => Some(ExpressionType::LogicalOr),
-                            _ => None
-                        }
-                    },
-                    // Assignment Operators
-                    15 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "=" => Some(ExpressionType::Assignment),
-                            "+=" => Some(ExpressionType::AddAssign),
-                            "-=" => Some(ExpressionType::SubtractAssign),
-                            "*=" => Some(ExpressionType::MultiplyAssign),
-                            "/=" => Some(ExpressionType::DivideAssign),
-                            "%=" => Some(ExpressionType::ModulusAssign),
-                            "<<=" => Some(ExpressionType::ShiftLeftAssign),
-                            ">>=" => Some(ExpressionType::ShiftRightAssign),
-                            "&=" => Some(ExpressionType::BitwiseAnd),
-                            "^=" => Some(ExpressionType::BitwiseXor),
-                            "|=" => Some(ExpressionType::BitwiseOr),
-                            _ => None
-                        }
-                    },
-                    // Comma
-                    17 =>
-                    {
-                        match current.data.as_str()
-                        {
-                            "," => Some(ExpressionType::Comma),
-                            _ => None
-                        }
-                    },
-                    _ => None
-                }
-                
+                "==" => Some(ExpressionType::Equal),
+                "!=" => Some(ExpressionType::NotEqual),
+                _ => None
             }
-            // If we have reached the EOF, there can't be an operator
-            else
+        },
+        // Bitwise And
+        9 =>
+        {
+            match data
             {
-                None
-            };
-
-            if op.is_none()
+                "&" => Some(ExpressionType::BitwiseAnd),
+                _ => None
+            }
+        },
+        // Bitwise Xor
+        10 =>
+        {
+            match data
             {
-                Ok((stream, prev))
+                "^" => Some(ExpressionType::BitwiseXor),
+                _ => None
             }
-            else
+        },
+        // Bitwise Or
+        11 =>
+        {
+            match data
             {
-                stream.consume();
-
-                // We know the expression is an assignment
-                if depth == 15
-                {
-                    prev = convert_to_left(prev)?;
-                }
-
-                let post = stream.accept_stream(recursive_expression(&stream, depth))?;
-                Ok((stream, ParseTreeNode::Expression(op.unwrap(), vec![prev, post])))
+                "|" => Some(ExpressionType::BitwiseOr),
+                _ => None
             }
         },
-        // Ternary Operator
-        14 =>
+        // Logical And
+        12 =>
         {
-            let prev = stream.accept_stream(recursive_expression(&stream, depth - 1))?;
-
-            if stream.check_current(String::from("?"))
+            match data
             {
-                stream.expect_and_consume(String::from("?"))?;
-                let inner = stream.accept_stream(parse_expression(&stream))?;
-                stream.expect_and_consume(String::from(":"))?;
-                let last = stream.accept_stream(recursive_expression(&stream, depth))?;
-
-                Ok((stream, ParseTreeNode::Expression(ExpressionType::Ternary, vec![prev, inner, last])))
+                "&&" => Some(ExpressionType::LogicalAnd),
+                _ => None
             }
-            else
+        },
+        // Logical Or
+        13 =>
+        {
+            match data
             {
-                Ok((stream, prev))
+                "||" => Some(ExpressionType::LogicalOr),
+                _ => None
             }
         },
-        // Cast
-        16 =>
+        // Assignment Operators
+        15 =>
         {
-            let prev = stream.accept_stream(recursive_expression(&stream, depth - 1))?;
-
-            if stream.check_current(String::from("as"))
+            match data
             {
-                stream.expect_and_consume(String::from("as"))?;
-                let datatype = stream.accept_stream(parse_type(&stream))?;
-
-                Ok((stream, ParseTreeNode::Expression(ExpressionType::Cast, vec![prev, datatype])))
+                "=" => Some(ExpressionType::Assignment),
+                "+=" => Some(ExpressionType::AddAssign),
+                "-=" => Some(ExpressionType::SubtractAssign),
+                "*=" => Some(ExpressionType::MultiplyAssign),
+                "/=" => Some(ExpressionType::DivideAssign),
+                "%=" => Some(ExpressionType::ModulusAssign),
+                "<<=" => Some(ExpressionType::ShiftLeftAssign),
+                ">>=" => Some(ExpressionType::ShiftRightAssign),
+                "&=" => Some(ExpressionType::BitwiseAndAssign),
+                "^=" => Some(ExpressionType::BitwiseXorAssign),
+                "|=" => Some(ExpressionType::BitwiseOrAssign),
+                _ => None
             }
-            else
+        },
+        // Comma
+        17 =>
+        {
+            match data
             {
-                Ok((stream, prev))
+                "," => Some(ExpressionType::Comma),
+                _ => None
             }
         },
-        default => panic!("Unexpected depth value of {}", default)
+        _ => None
     }
 }
 
 /// Parse out an expression
-fn parse_expression(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+fn parse_expression<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let stream = orig_stream.clone();
 
@@ -829,7 +1022,7 @@ fn parse_expression(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Er
 }
 
 /// Parse out an expression without comma expressions
-fn parse_expression_no_comma(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+fn parse_expression_no_comma<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let stream = orig_stream.clone();
 
@@ -840,8 +1033,8 @@ fn parse_expression_no_comma(orig_stream: &Stream)  -> Result<(Stream, ParseTree
     recursive_expression(&stream, MAX_EXPRESSION - 1)
 }
 
-/// Parse out an assignment
-fn parse_assignment(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+/// Parse out an assignment, where a struct may be declared without a value as it starts zeroed
+fn parse_assignment<'a>(orig_stream: &Stream<'a>, optional: bool)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -852,6 +1045,12 @@ fn parse_assignment(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Er
     let identifier = stream.accept_stream(parse_identifier(&stream))?;
     let mut items = vec![identifier];
 
+    if optional && !stream.check_current(String::from("="))
+    {
+        let node = ParseTreeNode::Assignment(stream.nodes(items));
+        return Ok((stream, node));
+    }
+
     // Next is an equals sign
     stream.expect_and_consume(String::from("="))?;
 
@@ -859,11 +1058,12 @@ fn parse_assignment(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Er
     let expr = stream.accept_stream(parse_expression_no_comma(&stream))?;
     items.push(expr);
 
-    Ok((stream, ParseTreeNode::Assignment(items)))
+    let node = ParseTreeNode::Assignment(stream.nodes(items));
+    Ok((stream, node))
 }
 
-/// Parse out assignments
-fn parse_assignments(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+/// Parse out assignments, whose values are optional when declaring structs
+fn parse_assignments<'a>(orig_stream: &Stream<'a>, optional: bool)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -872,20 +1072,62 @@ fn parse_assignments(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), E
     //stream.expect_current_exists("arguments")?;
 
     // First is getting the return type
-    let arg = stream.accept_stream(parse_assignment(&stream))?;
+    let arg = stream.accept_stream(parse_assignment(&stream, optional))?;
     let mut items = vec![arg];
 
     while stream.check_current(String::from(","))
     {
         stream.consume();
-        items.push(stream.accept_stream(parse_assignment(&stream))?);
+        items.push(stream.accept_stream(parse_assignment(&stream, optional))?);
+    }
+
+    let node = ParseTreeNode::Assignments(stream.nodes(items));
+    Ok((stream, node))
+}
+
+/// Parse out the condition of an if statement or while loop, parentheses around it are optional
+/// when the body is in braces, but needed when it is not as otherwise the condition would run on
+/// into the body, such as `if x -y;`
+fn parse_condition<'a>(orig_stream: &Stream<'a>, construct: &str) -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    stream.expect_current_exists("condition")?;
+    let first = stream.index;
+
+    let cond = stream.accept_stream(parse_expression(&stream))?;
+
+    let parenthesized = stream.tokens[first].data == "(" && closing_parenthesis(&stream.tokens, first) == Some(stream.index - 1);
+
+    if !parenthesized && stream.current().is_some() && !stream.check_current(String::from("{"))
+    {
+        return unparenthesized_condition_error(construct, &stream.tokens[first]);
+    }
+
+    Ok((stream, cond))
+}
+
+/// Find the index of the ')' closing the '(' at the given index
+fn closing_parenthesis(tokens: &[Token], open: usize) -> Option<usize>
+{
+    let mut depth = 0;
+
+    for (i, token) in tokens.iter().enumerate().skip(open)
+    {
+        match token.data.as_str()
+        {
+            "(" => {depth += 1;},
+            ")" if depth == 1 => {return Some(i);},
+            ")" => {depth -= 1;},
+            _ => {}
+        }
     }
 
-    Ok((stream, ParseTreeNode::Assignments(items)))
+    None
 }
 
 /// Parse out an if statement
-fn parse_if_statement(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+fn parse_if_statement<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -893,19 +1135,24 @@ fn parse_if_statement(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode),
     stream.expect_current_exists("if statement")?;
 
     // Must start with an if keyword
+    let if_token = stream.current().unwrap();
     stream.expect_and_consume(String::from("if"))?;
 
     // Get the condition
-    let cond = stream.accept_stream(parse_expression(&stream))?;
+    let cond = stream.accept_stream(parse_condition(&stream, "an if statement"))?;
     let mut items = vec![cond];
 
     // Get the main body
     let body = stream.accept_stream(parse_statement(&stream))?;
     items.push(body);
 
-    // Check if there is an else clause
+    // Check if there is an else clause, which always belongs to the nearest if without one, the
+    // keywords are kept after the clause so the placement of the else can be checked
+    let mut else_token = ParseTreeNode::Empty;
+
     if stream.check_current(String::from("else"))
     {
+        else_token = ParseTreeNode::RawToken(stream.current().unwrap());
         stream.expect_and_consume(String::from("else"))?;
 
         let clause = stream.accept_stream(parse_statement(&stream))?;
@@ -916,11 +1163,15 @@ fn parse_if_statement(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode),
         items.push(ParseTreeNode::Empty);
     }
 
-    Ok((stream, ParseTreeNode::IfStatement(items)))
+    items.push(ParseTreeNode::RawToken(if_token));
+    items.push(else_token);
+
+    let node = ParseTreeNode::IfStatement(stream.nodes(items));
+    Ok((stream, node))
 }
 
 /// Parse out a while loop
-fn parse_while_loop(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+fn parse_while_loop<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -930,16 +1181,17 @@ fn parse_while_loop(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Er
     // Must start with a while keyword
     stream.expect_and_consume(String::from("while"))?;
 
-    let cond = stream.accept_stream(parse_expression(&stream))?;
+    let cond = stream.accept_stream(parse_condition(&stream, "a while loop"))?;
     
     let statement = stream.accept_stream(parse_statement(&stream))?;
 
     
-    Ok((stream, ParseTreeNode::WhileLoop(vec![cond, statement])))
+    let node = ParseTreeNode::WhileLoop(stream.nodes(vec![cond, statement]));
+    Ok((stream, node))
 }
 
 /// Parse out a do while loop
-fn parse_do_while_loop(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+fn parse_do_while_loop<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -955,76 +1207,263 @@ fn parse_do_while_loop(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode),
     stream.expect_and_consume(String::from("while"))?;
 
     let cond = stream.accept_stream(parse_expression(&stream))?;
+
+    // And terminated by a semicolon
+    stream.expect_semicolon("statement")?;
     
-    Ok((stream, ParseTreeNode::DoWhileLoop(vec![cond, statement])))
+    let node = ParseTreeNode::DoWhileLoop(stream.nodes(vec![cond, statement]));
+    Ok((stream, node))
 }
 
-/// Parse out a loop
-fn parse_loop(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+/// Parse out a for loop, `for (init; cond; step) statement`, where each of the three parts may be
+/// left out. The initialization may declare variables
+fn parse_for_loop<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
     // Make sure the stream isn't exhausted
-    stream.expect_current_exists("loop")?;
+    stream.expect_current_exists("for loop")?;
 
-    // Must start with a while keyword
-    stream.expect_and_consume(String::from("loop"))?;
-
-    let statement = stream.accept_stream(parse_statement(&stream))?;
+    // Must start with a for keyword
+    stream.expect_and_consume(String::from("for"))?;
 
-    
-    Ok((stream, ParseTreeNode::Loop(vec![statement])))
-}
-
-/// Parse out a statement
-fn parse_statement(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
-{
-    let mut stream = orig_stream.clone();
+    stream.expect_and_consume(String::from("("))?;
 
-    // Make sure the stream isn't exhausted
-    stream.expect_current_exists("statement")?;
+    // Initialization
+    stream.expect_current_exists("initialization")?;
 
-    // Simplest example of a statement is just a semicolon
-    if stream.check_current(String::from(";"))
+    let init = if stream.check_current(String::from(";"))
     {
-        stream.consume();
-        Ok((stream, ParseTreeNode::Statement(vec![])))
+        ParseTreeNode::Empty
     }
-    // The next simplest is the compound statement
-    else if stream.check_current(String::from("{"))
+    else if let Ok(val) = parse_type(&stream)
     {
-        stream.consume();
-        
-        let mut statements = vec![];
-
-        while !stream.check_current(String::from("}"))
-        {
-            statements.push(stream.accept_stream(parse_statement(&stream))?);
-        }
+        let datatype = stream.accept_stream(Ok(val))?;
 
-        stream.expect_and_consume(String::from("}"))?;
+        let assignments = stream.accept_stream(parse_assignments(&stream, false))?;
 
-        Ok((stream, ParseTreeNode::Statements(statements)))
+        ParseTreeNode::AssignmentStatement(stream.nodes(vec![datatype, assignments]))
     }
-    // Continue and break commands
-    else if stream.check_current(String::from("continue")) || stream.check_current(String::from("break"))
+    else
     {
-        let items = vec![stream.accept_stream(parse_token(&stream, "command"))?];
+        let expr = stream.accept_stream(parse_expression(&stream))?;
 
-        stream.expect_and_consume(String::from(";"))?;
+        ParseTreeNode::Statement(stream.nodes(vec![expr]))
+    };
 
-        Ok((stream, ParseTreeNode::Statement(items)))
-    }
-    // Initialization statement
-    else if let Ok(val) = parse_type(&stream)
-    {
-        let datatype = stream.accept_stream(Ok(val))?;
-        
-        let assignments = stream.accept_stream(parse_assignments(&stream))?;
+    stream.expect_semicolon("initialization")?;
 
-        stream.expect_and_consume(String::from(";"))?;
+    // Condition
+    stream.expect_current_exists("condition")?;
 
-        Ok((stream, ParseTreeNode::AssignmentStatement(vec![datatype, assignments])))
+    let cond = if stream.check_current(String::from(";"))
+    {
+        ParseTreeNode::Empty
+    }
+    else
+    {
+        stream.accept_stream(parse_expression(&stream))?
+    };
+
+    stream.expect_semicolon("condition")?;
+
+    // Step
+    stream.expect_current_exists("step")?;
+
+    let step = if stream.check_current(String::from(")"))
+    {
+        ParseTreeNode::Empty
+    }
+    else
+    {
+        let expr = stream.accept_stream(parse_expression(&stream))?;
+
+        ParseTreeNode::Statement(stream.nodes(vec![expr]))
+    };
+
+    stream.expect_and_consume(String::from(")"))?;
+
+    let statement = stream.accept_stream(parse_statement(&stream))?;
+
+    let node = ParseTreeNode::ForLoop(stream.nodes(vec![init, cond, step, statement]));
+    Ok((stream, node))
+}
+
+/// Parse out a loop
+fn parse_loop<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    // Make sure the stream isn't exhausted
+    stream.expect_current_exists("loop")?;
+
+    // Must start with a while keyword
+    stream.expect_and_consume(String::from("loop"))?;
+
+    let statement = stream.accept_stream(parse_statement(&stream))?;
+
+    
+    let node = ParseTreeNode::Loop(stream.nodes(vec![statement]));
+    Ok((stream, node))
+}
+
+/// Parse out a loop preceded by attributes (for example #[unroll(4)] while ...), the attributes
+/// are kept after the other children of the loop
+fn parse_loop_with_attributes<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let attributes = stream.accept_stream(parse_attributes(&stream))?;
+
+    stream.expect_current_exists("loop")?;
+
+    let node = if stream.check_current(String::from("while"))
+    {
+        stream.accept_stream(parse_while_loop(&stream))?
+    }
+    else if stream.check_current(String::from("do"))
+    {
+        stream.accept_stream(parse_do_while_loop(&stream))?
+    }
+    else if stream.check_current(String::from("loop"))
+    {
+        stream.accept_stream(parse_loop(&stream))?
+    }
+    else if stream.check_current(String::from("for"))
+    {
+        stream.accept_stream(parse_for_loop(&stream))?
+    }
+    else
+    {
+        return expected_got_error("loop after attributes", &stream.current().unwrap());
+    };
+
+    let node = match &node
+    {
+        ParseTreeNode::WhileLoop(children) | ParseTreeNode::DoWhileLoop(children) | ParseTreeNode::Loop(children) |
+        ParseTreeNode::ForLoop(children) =>
+        {
+            let mut children = children.to_vec();
+            children.push(attributes);
+            node.with_children(stream.arena, children)
+        },
+        _ => node
+    };
+
+    Ok((stream, node))
+}
+
+/// Parse out a static assertion, `static_assert(expr, "message");`
+fn parse_static_assert<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    // Make sure the stream isn't exhausted
+    stream.expect_current_exists("static_assert")?;
+
+    let keyword = stream.current().unwrap();
+    stream.expect_and_consume(String::from("static_assert"))?;
+
+    stream.expect_and_consume(String::from("("))?;
+
+    let expr = stream.accept_stream(parse_expression_no_comma(&stream))?;
+
+    stream.expect_and_consume(String::from(","))?;
+
+    // The message must be a string
+    stream.expect_current_exists("string")?;
+    let message = stream.current().unwrap();
+
+    if !message.data.starts_with('"')
+    {
+        return expected_got_error("string", &message);
+    }
+
+    // A string which is never closed runs to the end of the file
+    if message.data.len() < 2 || !message.data.ends_with('"')
+    {
+        return unexpected_eof_error("'\"'", Some(&message));
+    }
+
+    stream.consume();
+
+    stream.expect_and_consume(String::from(")"))?;
+    stream.expect_semicolon("statement")?;
+
+    let node = ParseTreeNode::StaticAssert(stream.nodes(vec![ParseTreeNode::RawToken(keyword), expr, ParseTreeNode::RawToken(message)]));
+    Ok((stream, node))
+}
+
+/// Parse out a statement
+fn parse_statement<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    // Make sure the stream isn't exhausted
+    stream.expect_current_exists("statement")?;
+    stream.nest()?;
+
+    // Simplest example of a statement is just a semicolon
+    if stream.check_current(String::from(";"))
+    {
+        stream.consume();
+        let node = ParseTreeNode::Statement(stream.nodes(vec![]));
+        Ok((stream, node))
+    }
+    // The next simplest is the compound statement
+    else if stream.check_current(String::from("{"))
+    {
+        stream.consume();
+        
+        let mut statements = vec![];
+
+        while !stream.check_current(String::from("}"))
+        {
+            match parse_statement(&stream)
+            {
+                Err(error) if stream.recover =>
+                {
+                    statements.push(stream.recover_from(error, false));
+
+                    // A block left open at the end of the file is closed there
+                    if stream.current().is_none()
+                    {
+                        let node = ParseTreeNode::Statements(stream.nodes(statements));
+                        return Ok((stream, node));
+                    }
+                },
+                result => {statements.push(stream.accept_stream(result)?);}
+            }
+        }
+
+        stream.expect_and_consume(String::from("}"))?;
+
+        let node = ParseTreeNode::Statements(stream.nodes(statements));
+        Ok((stream, node))
+    }
+    // Continue and break commands
+    else if stream.check_current(String::from("continue")) || stream.check_current(String::from("break"))
+    {
+        let items = vec![stream.accept_stream(parse_token(&stream, "command"))?];
+
+        stream.expect_semicolon("statement")?;
+
+        let node = ParseTreeNode::Statement(stream.nodes(items));
+        Ok((stream, node))
+    }
+    // Initialization statement
+    else if let Ok(val) = parse_type(&stream)
+    {
+        let datatype = stream.accept_stream(Ok(val))?;
+        
+        // Only a struct, rather than a pointer to one, is zeroed when it is not given a value
+        let optional = matches!(datatype.children(), [ParseTreeNode::StructType(_), rest @ ..] if !rest.iter().any(|child| matches!(child, ParseTreeNode::RawToken(_))));
+        let assignments = stream.accept_stream(parse_assignments(&stream, optional))?;
+
+        stream.expect_semicolon("declaration")?;
+
+        let node = ParseTreeNode::AssignmentStatement(stream.nodes(vec![datatype, assignments]));
+        Ok((stream, node))
     }
     // If Statement
     else if stream.check_current(String::from("if"))
@@ -1046,32 +1485,62 @@ fn parse_statement(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Err
     {
         parse_loop(&stream)
     }
+    // For Loop
+    else if stream.check_current(String::from("for"))
+    {
+        parse_for_loop(&stream)
+    }
+    // Attributes placed on a loop
+    else if stream.check_current(String::from("#"))
+    {
+        parse_loop_with_attributes(&stream)
+    }
+    // Static assertion
+    else if stream.check_current(String::from("static_assert"))
+    {
+        parse_static_assert(&stream)
+    }
     // Return statement
     else if stream.check_current(String::from("return"))
     {
+        let keyword = stream.current().unwrap();
         stream.expect_and_consume(String::from("return"))?;
 
-        let expr = stream.accept_stream(parse_expression(&stream))?;
+        // A void function returns without a value
+        let expr = if stream.check_current(String::from(";"))
+        {
+            ParseTreeNode::Empty
+        }
+        else
+        {
+            stream.accept_stream(parse_expression(&stream))?
+        };
 
-        stream.expect_and_consume(String::from(";"))?;
+        stream.expect_semicolon("expression")?;
 
-        Ok((stream, ParseTreeNode::ReturnStatement(vec![expr])))
+        // The keyword is kept so the statement can be located
+        let node = ParseTreeNode::ReturnStatement(stream.nodes(vec![expr, ParseTreeNode::RawToken(keyword)]));
+        Ok((stream, node))
     }
     // Expression Statement
     else
     {
+        let first = stream.current().unwrap();
         let expr = stream.accept_stream(parse_expression(&stream))?;
 
-        stream.expect_and_consume(String::from(";"))?;
+        // A misspelled keyword such as `whlie (x) {` parses as an expression, but is then not
+        // followed by a semicolon
+        suggest(stream.expect_semicolon("expression"), &first.data, STATEMENT_KEYWORDS.iter().copied())?;
 
-        Ok((stream, ParseTreeNode::Statement(vec![expr])))
+        let node = ParseTreeNode::Statement(stream.nodes(vec![expr]));
+        Ok((stream, node))
     }
 }
 
 /// Parse out an argument
-/// for example u8** argv
-/// in otherwords, a type and an identifier
-fn parse_argument(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+/// for example u8** argv, or i32 base = 10
+/// in otherwords, a type and an identifier, with an optional default value
+fn parse_argument<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -1082,15 +1551,41 @@ fn parse_argument(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Erro
     let datatype = stream.accept_stream(parse_type(&stream))?;
     let mut items = vec![datatype];
 
+    // Pointers may be marked restrict
+    let restrict = if stream.check_current(String::from("restrict"))
+    {
+        Some(stream.accept_stream(parse_token(&stream, "restrict"))?)
+    }
+    else
+    {
+        None
+    };
+
     let name = stream.accept_stream(parse_identifier(&stream))?;
     items.push(name);
 
-    Ok((stream, ParseTreeNode::Argument(items)))
+    // The qualifier is kept after the name so the other children keep their positions
+    if let Some(restrict) = restrict
+    {
+        items.push(restrict);
+    }
+
+    // An argument may be given a default value, used when a call leaves it out
+    if stream.check_current(String::from("="))
+    {
+        stream.consume();
+
+        let value = stream.accept_stream(parse_expression_no_comma(&stream))?;
+        items.push(ParseTreeNode::DefaultValue(stream.nodes(vec![value])));
+    }
+
+    let node = ParseTreeNode::Argument(stream.nodes(items));
+    Ok((stream, node))
 }
 
 /// Parse out an argument list
 /// for example the argument list i32 argc, u8** argv
-fn parse_arguments(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+fn parse_arguments<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
@@ -1105,21 +1600,131 @@ fn parse_arguments(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Err
     while stream.check_current(String::from(","))
     {
         stream.consume();
+
+        // A trailing comma may be left before the ')'
+        if stream.check_current(String::from(")"))
+        {
+            break;
+        }
+
         items.push(stream.accept_stream(parse_argument(&stream))?);
     }
 
-    Ok((stream, ParseTreeNode::Arguments(items)))
+    let node = ParseTreeNode::Arguments(stream.nodes(items));
+    Ok((stream, node))
+}
+
+/// Parse out a single attribute
+/// (for example #[optimize(0)])
+fn parse_attribute<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    // Make sure the stream isn't exhausted
+    stream.expect_current_exists("attribute")?;
+
+    // An attribute begins with '#['
+    stream.expect_and_consume(String::from("#"))?;
+    stream.expect_and_consume(String::from("["))?;
+
+    // Next the attribute name
+    let name = stream.accept_stream(parse_identifier(&stream))?;
+    let mut items = vec![name];
+
+    // Followed by an optional list of arguments
+    if stream.check_current(String::from("("))
+    {
+        stream.consume();
+
+        while !stream.check_current(String::from(")"))
+        {
+            stream.expect_current_exists("attribute argument")?;
+
+            let argument = if parse_integer(&stream).is_ok()
+            {
+                stream.accept_stream(parse_integer(&stream))?
+            }
+            // Strings are kept as the token, along with their quotes
+            else if let Some(string) = stream.current().filter(|token| token.data.starts_with('"'))
+            {
+                if string.data.len() < 2 || !string.data.ends_with('"')
+                {
+                    return unexpected_eof_error("'\"'", Some(&string));
+                }
+
+                stream.consume();
+                ParseTreeNode::RawToken(string)
+            }
+            else
+            {
+                stream.accept_stream(parse_identifier(&stream))?
+            };
+
+            items.push(argument);
+
+            if !stream.check_current(String::from(")"))
+            {
+                stream.expect_and_consume(String::from(","))?;
+            }
+        }
+
+        stream.expect_and_consume(String::from(")"))?;
+    }
+
+    stream.expect_and_consume(String::from("]"))?;
+
+    let node = ParseTreeNode::Attribute(stream.nodes(items));
+    Ok((stream, node))
+}
+
+/// Parse out the attributes preceding an item
+fn parse_attributes<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let mut items = vec![];
+
+    while stream.check_current(String::from("#"))
+    {
+        let attribute = stream.accept_stream(parse_attribute(&stream))?;
+        items.push(attribute);
+    }
+
+    let node = ParseTreeNode::Attributes(stream.nodes(items));
+    Ok((stream, node))
 }
 
 /// Parse out a function
-/// Specifically, the return type, the function name, the arguments and a statement
-fn parse_function(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+/// Specifically, any attributes, the return type, the function name, the arguments and a statement
+fn parse_function<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
     // Make sure the stream isn't exhausted
     stream.expect_current_exists("function")?;
 
+    // A doc comment is attached to the first token of the function, before any attributes
+    let doc = match stream.current().unwrap()
+    {
+        Token { doc: Some(text), location, .. } => ParseTreeNode::DocComment(Token::new(location, text)),
+        _ => ParseTreeNode::Empty
+    };
+
+    // Any attributes come before the function
+    let attributes = stream.accept_stream(parse_attributes(&stream))?;
+
+    // Then whether it can be called from other modules
+    let visibility = if stream.check_current(String::from("pub"))
+    {
+        let keyword = stream.current().unwrap();
+        stream.consume();
+        ParseTreeNode::RawToken(keyword)
+    }
+    else
+    {
+        ParseTreeNode::Empty
+    };
+
     // First is getting the return type
     let return_type = stream.accept_stream(parse_type(&stream))?;
     let mut items = vec![return_type];
@@ -1148,25 +1753,272 @@ fn parse_function(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Erro
     stream.expect(String::from(")"))?;
     stream.consume();
 
-    // Finally, there should be a statement here
-    let statement = stream.accept_stream(parse_statement(&stream))?;
-    items.push(statement);
+    // Finally, there should be a statement here, or a ';' for a prototype with no body
+    if stream.check_current(String::from(";"))
+    {
+        stream.consume();
+        items.push(ParseTreeNode::Empty);
+    }
+    else
+    {
+        let statement = stream.accept_stream(parse_statement(&stream))?;
+        items.push(statement);
+    }
+
+    // The attributes, doc comment and visibility are kept after the statement so the other children keep their positions
+    items.push(attributes);
+    items.push(doc);
+    items.push(visibility);
+
+    let node = ParseTreeNode::Function(stream.nodes(items));
+    Ok((stream, node))
+}
+
+/// Parse out an import of another file, `import "path.pc";`
+fn parse_import<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let keyword = stream.current().unwrap();
+    stream.expect_and_consume(String::from("import"))?;
+
+    // The path must be a string
+    stream.expect_current_exists("string")?;
+    let path = stream.current().unwrap();
+
+    if !path.data.starts_with('"')
+    {
+        return expected_got_error("string", &path);
+    }
+
+    if path.data.len() < 2 || !path.data.ends_with('"')
+    {
+        return unexpected_eof_error("'\"'", Some(&path));
+    }
+
+    stream.consume();
+    stream.expect_semicolon("import")?;
+
+    let node = ParseTreeNode::Import(stream.nodes(vec![ParseTreeNode::RawToken(keyword), ParseTreeNode::RawToken(path)]));
+    Ok((stream, node))
+}
+
+/// Parse out a use of a function from an imported module, `use math::square;`
+fn parse_use<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    let keyword = stream.current().unwrap();
+    stream.expect_and_consume(String::from("use"))?;
+
+    let module = stream.accept_stream(parse_identifier(&stream))?;
+
+    stream.expect_and_consume(String::from(":"))?;
+    stream.expect_and_consume(String::from(":"))?;
+
+    let name = stream.accept_stream(parse_identifier(&stream))?;
+
+    stream.expect_semicolon("use")?;
+
+    let node = ParseTreeNode::Use(stream.nodes(vec![ParseTreeNode::RawToken(keyword), module, name]));
+    Ok((stream, node))
+}
+
+/// Parse out a macro, `macro name(a, b) { ... }`, whose body must be a block
+fn parse_macro<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    stream.expect_and_consume(String::from("macro"))?;
+
+    let name = stream.accept_stream(parse_identifier(&stream))?;
+
+    stream.expect_and_consume(String::from("("))?;
+
+    let mut parameters = vec![];
+
+    while !stream.check_current(String::from(")"))
+    {
+        if !parameters.is_empty()
+        {
+            stream.expect_and_consume(String::from(","))?;
+        }
 
-    Ok((stream, ParseTreeNode::Function(items)))
+        parameters.push(stream.accept_stream(parse_identifier(&stream))?);
+    }
+
+    stream.expect_and_consume(String::from(")"))?;
+    stream.expect(String::from("{"))?;
+
+    let body = stream.accept_stream(parse_statement(&stream))?;
+
+    let node = ParseTreeNode::Macro(stream.nodes(vec![name, ParseTreeNode::Arguments(stream.nodes(parameters)), body]));
+    Ok((stream, node))
+}
+
+/// Parse out a struct, `struct Point { i32 x; i32 y; }`, giving its name, then each field by its
+/// type and name, then its doc comment
+fn parse_struct<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    // A doc comment is attached to the `struct` keyword
+    let doc = match stream.current().unwrap()
+    {
+        Token { doc: Some(text), location, .. } => ParseTreeNode::DocComment(Token::new(location, text)),
+        _ => ParseTreeNode::Empty
+    };
+
+    stream.expect_and_consume(String::from("struct"))?;
+
+    let name = stream.accept_stream(parse_identifier(&stream))?;
+    let mut items = vec![name];
+
+    stream.expect_and_consume(String::from("{"))?;
+
+    while !stream.check_current(String::from("}"))
+    {
+        stream.expect_current_exists("field")?;
+
+        let datatype = stream.accept_stream(parse_type(&stream))?;
+        let field = stream.accept_stream(parse_identifier(&stream))?;
+
+        stream.expect_semicolon("field")?;
+
+        items.push(ParseTreeNode::Field(stream.nodes(vec![datatype, field])));
+    }
+
+    stream.expect_and_consume(String::from("}"))?;
+
+    // The semicolon after the closing brace is optional, as it is in C++
+    if stream.check_current(String::from(";"))
+    {
+        stream.consume();
+    }
+
+    items.push(doc);
+
+    let node = ParseTreeNode::Struct(stream.nodes(items));
+    Ok((stream, node))
+}
+
+/// Check if the stream is at a global variable, a type and a name followed by its value or a `;`,
+/// rather than the `(` of a function
+fn starts_global_variable(stream: &Stream) -> bool
+{
+    let mut stream = stream.clone();
+
+    if stream.check_current(String::from("pub"))
+    {
+        stream.consume();
+    }
+
+    let named = parse_type(&stream).and_then(|(stream, _)| parse_identifier(&stream));
+
+    match named
+    {
+        Ok((stream, _)) => stream.check_current(String::from("=")) || stream.check_current(String::from(";")),
+        Err(_) => false
+    }
+}
+
+/// Parse out a global variable, `i32 counter = 0;`, whose value may be left out to start it at zero
+fn parse_global_variable<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    let mut stream = orig_stream.clone();
+
+    // A doc comment is attached to the first token of the variable
+    let doc = match stream.current().unwrap()
+    {
+        Token { doc: Some(text), location, .. } => ParseTreeNode::DocComment(Token::new(location, text)),
+        _ => ParseTreeNode::Empty
+    };
+
+    // Whether it can be used from other modules comes first
+    let visibility = if stream.check_current(String::from("pub"))
+    {
+        let keyword = stream.current().unwrap();
+        stream.consume();
+        ParseTreeNode::RawToken(keyword)
+    }
+    else
+    {
+        ParseTreeNode::Empty
+    };
+
+    let datatype = stream.accept_stream(parse_type(&stream))?;
+    let name = stream.accept_stream(parse_identifier(&stream))?;
+
+    let value = if stream.check_current(String::from("="))
+    {
+        stream.consume();
+        stream.accept_stream(parse_expression_no_comma(&stream))?
+    }
+    else
+    {
+        ParseTreeNode::Empty
+    };
+
+    stream.expect_semicolon("global variable")?;
+
+    let node = ParseTreeNode::GlobalVariable(stream.nodes(vec![datatype, name, value, doc, visibility]));
+    Ok((stream, node))
 }
 
 /// Parse out a library
-fn parse_library(orig_stream: &Stream)  -> Result<(Stream, ParseTreeNode), Error>
+fn parse_library<'a>(orig_stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
 {
     let mut stream = orig_stream.clone();
 
     let mut items = vec![];
     
-    while stream.peek().is_some()
+    while stream.current().is_some()
     {
-        let func = stream.accept_stream(parse_function(&stream))?;
-        items.push(func);
+        match parse_item(&stream)
+        {
+            Err(error) if stream.recover => {items.push(stream.recover_from(error, true));},
+            result => {items.push(stream.accept_stream(result)?);}
+        }
     }
 
-    Ok((stream, ParseTreeNode::Library(items)))
+    let node = ParseTreeNode::Library(stream.nodes(items));
+    Ok((stream, node))
+}
+
+/// Parse out one item of a library, a function, global variable, struct, import, use, macro or
+/// static assertion
+fn parse_item<'a>(stream: &Stream<'a>)  -> Result<(Stream<'a>, ParseTreeNode<'a>), Error>
+{
+    // A function may also start with `struct`, when it returns a pointer to one
+    let declares_struct = stream.check_current(String::from("struct")) &&
+        stream.tokens.get(stream.index + 2).map(|token| token.data == "{").unwrap_or(false);
+
+    if declares_struct
+    {
+        parse_struct(stream)
+    }
+    else if stream.check_current(String::from("static_assert"))
+    {
+        parse_static_assert(stream)
+    }
+    else if stream.check_current(String::from("import"))
+    {
+        parse_import(stream)
+    }
+    else if stream.check_current(String::from("use"))
+    {
+        parse_use(stream)
+    }
+    else if stream.check_current(String::from("macro"))
+    {
+        parse_macro(stream)
+    }
+    else if starts_global_variable(stream)
+    {
+        parse_global_variable(stream)
+    }
+    else
+    {
+        parse_function(stream)
+    }
 }
\ No newline at end of file
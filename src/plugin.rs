@@ -0,0 +1,97 @@
+use std::fmt;
+use std::sync::RwLock;
+
+use lazy_static::lazy_static;
+
+use crate::cli::{Error, Options};
+use crate::codegen::CodegenMode;
+use crate::irgen::{Function, Pass, PASSES};
+
+/// Where in the optimization pipeline a pass added by a plugin runs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PassStage
+{
+    Start, // Once, before any of the built in passes
+    Loop, // On each round of the built in passes, until the function stops shrinking
+    End // Once, after the built in passes have finished
+}
+
+/// A code generator added by a plugin, selected with `-g` by the name it was registered with
+pub trait Backend: Send + Sync
+{
+    /// Get the extension for files holding the output of the code generator
+    fn extension(&self) -> &'static str;
+
+    /// Generate code for the given functions, declaring the external functions they call
+    fn render(&self, functions: &[&Function], external: &[&Function], options: &Options, out: &mut dyn fmt::Write) -> Result<(), Error>;
+}
+
+/// A pass added by a plugin, along with when it runs
+pub struct PluginPass
+{
+    pub pass: &'static Pass,
+    pub stage: PassStage,
+    pub level: usize // Lowest optimization level the pass runs at
+}
+
+/// Everything added by plugins. Registrations last for the rest of the run, so they are given out
+/// without holding the lock
+struct Registry
+{
+    passes: Vec<&'static PluginPass>,
+    backends: Vec<(&'static str, &'static dyn Backend)>
+}
+
+lazy_static!
+{
+    static ref REGISTRY: RwLock<Registry> = RwLock::new(Registry { passes: vec![], backends: vec![] });
+}
+
+/// Add a pass to the optimization pipeline, run over every function optimized at the given level
+/// or above at the given stage, after the passes registered before it. The pass can be named in
+/// --dump-ir-before and --dump-ir-after like the built in ones
+pub fn register_pass(pass: Pass, stage: PassStage, level: usize) -> Result<(), Error>
+{
+    let mut registry = REGISTRY.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    if pass.name == "all" || PASSES.iter().any(|other| other.name == pass.name) || registry.passes.iter().any(|other| other.pass.name == pass.name)
+    {
+        return Err(Error::fatal_error(&format!("A pass named '{}' already exists", pass.name)).with_code("E0112"));
+    }
+
+    let pass: &'static Pass = Box::leak(Box::new(pass));
+    registry.passes.push(Box::leak(Box::new(PluginPass { pass, stage, level })));
+
+    Ok(())
+}
+
+/// Add a code generator, which is used when its name is passed to `-g`
+pub fn register_backend(name: &'static str, backend: Box<dyn Backend>) -> Result<(), Error>
+{
+    if CodegenMode::from_mode(name).is_ok()
+    {
+        return Err(Error::fatal_error(&format!("A code generation mode named '{}' already exists", name)).with_code("E0112"));
+    }
+
+    REGISTRY.write().unwrap_or_else(|poisoned| poisoned.into_inner()).backends.push((name, Box::leak(backend)));
+
+    Ok(())
+}
+
+/// Get every pass added by a plugin, in the order they were registered
+pub fn plugin_passes() -> Vec<&'static PluginPass>
+{
+    REGISTRY.read().unwrap_or_else(|poisoned| poisoned.into_inner()).passes.clone()
+}
+
+/// Get the code generator added by a plugin under the given name
+pub fn find_backend(name: &str) -> Option<(&'static str, &'static dyn Backend)>
+{
+    REGISTRY.read().unwrap_or_else(|poisoned| poisoned.into_inner()).backends.iter().find(|(other, _)| *other == name).copied()
+}
+
+/// Get the names of the code generators added by plugins, in the order they were registered
+pub fn backend_names() -> Vec<&'static str>
+{
+    REGISTRY.read().unwrap_or_else(|poisoned| poisoned.into_inner()).backends.iter().map(|(name, _)| *name).collect()
+}
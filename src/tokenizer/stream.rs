@@ -1,12 +1,16 @@
 use std::fmt;
 
+use serde::{Serialize, Deserialize};
+
+use crate::intern::Symbol;
+
 static DEFAULT_FILE_NAME: &'static str = "[unknown]";
 
 /// Location within a file (name, line, column)
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FileLocation
 {
-    name: String,
+    name: Symbol,
     pub col: usize,
     pub row: usize
 }
@@ -18,7 +22,7 @@ impl FileLocation
     {
         FileLocation
         {
-            name: String::from(DEFAULT_FILE_NAME),
+            name: Symbol::intern(DEFAULT_FILE_NAME),
             col: 1,
             row: 1
         }
@@ -29,12 +33,18 @@ impl FileLocation
     {
         FileLocation
         {
-            name: String::from(name),
+            name: Symbol::intern(name),
             col: 1,
             row: 1
         }
     }
 
+    /// Get the name of the file
+    pub fn name(&self) -> &'static str
+    {
+        self.name.as_str()
+    }
+
     /// Consume a character and move the file location accordingly
     pub fn consume_char(&mut self, value: char)
     {
@@ -55,11 +65,12 @@ impl fmt::Display for FileLocation
     }
 }
 
-/// Stream object (pairs in a FileLocation)
+/// Stream object (pairs in a FileLocation), holding the characters of the data so each is found
+/// by its index without walking the string
 pub struct Stream
 {
     index: usize,
-    data: String,
+    data: Vec<char>,
     location: FileLocation
 }
 
@@ -71,7 +82,7 @@ impl Stream
         Self
         {
             index: 0,
-            data: data.clone(),
+            data: data.chars().collect(),
             location: FileLocation::from_name(&file_name)
         }
     }
@@ -85,15 +96,7 @@ impl Stream
         }
         else
         {
-            let v = self.data.chars().nth(self.index + 1);
-            if v.is_none()
-            {
-                None
-            }
-            else
-            {
-                Some((v.unwrap(), self.location.clone()))
-            }
+            Some((self.data[self.index + 1], self.location.clone()))
         }
     }
 
@@ -106,32 +109,21 @@ impl Stream
         }
         else
         {
-            let v = self.data.chars().nth(self.index);
-            if v.is_none()
-            {
-                None
-            }
-            else
-            {
-                Some((v.unwrap(), self.location.clone()))
-            }
+            Some((self.data[self.index], self.location.clone()))
         }
     }
 
-    /// Consume the current character
+    /// Consume the current character, moving the location past it
     pub fn consume(&mut self) -> bool
     {
-        self.index += 1;
-
-        match self.current()
+        if let Some(value) = self.data.get(self.index)
         {
-            Some(value) =>
-            {
-                self.location.consume_char(value.0);
-                true
-            },
-            None => false
+            self.location.consume_char(*value);
         }
+
+        self.index += 1;
+
+        self.index < self.data.len()
     }
 
     /// Get the current location
@@ -150,6 +142,12 @@ impl Stream
         }
     }
 
+    /// Check the characters following the current one against a string
+    pub fn check_next_str(&self, s: &str) -> bool
+    {
+        self.data.iter().skip(self.index + 1).take(s.chars().count()).copied().eq(s.chars())
+    }
+
     /// Check the next character against a vector
     pub fn check_next_vec(&self, v: Vec<char>) -> bool
     {
@@ -1,23 +1,26 @@
 use std::fmt;
 use super::FileLocation;
+use crate::intern::Symbol;
 
 /// Token Object (data and FileLocation)
 #[derive(Debug, Clone)]
 pub struct Token
 {
     pub location: FileLocation,
-    pub data: String
+    pub data: Symbol,
+    pub doc: Option<String> // Text of any `///` comments directly before the token
 }
 
 impl Token
 {
     /// Generate a new token object
-    pub fn new(location: FileLocation, data: String) -> Self
+    pub fn new(location: FileLocation, data: impl Into<Symbol>) -> Self
     {
         Self
         {
             location,
-            data
+            data: data.into(),
+            doc: None
         }
     }
 }
@@ -6,7 +6,8 @@ struct Tokenizer
     current_data: String,
     source: Stream,
     pos: FileLocation,
-    tokens: Vec<Token>
+    tokens: Vec<Token>,
+    doc: Vec<String>
 }
 
 impl Tokenizer
@@ -19,7 +20,8 @@ impl Tokenizer
             current_data: String::new(),
             pos: source.current_location().clone(),
             source: source,
-            tokens: vec![]
+            tokens: vec![],
+            doc: vec![]
         }
     }
 
@@ -28,34 +30,42 @@ impl Tokenizer
     {
         if self.current_data.len() != 0
         {
-            self.tokens.push(Token::new(self.pos.clone(), self.current_data.clone()));
+            let mut token = Token::new(self.pos.clone(), self.current_data.clone());
+
+            // Doc comments belong to the token that follows them
+            if !self.doc.is_empty()
+            {
+                token.doc = Some(self.doc.join("\n"));
+                self.doc.clear();
+            }
+
+            self.tokens.push(token);
             self.current_data = String::new();
         }
-
-        self.pos = self.source.current_location();
-        
     }
 
-    /// Push the current character
-    fn push_char(&mut self, c: char)
+    /// Finish a line of a doc comment, dropping the space after the `///`
+    fn push_doc_line(&mut self, line: &str)
     {
-        self.current_data.push(c);
+        let line = line.trim_end();
+        self.doc.push(line.strip_prefix(' ').unwrap_or(line).to_string());
     }
 
-    /// Consume the next character
-    fn consume(&mut self)
+    /// Push the current character, a token is located at its first character
+    fn push_char(&mut self, c: char)
     {
-        self.source.consume();
-        if self.current_data.len() == 0
+        if self.current_data.is_empty()
         {
             self.pos = self.source.current_location();
         }
+
+        self.current_data.push(c);
     }
 
-    /// Move the file position back
-    fn move_back(&mut self)
+    /// Consume the next character
+    fn consume(&mut self)
     {
-        self.pos.col -= 1;
+        self.source.consume();
     }
 }
 
@@ -66,6 +76,7 @@ pub fn tokenize(input: String, file_name: String) -> Vec<Token>
 
     let mut single_line_comment: bool = false;
     let mut multi_line_comment: bool = false;
+    let mut doc_comment: Option<String> = None;
 
     loop 
     {
@@ -73,7 +84,21 @@ pub fn tokenize(input: String, file_name: String) -> Vec<Token>
         {
             Some(current) =>
             {
-                if single_line_comment
+                if let Some(line) = &mut doc_comment
+                {
+                    // A doc comment always runs to the end of the line
+                    if current.0 == '\n'
+                    {
+                        let line = line.clone();
+                        tokenizer.push_doc_line(&line);
+                        doc_comment = None;
+                    }
+                    else
+                    {
+                        line.push(current.0);
+                    }
+                }
+                else if single_line_comment
                 {
                     if current.0 == '/' && tokenizer.source.check_next('/')
                     {
@@ -98,26 +123,35 @@ pub fn tokenize(input: String, file_name: String) -> Vec<Token>
                     match current.0
                     {
                         ' ' | '\n' => {tokenizer.push_current();},
-                        '{' | '}' | '(' | ')' | '[' | ']' | ';' | ',' | ':' | '.' | '?' | '~' => 
+                        // The '#' of a raw identifier, `r#loop`, stays part of the identifier
+                        '#' if tokenizer.current_data == "r" &&
+                            tokenizer.source.peek().map(|(c, _)| c.is_ascii_alphabetic() || c == '_').unwrap_or(false) =>
+                            {
+                                tokenizer.push_char('#');
+                            },
+                        '{' | '}' | '(' | ')' | '[' | ']' | ';' | ',' | ':' | '.' | '?' | '~' | '#' => 
                             {
                                 tokenizer.push_current();
                                 tokenizer.push_char(current.0);
-                                tokenizer.move_back();
                                 tokenizer.push_current();
                             },
                         '+' | '-' | '&' | '|' | '<' | '>' | '=' =>
                         {
                             tokenizer.push_current();
 
-                            // Repeated
+                            // Repeated, followed by an equals sign, or an arrow, always taking the
+                            // longest operator so `<<=` is one token but `<==` is `<=` then `=`
                             if tokenizer.source.check_next_vec(vec![current.0, '=']) || 
                                     current.0 == '-' && tokenizer.source.check_next('>')
                             {
                                 tokenizer.push_char(current.0);
-                                tokenizer.move_back();
                                 tokenizer.source.consume();
-                                tokenizer.push_char(tokenizer.source.current().unwrap().0);
-                                if vec!['<', '>'].contains(&current.0) && tokenizer.source.check_next('=')
+
+                                let second = tokenizer.source.current().unwrap().0;
+                                tokenizer.push_char(second);
+
+                                // Only shifts have a third character, as in `<<=` and `>>=`
+                                if ['<', '>'].contains(&current.0) && second == current.0 && tokenizer.source.check_next('=')
                                 {
                                     tokenizer.source.consume();
                                     tokenizer.push_char(tokenizer.source.current().unwrap().0);
@@ -126,7 +160,6 @@ pub fn tokenize(input: String, file_name: String) -> Vec<Token>
                             }
                             else
                             {
-                                tokenizer.move_back();
                                 tokenizer.push_char(current.0);
                                 tokenizer.push_current();
                             }
@@ -138,14 +171,12 @@ pub fn tokenize(input: String, file_name: String) -> Vec<Token>
                             if tokenizer.source.check_next('=')
                             {
                                 tokenizer.push_char(current.0);
-                                tokenizer.move_back();
                                 tokenizer.source.consume();
                                 tokenizer.push_char('=');
                                 tokenizer.push_current();
                             }
                             else
                             {
-                                tokenizer.move_back();
                                 tokenizer.push_char(current.0);
                                 tokenizer.push_current();
                             }
@@ -159,6 +190,13 @@ pub fn tokenize(input: String, file_name: String) -> Vec<Token>
                                 tokenizer.consume();
                                 multi_line_comment = true;
                             }
+                            // Exactly three slashes start a doc comment, `////` is an ordinary comment
+                            else if tokenizer.source.check_next_str("//") && !tokenizer.source.check_next_str("///")
+                            {
+                                tokenizer.consume();
+                                tokenizer.consume();
+                                doc_comment = Some(String::new());
+                            }
                             else if tokenizer.source.check_next('/')
                             {
                                 tokenizer.consume();
@@ -169,19 +207,51 @@ pub fn tokenize(input: String, file_name: String) -> Vec<Token>
                                 if tokenizer.source.check_next('=')
                                 {
                                     tokenizer.push_char(current.0);
-                                    tokenizer.move_back();
                                     tokenizer.source.consume();
                                     tokenizer.push_char('=');
                                     tokenizer.push_current();
                                 }
                                 else
                                 {
-                                    tokenizer.move_back();
                                     tokenizer.push_char(current.0);
                                     tokenizer.push_current();
                                 }
                             }
                         }
+                        // Strings are kept whole, along with their quotes and any escapes
+                        '"' =>
+                        {
+                            tokenizer.push_current();
+                            tokenizer.push_char('"');
+
+                            loop
+                            {
+                                tokenizer.source.consume();
+
+                                match tokenizer.source.current()
+                                {
+                                    Some(('"', _)) =>
+                                    {
+                                        tokenizer.push_char('"');
+                                        break;
+                                    },
+                                    Some(('\\', _)) =>
+                                    {
+                                        tokenizer.push_char('\\');
+                                        tokenizer.source.consume();
+
+                                        if let Some(escaped) = tokenizer.source.current()
+                                        {
+                                            tokenizer.push_char(escaped.0);
+                                        }
+                                    },
+                                    Some((c, _)) => {tokenizer.push_char(c);},
+                                    None => {break;}
+                                }
+                            }
+
+                            tokenizer.push_current();
+                        },
                         default => {tokenizer.push_char(default);}
                     }
                 }
@@ -192,7 +262,124 @@ pub fn tokenize(input: String, file_name: String) -> Vec<Token>
         }
     }
 
+    if let Some(line) = doc_comment
+    {
+        tokenizer.push_doc_line(&line);
+    }
+
     tokenizer.push_current();
 
     tokenizer.tokens
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests
+{
+    use super::*;
+
+    /// Give the text of each token of some input
+    fn texts(input: &str) -> Vec<String>
+    {
+        tokenize(input.to_string(), String::from("test.pc")).iter().map(|token| token.data.to_string()).collect()
+    }
+
+    #[test]
+    fn operators_are_single_tokens()
+    {
+        let operators = ["+", "-", "*", "/", "%", "&", "|", "^", "~", "!", "<", ">", "=", "?", ":", ".", ",", ";",
+            "++", "--", "->", "&&", "||", "<<", ">>", "==", "!=", "<=", ">=",
+            "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>="];
+
+        for operator in operators
+        {
+            assert_eq!(texts(&format!("a {} b", operator)), vec!["a", operator, "b"], "spaced '{}'", operator);
+            assert_eq!(texts(&format!("a{}b", operator)), vec!["a", operator, "b"], "unspaced '{}'", operator);
+        }
+    }
+
+    #[test]
+    fn brackets_are_single_tokens()
+    {
+        assert_eq!(texts("f(a[0]){}"), vec!["f", "(", "a", "[", "0", "]", ")", "{", "}"]);
+    }
+
+    #[test]
+    fn longest_operator_is_taken()
+    {
+        assert_eq!(texts("a<==b"), vec!["a", "<=", "=", "b"]);
+        assert_eq!(texts("a>>==b"), vec!["a", ">>=", "=", "b"]);
+        assert_eq!(texts("a&&&b"), vec!["a", "&&", "&", "b"]);
+        assert_eq!(texts("a|||b"), vec!["a", "||", "|", "b"]);
+        assert_eq!(texts("a---b"), vec!["a", "--", "-", "b"]);
+        assert_eq!(texts("a+++b"), vec!["a", "++", "+", "b"]);
+        assert_eq!(texts("a!==b"), vec!["a", "!=", "=", "b"]);
+        assert_eq!(texts("a<<<b"), vec!["a", "<<", "<", "b"]);
+    }
+
+    #[test]
+    fn arrow_is_not_a_minus_and_comparison()
+    {
+        assert_eq!(texts("p->x"), vec!["p", "->", "x"]);
+        assert_eq!(texts("a- >b"), vec!["a", "-", ">", "b"]);
+        assert_eq!(texts("a-->b"), vec!["a", "--", ">", "b"]);
+    }
+
+    #[test]
+    fn division_is_not_a_comment()
+    {
+        assert_eq!(texts("a/b"), vec!["a", "/", "b"]);
+        assert_eq!(texts("a/=b"), vec!["a", "/=", "b"]);
+        assert_eq!(texts("a//b\nc"), vec!["a", "c"]);
+        assert_eq!(texts("a/*b*/c"), vec!["a", "c"]);
+    }
+
+    #[test]
+    fn operator_at_end_of_input()
+    {
+        assert_eq!(texts("a+"), vec!["a", "+"]);
+        assert_eq!(texts("a<<"), vec!["a", "<<"]);
+        assert_eq!(texts("a<"), vec!["a", "<"]);
+        assert_eq!(texts("a!"), vec!["a", "!"]);
+        assert_eq!(texts("a/"), vec!["a", "/"]);
+        assert_eq!(texts(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn strings_are_kept_whole()
+    {
+        assert_eq!(texts("f(\"a + b\")"), vec!["f", "(", "\"a + b\"", ")"]);
+        assert_eq!(texts("\"a\\\"b\""), vec!["\"a\\\"b\""]);
+        assert_eq!(texts("\"open"), vec!["\"open"]);
+    }
+
+    #[test]
+    fn raw_identifiers_keep_their_hash()
+    {
+        assert_eq!(texts("r#loop"), vec!["r#loop"]);
+        assert_eq!(texts("r #loop"), vec!["r", "#", "loop"]);
+        assert_eq!(texts("#attr"), vec!["#", "attr"]);
+    }
+
+    #[test]
+    fn doc_comments_belong_to_the_next_token()
+    {
+        let tokens = tokenize(String::from("/// Adds\n///  two\ni32 add"), String::from("test.pc"));
+
+        assert_eq!(tokens[0].data, "i32");
+        assert_eq!((tokens[0].location.row, tokens[0].location.col), (3, 1));
+        assert_eq!(tokens[0].doc.as_deref(), Some("Adds\n two"));
+        assert_eq!(tokens[1].doc, None);
+
+        let tokens = tokenize(String::from("//// Not a doc comment\ni32"), String::from("test.pc"));
+        assert_eq!(tokens[0].doc, None);
+    }
+
+    #[test]
+    fn tokens_are_located()
+    {
+        let tokens = tokenize(String::from("a <<= b;\nc <<= d;\n  (e) x"), String::from("test.pc"));
+        let locations: Vec<(usize, usize)> = tokens.iter().map(|token| (token.location.row, token.location.col)).collect();
+
+        assert_eq!(locations, vec![(1, 1), (1, 3), (1, 7), (1, 8), (2, 1), (2, 3), (2, 7), (2, 8), (3, 3), (3, 4), (3, 5), (3, 7)]);
+    }
+}
@@ -0,0 +1,85 @@
+//! Checks programs compile with the AVR assembly backend at each optimization level, and that
+//! llvm-mc accepts the assembly written for them
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Compile the given program under tests with -g avrasm at the given optimization level and
+/// assemble the output with llvm-mc, giving false if llvm-mc is not available
+fn assemble(name: &str, level: usize) -> bool
+{
+    let (output, assembly) = compile(name, level);
+
+    let assembled = match Command::new("llvm-mc").arg("-triple=avr").arg("-mcpu=atmega328p").arg(&output).arg("-o").arg("/dev/null").output()
+    {
+        Ok(assembled) => assembled,
+        Err(_) => return false
+    };
+
+    assert!(assembled.status.success(), "llvm-mc rejected the output for {} at -O {}:\n{}\n{}", name, level, String::from_utf8_lossy(&assembled.stderr), assembly);
+
+    true
+}
+
+/// Compile the given program under tests with -g avrasm at the given optimization level, giving
+/// the path of the output and the assembly written to it
+fn compile(name: &str, level: usize) -> (PathBuf, String)
+{
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join(name);
+    let output = std::env::temp_dir().join(format!("compiler-avrasm-{}-{}-O{}.s", std::process::id(), name.replace('/', "-"), level));
+
+    let compiled = Command::new(env!("CARGO_BIN_EXE_compiler")).arg(&source).arg("-g").arg("avrasm").arg("-O").arg(level.to_string()).arg("-o").arg(&output).output().unwrap();
+    assert!(compiled.status.success(), "{} at -O {}:\n{}", name, level, String::from_utf8_lossy(&compiled.stderr));
+
+    let assembly = std::fs::read_to_string(&output).unwrap();
+
+    (output, assembly)
+}
+
+#[test]
+fn indexing_assembles_at_each_level()
+{
+    for level in 0..=3
+    {
+        if !assemble("test4.pc", level)
+        {
+            return;
+        }
+    }
+}
+
+#[test]
+fn avr_programs_assemble()
+{
+    for name in ["avr/test0.pc", "avr/test1.pc", "avr/test3.pc", "avr/test5.pc"]
+    {
+        if !assemble(name, 2)
+        {
+            return;
+        }
+    }
+}
+
+#[test]
+fn spilled_values_are_kept_in_the_frame()
+{
+    let (_, assembly) = compile("avr/spill.pc", 0);
+
+    assert!(assembly.contains("std Y+") && assembly.contains("ldd r"), "{}", assembly);
+    assert!(assembly.contains("adiw r28"), "{}", assembly);
+}
+
+#[test]
+fn spilled_values_assemble_at_each_level()
+{
+    for name in ["avr/spill.pc", "avr/test2.pc"]
+    {
+        for level in 0..=3
+        {
+            if !assemble(name, level)
+            {
+                return;
+            }
+        }
+    }
+}
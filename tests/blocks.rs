@@ -0,0 +1,100 @@
+//! Checks the IR handed to the code generators is rejected with an error, rather than a panic or
+//! leaving out instructions, when an instruction follows the end of a block without a label or
+//! uses a variable which was never declared
+
+use compiler::cli::{Error, Options};
+use compiler::codegen::{CodeGenerator, CodegenMode};
+use compiler::irgen::{DataType, Function, Instruction, Literal, NonPtrType, OpCode, Symbol, Value};
+
+/// Build a function returning, followed by a move into a register
+fn move_after_return() -> Function
+{
+    let datatype = DataType::new(NonPtrType::I32, 0, false);
+
+    let mut function = Function::new();
+    function.name = String::from("after_return");
+
+    function.add_instruction(Instruction::new(OpCode::Ret, vec![]));
+    function.add_instruction(Instruction::new(OpCode::Mov, vec![Value::Symbol(Symbol::register(String::from("R1"), datatype)), Value::Literal(Literal::new(1, datatype))]));
+
+    function
+}
+
+#[test]
+fn instruction_after_terminator_is_an_error()
+{
+    let error = move_after_return().check_blocks().expect_err("accepted an instruction after a return");
+
+    assert_eq!(error.code(), Some("E0114"));
+    assert!(error.message().contains("after_return"), "{}", error.message());
+}
+
+#[test]
+fn labeled_instruction_after_terminator_starts_a_block()
+{
+    let mut function = move_after_return();
+    function.place_label(String::from("next"), 1);
+
+    assert!(function.check_blocks().is_ok());
+}
+
+#[test]
+fn nop_after_terminator_is_allowed()
+{
+    let mut function = move_after_return();
+    function.change_to_nop(1);
+
+    assert!(function.check_blocks().is_ok());
+}
+
+/// Generate LLVM for a function
+fn render(function: Function) -> Result<String, Error>
+{
+    let options = Options::new(vec![String::from("compiler")]);
+
+    CodeGenerator::new(CodegenMode::LLVM, &[function], &options).render()
+}
+
+/// Build a function moving one value into another, then returning
+fn move_between(destination: Symbol, source: Symbol) -> Function
+{
+    let mut function = Function::new();
+    function.name = String::from("moves");
+
+    function.add_instruction(Instruction::new(OpCode::Mov, vec![Value::Symbol(destination), Value::Symbol(source)]));
+    function.add_instruction(Instruction::new(OpCode::Ret, vec![]));
+
+    function
+}
+
+#[test]
+fn registers_need_no_declaration()
+{
+    let datatype = DataType::new(NonPtrType::I32, 0, false);
+    let function = move_between(Symbol::register(String::from("R1"), datatype), Symbol::register(String::from("R2"), datatype));
+
+    assert!(render(function).is_ok());
+}
+
+#[test]
+fn variable_titled_like_a_register_must_be_declared()
+{
+    let datatype = DataType::new(NonPtrType::I32, 0, false);
+    let function = move_between(Symbol::new(String::from("R1"), datatype), Symbol::register(String::from("R2"), datatype));
+
+    let error = render(function).expect_err("accepted an undeclared variable");
+
+    assert_eq!(error.code(), Some("E0114"));
+    assert!(error.message().contains("'R1'"), "{}", error.message());
+}
+
+#[test]
+fn unallocated_register_is_an_error()
+{
+    let function = move_between(Symbol::register(String::from("R1"), DataType::new(NonPtrType::I32, 0, false)), Symbol::register(String::from("R2"), DataType::new(NonPtrType::Void, 0, false)));
+
+    let error = render(function).expect_err("accepted a register which was never allocated");
+
+    assert_eq!(error.code(), Some("E0114"));
+    assert!(error.message().contains("'R2'"), "{}", error.message());
+}
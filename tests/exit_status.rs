@@ -0,0 +1,143 @@
+//! Checks the compiler exits with a failing status whenever it reports an error, so builds and CI
+//! can tell a failed compile from a successful one
+
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Write a source file to a directory of its own under the temporary directory
+fn source(name: &str, data: &str) -> PathBuf
+{
+    let directory = std::env::temp_dir().join(format!("compiler-exit-status-{}-{}", std::process::id(), name));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let path = directory.join(format!("{}.pc", name));
+    std::fs::write(&path, data).unwrap();
+
+    path
+}
+
+/// Compile a source file with the given extra arguments, writing the output next to it
+fn compile(name: &str, data: &str, arguments: &[&str]) -> Output
+{
+    let path = source(name, data);
+
+    Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .arg(&path)
+        .arg("-o")
+        .arg(path.with_extension("ll"))
+        .args(arguments)
+        .output()
+        .unwrap()
+}
+
+/// Assert the compiler failed with the given error code
+fn assert_fails_with(output: &Output, code: &str)
+{
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(stderr.contains(code), "expected {} in:\n{}", code, stderr);
+}
+
+#[test]
+fn undeclared_variable_fails()
+{
+    let output = compile("undeclared", "i32 main()\n{\n    return x;\n}\n", &[]);
+
+    assert_fails_with(&output, "E0001");
+    assert_fails_with(&output, "E0113");
+}
+
+#[test]
+fn undeclared_function_fails()
+{
+    let output = compile("undeclared_function", "i32 square(i32 x)\n{\n    return x * x;\n}\n\ni32 main()\n{\n    return sqare(3);\n}\n", &[]);
+
+    assert_fails_with(&output, "E0043");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("did you mean `square`?"));
+}
+
+#[test]
+fn private_global_of_module_fails()
+{
+    let path = source("private_global", "import \"ticks.pc\";\n\ni32 main()\n{\n    return count;\n}\n");
+    std::fs::write(path.with_file_name("ticks.pc"), "i32 count = 3;\n\npub i32 total = 10;\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler")).arg(&path).arg("-o").arg(path.with_extension("ll")).output().unwrap();
+
+    assert_fails_with(&output, "E0034");
+}
+
+#[test]
+fn narrowing_initialization_fails()
+{
+    let output = compile("narrowing", "i32 main()\n{\n    i64 wide = 300;\n    u8 small = wide;\n    return small as i32;\n}\n", &[]);
+
+    assert_fails_with(&output, "E0023");
+}
+
+#[test]
+fn break_outside_loop_fails()
+{
+    let output = compile("break", "i32 main()\n{\n    break;\n    return 0;\n}\n", &[]);
+
+    assert_fails_with(&output, "E0113");
+}
+
+#[test]
+fn void_condition_fails()
+{
+    let output = compile("void_condition", "void nothing()\n{\n}\n\ni32 main()\n{\n    if nothing()\n    {\n        return 1;\n    }\n\n    return 0;\n}\n", &[]);
+
+    assert_fails_with(&output, "E0113");
+}
+
+#[test]
+fn valid_program_succeeds()
+{
+    let output = compile("valid", "i32 main()\n{\n    return 0;\n}\n", &[]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn warning_succeeds_without_werror()
+{
+    let output = compile("warning", "i32 main()\n{\n    return 0;\n    return 1;\n}\n", &[]);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn werror_fails()
+{
+    let output = compile("werror", "i32 main()\n{\n    return 0;\n    return 1;\n}\n", &["-Werror"]);
+
+    assert_fails_with(&output, "E0104");
+}
+
+#[test]
+fn denied_warning_fails()
+{
+    let output = compile("deny", "i32 main()\n{\n    i32 x = 1;\n    i32 x = 2;\n    return x;\n}\n", &["--deny", "shadowing"]);
+
+    assert_fails_with(&output, "E0104");
+}
+
+#[test]
+fn check_with_werror_fails()
+{
+    let path = source("check_werror", "i32 main()\n{\n    return 0;\n    return 1;\n}\n");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler")).arg("check").arg(&path).arg("-Werror").output().unwrap();
+
+    assert_fails_with(&output, "E0104");
+}
+
+#[test]
+fn unsupported_type_in_avr_assembly_fails()
+{
+    let output = compile("avrasm_i32", "void f()\n{\n    i32* p = 36;\n    *p = *p + 1;\n    return;\n}\n", &["-g", "avrasm"]);
+
+    assert_fails_with(&output, "E0200");
+}
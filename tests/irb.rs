@@ -0,0 +1,53 @@
+//! Checks IR files written by --emit irb stay in proportion to the library, and compile back to
+//! the same program with --from-irb
+
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Write a library of the given number of small functions to a directory of its own under the
+/// temporary directory, compile it with --emit irb and give the path of the IR file
+fn write_irb(count: usize) -> PathBuf
+{
+    let directory = std::env::temp_dir().join(format!("compiler-irb-{}-{}", std::process::id(), count));
+    std::fs::create_dir_all(&directory).unwrap();
+
+    let mut data = String::new();
+
+    for i in 0..count
+    {
+        data += &format!("i32 f{}(i32 x)\n{{\n    return x + {};\n}}\n\n", i, i);
+    }
+
+    data += "i32 main()\n{\n    return f1(2);\n}\n";
+
+    let path = directory.join("library.pc");
+    std::fs::write(&path, data).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler")).arg(&path).arg("-o").arg(path.with_extension("ll")).arg("--emit").arg("irb").output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    path.with_extension("irb")
+}
+
+#[test]
+fn irb_grows_with_the_number_of_functions()
+{
+    let small = std::fs::metadata(write_irb(200)).unwrap().len();
+    let large = std::fs::metadata(write_irb(400)).unwrap().len();
+
+    // Twice the functions should take about twice the bytes, not four times as many
+    assert!(large < small * 3, "{} bytes for 200 functions but {} for 400", small, large);
+}
+
+#[test]
+fn irb_compiles_back()
+{
+    let path = write_irb(10);
+    let output = path.with_file_name("from_irb.ll");
+
+    let result = Command::new(env!("CARGO_BIN_EXE_compiler")).arg(&path).arg("--from-irb").arg("-o").arg(&output).output().unwrap();
+    assert!(result.status.success(), "{}", String::from_utf8_lossy(&result.stderr));
+
+    let ir = std::fs::read_to_string(output).unwrap();
+    assert!(ir.contains("f1"), "{}", ir);
+}
@@ -0,0 +1,132 @@
+//! Checks libraries compiled one after another, or at the same time, in the same process each see
+//! only the structs and global variables they declare themselves, and the sizes of their own target
+
+use compiler::cli::Options;
+use compiler::compile::generate;
+use compiler::io::InputFile;
+
+/// Generate LLVM for a library from its source
+fn compile(name: &str, data: &str) -> String
+{
+    compile_for(name, data, &[])
+}
+
+/// Generate LLVM for a library from its source, with the given extra arguments
+fn compile_for(name: &str, data: &str, arguments: &[&str]) -> String
+{
+    let arguments: Vec<String> = ["compiler", "-g", "llvm"].iter().chain(arguments).map(|argument| argument.to_string()).collect();
+
+    let options = Options::new(arguments);
+    let input = InputFile { data: data.to_string(), filename: format!("{}.pc", name) };
+
+    generate(&input, &options, false).unwrap_or_else(|error| panic!("{}: {}", name, error)).0
+}
+
+/// A library declaring a struct named `Pair`, holding two fields of the given type
+fn pair_of(datatype: &str) -> String
+{
+    format!("struct Pair\n{{\n    {} first;\n    {} second;\n}}\n\nstruct Pair* main_pair;\n\ni32 main()\n{{\n    struct Pair pair;\n    pair.second = 1 as {};\n    return pair.second as i32;\n}}\n", datatype, datatype, datatype)
+}
+
+#[test]
+fn structs_of_one_library_are_not_seen_by_another()
+{
+    let declares = compile("declares", &pair_of("i8"));
+    assert!(declares.contains("%struct.Pair = type { i8, i8 }"), "{}", declares);
+
+    // A struct of the library before is unknown to the next
+    let options = Options::new(vec![String::from("compiler")]);
+    let input = InputFile { data: String::from("i32 main()\n{\n    struct Pair pair;\n    return 0;\n}\n"), filename: String::from("uses.pc") };
+
+    let error = generate(&input, &options, false).expect_err("accepted a struct declared by another library");
+    assert_eq!(error.code(), Some("E0040"));
+}
+
+#[test]
+fn libraries_compiled_at_once_keep_their_own_structs()
+{
+    let threads: Vec<_> = ["i8", "i16", "i32", "i64"].iter().map(|&datatype| std::thread::spawn(move ||
+    {
+        for _ in 0..20
+        {
+            let output = compile(datatype, &pair_of(datatype));
+            let expected = format!("%struct.Pair = type {{ {}, {} }}", datatype, datatype);
+
+            assert!(output.contains(&expected), "{}", output);
+            assert_eq!(output.matches("%struct.Pair = type").count(), 1, "{}", output);
+        }
+    })).collect();
+
+    for thread in threads
+    {
+        thread.join().unwrap();
+    }
+}
+
+/// A library with a global variable named `count` of the given type, starting with the given value
+fn count_of(datatype: &str, value: usize) -> String
+{
+    format!("{} count = {};\n\ni32 bump()\n{{\n    count += 1 as {};\n    return count as i32;\n}}\n", datatype, value, datatype)
+}
+
+#[test]
+fn globals_of_one_library_are_not_seen_by_another()
+{
+    let declares = compile("declares", &count_of("i16", 3));
+    assert!(declares.contains("@count = global i16 3"), "{}", declares);
+
+    // A global variable of the library before is unknown to the next
+    let options = Options::new(vec![String::from("compiler")]);
+    let input = InputFile { data: String::from("i32 main()\n{\n    return count;\n}\n"), filename: String::from("uses.pc") };
+
+    let error = generate(&input, &options, false).expect_err("accepted a global variable declared by another library");
+    assert_eq!(error.code(), Some("E0001"));
+
+    let empty = compile("empty", "i32 main()\n{\n    return 0;\n}\n");
+    assert!(!empty.contains("@count"), "{}", empty);
+}
+
+#[test]
+fn libraries_compiled_at_once_keep_their_own_globals()
+{
+    let threads: Vec<_> = ["i8", "i16", "i32", "i64"].iter().enumerate().map(|(value, &datatype)| std::thread::spawn(move ||
+    {
+        for _ in 0..20
+        {
+            let output = compile(datatype, &count_of(datatype, value));
+            let expected = format!("@count = global {} {}", datatype, value);
+
+            assert!(output.contains(&expected), "{}", output);
+            assert_eq!(output.matches("@count = global").count(), 1, "{}", output);
+        }
+    })).collect();
+
+    for thread in threads
+    {
+        thread.join().unwrap();
+    }
+}
+
+#[test]
+fn libraries_compiled_at_once_keep_their_own_targets()
+{
+    let source = "u64 size = sizeof(i8*);\n\ni32 main()\n{\n    return size as i32;\n}\n";
+
+    let threads: Vec<_> = [(None, 8), (Some("wasm32-unknown-unknown"), 4), (Some("x86_64-unknown-none"), 8), (Some("riscv64gc-unknown-none-elf"), 8)].iter().map(|&(target, size)| std::thread::spawn(move ||
+    {
+        let arguments: Vec<&str> = target.map(|target| vec!["--target", target]).unwrap_or_default();
+
+        for _ in 0..20
+        {
+            let output = compile_for(target.unwrap_or("host"), source, &arguments);
+            let expected = format!("@size = global i64 {}", size);
+
+            assert!(output.contains(&expected), "{}", output);
+        }
+    })).collect();
+
+    for thread in threads
+    {
+        thread.join().unwrap();
+    }
+}
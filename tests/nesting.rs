@@ -0,0 +1,59 @@
+//! Checks programs nested as deeply as the compiler allows compile on the stack of a main thread,
+//! and that nesting them one level deeper gives an error rather than running out of stack
+
+use std::process::Command;
+
+/// A program whose main function nests the given construct the given number of times
+fn nested(shape: &str, depth: usize) -> String
+{
+    let body = match shape
+    {
+        "parentheses" => format!("    i32 x = {}1{};\n    return x;\n", "(".repeat(depth), ")".repeat(depth)),
+        "operators" => format!("    i32 y = 1;\n    i32 x = y{};\n    return x;\n", " + 1".repeat(depth)),
+        "calls" => format!("    i32 x = {}1{};\n    return x;\n", "f(".repeat(depth), ")".repeat(depth)),
+        "ifs" => format!("    i32 x = 0;\n{}x++;\n{}    return x;\n", "if (x == 0) {\n".repeat(depth), "}\n".repeat(depth)),
+        "loops" => format!("    i32 x = 0;\n{}x++;\n{}    return x;\n", "while (x < 0) {\n".repeat(depth), "}\n".repeat(depth)),
+        "blocks" => format!("    i32 x = 0;\n{}x++;\n{}    return x;\n", "{\n".repeat(depth), "}\n".repeat(depth)),
+        "else ifs" => format!("    i32 x = 0;\n    if (x == 1) {{x = 1;}}\n{}    return x;\n", "else if (x == 2) {x = 2;}\n".repeat(depth)),
+        _ => unreachable!()
+    };
+
+    format!("i32 f(i32 a)\n{{\n    return a;\n}}\n\ni32 main()\n{{\n{}}}\n", body)
+}
+
+/// Compile the given construct nested to the given depth at -O 2, giving the exit code and
+/// the error output
+fn compile(shape: &str, depth: usize) -> (Option<i32>, String)
+{
+    let path = std::env::temp_dir().join(format!("compiler-nesting-{}-{}-{}.pc", std::process::id(), shape.replace(' ', "-"), depth));
+    std::fs::write(&path, nested(shape, depth)).unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler")).arg(&path).arg("-O").arg("2").arg("-o").arg(path.with_extension("ll")).output().unwrap();
+
+    (output.status.code(), String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+/// Each construct with the deepest it can be nested in the main function of a program, where
+/// statements count a level for their body and the block around it
+const DEEPEST: [(&str, usize); 7] = [("parentheses", 98), ("operators", 98), ("calls", 98), ("ifs", 48), ("loops", 48), ("blocks", 97), ("else ifs", 95)];
+
+#[test]
+fn nesting_to_the_limit_compiles()
+{
+    for (shape, depth) in DEEPEST
+    {
+        let (code, errors) = compile(shape, depth);
+        assert_eq!(code, Some(0), "{} nested {} deep:\n{}", shape, depth, errors);
+    }
+}
+
+#[test]
+fn nesting_beyond_the_limit_is_an_error()
+{
+    for (shape, depth) in DEEPEST
+    {
+        let (code, errors) = compile(shape, depth + 1);
+        assert_eq!(code, Some(1), "{} nested {} deep:\n{}", shape, depth + 1, errors);
+        assert!(errors.contains("E0037"), "{} nested {} deep:\n{}", shape, depth + 1, errors);
+    }
+}
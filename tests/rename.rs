@@ -0,0 +1,69 @@
+//! Checks a rename started from a file imported by others reaches the files importing it, found
+//! among the source files under the current directory
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Write the files of a program to a directory of its own under the temporary directory
+fn program(name: &str, files: &[(&str, &str)]) -> PathBuf
+{
+    let directory = std::env::temp_dir().join(format!("compiler-rename-{}-{}", std::process::id(), name));
+
+    for (path, data) in files
+    {
+        let path = directory.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, data).unwrap();
+    }
+
+    directory
+}
+
+/// Rename the name at a line and column of a file of a program, from the directory of the program
+fn rename(directory: &Path, file: &str, line: usize, column: usize, new_name: &str)
+{
+    let output = Command::new(env!("CARGO_BIN_EXE_compiler"))
+        .current_dir(directory)
+        .args(["rename", file, &line.to_string(), &column.to_string(), new_name])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+}
+
+/// Read a file of a program
+fn read(directory: &Path, file: &str) -> String
+{
+    std::fs::read_to_string(directory.join(file)).unwrap()
+}
+
+const MATH: &str = "pub i32 square(i32 x)\n{\n    return x * x;\n}\n";
+
+#[test]
+fn rename_in_module_reaches_importer()
+{
+    let directory = program("importer", &[("lib/math.pc", MATH), ("main.pc", "import \"lib/math.pc\";\n\ni32 main()\n{\n    return math::square(3);\n}\n")]);
+
+    rename(&directory, "lib/math.pc", 1, 9, "cube");
+
+    assert!(read(&directory, "lib/math.pc").starts_with("pub i32 cube(i32 x)"));
+    assert!(read(&directory, "main.pc").contains("return math::cube(3);"));
+}
+
+#[test]
+fn rename_in_module_reaches_every_module_tree()
+{
+    let directory = program("trees", &[
+        ("lib/math.pc", MATH),
+        ("lib/shapes.pc", "import \"math.pc\";\n\npub i32 area(i32 side)\n{\n    return square(side);\n}\n"),
+        ("main.pc", "import \"lib/shapes.pc\";\n\ni32 main()\n{\n    return area(3);\n}\n"),
+        ("tests/check.pc", "import \"../lib/math.pc\";\n\ni32 main()\n{\n    return math::square(2) - 4;\n}\n")
+    ]);
+
+    rename(&directory, "lib/math.pc", 1, 9, "cube");
+
+    assert!(read(&directory, "lib/math.pc").starts_with("pub i32 cube(i32 x)"));
+    assert!(read(&directory, "lib/shapes.pc").contains("return cube(side);"));
+    assert!(read(&directory, "tests/check.pc").contains("return math::cube(2) - 4;"));
+    assert_eq!(read(&directory, "main.pc"), "import \"lib/shapes.pc\";\n\ni32 main()\n{\n    return area(3);\n}\n");
+}
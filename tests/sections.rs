@@ -0,0 +1,44 @@
+//! Checks functions placed in sections compile to LLVM which llc accepts, on the host and on each
+//! built in target, as those give every function attributes for the CPU and its features
+
+use std::path::Path;
+use std::process::Command;
+
+/// Compile tests/sections.pc with the given extra arguments and assemble the output with llc,
+/// giving false if llc is not available
+fn assemble(name: &str, arguments: &[&str]) -> bool
+{
+    let source = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("sections.pc");
+    let output = std::env::temp_dir().join(format!("compiler-sections-{}-{}.ll", std::process::id(), name));
+
+    let compiled = Command::new(env!("CARGO_BIN_EXE_compiler")).arg(&source).arg("-g").arg("llvm").arg("-o").arg(&output).args(arguments).output().unwrap();
+    assert!(compiled.status.success(), "{}", String::from_utf8_lossy(&compiled.stderr));
+
+    let assembled = match Command::new("llc").arg(&output).arg("-o").arg("/dev/null").output()
+    {
+        Ok(assembled) => assembled,
+        Err(_) => return false
+    };
+
+    assert!(assembled.status.success(), "llc rejected the output for {}:\n{}\n{}", name, String::from_utf8_lossy(&assembled.stderr), std::fs::read_to_string(&output).unwrap());
+
+    true
+}
+
+#[test]
+fn sections_assemble_on_the_host()
+{
+    assemble("host", &[]);
+}
+
+#[test]
+fn sections_assemble_on_each_target()
+{
+    for target in ["x86_64-unknown-none", "riscv64gc-unknown-none-elf", "wasm32-unknown-unknown"]
+    {
+        if !assemble(target, &["--target", target])
+        {
+            return;
+        }
+    }
+}
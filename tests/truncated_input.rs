@@ -0,0 +1,106 @@
+//! Feeds every prefix of the conformance programs to the tokenizer and parser, checking a file which
+//! ends part way through a construct gives a diagnostic rather than a panic
+
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use compiler::parser::{parse, parse_recovering, NodeArena};
+use compiler::tokenizer::{tokenize, Token};
+
+/// Parse the tokens of a prefix of a program both ways, panicking with the prefix if a file which
+/// leaves a block open is accepted
+fn parse_prefix(name: &str, tokens: &[Token])
+{
+    let open = tokens.iter().filter(|token| token.data.as_str() == "{").count();
+    let closed = tokens.iter().filter(|token| token.data.as_str() == "}").count();
+
+    let arena = NodeArena::new();
+    let result = parse(tokens.to_vec(), &arena);
+
+    let arena = NodeArena::new();
+    let (_, errors) = parse_recovering(tokens.to_vec(), &arena);
+
+    if open > closed
+    {
+        let last = tokens.last().map(|token| token.location.clone());
+        let error = result.err().unwrap_or_else(|| panic!("accepted a block left open in {} ending at {:?}", name, last));
+
+        assert!(error.code().is_some(), "no error code for '{}' in {} ending at {:?}", error.message(), name, last);
+        assert!(!errors.is_empty(), "recovering parser accepted a block left open in {} ending at {:?}", name, last);
+    }
+}
+
+/// Tokenize every prefix of a program, and parse every prefix of its tokens along with each
+/// prefix ending part way through a string, as cutting any other token gives a prefix of tokens
+/// which is parsed already
+fn check_prefixes(name: &str, data: &str)
+{
+    let all = tokenize(data.to_string(), name.to_string());
+
+    for (end, _) in data.char_indices()
+    {
+        let tokens = tokenize(data[..end].to_string(), name.to_string());
+
+        let cuts_string = tokens.last().is_some_and(|token|
+        {
+            let text = token.data.as_str();
+            text.starts_with('"') && (text.len() < 2 || !text.ends_with('"'))
+        });
+
+        if cuts_string
+        {
+            parse_prefix(name, &tokens);
+        }
+    }
+
+    for count in 0..=all.len()
+    {
+        parse_prefix(name, &all[..count]);
+    }
+}
+
+/// Check every prefix of every program in a directory, naming the program whose prefix panicked
+fn check_prefixes_in(directory: &str)
+{
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join(directory);
+
+    let mut programs: Vec<_> = std::fs::read_dir(&directory).unwrap().map(|entry| entry.unwrap().path()).filter(|path| path.extension().is_some_and(|extension| extension == "pc")).collect();
+    programs.sort();
+
+    assert!(!programs.is_empty(), "no programs in {}", directory.display());
+
+    for path in programs
+    {
+        let name = path.display().to_string();
+        let data = std::fs::read_to_string(&path).unwrap();
+
+        if let Err(panic) = panic::catch_unwind(AssertUnwindSafe(|| check_prefixes(&name, &data)))
+        {
+            let message = panic.downcast_ref::<String>().cloned().or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string())).unwrap_or_default();
+            panic!("{}: {}", name, message);
+        }
+    }
+}
+
+#[test]
+fn truncated_spec_programs_give_diagnostics()
+{
+    check_prefixes_in("spec");
+}
+
+#[test]
+fn truncated_imported_modules_give_diagnostics()
+{
+    check_prefixes_in("spec/imports");
+}
+
+#[test]
+fn trailing_token_is_not_dropped()
+{
+    let tokens = tokenize(String::from("i32 main()\n{\n    return 0;\n}\n\ni3"), String::from("trailing.pc"));
+
+    let arena = NodeArena::new();
+    let error = parse(tokens, &arena).expect_err("accepted a file ending in a lone token");
+
+    assert_eq!(error.code(), Some("E0006"));
+}